@@ -0,0 +1,68 @@
+//! Drawing a chat of 100 long messages at 120 columns, with the rows of the messages
+//! taken from the row cache and laid out again every frame as before it.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use ratatui::{Terminal, backend::TestBackend};
+use rivetui::{
+    api::{ApiClient, Message, User},
+    app::{App, AppState},
+    ui::draw_ui,
+};
+use serde_json::json;
+
+fn chat() -> App {
+    let user = User {
+        id: "100".to_string(),
+        username: "tester".to_string(),
+        global_name: None,
+        avatar: None,
+        bot: false,
+    };
+    let client = ApiClient::new(
+        reqwest::Client::new(),
+        "token".to_string(),
+        "http://127.0.0.1:9".to_string(),
+    );
+    let mut app = App::new(client, user);
+    app.state = AppState::Chatting("c1".to_string());
+    let content = "Some **bold** and `code` with a link to https://example.com/page and \
+        a mention of <@7>, then a long run of words to wrap over several rows. "
+        .repeat(4);
+    app.messages = (1..=100)
+        .rev()
+        .map(|id| {
+            serde_json::from_value::<Message>(json!({
+                "id": id.to_string(),
+                "channel_id": "c1",
+                "author": { "id": (id % 5).to_string(), "username": format!("user{}", id % 5) },
+                "content": content,
+                "timestamp": format!("2024-01-01T12:{:02}:00Z", id % 60),
+                "mentions": [{ "id": "7", "username": "bob" }],
+            }))
+            .unwrap()
+        })
+        .collect();
+    app
+}
+
+fn draw_chat(c: &mut Criterion) {
+    let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+
+    let mut app = chat();
+    c.bench_function("draw 100 messages, cached rows", |b| {
+        b.iter(|| {
+            terminal.draw(|f| draw_ui(f, &mut app)).unwrap();
+        });
+    });
+
+    let mut app = chat();
+    c.bench_function("draw 100 messages, rows laid out every frame", |b| {
+        b.iter(|| {
+            app.invalidate_rows();
+            terminal.draw(|f| draw_ui(f, &mut app)).unwrap();
+        });
+    });
+}
+
+criterion_group!(benches, draw_chat);
+criterion_main!(benches);
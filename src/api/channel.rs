@@ -1,19 +1,58 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::Error;
+use crate::{
+    Error,
+    api::{User, guild::GuildMember},
+    ui::filter::fuzzy_match,
+};
 
+const ADMINISTRATOR_PERMISSION: u64 = 1 << 3;
 const VIEW_CHANNEL_PERMISSION: u64 = 1 << 10;
+const MANAGE_MESSAGES_PERMISSION: u64 = 1 << 13;
+const MANAGE_GUILD_EXPRESSIONS_PERMISSION: u64 = 1 << 30;
 
-#[derive(Debug, Deserialize, Clone)]
+/// Type of the channels created from the channel list.
+pub const GUILD_TEXT: u8 = 0;
+pub const GUILD_ANNOUNCEMENT: u8 = 5;
+/// Longest channel name Discord accepts, in characters.
+pub const MAX_CHANNEL_NAME_LENGTH: usize = 100;
+
+/// Checks a name typed for a channel of `channel_type`, turning it into the name Discord
+/// would give it: text, announcement and forum channel names are lowercase, with dashes
+/// for spaces. Other channels keep the name as typed, trimmed.
+pub fn normalize_channel_name(name: &str, channel_type: u8) -> Result<String, String> {
+    let name = if matches!(channel_type, 0 | 5 | 15 | 16) {
+        name.split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase()
+    } else {
+        name.trim().to_string()
+    };
+
+    match name.chars().count() {
+        0 => Err("The channel name is empty.".to_string()),
+        length if length > MAX_CHANNEL_NAME_LENGTH => Err(format!(
+            "Channel names are at most {MAX_CHANNEL_NAME_LENGTH} characters, not {length}."
+        )),
+        _ => Ok(name),
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Role {
     pub id: String,
     pub name: String,
     pub permissions: String,
+    #[serde(default)]
+    pub color: u32,
+    #[serde(default)]
+    pub position: i64,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PermissionContext {
     pub user_id: String,
     pub user_role_ids: Vec<String>,
@@ -21,7 +60,47 @@ pub struct PermissionContext {
     pub everyone_role_id: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl PermissionContext {
+    /// The same guild as seen by `member`, to check which channels they can access.
+    pub fn for_member(&self, member: &GuildMember) -> Self {
+        let mut user_role_ids = member.roles.clone();
+        if !user_role_ids.contains(&self.everyone_role_id) {
+            user_role_ids.push(self.everyone_role_id.clone());
+        }
+
+        Self {
+            user_id: member.user.id.clone(),
+            user_role_ids,
+            all_guild_roles: self.all_guild_roles.clone(),
+            everyone_role_id: self.everyone_role_id.clone(),
+        }
+    }
+
+    /// The permissions granted by the roles, before any channel overwrite.
+    fn role_permissions(&self) -> u64 {
+        let everyone = self
+            .all_guild_roles
+            .iter()
+            .find(|r| r.id == self.everyone_role_id)
+            .map_or(0, |r| parse_permission_string(&r.permissions));
+
+        self.user_role_ids
+            .iter()
+            .filter(|&id| id != &self.everyone_role_id)
+            .filter_map(|id| self.all_guild_roles.iter().find(|r| &r.id == id))
+            .fold(everyone, |permissions, role| {
+                permissions | parse_permission_string(&role.permissions)
+            })
+    }
+
+    /// Whether emojis and stickers of the guild can be added and removed.
+    pub fn can_manage_expressions(&self) -> bool {
+        let permissions = self.role_permissions();
+        permissions & (ADMINISTRATOR_PERMISSION | MANAGE_GUILD_EXPRESSIONS_PERMISSION) != 0
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Overwrite {
     pub id: String,
     pub r#type: u8,
@@ -29,19 +108,37 @@ pub struct Overwrite {
     pub deny: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Channel {
     pub id: String,
+    #[serde(default)]
     pub name: String,
     #[serde(rename = "type")]
     pub channel_type: u8,
     pub guild_id: Option<String>,
     pub parent_id: Option<String>,
     #[serde(default)]
+    pub position: i64,
+    pub last_message_id: Option<String>,
+    pub topic: Option<String>,
+    /// Messages in a thread, not counting its first one.
+    pub message_count: Option<u32>,
+    /// Age-restricted. Threads don't say, their parent does.
+    #[serde(default)]
+    pub nsfw: bool,
+    #[serde(default)]
     pub permission_overwrites: Vec<Overwrite>,
+    #[serde(default)]
+    pub recipients: Vec<User>,
     pub children: Option<Vec<Channel>>,
 }
 
+/// Response of the endpoints listing threads.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ThreadList {
+    pub threads: Vec<Channel>,
+}
+
 fn parse_permission_string(hex_string: &str) -> u64 {
     hex_string
         .parse::<u64>()
@@ -50,30 +147,11 @@ fn parse_permission_string(hex_string: &str) -> u64 {
 
 impl Channel {
     fn calculate_permissions(&self, context: &PermissionContext) -> u64 {
-        let everyone_role = context
-            .all_guild_roles
-            .iter()
-            .find(|r| r.id == context.everyone_role_id)
-            .cloned()
-            .unwrap_or_else(|| Role {
-                id: context.everyone_role_id.clone(),
-                name: "@everyone".to_string(),
-                permissions: "0".to_string(),
-            });
+        let mut permissions = context.role_permissions();
 
-        let mut permissions = parse_permission_string(&everyone_role.permissions);
-        for user_role_id in context
-            .user_role_ids
-            .iter()
-            .filter(|&id| id != &context.everyone_role_id)
-        {
-            if let Some(role) = context
-                .all_guild_roles
-                .iter()
-                .find(|r| &r.id == user_role_id)
-            {
-                permissions |= parse_permission_string(&role.permissions);
-            }
+        // Administrators bypass every channel overwrite.
+        if permissions & ADMINISTRATOR_PERMISSION != 0 {
+            return u64::MAX;
         }
 
         if let Some(everyone_overwrite) = self
@@ -109,16 +187,10 @@ impl Channel {
         permissions &= !role_denies;
         permissions |= role_allows;
 
-        let user_id = context
-            .user_role_ids
-            .first()
-            .unwrap_or(&String::new())
-            .clone();
-
         if let Some(member_overwrite) = self
             .permission_overwrites
             .iter()
-            .find(|o| o.r#type == 1 && o.id == user_id)
+            .find(|o| o.r#type == 1 && o.id == context.user_id)
         {
             let deny = parse_permission_string(&member_overwrite.deny);
             let allow = parse_permission_string(&member_overwrite.allow);
@@ -130,18 +202,81 @@ impl Channel {
         permissions
     }
 
+    pub fn is_category(&self) -> bool {
+        self.channel_type == 4
+    }
+
+    pub fn is_thread(&self) -> bool {
+        matches!(self.channel_type, 10..=12)
+    }
+
+    /// Whether the channel can be highlighted in the channel list: everything but
+    /// categories.
+    pub fn is_selectable(&self) -> bool {
+        self.is_text() || self.is_voice() || self.is_forum()
+    }
+
+    /// Forum and media channels, whose messages live in threads called posts.
+    pub fn is_forum(&self) -> bool {
+        matches!(self.channel_type, 15 | 16)
+    }
+
+    /// Whether the channel has a message history to open: text and announcement channels,
+    /// and threads.
+    pub fn is_text(&self) -> bool {
+        matches!(self.channel_type, 0 | 5 | 10..=12)
+    }
+
+    /// Voice and stage channels, which only show who is connected.
+    pub fn is_voice(&self) -> bool {
+        matches!(self.channel_type, 2 | 13)
+    }
+
+    /// Whether the channel has a message newer than `read_marker`, the id of the newest
+    /// message we have seen in it.
+    pub fn has_unread(&self, read_marker: Option<&String>) -> bool {
+        let snowflake = |id: &String| id.parse::<u64>().unwrap_or(0);
+        match (&self.last_message_id, read_marker) {
+            (Some(last), Some(read)) => snowflake(last) > snowflake(read),
+            _ => false,
+        }
+    }
+
+    /// Whether opening the channel waits for its NSFW warning to be confirmed: it, or
+    /// the `parent` it is a thread of, is marked NSFW, and neither was confirmed yet.
+    pub fn is_age_gated(&self, parent: Option<&Self>, confirmed: &HashSet<String>) -> bool {
+        let nsfw = self.nsfw || parent.is_some_and(|parent| parent.nsfw);
+        let was_confirmed = confirmed.contains(&self.id)
+            || parent.is_some_and(|parent| confirmed.contains(&parent.id));
+        nsfw && !was_confirmed
+    }
+
     pub fn is_readable(&self, context: &PermissionContext) -> bool {
         let permissions = self.calculate_permissions(context);
         (permissions & VIEW_CHANNEL_PERMISSION) != 0
     }
 
+    /// Whether the messages of others can be deleted, pinned and published.
+    pub fn can_manage_messages(&self, context: &PermissionContext) -> bool {
+        let permissions = self.calculate_permissions(context);
+        (permissions & MANAGE_MESSAGES_PERMISSION) != 0
+    }
+
+    /// Announcement channels, whose messages can be published to the channels following
+    /// them.
+    pub fn is_announcement(&self) -> bool {
+        self.channel_type == GUILD_ANNOUNCEMENT
+    }
+
+    /// Groups channels under their categories, sorted by position. Channels without a
+    /// parent are collected into a leading "uncategorized" group.
     pub fn filter_channels_by_categories(channels: Vec<Self>) -> Result<Vec<Self>, Error> {
         if channels.is_empty() {
             return Err("Error: channels must not be empty.".into());
         }
 
-        let (categories, other_channels): (Vec<Self>, Vec<Self>) =
-            channels.into_iter().partition(|c| c.channel_type == 4);
+        let (mut categories, other_channels): (Vec<Self>, Vec<Self>) =
+            channels.into_iter().partition(|c| c.is_category());
 
         let mut categorized_map: HashMap<String, Vec<Self>> = HashMap::new();
         let mut uncategorized_channels: Vec<Self> = Vec::new();
@@ -157,17 +292,181 @@ impl Channel {
             }
         }
 
+        categories.sort_by_key(|c| c.position);
+
         let mut final_list: Vec<Self> = Vec::new();
 
+        if !uncategorized_channels.is_empty() {
+            uncategorized_channels.sort_by_key(|c| c.position);
+            final_list.push(Self {
+                id: String::new(),
+                name: "uncategorized".to_string(),
+                channel_type: 4,
+                guild_id: None,
+                parent_id: None,
+                position: i64::MIN,
+                last_message_id: None,
+                topic: None,
+                message_count: None,
+                nsfw: false,
+                permission_overwrites: Vec::new(),
+                recipients: Vec::new(),
+                children: Some(uncategorized_channels),
+            });
+        }
+
         for mut category in categories {
-            if let Some(children) = categorized_map.remove(&category.id) {
+            if let Some(mut children) = categorized_map.remove(&category.id) {
+                children.sort_by_key(|c| c.position);
                 category.children = Some(children);
             }
             final_list.push(category);
         }
 
-        final_list.extend(uncategorized_channels);
-
         Ok(final_list)
     }
+
+    /// Unread channels per guild, in the grouped `channels` cached for each: text channels
+    /// readable with the guild's context and newer than their read marker. Muted channels
+    /// and categories don't count.
+    pub fn unread_counts(
+        channels: &HashMap<String, Vec<Self>>,
+        contexts: &HashMap<String, PermissionContext>,
+        read_markers: &HashMap<String, String>,
+        muted: &HashSet<String>,
+    ) -> HashMap<String, usize> {
+        channels
+            .iter()
+            .map(|(guild_id, grouped)| {
+                let context = contexts.get(guild_id);
+                let count = grouped
+                    .iter()
+                    .filter(|category| !muted.contains(&category.id))
+                    .flat_map(|category| category.children.iter().flatten())
+                    .filter(|c| c.is_text() && !muted.contains(&c.id))
+                    .filter(|c| context.is_none_or(|context| c.is_readable(context)))
+                    .filter(|c| c.has_unread(read_markers.get(&c.id)))
+                    .count();
+                (guild_id.clone(), count)
+            })
+            .collect()
+    }
+
+    /// Flattens grouped channels into the rows of the channel list: each category
+    /// header followed by its readable children matching `filter`, each child followed by
+    /// its `threads`. Categories without any such child are left out, and so is anything
+    /// `hidden`, with what it contains.
+    pub fn visible_rows<'a>(
+        channels: &'a [Self],
+        threads: &'a [Self],
+        context: Option<&PermissionContext>,
+        filter: &str,
+        hidden: Option<&HashSet<String>>,
+    ) -> Vec<&'a Self> {
+        let matches = |c: &Self| fuzzy_match(filter, &c.name);
+        let readable = |c: &Self| context.is_some_and(|context| c.is_readable(context));
+        let is_hidden = |c: &Self| hidden.is_some_and(|hidden| hidden.contains(&c.id));
+
+        let mut rows = Vec::new();
+
+        for channel in channels.iter().filter(|c| !is_hidden(c)) {
+            if !channel.is_category() {
+                if readable(channel) && matches(channel) {
+                    rows.push(channel);
+                }
+                continue;
+            }
+
+            let category_matches = !filter.is_empty() && matches(channel);
+            let mut children: Vec<&Self> = Vec::new();
+
+            for child in channel
+                .children
+                .iter()
+                .flatten()
+                .filter(|c| readable(c) && !is_hidden(c))
+            {
+                // Threads inherit access from their parent, and show up when either
+                // they or their parent match.
+                let child_matches = category_matches || matches(child);
+                let child_threads: Vec<&Self> = threads
+                    .iter()
+                    .filter(|t| t.parent_id.as_ref() == Some(&child.id) && !is_hidden(t))
+                    .filter(|t| child_matches || matches(t))
+                    .collect();
+
+                if child_matches || !child_threads.is_empty() {
+                    children.push(child);
+                    children.extend(child_threads);
+                }
+            }
+
+            if !children.is_empty() {
+                rows.push(channel);
+                rows.extend(children);
+            }
+        }
+
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn channel(id: &str, parent_id: Option<&str>, nsfw: bool) -> Channel {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "type": 0,
+            "guild_id": "g1",
+            "parent_id": parent_id,
+            "last_message_id": null,
+            "topic": null,
+            "message_count": null,
+            "nsfw": nsfw,
+            "children": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn gates_nsfw_channels_until_confirmed() {
+        let nsfw = channel("1", None, true);
+        let mut confirmed = HashSet::new();
+        assert!(nsfw.is_age_gated(None, &confirmed));
+        assert!(!channel("2", None, false).is_age_gated(None, &confirmed));
+
+        confirmed.insert("1".to_string());
+        assert!(!nsfw.is_age_gated(None, &confirmed));
+    }
+
+    #[test]
+    fn gates_threads_by_their_parent() {
+        let parent = channel("1", None, true);
+        let thread = channel("2", Some("1"), false);
+        let mut confirmed = HashSet::new();
+        assert!(thread.is_age_gated(Some(&parent), &confirmed));
+
+        confirmed.insert("1".to_string());
+        assert!(!thread.is_age_gated(Some(&parent), &confirmed));
+    }
+
+    #[test]
+    fn reads_channels_without_the_nsfw_field() {
+        let channel: Channel = serde_json::from_value(json!({
+            "id": "1",
+            "type": 0,
+            "guild_id": "g1",
+            "parent_id": null,
+            "last_message_id": null,
+            "topic": null,
+            "message_count": null,
+        }))
+        .unwrap();
+        assert!(!channel.nsfw);
+    }
 }
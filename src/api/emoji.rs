@@ -1,8 +1,92 @@
+use std::path::Path;
+
 use serde::Deserialize;
 
+use crate::api::User;
+
+/// Largest image Discord accepts for an emoji.
+pub const MAX_EMOJI_BYTES: u64 = 256 * 1024;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Emoji {
     pub id: String,
     pub name: String,
     pub animated: Option<bool>,
+    /// `false` once the guild lost the boost level the emoji needs.
+    pub available: Option<bool>,
+    /// Who uploaded it, only sent to members who can manage expressions.
+    pub user: Option<User>,
+}
+
+/// Checks a name for a new emoji: 2 to 32 letters, digits or underscores.
+pub fn validate_emoji_name(name: &str) -> Result<String, String> {
+    let name = name.trim().trim_matches(':');
+    if !(2..=32).contains(&name.len()) {
+        return Err("Emoji names are 2 to 32 characters long.".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("Emoji names only take letters, digits and underscores.".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// Reads the image at `path` as the data URI Discord takes for a new emoji, refusing
+/// files over `MAX_EMOJI_BYTES` and anything but PNG, JPEG and GIF before uploading.
+pub async fn read_emoji_image(path: &Path) -> Result<String, String> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+    if !metadata.is_file() {
+        return Err(format!("Not a file: {}", path.display()));
+    }
+    if metadata.len() > MAX_EMOJI_BYTES {
+        return Err(format!(
+            "{} is {} KiB, over the {} KiB emoji limit",
+            path.display(),
+            metadata.len().div_ceil(1024),
+            MAX_EMOJI_BYTES / 1024
+        ));
+    }
+
+    let data = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+    // By the content, as extensions are easily wrong.
+    let content_type = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        "image/gif"
+    } else {
+        return Err(format!(
+            "{} is not a PNG, JPEG or GIF image",
+            path.display()
+        ));
+    };
+
+    Ok(format!("data:{content_type};base64,{}", base64(&data)))
+}
+
+/// Standard base64 with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
 }
@@ -0,0 +1,498 @@
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::{
+    sync::{broadcast, mpsc::Sender, watch},
+    time::{self, Instant, Interval},
+};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Message as WsMessage, protocol::frame::coding::CloseCode},
+};
+
+use crate::{
+    Error,
+    api::{Message, User, user::OwnPresence},
+    app::AppAction,
+};
+
+const GATEWAY_URL: &str = "wss://gateway.discord.gg";
+const GATEWAY_QUERY: &str = "/?v=10&encoding=json";
+
+const OP_DISPATCH: u8 = 0;
+const OP_HEARTBEAT: u8 = 1;
+const OP_IDENTIFY: u8 = 2;
+const OP_PRESENCE_UPDATE: u8 = 3;
+const OP_RESUME: u8 = 6;
+const OP_RECONNECT: u8 = 7;
+const OP_INVALID_SESSION: u8 = 9;
+const OP_HELLO: u8 = 10;
+const OP_HEARTBEAT_ACK: u8 = 11;
+
+/// GUILDS | GUILD_VOICE_STATES | GUILD_PRESENCES | GUILD_MESSAGES | DIRECT_MESSAGES |
+/// MESSAGE_CONTENT
+const INTENTS: u64 = (1 << 0) | (1 << 7) | (1 << 8) | (1 << 9) | (1 << 12) | (1 << 15);
+
+const CLOSE_AUTHENTICATION_FAILED: u16 = 4004;
+
+/// Close codes after which Discord will never accept the same token/intents again.
+const FATAL_CLOSE_CODES: [u16; 6] = [4004, 4010, 4011, 4012, 4013, 4014];
+
+const MAX_BACKOFF_SECS: u64 = 60;
+
+#[derive(Debug, Deserialize)]
+struct Payload {
+    op: u8,
+    #[serde(default)]
+    d: Value,
+    s: Option<u64>,
+    t: Option<String>,
+}
+
+/// Someone connected to a voice or stage channel, or leaving one when `channel_id` is
+/// `None`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct VoiceState {
+    pub user_id: String,
+    pub channel_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Online,
+    Idle,
+    DoNotDisturb,
+    /// Also what invisible users look like to others.
+    Offline,
+}
+
+#[derive(Debug, Clone)]
+pub struct Presence {
+    pub user_id: String,
+    pub status: Status,
+}
+
+impl Presence {
+    /// Reads a presence as sent in PRESENCE_UPDATE, or listed by READY and GUILD_CREATE,
+    /// where the user is either an object or just `user_id`.
+    fn from_value(value: &Value) -> Option<Self> {
+        let user_id = value["user"]["id"]
+            .as_str()
+            .or_else(|| value["user_id"].as_str())?;
+        let status = match value["status"].as_str()? {
+            "online" => Status::Online,
+            "idle" => Status::Idle,
+            "dnd" => Status::DoNotDisturb,
+            "offline" | "invisible" => Status::Offline,
+            _ => return None,
+        };
+
+        Some(Self {
+            user_id: user_id.to_string(),
+            status,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    session_id: Option<String>,
+    resume_url: Option<String>,
+    sequence: Option<u64>,
+    /// Our own user id, used to ignore our own typing events.
+    user_id: Option<String>,
+    /// Set once READY or RESUMED was received on the current connection.
+    established: bool,
+}
+
+impl Session {
+    fn can_resume(&self) -> bool {
+        self.session_id.is_some() && self.sequence.is_some()
+    }
+
+    fn url(&self) -> String {
+        let base = match (&self.resume_url, self.can_resume()) {
+            (Some(url), true) => url.trim_end_matches('/'),
+            _ => GATEWAY_URL,
+        };
+        format!("{base}{GATEWAY_QUERY}")
+    }
+}
+
+enum SessionEnd {
+    Shutdown,
+    Resume,
+    Fresh,
+    Fatal,
+}
+
+/// Keeps a gateway connection alive until the shutdown broadcast fires, forwarding
+/// `MESSAGE_CREATE` events as [`AppAction::GatewayMessage`]. Every connection drop is
+/// reported with [`AppAction::GatewayStatus`] so the REST poller can take over. Our own
+/// presence follows `rx_presence`, and is sent again with each new session.
+pub async fn run_gateway(
+    token: String,
+    tx: Sender<AppAction>,
+    mut rx_shutdown: broadcast::Receiver<()>,
+    mut rx_presence: watch::Receiver<Option<OwnPresence>>,
+) {
+    let mut session = Session::default();
+    let mut backoff_secs = 1;
+
+    loop {
+        session.established = false;
+        let end = run_session(
+            &token,
+            &mut session,
+            &tx,
+            &mut rx_shutdown,
+            &mut rx_presence,
+        )
+        .await;
+
+        tx.send(AppAction::GatewayStatus(false)).await.ok();
+
+        if session.established {
+            backoff_secs = 1;
+        }
+
+        match &end {
+            Ok(SessionEnd::Shutdown) => {}
+            Ok(SessionEnd::Fatal) => tracing::error!("gateway closed for good"),
+            Ok(_) => tracing::info!("gateway disconnected, reconnecting in {backoff_secs}s"),
+            Err(e) => tracing::warn!("gateway error, reconnecting in {backoff_secs}s: {e}"),
+        }
+
+        match end {
+            Ok(SessionEnd::Shutdown) | Ok(SessionEnd::Fatal) => return,
+            Ok(SessionEnd::Resume) | Err(_) => {}
+            Ok(SessionEnd::Fresh) => session = Session::default(),
+        }
+
+        tokio::select! {
+            _ = rx_shutdown.recv() => return,
+            _ = time::sleep(Duration::from_secs(backoff_secs)) => {}
+        }
+
+        backoff_secs = (backoff_secs * 2).min(MAX_BACKOFF_SECS);
+    }
+}
+
+async fn next_heartbeat(heartbeat: &mut Option<Interval>) {
+    match heartbeat {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending::<()>().await,
+    }
+}
+
+async fn run_session(
+    token: &str,
+    session: &mut Session,
+    tx: &Sender<AppAction>,
+    rx_shutdown: &mut broadcast::Receiver<()>,
+    rx_presence: &mut watch::Receiver<Option<OwnPresence>>,
+) -> Result<SessionEnd, Error> {
+    let (mut socket, _) = tokio::select! {
+        _ = rx_shutdown.recv() => return Ok(SessionEnd::Shutdown),
+        res = connect_async(session.url()) => res?,
+    };
+
+    let mut heartbeat: Option<Interval> = None;
+    let mut awaiting_ack = false;
+
+    loop {
+        tokio::select! {
+            _ = rx_shutdown.recv() => {
+                time::timeout(Duration::from_secs(1), socket.close(None)).await.ok();
+                return Ok(SessionEnd::Shutdown);
+            }
+
+            Ok(()) = rx_presence.changed() => {
+                let presence = rx_presence.borrow_and_update().clone();
+                // Before READY, the presence goes with IDENTIFY instead.
+                if session.established
+                    && let Some(presence) = presence
+                {
+                    let update = json!({ "op": OP_PRESENCE_UPDATE, "d": presence.gateway_payload() });
+                    socket.send(WsMessage::Text(update.to_string().into())).await?;
+                }
+            }
+
+            _ = next_heartbeat(&mut heartbeat) => {
+                if awaiting_ack {
+                    // Zombied connection: the last heartbeat was never acknowledged.
+                    return Ok(SessionEnd::Resume);
+                }
+                let beat = json!({ "op": OP_HEARTBEAT, "d": session.sequence });
+                socket.send(WsMessage::Text(beat.to_string().into())).await?;
+                awaiting_ack = true;
+            }
+
+            frame = socket.next() => {
+                let text = match frame {
+                    Some(Ok(WsMessage::Text(text))) => text,
+                    Some(Ok(WsMessage::Close(close))) => {
+                        let code = close.map(|c| u16::from(c.code)).unwrap_or(u16::from(CloseCode::Normal));
+                        tracing::info!(code, "gateway closed the connection");
+                        if code == CLOSE_AUTHENTICATION_FAILED {
+                            tx.send(AppAction::Unauthorized).await.ok();
+                        }
+                        if FATAL_CLOSE_CODES.contains(&code) {
+                            return Ok(SessionEnd::Fatal);
+                        }
+                        return Ok(SessionEnd::Resume);
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Ok(SessionEnd::Resume),
+                };
+
+                let payload: Payload = match serde_json::from_str(text.as_str()) {
+                    Ok(p) => p,
+                    Err(_) => continue,
+                };
+
+                if payload.s.is_some() {
+                    session.sequence = payload.s;
+                }
+
+                match payload.op {
+                    OP_HELLO => {
+                        let period = payload.d["heartbeat_interval"].as_u64().unwrap_or(41_250);
+                        let period = Duration::from_millis(period);
+                        heartbeat = Some(time::interval_at(Instant::now() + period / 2, period));
+
+                        let handshake = if session.can_resume() {
+                            json!({
+                                "op": OP_RESUME,
+                                "d": {
+                                    "token": token,
+                                    "session_id": session.session_id,
+                                    "seq": session.sequence,
+                                }
+                            })
+                        } else {
+                            let mut identify = json!({
+                                "op": OP_IDENTIFY,
+                                "d": {
+                                    "token": token,
+                                    "intents": INTENTS,
+                                    "properties": {
+                                        "os": std::env::consts::OS,
+                                        "browser": "rivetui",
+                                        "device": "rivetui",
+                                    },
+                                }
+                            });
+                            if let Some(presence) = &*rx_presence.borrow_and_update() {
+                                identify["d"]["presence"] = presence.gateway_payload();
+                            }
+                            identify
+                        };
+                        socket.send(WsMessage::Text(handshake.to_string().into())).await?;
+                    }
+                    OP_HEARTBEAT => {
+                        let beat = json!({ "op": OP_HEARTBEAT, "d": session.sequence });
+                        socket.send(WsMessage::Text(beat.to_string().into())).await?;
+                    }
+                    OP_HEARTBEAT_ACK => awaiting_ack = false,
+                    OP_RECONNECT => return Ok(SessionEnd::Resume),
+                    OP_INVALID_SESSION => {
+                        return Ok(if payload.d.as_bool().unwrap_or(false) {
+                            SessionEnd::Resume
+                        } else {
+                            SessionEnd::Fresh
+                        });
+                    }
+                    OP_DISPATCH => handle_dispatch(payload, session, tx).await,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Forwards who is in the voice channels of a guild object, as found in READY and
+/// GUILD_CREATE.
+async fn send_voice_states(guild: &Value, tx: &Sender<AppAction>) {
+    let Some(guild_id) = guild["id"].as_str() else {
+        return;
+    };
+
+    let voice_states = guild["voice_states"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|state| serde_json::from_value::<VoiceState>(state.clone()).ok())
+        .collect();
+
+    tx.send(AppAction::GatewayVoiceStates(
+        guild_id.to_string(),
+        voice_states,
+    ))
+    .await
+    .ok();
+}
+
+async fn send_presences<'a>(presences: impl Iterator<Item = &'a Value>, tx: &Sender<AppAction>) {
+    let presences: Vec<Presence> = presences.filter_map(Presence::from_value).collect();
+    if !presences.is_empty() {
+        tx.send(AppAction::GatewayPresences(presences)).await.ok();
+    }
+}
+
+/// Items of `value` when it is an array, nothing otherwise.
+fn array_items(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+async fn handle_dispatch(payload: Payload, session: &mut Session, tx: &Sender<AppAction>) {
+    match payload.t.as_deref() {
+        Some("READY") => {
+            session.session_id = payload.d["session_id"].as_str().map(str::to_string);
+            session.resume_url = payload.d["resume_gateway_url"].as_str().map(str::to_string);
+            session.user_id = payload.d["user"]["id"].as_str().map(str::to_string);
+            session.established = true;
+            tracing::info!("gateway session ready");
+            tx.send(AppAction::GatewayStatus(true)).await.ok();
+
+            for guild in payload.d["guilds"].as_array().into_iter().flatten() {
+                send_voice_states(guild, tx).await;
+            }
+
+            // Bots get presences with each guild, user accounts get them merged in READY.
+            let merged = &payload.d["merged_presences"];
+            let presences = array_items(&payload.d["presences"])
+                .chain(array_items(&merged["friends"]))
+                .chain(array_items(&merged["guilds"]).flat_map(array_items))
+                .chain(
+                    array_items(&payload.d["guilds"]).flat_map(|g| array_items(&g["presences"])),
+                );
+            send_presences(presences, tx).await;
+        }
+        Some("GUILD_CREATE") => {
+            send_voice_states(&payload.d, tx).await;
+            send_presences(array_items(&payload.d["presences"]), tx).await;
+        }
+        Some("PRESENCE_UPDATE") => send_presences(std::iter::once(&payload.d), tx).await,
+        Some("VOICE_STATE_UPDATE") => {
+            let Some(guild_id) = payload.d["guild_id"].as_str() else {
+                return;
+            };
+            if let Ok(voice_state) = serde_json::from_value::<VoiceState>(payload.d.clone()) {
+                tx.send(AppAction::GatewayVoiceStateUpdate(
+                    guild_id.to_string(),
+                    voice_state,
+                ))
+                .await
+                .ok();
+            }
+        }
+        Some("RESUMED") => {
+            session.established = true;
+            tracing::info!("gateway session resumed");
+            tx.send(AppAction::GatewayStatus(true)).await.ok();
+        }
+        Some("TYPING_START") => {
+            let (Some(user_id), Some(channel_id)) = (
+                payload.d["user_id"].as_str(),
+                payload.d["channel_id"].as_str(),
+            ) else {
+                return;
+            };
+
+            if session.user_id.as_deref() == Some(user_id) {
+                return;
+            }
+
+            // Guild events carry the member, DM events only the id.
+            let user = serde_json::from_value::<User>(payload.d["member"]["user"].clone())
+                .unwrap_or_else(|_| User {
+                    id: user_id.to_string(),
+                    username: String::new(),
+                    global_name: None,
+                    avatar: None,
+                    bot: false,
+                });
+
+            tx.send(AppAction::TypingStarted(user, channel_id.to_string()))
+                .await
+                .ok();
+        }
+        Some("MESSAGE_CREATE") => {
+            if let Ok(message) = serde_json::from_value::<Message>(payload.d) {
+                tx.send(AppAction::GatewayMessage(message)).await.ok();
+            }
+        }
+        Some("MESSAGE_DELETE") => {
+            if let Some(message_id) = payload.d["id"].as_str() {
+                tx.send(AppAction::ApiRemoveMessage(message_id.to_string()))
+                    .await
+                    .ok();
+            }
+        }
+        Some("MESSAGE_UPDATE") => {
+            if let Ok(message) = serde_json::from_value::<Message>(payload.d) {
+                tx.send(AppAction::ApiReplaceMessage(message)).await.ok();
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    fn dispatch(event: &str, d: Value) -> Payload {
+        Payload {
+            op: OP_DISPATCH,
+            d,
+            s: None,
+            t: Some(event.to_string()),
+        }
+    }
+
+    fn typing(user_id: &str) -> Payload {
+        dispatch(
+            "TYPING_START",
+            json!({ "user_id": user_id, "channel_id": "c1", "timestamp": 0 }),
+        )
+    }
+
+    /// Who the actions sent so far say is typing.
+    fn typists(rx: &mut mpsc::Receiver<AppAction>) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|action| match action {
+                AppAction::TypingStarted(user, _) => Some(user.id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn ignores_our_own_typing() {
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut session = Session::default();
+        let ready = dispatch(
+            "READY",
+            json!({
+                "session_id": "s1",
+                "resume_gateway_url": "wss://resume.example",
+                "user": { "id": "100", "username": "tester" },
+            }),
+        );
+        handle_dispatch(ready, &mut session, &tx).await;
+        assert_eq!(session.user_id.as_deref(), Some("100"));
+
+        handle_dispatch(typing("100"), &mut session, &tx).await;
+        handle_dispatch(typing("7"), &mut session, &tx).await;
+        assert_eq!(typists(&mut rx), ["7"]);
+    }
+}
@@ -1,15 +1,59 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::api::User;
+use crate::api::{User, channel::Role};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct GuildMember {
     pub user: User,
+    pub nick: Option<String>,
     pub roles: Vec<String>,
+    #[serde(default)]
+    pub joined_at: Option<DateTime<Utc>>,
+}
+
+impl GuildMember {
+    /// Nickname, falling back to the global display name and then the username.
+    pub fn display_name(&self) -> &str {
+        self.nick
+            .as_deref()
+            .or(self.user.global_name.as_deref())
+            .unwrap_or(&self.user.username)
+    }
+
+    /// Color of the member's highest positioned role that has one.
+    pub fn role_color(&self, guild_roles: &[Role]) -> Option<u32> {
+        guild_roles
+            .iter()
+            .filter(|r| r.color != 0 && self.roles.contains(&r.id))
+            .max_by_key(|r| r.position)
+            .map(|r| r.color)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Guild {
     pub id: String,
     pub name: String,
+    /// Hash of the icon image, `None` for guilds showing their initials.
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reads_the_icon_hash_when_there_is_one() {
+        let guild: Guild =
+            serde_json::from_value(json!({ "id": "1", "name": "Rust", "icon": "a1b2" })).unwrap();
+        assert_eq!(guild.icon.as_deref(), Some("a1b2"));
+
+        // Guild lists cached before icons were kept have none.
+        let guild: Guild = serde_json::from_value(json!({ "id": "1", "name": "Rust" })).unwrap();
+        assert_eq!(guild.icon, None);
+    }
 }
@@ -0,0 +1,134 @@
+use serde::Deserialize;
+
+use crate::api::User;
+
+/// Hosts of invite links, with the path their code follows.
+const INVITE_PREFIXES: &[&str] = &[
+    "discord.gg/",
+    "discord.com/invite/",
+    "discordapp.com/invite/",
+    "canary.discord.com/invite/",
+    "ptb.discord.com/invite/",
+];
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct InviteGuild {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct InviteChannel {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+/// An invite as previewed before joining. Invites to group DMs have no guild.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Invite {
+    pub code: String,
+    #[serde(default)]
+    pub guild: Option<InviteGuild>,
+    #[serde(default)]
+    pub channel: Option<InviteChannel>,
+    #[serde(default)]
+    pub inviter: Option<User>,
+    /// Only sent when asked for with `with_counts`.
+    #[serde(default)]
+    pub approximate_member_count: Option<u64>,
+    #[serde(default)]
+    pub approximate_presence_count: Option<u64>,
+}
+
+impl Invite {
+    pub fn guild_name(&self) -> &str {
+        self.guild
+            .as_ref()
+            .map_or("a group DM", |guild| guild.name.as_str())
+    }
+}
+
+/// Reads an invite code from a `discord.gg/…` or `discord.com/invite/…` link, with or
+/// without its scheme, or from a bare code.
+pub fn parse_invite_code(text: &str) -> Option<String> {
+    let text = text.trim().trim_start_matches('<').trim_end_matches('>');
+    let rest = text
+        .strip_prefix("https://")
+        .or_else(|| text.strip_prefix("http://"))
+        .unwrap_or(text);
+    let rest = rest.strip_prefix("www.").unwrap_or(rest);
+
+    let code = match INVITE_PREFIXES
+        .iter()
+        .find_map(|prefix| rest.strip_prefix(prefix))
+    {
+        Some(code) => code.split(['?', '#']).next().unwrap_or_default(),
+        // Anything else with a slash is a link to something other than an invite.
+        None if rest.contains('/') || rest.contains('.') => return None,
+        None => rest,
+    };
+    let code = code.trim_end_matches('/');
+
+    let valid = (2..=32).contains(&code.len())
+        && code.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+    valid.then(|| code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reads_codes_from_links_and_bare_codes() {
+        for text in [
+            "abc123",
+            "discord.gg/abc123",
+            "https://discord.gg/abc123",
+            "<https://discord.gg/abc123>",
+            "http://www.discord.com/invite/abc123/",
+            "https://canary.discord.com/invite/abc123?event=1",
+            "https://discordapp.com/invite/abc123#top",
+        ] {
+            assert_eq!(parse_invite_code(text).as_deref(), Some("abc123"), "{text}");
+        }
+        assert_eq!(
+            parse_invite_code("  my-server ").as_deref(),
+            Some("my-server")
+        );
+    }
+
+    #[test]
+    fn refuses_other_links_and_malformed_codes() {
+        for text in [
+            "",
+            "a",
+            "https://discord.com/channels/1/2",
+            "https://example.com/abc123",
+            "example.com",
+            "discord.gg/",
+            "discord.gg/abc 123",
+            "not_a_code",
+            &"a".repeat(33),
+        ] {
+            assert_eq!(parse_invite_code(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn names_group_dm_invites() {
+        let invite: Invite = serde_json::from_value(json!({ "code": "abc" })).unwrap();
+        assert_eq!(invite.guild_name(), "a group DM");
+        assert_eq!(invite.approximate_member_count, None);
+
+        let invite: Invite = serde_json::from_value(json!({
+            "code": "abc",
+            "guild": { "id": "1", "name": "Rust" },
+            "approximate_member_count": 12,
+        }))
+        .unwrap();
+        assert_eq!(invite.guild_name(), "Rust");
+        assert_eq!(invite.approximate_member_count, Some(12));
+    }
+}
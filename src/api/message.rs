@@ -1,34 +1,269 @@
-use serde::Deserialize;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
 use crate::api::User;
 
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReactionEmoji {
+    pub id: Option<String>,
+    pub name: Option<String>,
+}
+
+impl ReactionEmoji {
+    /// Parses the `name:id` form used for custom emojis, or a bare Unicode emoji.
+    pub fn from_api_name(api_name: &str) -> Self {
+        match api_name.split_once(':') {
+            Some((name, id)) => Self {
+                id: Some(id.to_string()),
+                name: Some(name.to_string()),
+            },
+            None => Self {
+                id: None,
+                name: Some(api_name.to_string()),
+            },
+        }
+    }
+
+    /// The form the reaction endpoints expect before URL-encoding.
+    pub fn api_name(&self) -> String {
+        let name = self.name.as_deref().unwrap_or("");
+        match &self.id {
+            Some(id) => format!("{name}:{id}"),
+            None => name.to_string(),
+        }
+    }
+
+    pub fn display(&self) -> String {
+        let name = self.name.as_deref().unwrap_or("?");
+        match &self.id {
+            Some(_) => format!(":{name}:"),
+            None => name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Reaction {
+    pub count: u32,
+    pub me: bool,
+    pub emoji: ReactionEmoji,
+}
+
+/// Percent-encodes an emoji for use as a path segment, leaving the `name:id`
+/// separator of custom emojis intact.
+pub fn encode_emoji(emoji: &str) -> String {
+    emoji
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub filename: String,
+    pub url: String,
+    pub content_type: Option<String>,
+    pub size: u64,
+}
+
+impl Attachment {
+    /// Whether the file was sent as a spoiler, which Discord marks in its name.
+    pub fn is_spoiler(&self) -> bool {
+        self.filename.starts_with("SPOILER_")
+    }
+}
+
+/// Longest message Discord accepts from accounts without Nitro, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Length of a message the way Discord counts it against `MAX_MESSAGE_LENGTH`: in
+/// Unicode code points, so custom emoji markup like `<:name:id>` counts in full.
+pub fn message_length(content: &str) -> usize {
+    content.chars().count()
+}
+
+/// What a mention can ping, in the `parse` list of `allowed_mentions`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MentionKind {
+    Users,
+    Roles,
+    /// `@everyone` and `@here`.
+    Everyone,
+}
+
+/// What a sent message may ping, whatever its content mentions. Sent with every message,
+/// so a stray `@everyone` only pings when it was meant to.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AllowedMentions {
+    pub parse: Vec<MentionKind>,
+    /// Pings the author of the message replied to.
+    pub replied_user: bool,
+}
+
+impl AllowedMentions {
+    pub fn new(parse: Vec<MentionKind>) -> Self {
+        Self {
+            parse,
+            replied_user: true,
+        }
+    }
+
+    pub fn all() -> Self {
+        Self::new(vec![
+            MentionKind::Users,
+            MentionKind::Roles,
+            MentionKind::Everyone,
+        ])
+    }
+
+    pub fn allows(&self, kind: MentionKind) -> bool {
+        self.parse.contains(&kind)
+    }
+}
+
+/// Largest file Discord accepts from accounts without Nitro, in bytes.
+pub const MAX_UPLOAD_BYTES: u64 = 10 * 1024 * 1024;
+
+/// A file read from disk, to be sent with a message.
+#[derive(Debug, Clone)]
+pub struct FileUpload {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+impl FileUpload {
+    /// Reads the file at `path`, refusing it before reading when it is over
+    /// `MAX_UPLOAD_BYTES`.
+    pub async fn read(path: &Path) -> Result<Self, String> {
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("Not a file: {}", path.display()))?;
+
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+        if !metadata.is_file() {
+            return Err(format!("Not a file: {}", path.display()));
+        }
+        if metadata.len() > MAX_UPLOAD_BYTES {
+            return Err(format!(
+                "{filename} is {:.1} MiB, over Discord's {} MiB upload limit",
+                metadata.len() as f64 / (1024.0 * 1024.0),
+                MAX_UPLOAD_BYTES / (1024 * 1024)
+            ));
+        }
+
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+
+        Ok(Self {
+            content_type: content_type(&filename),
+            filename,
+            data,
+        })
+    }
+}
+
+/// MIME type of a file from its extension, for the types Discord previews.
+fn content_type(filename: &str) -> &'static str {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct StickerItem {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Embed {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Flag of a message published from an announcement channel to those following it.
+pub const CROSSPOSTED: u64 = 1 << 0;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Message {
-    //pub id: Snowflake,
-    //pub channel_id: Snowflake,
+    pub id: String,
+    pub channel_id: String,
+    /// Only set on messages received from the gateway.
+    #[serde(default)]
+    pub guild_id: Option<String>,
     pub author: User,
     pub content: Option<String>,
-    pub timestamp: String,
-    /*pub edited_timestamp: Option<Timestamp>,
-    pub tts: bool,
-    pub mention_everyone: bool,
+    /// `None` when Discord sent none, or one that doesn't parse; the chat shows `??:??`.
+    #[serde(default, deserialize_with = "lenient_timestamp")]
+    pub timestamp: Option<DateTime<Utc>>,
+    pub edited_timestamp: Option<String>,
+    #[serde(default)]
+    pub referenced_message: Option<Box<Message>>,
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default)]
     pub mentions: Vec<User>,
+    #[serde(default)]
+    pub sticker_items: Vec<StickerItem>,
+    /// Bit field, see `CROSSPOSTED`.
+    #[serde(default)]
+    pub flags: u64,
+    /// 0 for a plain message; the others are mostly system messages such as joins and
+    /// pins, see `ui::system`.
+    #[serde(default, rename = "type")]
+    pub message_type: u8,
+    /*pub tts: bool,
+    pub mention_everyone: bool,
     pub mention_roles: Vec<Role>,
     pub mention_channels: Vec<ChannelMention>,
-    pub attachments: Vec<Attachment>,
-    pub embeds: Vec<Embed>,
-    pub reactions: Vec<Reaction>,
     pub nonce: Nonce,
     pub pinned: bool,
     pub webhook_id: Option<Snowflake>,
-    pub message_type: i32,
     pub activity: Option<MessageActivity>,
     pub application: Option<Application>,
     pub application_id: Snowflake,
-    pub flags: Option<i32>,
     pub message_reference: Option<MessageReference>,
     pub message_snapshots: Option<Vec<MessageSnapshot>>,
-    pub referenced_message: Option<Box<Message>>,
     pub interaction_metadata: Option<Box<MessageInteractionMetadata>>,
     pub interaction: Option<Box<MessageInteraction>>,
     pub thread: Option<Channel>,
@@ -41,3 +276,289 @@ pub struct Message {
     pub poll: Option<Box<Poll>>,
     pub call: Option<MessageCall>,*/
 }
+
+/// Reads a timestamp, leaving it out rather than refusing the whole message, and with it
+/// the page it came in, when it isn't a valid RFC 3339 time.
+fn lenient_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    Ok(value.as_str().and_then(|text| text.parse().ok()))
+}
+
+impl Message {
+    pub fn snowflake(&self) -> u64 {
+        self.id.parse().unwrap_or(0)
+    }
+
+    /// Whether `other`, a fresher copy of this message, shows the same reactions, embeds,
+    /// attachments and replied-to message. Those change without the message being edited.
+    pub fn same_extras(&self, other: &Self) -> bool {
+        self.reactions == other.reactions
+            && self.embeds == other.embeds
+            && self.attachments == other.attachments
+            && self.referenced_message.as_ref().map(|reply| &reply.content)
+                == other
+                    .referenced_message
+                    .as_ref()
+                    .map(|reply| &reply.content)
+    }
+
+    /// Whether the message was published to the channels following its announcement
+    /// channel.
+    pub fn is_crossposted(&self) -> bool {
+        self.flags & CROSSPOSTED != 0
+    }
+
+    /// Merges a fresh newest-first page into the already loaded history. Loaded messages
+    /// covered by the fresh page are replaced by it, older ones are kept.
+    pub fn merge_latest(existing: Vec<Self>, latest: Vec<Self>) -> Vec<Self> {
+        let Some((oldest, channel_id)) =
+            latest.last().map(|m| (m.snowflake(), m.channel_id.clone()))
+        else {
+            return existing;
+        };
+
+        let older = existing
+            .into_iter()
+            .filter(|m| m.channel_id == channel_id && m.snowflake() < oldest);
+
+        latest.into_iter().chain(older).collect()
+    }
+
+    /// Merges messages fetched with `after` into the loaded history: already loaded ones
+    /// are replaced, in case they were edited, and the rest are added in order.
+    pub fn merge_newer(mut existing: Vec<Self>, newer: Vec<Self>) -> Vec<Self> {
+        for message in newer {
+            match existing.iter_mut().find(|m| m.id == message.id) {
+                Some(loaded) => *loaded = message,
+                None => existing.push(message),
+            }
+        }
+        existing.sort_by_key(|m| std::cmp::Reverse(m.snowflake()));
+        existing
+    }
+
+    /// Appends a newest-first page fetched with `before` to the end of the loaded history,
+    /// skipping anything already present.
+    pub fn merge_older(mut existing: Vec<Self>, older: Vec<Self>) -> Vec<Self> {
+        let oldest = existing.last().map(Self::snowflake).unwrap_or(u64::MAX);
+        existing.extend(older.into_iter().filter(|m| m.snowflake() < oldest));
+        existing
+    }
+}
+
+#[cfg(test)]
+impl Message {
+    /// A plain message in channel `c1`, posted by alice (id `2`) at noon UTC on
+    /// 2024-01-01. Tests change the fields they are about.
+    pub fn for_tests(id: &str, content: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            channel_id: "c1".to_string(),
+            guild_id: None,
+            author: User::for_tests("2", "alice"),
+            content: Some(content.to_string()),
+            timestamp: "2024-01-01T12:00:00Z".parse().ok(),
+            edited_timestamp: None,
+            referenced_message: None,
+            reactions: Vec::new(),
+            attachments: Vec::new(),
+            embeds: Vec::new(),
+            mentions: Vec::new(),
+            sticker_items: Vec::new(),
+            flags: 0,
+            message_type: 0,
+        }
+    }
+
+    pub fn by(mut self, id: &str, username: &str) -> Self {
+        self.author = User::for_tests(id, username);
+        self
+    }
+
+    pub fn mentioning(mut self, id: &str, username: &str) -> Self {
+        self.mentions.push(User::for_tests(id, username));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn ids(messages: &[Message]) -> Vec<u64> {
+        messages.iter().map(Message::snowflake).collect()
+    }
+
+    #[test]
+    fn merges_newer_messages_in_order() {
+        let existing = vec![Message::for_tests("5", "e"), Message::for_tests("3", "c")];
+        // As Discord returns `after` pages: oldest first.
+        let newer = vec![Message::for_tests("6", "f"), Message::for_tests("7", "g")];
+
+        let merged = Message::merge_newer(existing, newer);
+        assert_eq!(ids(&merged), [7, 6, 5, 3]);
+    }
+
+    #[test]
+    fn replaces_newer_messages_already_loaded() {
+        let existing = vec![
+            Message::for_tests("5", "before"),
+            Message::for_tests("3", "c"),
+        ];
+
+        let merged = Message::merge_newer(existing, vec![Message::for_tests("5", "after")]);
+        assert_eq!(ids(&merged), [5, 3]);
+        assert_eq!(merged[0].content.as_deref(), Some("after"));
+    }
+
+    #[test]
+    fn drops_what_the_latest_page_no_longer_has() {
+        // Message 4 was deleted since.
+        let existing = vec![
+            Message::for_tests("5", "e"),
+            Message::for_tests("4", "d"),
+            Message::for_tests("3", "c"),
+            Message::for_tests("1", "a"),
+        ];
+        let latest = vec![
+            Message::for_tests("6", "f"),
+            Message::for_tests("5", "e"),
+            Message::for_tests("3", "c"),
+        ];
+
+        let merged = Message::merge_latest(existing, latest);
+        assert_eq!(ids(&merged), [6, 5, 3, 1]);
+    }
+
+    #[test]
+    fn keeps_the_history_for_an_empty_latest_page() {
+        let existing = vec![Message::for_tests("5", "e")];
+        assert_eq!(ids(&Message::merge_latest(existing, Vec::new())), [5]);
+    }
+
+    #[test]
+    fn appends_only_older_messages() {
+        let existing = vec![Message::for_tests("5", "e"), Message::for_tests("3", "c")];
+        let older = vec![Message::for_tests("3", "c"), Message::for_tests("2", "b")];
+        assert_eq!(ids(&Message::merge_older(existing, older)), [5, 3, 2]);
+    }
+
+    #[test]
+    fn round_trips_reaction_emoji_names() {
+        let custom = ReactionEmoji::from_api_name("party:1234");
+        assert_eq!(custom.id.as_deref(), Some("1234"));
+        assert_eq!(custom.api_name(), "party:1234");
+        assert_eq!(custom.display(), ":party:");
+
+        let unicode = ReactionEmoji::from_api_name("👍");
+        assert_eq!(unicode.id, None);
+        assert_eq!(unicode.api_name(), "👍");
+        assert_eq!(unicode.display(), "👍");
+    }
+
+    #[test]
+    fn percent_encodes_emoji_path_segments() {
+        assert_eq!(encode_emoji("👍"), "%F0%9F%91%8D");
+        assert_eq!(encode_emoji("party_1:1234"), "party_1:1234");
+        assert_eq!(encode_emoji("a/b"), "a%2Fb");
+    }
+
+    #[test]
+    fn keeps_messages_with_missing_or_invalid_timestamps() {
+        let message = |timestamp: Option<Value>| {
+            let mut message = json!({
+                "id": "1",
+                "channel_id": "10",
+                "author": { "id": "1", "username": "someone" },
+                "content": "hi",
+                "edited_timestamp": null,
+            });
+            if let Some(timestamp) = timestamp {
+                message["timestamp"] = timestamp;
+            }
+            message
+        };
+        let page: Vec<Message> = serde_json::from_value(json!([
+            message(Some(json!("2024-01-01T12:00:00.123+00:00"))),
+            message(Some(json!("yesterday"))),
+            message(Some(json!(1704110400))),
+            message(Some(Value::Null)),
+            message(None),
+        ]))
+        .unwrap();
+
+        let timestamps: Vec<_> = page.iter().map(|message| message.timestamp).collect();
+        assert_eq!(
+            timestamps,
+            [
+                Some("2024-01-01T12:00:00.123Z".parse().unwrap()),
+                None,
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn deserializes_reactions() {
+        let reacted: Message = serde_json::from_value(json!({
+            "id": "1",
+            "channel_id": "10",
+            "author": { "id": "1", "username": "someone" },
+            "content": "hi",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "edited_timestamp": null,
+            "reactions": [
+                { "count": 3, "me": true, "emoji": { "id": null, "name": "👍" } },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(reacted.reactions.len(), 1);
+        assert_eq!(reacted.reactions[0].count, 3);
+        assert!(reacted.reactions[0].me);
+        assert!(Message::for_tests("2", "no reactions").reactions.is_empty());
+    }
+
+    #[test]
+    fn counts_message_length_in_code_points() {
+        assert_eq!(message_length("hello"), 5);
+        assert_eq!(message_length("日本語"), 3);
+        assert_eq!(message_length("é"), 1);
+        // A skin tone modifier is a code point of its own.
+        assert_eq!(message_length("👍🏽"), 2);
+        assert_eq!(message_length("<:wave:12>"), 10);
+    }
+
+    #[test]
+    fn serializes_allowed_mentions() {
+        let users_only = AllowedMentions::new(vec![MentionKind::Users]);
+        assert_eq!(
+            serde_json::to_value(&users_only).unwrap(),
+            json!({ "parse": ["users"], "replied_user": true })
+        );
+        assert!(users_only.allows(MentionKind::Users));
+        assert!(!users_only.allows(MentionKind::Everyone));
+
+        let all = AllowedMentions::all();
+        assert!(all.allows(MentionKind::Roles) && all.allows(MentionKind::Everyone));
+    }
+
+    #[test]
+    fn tells_spoiler_attachments_by_their_name() {
+        let attachment = |filename: &str| Attachment {
+            filename: filename.to_string(),
+            url: String::new(),
+            content_type: None,
+            size: 0,
+        };
+        assert!(attachment("SPOILER_cat.png").is_spoiler());
+        assert!(!attachment("cat.png").is_spoiler());
+        assert!(!attachment("spoiler_cat.png").is_spoiler());
+    }
+}
@@ -1,89 +1,649 @@
 pub mod channel;
 pub mod dm;
 pub mod emoji;
+pub mod gateway;
 pub mod guild;
+pub mod invite;
 pub mod message;
+pub mod ratelimit;
 pub mod user;
+pub mod webhook;
 
-use reqwest::{Client, Method};
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method, Response, StatusCode, header::CONTENT_TYPE};
 
 pub use channel::Channel;
 pub use dm::DM;
 pub use emoji::Emoji;
 pub use guild::Guild;
 pub use message::Message;
-use serde::de::DeserializeOwned;
+use serde::{Deserialize, de::DeserializeOwned};
 pub use user::User;
 
-use crate::{
-    Error,
-    api::{
-        channel::{PermissionContext, Role},
-        guild::GuildMember,
-    },
+use crate::api::{
+    channel::{PermissionContext, Role, ThreadList},
+    guild::GuildMember,
+    invite::Invite,
+    message::{AllowedMentions, FileUpload},
+    ratelimit::{RateLimiter, route_key},
+    user::OwnPresence,
+    webhook::Webhook,
 };
 
+const DISCORD_BASE_URL: &str = "https://discord.com/api/v10";
+/// Points the REST API somewhere else, such as a local fake of Discord to develop against.
+const API_BASE_ENV: &str = "RIVET_API_BASE";
+/// Attempts made for a request that keeps getting rate limited.
+const MAX_ATTEMPTS: u32 = 3;
+/// Longest a request waits on a rate limit before failing instead. Some routes, such as
+/// publishing announcements, are limited for an hour at a time.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+/// Forces the kind of token, `bot` or `user`, when guessing from the token is not enough.
+pub const TOKEN_TYPE_ENV: &str = "DISCORD_TOKEN_TYPE";
+/// Shown when a token is rejected while being sent as a user token.
+pub const BOT_TOKEN_HINT: &str =
+    "For a bot token, set DISCORD_TOKEN_TYPE=bot or prefix the token with \"Bot \".";
+const BOT_PREFIX: &str = "Bot ";
+/// JSON error codes of endpoints closed to bots, and of endpoints only bots may use.
+const CODE_NOT_FOR_BOTS: u64 = 20001;
+const CODE_BOTS_ONLY: u64 = 20002;
+/// JSON error code of a DM refused by its recipient's privacy settings.
+const CODE_CANNOT_DM: u64 = 50007;
+/// JSON error code of an invite that doesn't exist, or expired.
+const CODE_UNKNOWN_INVITE: u64 = 10006;
+/// JSON error code of a 429 from the slowmode of a channel, not from a rate limit.
+const CODE_SLOWMODE: u64 = 20016;
+/// JSON error code of a request refused while we are timed out in its guild.
+const CODE_TIMED_OUT: u64 = 40058;
+/// JSON error code of a form with invalid fields, listed in its `errors`.
+const CODE_INVALID_FORM: u64 = 50035;
+/// Guilds per request, the most Discord allows, and the pause between two pages.
+pub const GUILD_PAGE_SIZE: usize = 200;
+pub const GUILD_PAGE_DELAY: Duration = Duration::from_millis(500);
+/// Longest part of a non-JSON error body kept in the error.
+const MAX_ERROR_BODY_LEN: usize = 200;
+
+/// Discord ids count milliseconds from the start of 2015, this many after the Unix epoch.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+/// Where REST requests go: `RIVET_API_BASE` when set, or else Discord.
+pub fn api_base_url() -> String {
+    std::env::var(API_BASE_ENV)
+        .ok()
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| DISCORD_BASE_URL.to_string())
+}
+
+/// When the object with this id was created, which Discord ids encode in their top bits.
+pub fn snowflake_to_datetime(id: &str) -> Option<DateTime<Utc>> {
+    let id: u64 = id.parse().ok()?;
+    DateTime::from_timestamp_millis((id >> 22) as i64 + DISCORD_EPOCH_MS)
+}
+
+/// Body of a request: JSON, or an already encoded `multipart/form-data` form for uploads.
+enum Body {
+    Json(serde_json::Value),
+    Multipart {
+        content_type: String,
+        bytes: Vec<u8>,
+    },
+}
+
+impl Body {
+    /// A form with the JSON `payload` followed by each file as `files[n]`, as the message
+    /// endpoints expect for attachments.
+    fn multipart(payload: &serde_json::Value, files: &[FileUpload]) -> Self {
+        let boundary = format!(
+            "rivet-{:x}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        );
+
+        let mut bytes = Vec::new();
+        bytes.extend(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"payload_json\"\r\n\
+                 Content-Type: application/json\r\n\r\n{payload}\r\n"
+            )
+            .as_bytes(),
+        );
+        for (index, file) in files.iter().enumerate() {
+            let filename = file.filename.replace(['"', '\r', '\n'], "_");
+            bytes.extend(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"files[{index}]\"; \
+                     filename=\"{filename}\"\r\nContent-Type: {}\r\n\r\n",
+                    file.content_type
+                )
+                .as_bytes(),
+            );
+            bytes.extend(&file.data);
+            bytes.extend(b"\r\n");
+        }
+        bytes.extend(format!("--{boundary}--\r\n").as_bytes());
+
+        Self::Multipart {
+            content_type: format!("multipart/form-data; boundary={boundary}"),
+            bytes,
+        }
+    }
+}
+
+/// Kind of account a token belongs to, which decides the `Authorization` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenType {
+    User,
+    Bot,
+}
+
+impl TokenType {
+    /// Splits off a `Bot ` prefix from `token`. Without one, `DISCORD_TOKEN_TYPE` decides,
+    /// and otherwise the token is taken for a user token: both kinds look the same.
+    pub fn detect(token: &str) -> (Self, &str) {
+        if let Some(token) = token.strip_prefix(BOT_PREFIX) {
+            return (Self::Bot, token);
+        }
+
+        let token_type = match std::env::var(TOKEN_TYPE_ENV) {
+            Ok(value) if value.eq_ignore_ascii_case("bot") => Self::Bot,
+            _ => Self::User,
+        };
+        (token_type, token)
+    }
+
+    /// What to tell the user when Discord rejects a token of this kind, with the ways to
+    /// fix it.
+    pub fn rejected_message(self) -> String {
+        let message = "Discord rejected the token (401 Unauthorized). Check DISCORD_TOKEN, or run with --logout to forget the stored one.";
+        match self {
+            Self::User => format!("{message} {BOT_TOKEN_HINT}"),
+            Self::Bot => message.to_string(),
+        }
+    }
+}
+
+/// Body of an error response, such as `{"code": 50013, "message": "Missing Permissions"}`.
+#[derive(Debug, Deserialize)]
+struct DiscordApiErrorBody {
+    code: u64,
+    #[serde(default)]
+    message: String,
+    /// What was wrong with each field of a rejected form.
+    #[serde(default)]
+    errors: Option<serde_json::Value>,
+}
+
+impl DiscordApiErrorBody {
+    /// A short text for the common codes, otherwise what Discord said.
+    fn friendly_message(&self) -> String {
+        let friendly = match self.code {
+            10003 => "Unknown channel",
+            10008 => "Unknown message",
+            20028 => "This channel is receiving too many messages, try again shortly",
+            40005 => "Too large for Discord",
+            50001 => "Missing access",
+            50013 => "Missing permissions",
+            _ => return self.message.clone(),
+        };
+        friendly.to_string()
+    }
+}
+
+/// Errors of the API. Those carrying a `String` hold a text fit for the status bar.
+#[derive(Debug)]
+pub enum ApiError {
+    Unauthorized,
+    Forbidden(String),
+    NotFound(String),
+    /// The endpoint can't be used with a bot token.
+    NotForBots,
+    /// The endpoint can only be used with a bot token.
+    BotsOnly,
+    /// The user doesn't accept direct messages from us.
+    CannotDm,
+    /// The invite is mistyped, expired or was revoked.
+    UnknownInvite,
+    RateLimited {
+        retry_after: f64,
+    },
+    /// The channel's slowmode allows our next message after `retry_after` seconds.
+    SlowMode {
+        retry_after: f64,
+    },
+    /// We are timed out in the guild and can't talk until the timeout ends.
+    TimedOut,
+    /// A field of the request was rejected. `field` is its path, such as `content` or
+    /// `embeds.0.title`, and `code` Discord's reason, such as `BASE_TYPE_MAX_LENGTH`.
+    InvalidForm {
+        field: String,
+        code: String,
+        message: String,
+    },
+    Http(StatusCode, String),
+    Network(reqwest::Error),
+    Decode(serde_json::Error),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "Discord rejected the token (401 Unauthorized)"),
+            Self::Forbidden(message) | Self::NotFound(message) => write!(f, "{message}"),
+            Self::NotForBots => write!(f, "Not available to bot accounts"),
+            Self::BotsOnly => write!(f, "Only available to bot accounts"),
+            Self::CannotDm => write!(f, "Cannot DM this user"),
+            Self::UnknownInvite => write!(f, "This invite is invalid or has expired"),
+            Self::RateLimited { retry_after } => {
+                write!(f, "Rate limited, retry after {retry_after:.1}s")
+            }
+            Self::SlowMode { retry_after } => {
+                write!(f, "Slowmode is on, wait {retry_after:.0}s")
+            }
+            Self::TimedOut => write!(f, "You are timed out in this server"),
+            Self::InvalidForm { field, message, .. } => write!(f, "{field}: {message}"),
+            Self::Http(status, message) => write!(f, "{message} ({status})"),
+            Self::Network(e) => write!(f, "Network error: {e}"),
+            Self::Decode(e) => write!(f, "Unexpected response: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    /// Whether the content of a message was refused for its length.
+    pub fn is_too_long(&self) -> bool {
+        matches!(self, Self::InvalidForm { field, code, .. }
+            if field == "content" && code.ends_with("MAX_LENGTH"))
+    }
+}
+
+/// The path, code and message of the first rejected field in the `errors` of an invalid
+/// form, where each field nests down to an `_errors` list.
+fn first_form_error(errors: &serde_json::Value) -> Option<(String, String, String)> {
+    let fields = errors.as_object()?;
+    if let Some(error) = fields.get("_errors").and_then(|errors| errors.get(0)) {
+        return Some((
+            String::new(),
+            error["code"].as_str().unwrap_or_default().to_string(),
+            error["message"].as_str().unwrap_or_default().to_string(),
+        ));
+    }
+    fields.iter().find_map(|(name, nested)| {
+        let (path, code, message) = first_form_error(nested)?;
+        let path = if path.is_empty() {
+            name.clone()
+        } else {
+            format!("{name}.{path}")
+        };
+        Some((path, code, message))
+    })
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Network(e)
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Decode(e)
+    }
+}
+
+/// Client for Discord's REST API; every endpoint is a method. `base_url` is taken as is,
+/// so it can also point at a local server standing in for Discord.
 #[derive(Debug, Clone)]
 pub struct ApiClient {
     pub http_client: Client,
+    /// The token without any `Bot ` prefix, as the gateway wants it.
     pub auth_token: String,
+    pub token_type: TokenType,
     pub base_url: String,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Requests sent and not answered yet, across every clone of the client.
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// Counts a request in `ApiClient::in_flight` until it is dropped, whichever way the
+/// request ends.
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn start(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl ApiClient {
     pub fn new(http_client: Client, auth_token: String, base_url: String) -> Self {
+        let (token_type, auth_token) = TokenType::detect(&auth_token);
         Self {
             http_client,
-            auth_token,
+            auth_token: auth_token.to_string(),
+            token_type,
             base_url,
+            rate_limiter: Arc::new(RateLimiter::default()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
         }
     }
 
-    async fn api_request<T: DeserializeOwned>(
+    /// Number of requests waiting for an answer.
+    pub fn requests_in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Value of the `Authorization` header: bot tokens go with a `Bot ` prefix.
+    fn authorization(&self) -> String {
+        match self.token_type {
+            TokenType::User => self.auth_token.clone(),
+            TokenType::Bot => format!("{BOT_PREFIX}{}", self.auth_token),
+        }
+    }
+
+    async fn send_request(
         &self,
         endpoint: &str,
         method: Method,
-        body: Option<serde_json::Value>,
-    ) -> Result<T, Error> {
+        body: Option<Body>,
+    ) -> Result<Response, ApiError> {
+        self.send(endpoint, method, body, Some(self.authorization()))
+            .await
+    }
+
+    /// Sends a request, waiting for its rate-limit bucket first and retrying after a
+    /// 429 up to `MAX_ATTEMPTS` times. A wait longer than `MAX_RATE_LIMIT_WAIT` fails
+    /// with `ApiError::RateLimited` instead, and the route stays closed until it is over.
+    /// Without `authorization`, no `Authorization` header
+    /// is sent, as for executing a webhook, whose token is in the URL instead.
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(method = %method, route = %route_key(&method, endpoint))
+    )]
+    async fn send(
+        &self,
+        endpoint: &str,
+        method: Method,
+        body: Option<Body>,
+        authorization: Option<String>,
+    ) -> Result<Response, ApiError> {
+        let _in_flight = InFlight::start(&self.in_flight);
         let url = format!("{}/{}", self.base_url, endpoint);
-        let mut request = self
-            .http_client
-            .request(method, &url)
-            .header("Authorization", self.auth_token.as_str());
+        let route = route_key(&method, endpoint);
+        let started = Instant::now();
+        let mut attempt = 1;
 
-        if let Some(data) = body {
-            request = request.json(&data);
-        }
+        let response = loop {
+            if let Some(wait) = self.rate_limiter.wait_time(&route) {
+                if wait > MAX_RATE_LIMIT_WAIT {
+                    return Err(ApiError::RateLimited {
+                        retry_after: wait.as_secs_f64(),
+                    });
+                }
+                tokio::time::sleep(wait).await;
+            }
+
+            let mut request = self.http_client.request(method.clone(), &url);
+            if let Some(authorization) = &authorization {
+                request = request.header("Authorization", authorization);
+            }
+
+            match &body {
+                Some(Body::Json(data)) => request = request.json(data),
+                Some(Body::Multipart {
+                    content_type,
+                    bytes,
+                }) => {
+                    request = request
+                        .header(CONTENT_TYPE, content_type)
+                        .body(bytes.clone())
+                }
+                None => {}
+            }
+
+            let response = request.send().await.inspect_err(|e| {
+                tracing::warn!(
+                    duration_ms = started.elapsed().as_millis(),
+                    "request failed: {e}"
+                );
+            })?;
+            self.rate_limiter.update(&route, response.headers());
+
+            if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= MAX_ATTEMPTS {
+                break response;
+            }
+
+            let limited = response.json::<serde_json::Value>().await.ok();
+            let retry_after = limited
+                .as_ref()
+                .and_then(|body| body["retry_after"].as_f64())
+                .unwrap_or(1.0);
+            if limited.is_some_and(|body| body["code"].as_u64() == Some(CODE_SLOWMODE)) {
+                // Waiting out a slowmode would hold the message back silently.
+                tracing::info!("slowmode, next message in {retry_after:.1}s");
+                return Err(ApiError::SlowMode { retry_after });
+            }
+            let retry_after = Duration::from_secs_f64(retry_after.max(0.0));
+            self.rate_limiter.exhaust(&route, retry_after);
+            if retry_after > MAX_RATE_LIMIT_WAIT {
+                tracing::warn!("rate limited for {retry_after:?}, not retrying");
+                return Err(ApiError::RateLimited {
+                    retry_after: retry_after.as_secs_f64(),
+                });
+            }
+            tracing::info!("rate limited, retrying in {retry_after:?}");
+
+            self.rate_limiter.set_limited(Some(retry_after));
+            tokio::time::sleep(retry_after).await;
+            self.rate_limiter.set_limited(None);
+
+            attempt += 1;
+        };
 
-        let response = request.send().await?;
         let status = response.status();
+        let duration_ms = started.elapsed().as_millis();
 
         if status.is_success() {
-            Ok(response.json::<T>().await?)
-        } else {
-            let body = response
-                .text()
-                .await
-                .unwrap_or("Failed to read error body".to_string());
-            Err(format!("API Error: Status {status}. Details: {body}").into())
+            tracing::debug!(status = status.as_u16(), duration_ms, "request done");
+            return Ok(response);
+        }
+
+        let body = response
+            .text()
+            .await
+            .unwrap_or("Failed to read error body".to_string());
+
+        let json = serde_json::from_str::<serde_json::Value>(&body).ok();
+        let error_body = serde_json::from_str::<DiscordApiErrorBody>(&body).ok();
+        let code = error_body.as_ref().map(|error| error.code);
+        match &error_body {
+            Some(error) => tracing::warn!(
+                status = status.as_u16(),
+                duration_ms,
+                code = error.code,
+                errors = error.errors.as_ref().map(|errors| errors.to_string()),
+                "request failed: {}",
+                error.message
+            ),
+            None => tracing::warn!(status = status.as_u16(), duration_ms, "request failed"),
         }
+
+        // What the status bar shows: Discord's message made friendly, or the start of a
+        // body that isn't JSON, such as a proxy's error page.
+        let message = match &error_body {
+            Some(error) => error.friendly_message(),
+            None if body.trim().is_empty() => status
+                .canonical_reason()
+                .unwrap_or("Request failed")
+                .to_string(),
+            None => body.trim().chars().take(MAX_ERROR_BODY_LEN).collect(),
+        };
+
+        Err(match (status, code) {
+            (_, Some(CODE_NOT_FOR_BOTS)) => ApiError::NotForBots,
+            (_, Some(CODE_BOTS_ONLY)) => ApiError::BotsOnly,
+            (_, Some(CODE_CANNOT_DM)) => ApiError::CannotDm,
+            (_, Some(CODE_UNKNOWN_INVITE)) => ApiError::UnknownInvite,
+            (_, Some(CODE_TIMED_OUT)) => ApiError::TimedOut,
+            (_, Some(CODE_SLOWMODE)) => ApiError::SlowMode {
+                retry_after: json
+                    .and_then(|json| json["retry_after"].as_f64())
+                    .unwrap_or(0.0),
+            },
+            (_, Some(CODE_INVALID_FORM))
+                if let Some((field, code, message)) = error_body
+                    .as_ref()
+                    .and_then(|error| error.errors.as_ref())
+                    .and_then(first_form_error) =>
+            {
+                ApiError::InvalidForm {
+                    field,
+                    code,
+                    message,
+                }
+            }
+            (StatusCode::UNAUTHORIZED, _) => ApiError::Unauthorized,
+            (StatusCode::FORBIDDEN, None) => ApiError::Forbidden("Missing access".to_string()),
+            (StatusCode::FORBIDDEN, _) => ApiError::Forbidden(message),
+            (StatusCode::NOT_FOUND, None) => ApiError::NotFound("Not found".to_string()),
+            (StatusCode::NOT_FOUND, _) => ApiError::NotFound(message),
+            (StatusCode::TOO_MANY_REQUESTS, _) => ApiError::RateLimited {
+                retry_after: json
+                    .and_then(|json| json["retry_after"].as_f64())
+                    .unwrap_or(0.0),
+            },
+            _ => ApiError::Http(status, message),
+        })
+    }
+
+    async fn api_request<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        method: Method,
+        body: Option<serde_json::Value>,
+    ) -> Result<T, ApiError> {
+        let response = self
+            .send_request(endpoint, method, body.map(Body::Json))
+            .await?;
+        let bytes = response.bytes().await?;
+        Ok(serde_json::from_slice::<T>(&bytes)?)
+    }
+
+    /// Same as `api_request`, for endpoints answering with `204 No Content`.
+    async fn api_request_empty(
+        &self,
+        endpoint: &str,
+        method: Method,
+        body: Option<serde_json::Value>,
+    ) -> Result<(), ApiError> {
+        self.send_request(endpoint, method, body.map(Body::Json))
+            .await?;
+        Ok(())
     }
 
-    pub async fn get_current_user(&self) -> Result<User, Error> {
+    pub async fn get_current_user(&self) -> Result<User, ApiError> {
         self.api_request("users/@me", Method::GET, None).await
     }
 
-    pub async fn get_channel(&self, channel_id: &str) -> Result<Channel, Error> {
+    /// Sets our status and custom status in the account settings, how user accounts change
+    /// their presence without the gateway.
+    pub async fn update_presence_settings(&self, presence: &OwnPresence) -> Result<(), ApiError> {
+        self.api_request_empty(
+            "users/@me/settings",
+            Method::PATCH,
+            Some(presence.settings_payload()),
+        )
+        .await
+    }
+
+    pub async fn get_channel(&self, channel_id: &str) -> Result<Channel, ApiError> {
         self.api_request(format!("channels/{channel_id}").as_str(), Method::GET, None)
             .await
     }
 
-    pub async fn get_dms(&self) -> Result<Vec<DM>, Error> {
+    /// Creates a channel in a guild, inside the category `parent_id` when there is one.
+    pub async fn create_guild_channel(
+        &self,
+        guild_id: &str,
+        name: &str,
+        channel_type: u8,
+        parent_id: Option<&str>,
+    ) -> Result<Channel, ApiError> {
+        self.api_request(
+            format!("guilds/{guild_id}/channels").as_str(),
+            Method::POST,
+            Some(serde_json::json!({ "name": name, "type": channel_type, "parent_id": parent_id })),
+        )
+        .await
+    }
+
+    /// Renames a channel, category or thread.
+    pub async fn modify_channel(&self, channel_id: &str, name: &str) -> Result<Channel, ApiError> {
+        self.api_request(
+            format!("channels/{channel_id}").as_str(),
+            Method::PATCH,
+            Some(serde_json::json!({ "name": name })),
+        )
+        .await
+    }
+
+    /// Previews an invite, with the approximate member counts of its guild.
+    pub async fn get_invite(&self, code: &str) -> Result<Invite, ApiError> {
+        self.api_request(
+            format!("invites/{code}?with_counts=true").as_str(),
+            Method::GET,
+            None,
+        )
+        .await
+    }
+
+    /// Joins the guild or group DM of an invite. User accounts only.
+    pub async fn accept_invite(&self, code: &str) -> Result<Invite, ApiError> {
+        self.api_request(
+            format!("invites/{code}").as_str(),
+            Method::POST,
+            Some(serde_json::json!({})),
+        )
+        .await
+    }
+
+    pub async fn get_dms(&self) -> Result<Vec<DM>, ApiError> {
         self.api_request("users/@me/channels", Method::GET, None)
             .await
     }
 
-    pub async fn get_guild_emojis(&self, guild_id: &str) -> Result<Vec<Emoji>, Error> {
+    /// Opens the DM channel with a user, which Discord creates on first use.
+    pub async fn create_dm(&self, recipient_id: &str) -> Result<DM, ApiError> {
+        self.api_request(
+            "users/@me/channels",
+            Method::POST,
+            Some(serde_json::json!({ "recipient_id": recipient_id })),
+        )
+        .await
+    }
+
+    pub async fn get_guild_emojis(&self, guild_id: &str) -> Result<Vec<Emoji>, ApiError> {
         self.api_request(
             format!("guilds/{guild_id}/emojis").as_str(),
             Method::GET,
@@ -92,7 +652,31 @@ impl ApiClient {
         .await
     }
 
-    pub async fn get_guild_channels(&self, guild_id: &str) -> Result<Vec<Channel>, Error> {
+    /// Adds an emoji to a guild; `image` is a data URI, see `emoji::read_emoji_image`.
+    pub async fn create_guild_emoji(
+        &self,
+        guild_id: &str,
+        name: &str,
+        image: &str,
+    ) -> Result<Emoji, ApiError> {
+        self.api_request(
+            format!("guilds/{guild_id}/emojis").as_str(),
+            Method::POST,
+            Some(serde_json::json!({ "name": name, "image": image })),
+        )
+        .await
+    }
+
+    pub async fn delete_guild_emoji(&self, guild_id: &str, emoji_id: &str) -> Result<(), ApiError> {
+        self.api_request_empty(
+            format!("guilds/{guild_id}/emojis/{emoji_id}").as_str(),
+            Method::DELETE,
+            None,
+        )
+        .await
+    }
+
+    pub async fn get_guild_channels(&self, guild_id: &str) -> Result<Vec<Channel>, ApiError> {
         self.api_request(
             format!("guilds/{guild_id}/channels").as_str(),
             Method::GET,
@@ -101,7 +685,43 @@ impl ApiClient {
         .await
     }
 
-    pub async fn get_guild_roles(&self, guild_id: &str) -> Result<Vec<Role>, Error> {
+    pub async fn get_active_guild_threads(&self, guild_id: &str) -> Result<ThreadList, ApiError> {
+        self.api_request(
+            format!("guilds/{guild_id}/threads/active").as_str(),
+            Method::GET,
+            None,
+        )
+        .await
+    }
+
+    /// Starts a post in a forum channel, returning its thread.
+    pub async fn create_forum_post(
+        &self,
+        channel_id: &str,
+        title: String,
+        content: String,
+    ) -> Result<Channel, ApiError> {
+        self.api_request(
+            format!("channels/{channel_id}/threads").as_str(),
+            Method::POST,
+            Some(serde_json::json!({ "name": title, "message": { "content": content } })),
+        )
+        .await
+    }
+
+    pub async fn list_public_archived_threads(
+        &self,
+        channel_id: &str,
+    ) -> Result<ThreadList, ApiError> {
+        self.api_request(
+            format!("channels/{channel_id}/threads/archived/public").as_str(),
+            Method::GET,
+            None,
+        )
+        .await
+    }
+
+    pub async fn get_guild_roles(&self, guild_id: &str) -> Result<Vec<Role>, ApiError> {
         self.api_request(
             format!("guilds/{guild_id}/roles").as_str(),
             Method::GET,
@@ -110,19 +730,49 @@ impl ApiClient {
         .await
     }
 
-    pub async fn get_guild_member(&self, guild_id: &str) -> Result<GuildMember, Error> {
-        let user = self.get_current_user().await?;
+    pub async fn get_guild_member(
+        &self,
+        guild_id: &str,
+        user_id: &str,
+    ) -> Result<GuildMember, ApiError> {
         self.api_request(
-            format!("guilds/{guild_id}/members/{}", user.id).as_str(),
+            format!("guilds/{guild_id}/members/{user_id}").as_str(),
             Method::GET,
             None,
         )
         .await
     }
 
-    pub async fn get_permission_context(&self, guild_id: &str) -> Result<PermissionContext, Error> {
-        let all_guild_roles: Vec<Role> = self.get_guild_roles(guild_id).await?;
-        let member_info: GuildMember = self.get_guild_member(guild_id).await?;
+    /// Lists every member of a guild, following the `after` cursor a thousand at a time.
+    pub async fn list_guild_members(&self, guild_id: &str) -> Result<Vec<GuildMember>, ApiError> {
+        const PAGE_SIZE: usize = 1000;
+        let mut members: Vec<GuildMember> = Vec::new();
+
+        loop {
+            let mut endpoint = format!("guilds/{guild_id}/members?limit={PAGE_SIZE}");
+            if let Some(last) = members.last() {
+                endpoint.push_str(&format!("&after={}", last.user.id));
+            }
+
+            let page: Vec<GuildMember> = self.api_request(&endpoint, Method::GET, None).await?;
+            let done = page.len() < PAGE_SIZE;
+            members.extend(page);
+
+            if done {
+                return Ok(members);
+            }
+        }
+    }
+
+    pub async fn get_permission_context(
+        &self,
+        guild_id: &str,
+        user_id: &str,
+    ) -> Result<PermissionContext, ApiError> {
+        let (all_guild_roles, member_info) = tokio::try_join!(
+            self.get_guild_roles(guild_id),
+            self.get_guild_member(guild_id, user_id)
+        )?;
 
         Ok(PermissionContext {
             user_id: member_info.user.id,
@@ -144,11 +794,195 @@ impl ApiClient {
         channel_id: &str,
         content: Option<String>,
         tts: bool,
-    ) -> Result<Message, Error> {
+        message_reference: Option<String>,
+        allowed_mentions: &AllowedMentions,
+    ) -> Result<Message, ApiError> {
+        let mut body = serde_json::json!({
+            "content": content,
+            "tts": tts,
+            "allowed_mentions": allowed_mentions,
+        });
+        if let Some(message_id) = message_reference {
+            body["message_reference"] = serde_json::json!({ "message_id": message_id });
+        }
+
         self.api_request(
             format!("channels/{channel_id}/messages").as_str(),
             Method::POST,
-            Some(serde_json::json!({ "content": content, "tts": tts })),
+            Some(body),
+        )
+        .await
+    }
+
+    /// Sends a message with `files` attached, as a multipart upload.
+    pub async fn create_message_with_attachments(
+        &self,
+        channel_id: &str,
+        content: Option<String>,
+        message_reference: Option<String>,
+        allowed_mentions: &AllowedMentions,
+        files: Vec<FileUpload>,
+    ) -> Result<Message, ApiError> {
+        let attachments: Vec<serde_json::Value> = files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| serde_json::json!({ "id": index, "filename": file.filename }))
+            .collect();
+        let mut payload = serde_json::json!({
+            "content": content,
+            "attachments": attachments,
+            "allowed_mentions": allowed_mentions,
+        });
+        if let Some(message_id) = message_reference {
+            payload["message_reference"] = serde_json::json!({ "message_id": message_id });
+        }
+
+        let response = self
+            .send_request(
+                format!("channels/{channel_id}/messages").as_str(),
+                Method::POST,
+                Some(Body::multipart(&payload, &files)),
+            )
+            .await?;
+        let bytes = response.bytes().await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Webhooks of a channel, which needs Manage Webhooks.
+    pub async fn get_channel_webhooks(&self, channel_id: &str) -> Result<Vec<Webhook>, ApiError> {
+        self.api_request(
+            format!("channels/{channel_id}/webhooks").as_str(),
+            Method::GET,
+            None,
+        )
+        .await
+    }
+
+    /// Posts a message through `webhook`, shown under `username` and `avatar_url` when
+    /// given instead of the webhook's own. The webhook's token authorizes it, so it goes
+    /// without the `Authorization` header.
+    pub async fn execute_webhook(
+        &self,
+        webhook: &Webhook,
+        content: Option<String>,
+        username: Option<&str>,
+        avatar_url: Option<&str>,
+        allowed_mentions: &AllowedMentions,
+    ) -> Result<Message, ApiError> {
+        let Some(token) = &webhook.token else {
+            return Err(ApiError::Forbidden(
+                "This webhook cannot be executed".to_string(),
+            ));
+        };
+        let body = serde_json::json!({
+            "content": content,
+            "username": username,
+            "avatar_url": avatar_url,
+            "allowed_mentions": allowed_mentions,
+        });
+
+        // `wait` makes Discord answer with the message instead of `204 No Content`.
+        let response = self
+            .send(
+                format!("webhooks/{}/{token}?wait=true", webhook.id).as_str(),
+                Method::POST,
+                Some(Body::Json(body)),
+                None,
+            )
+            .await?;
+        let bytes = response.bytes().await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub async fn edit_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        content: String,
+    ) -> Result<Message, ApiError> {
+        self.api_request(
+            format!("channels/{channel_id}/messages/{message_id}").as_str(),
+            Method::PATCH,
+            Some(serde_json::json!({ "content": content })),
+        )
+        .await
+    }
+
+    pub async fn delete_message(&self, channel_id: &str, message_id: &str) -> Result<(), ApiError> {
+        self.api_request_empty(
+            format!("channels/{channel_id}/messages/{message_id}").as_str(),
+            Method::DELETE,
+            None,
+        )
+        .await
+    }
+
+    /// Publishes a message of an announcement channel to the channels following it.
+    pub async fn crosspost_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<Message, ApiError> {
+        self.api_request(
+            format!("channels/{channel_id}/messages/{message_id}/crosspost").as_str(),
+            Method::POST,
+            None,
+        )
+        .await
+    }
+
+    /// Marks the channel as read up to `message_id`, for the other clients of the account.
+    /// User accounts only.
+    pub async fn ack_message(&self, channel_id: &str, message_id: &str) -> Result<(), ApiError> {
+        self.api_request_empty(
+            format!("channels/{channel_id}/messages/{message_id}/ack").as_str(),
+            Method::POST,
+            Some(serde_json::json!({ "token": null })),
+        )
+        .await
+    }
+
+    pub async fn create_reaction(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), ApiError> {
+        self.api_request_empty(
+            format!(
+                "channels/{channel_id}/messages/{message_id}/reactions/{}/@me",
+                message::encode_emoji(emoji)
+            )
+            .as_str(),
+            Method::PUT,
+            None,
+        )
+        .await
+    }
+
+    pub async fn delete_own_reaction(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), ApiError> {
+        self.api_request_empty(
+            format!(
+                "channels/{channel_id}/messages/{message_id}/reactions/{}/@me",
+                message::encode_emoji(emoji)
+            )
+            .as_str(),
+            Method::DELETE,
+            None,
+        )
+        .await
+    }
+
+    pub async fn trigger_typing(&self, channel_id: &str) -> Result<(), ApiError> {
+        self.api_request_empty(
+            format!("channels/{channel_id}/typing").as_str(),
+            Method::POST,
+            None,
         )
         .await
     }
@@ -160,7 +994,7 @@ impl ApiClient {
         before: Option<String>,
         after: Option<String>,
         limit: Option<usize>,
-    ) -> Result<Vec<Message>, Error> {
+    ) -> Result<Vec<Message>, ApiError> {
         let mut endpoint = format!("channels/{channel_id}/messages");
         let mut query = Vec::new();
 
@@ -185,8 +1019,602 @@ impl ApiClient {
         self.api_request(&endpoint, Method::GET, None).await
     }
 
-    pub async fn get_current_user_guilds(&self) -> Result<Vec<Guild>, Error> {
-        self.api_request("/users/@me/guilds", Method::GET, None)
+    /// Pinned messages of a channel, newest pin first.
+    pub async fn get_pinned_messages(&self, channel_id: &str) -> Result<Vec<Message>, ApiError> {
+        self.api_request(
+            format!("channels/{channel_id}/pins").as_str(),
+            Method::GET,
+            None,
+        )
+        .await
+    }
+
+    /// A page of the guilds we are in, sorted by id. Discord returns at most 200 at once.
+    pub async fn get_current_user_guilds(
+        &self,
+        before: Option<String>,
+        after: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Guild>, ApiError> {
+        let mut endpoint = "users/@me/guilds".to_string();
+        let mut query = Vec::new();
+
+        if let Some(b) = before {
+            query.push(format!("before={b}"));
+        }
+        if let Some(a) = after {
+            query.push(format!("after={a}"));
+        }
+        if let Some(l) = limit {
+            query.push(format!("limit={l}"));
+        }
+
+        if !query.is_empty() {
+            endpoint.push('?');
+            endpoint.push_str(&query.join("&"));
+        }
+
+        self.api_request(&endpoint, Method::GET, None).await
+    }
+
+    /// All the guilds we are in, fetched a page at a time until a page comes back short.
+    pub async fn get_all_current_user_guilds(&self) -> Result<Vec<Guild>, ApiError> {
+        let mut guilds: Vec<Guild> = Vec::new();
+        loop {
+            let after = guilds.last().map(|g| g.id.clone());
+            let page = self
+                .get_current_user_guilds(None, after, Some(GUILD_PAGE_SIZE))
+                .await?;
+            let complete = page.len() < GUILD_PAGE_SIZE;
+            guilds.extend(page);
+            if complete {
+                return Ok(guilds);
+            }
+            tokio::time::sleep(GUILD_PAGE_DELAY).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, header, method, path, query_param, query_param_is_missing},
+    };
+
+    use super::*;
+    use crate::api::message::MentionKind;
+
+    fn client(server: &MockServer) -> ApiClient {
+        ApiClient::new(Client::new(), "user-token".to_string(), server.uri())
+    }
+
+    fn message(id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "channel_id": "10",
+            "author": { "id": "1", "username": "someone" },
+            "content": "hi",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "edited_timestamp": null,
+        })
+    }
+
+    /// Answers `status` with `body` to any request, and returns what the request failed
+    /// with.
+    async fn error_for(status: u16, body: &str) -> ApiError {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(status).set_body_string(body))
+            .mount(&server)
+            .await;
+        client(&server).get_channel("10").await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn sends_the_token_as_is_for_users_and_prefixed_for_bots() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10"))
+            .and(header("Authorization", "user-token"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "id": "10", "type": 0 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10"))
+            .and(header("Authorization", "Bot bot-token"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "id": "10", "type": 0 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = client(&server).get_channel("10").await.unwrap();
+        assert_eq!(channel.id, "10");
+        let bot = ApiClient::new(Client::new(), "Bot bot-token".to_string(), server.uri());
+        assert_eq!(bot.token_type, TokenType::Bot);
+        bot.get_channel("10").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn requests_guild_resources_at_their_paths() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/guilds/20/channels"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "id": "10", "type": 0, "guild_id": "20" }
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/guilds/20/emojis"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "id": "30", "name": "ferris", "animated": false }
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api_client = client(&server);
+        let channels = api_client.get_guild_channels("20").await.unwrap();
+        assert_eq!(channels[0].guild_id.as_deref(), Some("20"));
+        let emojis = api_client.get_guild_emojis("20").await.unwrap();
+        assert_eq!(emojis[0].name, "ferris");
+    }
+
+    #[tokio::test]
+    async fn pages_messages_with_the_query_given() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10/messages"))
+            .and(query_param("before", "500"))
+            .and(query_param("limit", "50"))
+            .and(query_param_is_missing("after"))
+            .and(query_param_is_missing("around"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([message("499")])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10/messages"))
+            .and(query_param("after", "500"))
+            .and(query_param_is_missing("before"))
+            .and(query_param_is_missing("limit"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([message("501")])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10/messages"))
+            .and(query_param("around", "500"))
+            .and(query_param("limit", "5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api_client = client(&server);
+        let older = api_client
+            .get_channel_messages("10", None, Some("500".into()), None, Some(50))
+            .await
+            .unwrap();
+        assert_eq!(older[0].id, "499");
+        let newer = api_client
+            .get_channel_messages("10", None, None, Some("500".into()), None)
             .await
+            .unwrap();
+        assert_eq!(newer[0].id, "501");
+        let around = api_client
+            .get_channel_messages("10", Some("500".into()), None, None, Some(5))
+            .await
+            .unwrap();
+        assert!(around.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pages_guilds_with_the_query_given() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/users/@me/guilds"))
+            .and(query_param("after", "20"))
+            .and(query_param("limit", "200"))
+            .and(query_param_is_missing("before"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "id": "21", "name": "Next" }
+            ])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let guilds = client(&server)
+            .get_current_user_guilds(None, Some("20".into()), Some(200))
+            .await
+            .unwrap();
+        assert_eq!(guilds[0].name, "Next");
+    }
+
+    /// A page of `count` guilds with ids following `after`.
+    fn guild_page(after: usize, count: usize) -> ResponseTemplate {
+        let guilds: Vec<_> = (after + 1..=after + count)
+            .map(|id| json!({ "id": id.to_string(), "name": format!("Guild {id}") }))
+            .collect();
+        ResponseTemplate::new(200).set_body_json(guilds)
+    }
+
+    #[tokio::test]
+    async fn loads_every_page_of_guilds() {
+        let server = MockServer::start().await;
+        Mock::given(path("/users/@me/guilds"))
+            .and(query_param_is_missing("after"))
+            .and(query_param("limit", "200"))
+            .respond_with(guild_page(1000, 200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/users/@me/guilds"))
+            .and(query_param("after", "1200"))
+            .respond_with(guild_page(1200, 200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/users/@me/guilds"))
+            .and(query_param("after", "1400"))
+            .respond_with(guild_page(1400, 3))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let guilds = client(&server).get_all_current_user_guilds().await.unwrap();
+        assert_eq!(guilds.len(), 403);
+        assert_eq!(guilds.last().unwrap().id, "1403");
+    }
+
+    #[tokio::test]
+    async fn stops_at_an_empty_page() {
+        let server = MockServer::start().await;
+        Mock::given(path("/users/@me/guilds"))
+            .and(query_param_is_missing("after"))
+            .respond_with(guild_page(0, 200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/users/@me/guilds"))
+            .and(query_param("after", "200"))
+            .respond_with(guild_page(200, 0))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let guilds = client(&server).get_all_current_user_guilds().await.unwrap();
+        assert_eq!(guilds.len(), 200);
+    }
+
+    #[tokio::test]
+    async fn fails_the_guild_list_with_a_failed_page() {
+        let server = MockServer::start().await;
+        Mock::given(path("/users/@me/guilds"))
+            .and(query_param_is_missing("after"))
+            .respond_with(guild_page(0, 200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/users/@me/guilds"))
+            .and(query_param("after", "200"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let error = client(&server)
+            .get_all_current_user_guilds()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ApiError::Unauthorized));
+    }
+
+    #[tokio::test]
+    async fn posts_a_message_as_json() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/channels/10/messages"))
+            .and(header("Authorization", "user-token"))
+            .and(body_json(json!({
+                "content": "hello",
+                "tts": false,
+                "allowed_mentions": { "parse": ["users"], "replied_user": true },
+                "message_reference": { "message_id": "9" },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(message("11")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sent = client(&server)
+            .create_message(
+                "10",
+                Some("hello".to_string()),
+                false,
+                Some("9".to_string()),
+                &AllowedMentions::new(vec![MentionKind::Users]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(sent.id, "11");
+    }
+
+    #[tokio::test]
+    async fn decodes_error_statuses() {
+        let error = error_for(401, r#"{"code": 0, "message": "401: Unauthorized"}"#).await;
+        assert!(matches!(error, ApiError::Unauthorized));
+
+        let error = error_for(403, r#"{"code": 50013, "message": "Missing Permissions"}"#).await;
+        assert!(matches!(error, ApiError::Forbidden(m) if m == "Missing permissions"));
+
+        let error = error_for(404, "").await;
+        assert!(matches!(error, ApiError::NotFound(m) if m == "Not found"));
+
+        let error = error_for(400, r#"{"code": 50007, "message": "Cannot send"}"#).await;
+        assert!(matches!(error, ApiError::CannotDm));
+
+        let error = error_for(404, r#"{"code": 10006, "message": "Unknown Invite"}"#).await;
+        assert!(matches!(error, ApiError::UnknownInvite));
+
+        let error = error_for(400, r#"{"code": 12345, "message": "Something else"}"#).await;
+        assert!(
+            matches!(error, ApiError::Http(StatusCode::BAD_REQUEST, m) if m == "Something else")
+        );
+
+        // A proxy's page rather than Discord's JSON.
+        let error = error_for(502, "<html>Bad Gateway</html>").await;
+        assert!(
+            matches!(error, ApiError::Http(StatusCode::BAD_GATEWAY, m) if m == "<html>Bad Gateway</html>")
+        );
+
+        let error = error_for(200, "not json").await;
+        assert!(matches!(error, ApiError::Decode(_)));
+    }
+
+    #[tokio::test]
+    async fn decodes_a_rejected_form_to_its_first_field() {
+        let body = json!({
+            "code": 50035,
+            "message": "Invalid Form Body",
+            "errors": {
+                "content": {
+                    "_errors": [{
+                        "code": "BASE_TYPE_MAX_LENGTH",
+                        "message": "Must be 2000 or fewer in length.",
+                    }]
+                }
+            }
+        });
+        let error = error_for(400, &body.to_string()).await;
+        assert!(error.is_too_long());
+        assert_eq!(
+            error.to_string(),
+            "content: Must be 2000 or fewer in length."
+        );
+    }
+
+    fn too_many_requests(retry_after: f64) -> ResponseTemplate {
+        ResponseTemplate::new(429).set_body_json(json!({
+            "message": "You are being rate limited.",
+            "retry_after": retry_after,
+            "global": false,
+        }))
+    }
+
+    #[tokio::test]
+    async fn retries_after_a_429_for_as_long_as_asked() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10"))
+            .respond_with(too_many_requests(0.1))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "id": "10", "type": 0 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let started = Instant::now();
+        let channel = client(&server).get_channel("10").await.unwrap();
+        assert_eq!(channel.id, "10");
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(too_many_requests(0.01))
+            .expect(u64::from(MAX_ATTEMPTS))
+            .mount(&server)
+            .await;
+
+        let error = client(&server).get_channel("10").await.unwrap_err();
+        assert!(matches!(error, ApiError::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn fails_a_long_wait_at_once_and_keeps_the_route_closed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(too_many_requests(120.0))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api_client = client(&server);
+        let error = api_client.get_channel("10").await.unwrap_err();
+        assert!(matches!(error, ApiError::RateLimited { retry_after } if retry_after > 60.0));
+        // Refused without asking Discord again.
+        let error = api_client.get_channel("10").await.unwrap_err();
+        assert!(matches!(error, ApiError::RateLimited { .. }));
+    }
+
+    #[tokio::test]
+    async fn reports_a_slowmode_instead_of_waiting_it_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(json!({
+                "code": 20016,
+                "message": "Slowmode rate limit",
+                "retry_after": 30.0,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let error = client(&server)
+            .create_message(
+                "10",
+                Some("hi".into()),
+                false,
+                None,
+                &AllowedMentions::all(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ApiError::SlowMode { retry_after } if retry_after == 30.0));
+    }
+
+    #[tokio::test]
+    async fn waits_for_an_empty_bucket_on_its_route_only() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10/messages"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([]))
+                    .insert_header("X-RateLimit-Remaining", "0")
+                    .insert_header("X-RateLimit-Reset-After", "0.3"),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/channels/10/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(message("11")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api_client = client(&server);
+        let poll = || api_client.get_channel_messages("10", None, None, None, Some(100));
+        poll().await.unwrap();
+
+        // Polling drained its bucket, sending doesn't wait on it.
+        let started = Instant::now();
+        api_client
+            .create_message(
+                "10",
+                Some("hi".into()),
+                false,
+                None,
+                &AllowedMentions::all(),
+            )
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_millis(300));
+
+        poll().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[test]
+    fn finds_the_path_of_a_nested_form_error() {
+        let errors = json!({
+            "embeds": {
+                "0": {
+                    "title": {
+                        "_errors": [{ "code": "BASE_TYPE_REQUIRED", "message": "Required." }]
+                    }
+                }
+            }
+        });
+        assert_eq!(
+            first_form_error(&errors),
+            Some((
+                "embeds.0.title".to_string(),
+                "BASE_TYPE_REQUIRED".to_string(),
+                "Required.".to_string(),
+            ))
+        );
+        assert_eq!(first_form_error(&json!({})), None);
+        assert_eq!(first_form_error(&json!("not an object")), None);
+    }
+
+    #[test]
+    fn makes_common_error_codes_friendly() {
+        let body: DiscordApiErrorBody =
+            serde_json::from_str(r#"{"code": 10003, "message": "Unknown Channel"}"#).unwrap();
+        assert_eq!(body.friendly_message(), "Unknown channel");
+        let body: DiscordApiErrorBody = serde_json::from_str(r#"{"code": 1}"#).unwrap();
+        assert_eq!(body.friendly_message(), "");
+        assert!(body.errors.is_none());
+    }
+
+    #[test]
+    fn maps_each_listed_error_code() {
+        let cases = [
+            (10003, "Unknown channel"),
+            (10008, "Unknown message"),
+            (
+                20028,
+                "This channel is receiving too many messages, try again shortly",
+            ),
+            (40005, "Too large for Discord"),
+            (50001, "Missing access"),
+            (50013, "Missing permissions"),
+        ];
+        for (code, friendly) in cases {
+            let body: DiscordApiErrorBody =
+                serde_json::from_value(json!({ "code": code, "message": "raw" })).unwrap();
+            assert_eq!(body.friendly_message(), friendly, "{code}");
+        }
+    }
+
+    #[tokio::test]
+    async fn shows_the_friendly_text_rather_than_the_body() {
+        let error = error_for(404, r#"{"code": 10008, "message": "Unknown Message"}"#).await;
+        assert!(matches!(&error, ApiError::NotFound(m) if m == "Unknown message"));
+
+        let error = error_for(
+            413,
+            r#"{"code": 40005, "message": "Request entity too large"}"#,
+        )
+        .await;
+        assert!(
+            matches!(&error, ApiError::Http(StatusCode::PAYLOAD_TOO_LARGE, m) if m == "Too large for Discord")
+        );
+        assert!(!error.to_string().contains('{'));
+
+        // A long page that isn't JSON is cut short.
+        let error = error_for(500, &"x".repeat(MAX_ERROR_BODY_LEN * 2)).await;
+        assert!(matches!(error, ApiError::Http(_, m) if m.chars().count() == MAX_ERROR_BODY_LEN));
+    }
+
+    #[test]
+    fn reads_the_creation_time_of_a_snowflake() {
+        // The example of Discord's documentation.
+        let created = snowflake_to_datetime("175928847299117063").unwrap();
+        assert_eq!(created.to_rfc3339(), "2016-04-30T11:18:25.796+00:00");
+
+        let epoch = snowflake_to_datetime("0").unwrap();
+        assert_eq!(epoch.timestamp_millis(), DISCORD_EPOCH_MS);
+
+        assert_eq!(snowflake_to_datetime("abc"), None);
+        assert_eq!(snowflake_to_datetime(""), None);
     }
 }
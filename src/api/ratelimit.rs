@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::{Method, header::HeaderMap};
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    remaining: u64,
+    reset_at: Instant,
+}
+
+/// Tracks Discord's rate-limit headers per route, so a busy route (such as message
+/// polling) only ever waits on its own bucket.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    /// Set while a 429 is being waited out, for the status bar.
+    limited_until: Mutex<Option<Instant>>,
+}
+
+/// Key identifying the bucket of a request: the method plus the path with every id
+/// except the top-level channel, guild or webhook id replaced, as Discord groups its
+/// limits. A webhook token is replaced too, so that it never ends up in the logs.
+pub fn route_key(method: &Method, endpoint: &str) -> String {
+    let path = endpoint.split('?').next().unwrap_or(endpoint);
+    let mut previous = "";
+    let segments: Vec<&str> = path
+        .split('/')
+        .enumerate()
+        .map(|(i, segment)| {
+            let is_major = i == 1 && matches!(previous, "channels" | "guilds" | "webhooks");
+            let is_id = segment.bytes().all(|b| b.is_ascii_digit()) && !segment.is_empty();
+            let is_token = i == 2 && path.starts_with("webhooks/");
+            previous = segment;
+            if is_token {
+                ":token"
+            } else if is_id && !is_major {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect();
+
+    format!("{method} {}", segments.join("/"))
+}
+
+impl RateLimiter {
+    /// How long a request on `route` has to wait for its bucket to refill.
+    pub fn wait_time(&self, route: &str) -> Option<Duration> {
+        let buckets = self.buckets.lock().ok()?;
+        let bucket = buckets.get(route)?;
+        let now = Instant::now();
+        (bucket.remaining == 0 && bucket.reset_at > now).then(|| bucket.reset_at - now)
+    }
+
+    /// Records the `X-RateLimit-*` headers of a response.
+    pub fn update(&self, route: &str, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<f64>().ok())
+        };
+
+        let (Some(remaining), Some(reset_after)) = (
+            header("x-ratelimit-remaining"),
+            header("x-ratelimit-reset-after"),
+        ) else {
+            return;
+        };
+
+        if let Ok(mut buckets) = self.buckets.lock() {
+            buckets.insert(
+                route.to_string(),
+                Bucket {
+                    remaining: remaining as u64,
+                    reset_at: Instant::now() + Duration::from_secs_f64(reset_after.max(0.0)),
+                },
+            );
+        }
+    }
+
+    /// Empties the bucket of `route` for `retry_after`, as a 429 asks, so that nothing
+    /// else is sent on it before then.
+    pub fn exhaust(&self, route: &str, retry_after: Duration) {
+        if let Ok(mut buckets) = self.buckets.lock() {
+            buckets.insert(
+                route.to_string(),
+                Bucket {
+                    remaining: 0,
+                    reset_at: Instant::now() + retry_after,
+                },
+            );
+        }
+    }
+
+    pub fn set_limited(&self, retry_after: Option<Duration>) {
+        if let Ok(mut limited_until) = self.limited_until.lock() {
+            *limited_until = retry_after.map(|d| Instant::now() + d);
+        }
+    }
+
+    /// Time left until a request stalled by a 429 is retried.
+    pub fn retry_in(&self) -> Option<Duration> {
+        let limited_until = (*self.limited_until.lock().ok()?)?;
+        limited_until.checked_duration_since(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn keys_routes_by_their_major_id() {
+        assert_eq!(
+            route_key(&Method::GET, "channels/10/messages?limit=100"),
+            "GET channels/10/messages"
+        );
+        assert_eq!(
+            route_key(&Method::DELETE, "channels/10/messages/11"),
+            "DELETE channels/10/messages/:id"
+        );
+        assert_eq!(
+            route_key(&Method::GET, "guilds/20/members/30"),
+            "GET guilds/20/members/:id"
+        );
+        assert_eq!(
+            route_key(&Method::POST, "webhooks/40/secret-token"),
+            "POST webhooks/40/:token"
+        );
+        assert_eq!(
+            route_key(&Method::GET, "users/@me/guilds"),
+            "GET users/@me/guilds"
+        );
+    }
+
+    #[test]
+    fn waits_only_for_an_empty_bucket() {
+        let limiter = RateLimiter::default();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("1"));
+        headers.insert("x-ratelimit-reset-after", HeaderValue::from_static("5"));
+        limiter.update("GET a", &headers);
+        assert_eq!(limiter.wait_time("GET a"), None);
+
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        limiter.update("GET a", &headers);
+        let wait = limiter.wait_time("GET a").unwrap();
+        assert!(wait > Duration::from_secs(4) && wait <= Duration::from_secs(5));
+        assert_eq!(limiter.wait_time("GET b"), None);
+
+        limiter.exhaust("GET b", Duration::from_secs(2));
+        assert!(limiter.wait_time("GET b").is_some());
+    }
+
+    #[test]
+    fn ignores_responses_without_limits() {
+        let limiter = RateLimiter::default();
+        limiter.update("GET a", &HeaderMap::new());
+        assert_eq!(limiter.wait_time("GET a"), None);
+    }
+
+    #[test]
+    fn tells_how_long_until_the_retry() {
+        let limiter = RateLimiter::default();
+        assert_eq!(limiter.retry_in(), None);
+        limiter.set_limited(Some(Duration::from_secs(3)));
+        assert!(
+            limiter
+                .retry_in()
+                .is_some_and(|left| left <= Duration::from_secs(3))
+        );
+        limiter.set_limited(None);
+        assert_eq!(limiter.retry_in(), None);
+    }
+}
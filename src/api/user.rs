@@ -1,11 +1,155 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct User {
     pub id: String,
     pub username: String,
     //pub discriminator: String,
     pub global_name: Option<String>,
-    //pub avatar : Option<String>,
-    //pub bot: Option<bool>,
+    /// Hash of the avatar image, `None` for the default avatar.
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub bot: bool,
+}
+
+impl User {
+    pub fn avatar_url(&self) -> Option<String> {
+        self.avatar
+            .as_ref()
+            .map(|hash| format!("https://cdn.discordapp.com/avatars/{}/{hash}.png", self.id))
+    }
+}
+
+#[cfg(test)]
+impl User {
+    /// A user with nothing but an id and a username.
+    pub fn for_tests(id: &str, username: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            username: username.to_string(),
+            global_name: None,
+            avatar: None,
+            bot: false,
+        }
+    }
+}
+
+/// Longest custom status text Discord accepts.
+pub const MAX_CUSTOM_STATUS_LENGTH: usize = 128;
+
+/// The status we show to others, as set with `/status`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnStatus {
+    #[default]
+    Online,
+    Idle,
+    Dnd,
+    Invisible,
+}
+
+impl OwnStatus {
+    pub const ALL: [OwnStatus; 4] = [Self::Online, Self::Idle, Self::Dnd, Self::Invisible];
+
+    /// The word Discord and `/status` use for it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Online => "online",
+            Self::Idle => "idle",
+            Self::Dnd => "dnd",
+            Self::Invisible => "invisible",
+        }
+    }
+
+    pub fn parse(word: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str().eq_ignore_ascii_case(word))
+    }
+}
+
+/// Our own presence: a status, and a custom status text with an optional emoji.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct OwnPresence {
+    #[serde(default)]
+    pub status: OwnStatus,
+    #[serde(default)]
+    pub text: Option<String>,
+    /// A unicode emoji shown before the text.
+    #[serde(default)]
+    pub emoji: Option<String>,
+}
+
+impl OwnPresence {
+    /// Checks the custom status against Discord's limit.
+    pub fn validate(&self) -> Result<(), String> {
+        let length = self.text.as_deref().map_or(0, |text| text.chars().count());
+        if length > MAX_CUSTOM_STATUS_LENGTH {
+            return Err(format!(
+                "Custom status too long: {length}/{MAX_CUSTOM_STATUS_LENGTH} characters."
+            ));
+        }
+        Ok(())
+    }
+
+    /// The `d` of a gateway presence update, also sent in IDENTIFY.
+    pub fn gateway_payload(&self) -> serde_json::Value {
+        let activities: Vec<serde_json::Value> = if self.text.is_some() || self.emoji.is_some() {
+            vec![serde_json::json!({
+                "name": "Custom Status",
+                "type": 4,
+                "state": self.text,
+                "emoji": self.emoji.as_ref().map(|name| serde_json::json!({ "name": name })),
+            })]
+        } else {
+            Vec::new()
+        };
+        serde_json::json!({
+            "since": null,
+            "activities": activities,
+            "status": self.status.as_str(),
+            "afk": false,
+        })
+    }
+
+    /// The body of `PATCH /users/@me/settings`, how user accounts set it without the
+    /// gateway.
+    pub fn settings_payload(&self) -> serde_json::Value {
+        let custom_status = (self.text.is_some() || self.emoji.is_some()).then(|| {
+            serde_json::json!({
+                "text": self.text,
+                "emoji_name": self.emoji,
+            })
+        });
+        serde_json::json!({
+            "status": self.status.as_str(),
+            "custom_status": custom_status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_avatar_url_from_the_hash() {
+        let mut user = User {
+            id: "80351110224678912".to_string(),
+            username: "nelly".to_string(),
+            global_name: None,
+            avatar: Some("8342729096ea3675442027381ff50dfe".to_string()),
+            bot: false,
+        };
+        assert_eq!(
+            user.avatar_url().as_deref(),
+            Some(
+                "https://cdn.discordapp.com/avatars/80351110224678912/8342729096ea3675442027381ff50dfe.png"
+            )
+        );
+
+        user.avatar = None;
+        assert_eq!(user.avatar_url(), None);
+    }
 }
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+
+/// Incoming webhooks are the only kind that can be executed; the others, which follow
+/// announcement channels or belong to applications, come without a token.
+pub const INCOMING_WEBHOOK: u8 = 1;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Webhook {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: u8,
+    pub name: Option<String>,
+    /// Only sent to members with Manage Webhooks, and only for incoming webhooks.
+    pub token: Option<String>,
+}
+
+impl Webhook {
+    pub fn can_execute(&self) -> bool {
+        self.kind == INCOMING_WEBHOOK && self.token.is_some()
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("Unnamed webhook")
+    }
+}
+
+/// The webhook messages are sent through while webhook mode is on, as set by `/webhook`.
+#[derive(Debug, Clone)]
+pub struct ActiveWebhook {
+    pub webhook: Webhook,
+    pub channel_id: String,
+    /// Name shown on the messages, the webhook's own unless one was given.
+    pub username: String,
+    pub avatar_url: Option<String>,
+}
+
+/// Checks a name to send as through a webhook: 1 to 80 characters, without the words
+/// Discord refuses in webhook names.
+pub fn validate_webhook_username(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if !(1..=80).contains(&name.chars().count()) {
+        return Err("Webhook names are 1 to 80 characters long.".to_string());
+    }
+    let lowercase = name.to_lowercase();
+    if let Some(word) = ["clyde", "discord"]
+        .into_iter()
+        .find(|word| lowercase.contains(word))
+    {
+        return Err(format!(
+            "Discord refuses webhook names containing \"{word}\"."
+        ));
+    }
+    Ok(name.to_string())
+}
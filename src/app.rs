@@ -0,0 +1,3274 @@
+//! State of the client and the actions that change it. Key presses, API responses and
+//! gateway events all arrive as an [`AppAction`], which [`apply_action`] applies to the
+//! [`App`], handing back what else to do as [`Command`]s. What an action does in each
+//! part of the app is left to the submodules.
+
+mod chat;
+mod lists;
+mod overlays;
+mod selection;
+mod submit;
+
+pub use chat::{resume_channel, stash_draft};
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use ratatui::layout::Rect;
+use tokio::sync::{mpsc::Sender, watch};
+
+use crate::{
+    api::{
+        ApiClient, ApiError, Channel, Emoji, Guild, Message, User,
+        channel::PermissionContext,
+        dm::DM,
+        gateway::{Presence, Status, VoiceState},
+        guild::GuildMember,
+        invite::Invite,
+        message::{AllowedMentions, MAX_MESSAGE_LENGTH, message_length},
+        user::OwnPresence,
+        webhook::{ActiveWebhook, Webhook},
+    },
+    config::{self, PinKind},
+    desktop::DesktopNotifier,
+    tasks::PendingRequests,
+    ui::{
+        aliases::AliasList,
+        emojis::EmojiManager,
+        errors::{ErrorEntry, toast_visible},
+        filter::filter_guilds,
+        forum::ForumViewer,
+        history::{self, Recall},
+        ignore::IgnoreList,
+        input,
+        invite::InvitePrompt,
+        keymap,
+        links::LinkHints,
+        notifications::Notification,
+        pins::PinViewer,
+        presence::PRESENCE_TTL,
+        preview::ImagePreview,
+        profile::{MemberDetails, ProfileViewer},
+        rows::RowCache,
+        search::{MessageSearch, SEARCH_PAGE_SIZE, SEARCH_PAGES, SearchOutcome, SearchPhase},
+        split::SplitPane,
+        switcher::QuickSwitcher,
+        theme::Theme,
+        vim::{self, VimState},
+        webhooks::WebhookPicker,
+    },
+};
+
+use chat::{
+    ack_latest, back_from_chat, changes_loaded_rows, chat_channel_id,
+    expand_shortcodes_before_cursor, flush_outbox, gate_channel, handle_attach_keys,
+    handle_nsfw_keys, handle_ping_keys, handle_resume_keys, insert_char_at_cursor, leave_channel,
+    mark_read, notify_typing, open_attach_prompt, open_channel, recall_history, request_members,
+    restore_failed_message, shown_guild_id, store_drafts, toggle_split,
+};
+use lists::{
+    click_list, leave_emoji_selection, mark_guild_read, move_selection, refresh_member_list,
+    remembered_channel_row, request_guild_channels, request_member_list, store_list_cache,
+    store_read_markers, toggle_favorite, toggle_mute, toggle_show_muted, update_guilds,
+};
+use overlays::{
+    emoji_request_failed, handle_alias_list_keys, handle_channel_prompt_keys,
+    handle_command_help_keys, handle_emoji_manager_keys, handle_error_log_keys, handle_forum_keys,
+    handle_ignore_list_keys, handle_invite_keys, handle_key_help_keys, handle_notification_keys,
+    handle_profile_keys, handle_search_keys, handle_switcher_keys, handle_webhook_picker_keys,
+    jump_to_notification, notify_mention, open_channel_prompt, open_emoji_manager,
+    open_search_prompt, open_switcher, report_error,
+};
+use selection::{
+    handle_link_keys, handle_message_selection_keys, handle_pin_keys, jump_to_message,
+    open_link_hints, open_pins, selection_help,
+};
+use submit::input_submit;
+
+#[derive(Debug)]
+pub enum KeywordAction {
+    Continue,
+    Break,
+}
+
+/// Something applying an action asks the caller to do, outside the app.
+pub enum Command {
+    /// Handles another action, after those already waiting.
+    Dispatch(Box<AppAction>),
+    /// Runs a task in the background, which reports back by sending actions.
+    Spawn(BoxFuture<'static, ()>),
+    /// Like `Spawn`, for a change to Discord that quitting waits for. What failed is
+    /// printed then.
+    Track(BoxFuture<'static, Result<(), String>>),
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dispatch(action) => f.debug_tuple("Dispatch").field(action).finish(),
+            Self::Spawn(_) => f.write_str("Spawn"),
+            Self::Track(_) => f.write_str("Track"),
+        }
+    }
+}
+
+/// The commands gathered while applying an action. Tasks report back through
+/// `sender`, which they take along without sending anything yet.
+#[derive(Debug)]
+pub struct Commands {
+    tx_action: Sender<AppAction>,
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    pub fn new(tx_action: Sender<AppAction>) -> Self {
+        Self {
+            tx_action,
+            queue: Vec::new(),
+        }
+    }
+
+    /// A sender for a task to report back with.
+    pub fn sender(&self) -> Sender<AppAction> {
+        self.tx_action.clone()
+    }
+
+    pub fn dispatch(&mut self, action: AppAction) {
+        self.queue.push(Command::Dispatch(Box::new(action)));
+    }
+
+    pub fn spawn(&mut self, task: impl Future<Output = ()> + Send + 'static) {
+        self.queue.push(Command::Spawn(Box::pin(task)));
+    }
+
+    pub fn track(&mut self, request: impl Future<Output = Result<(), String>> + Send + 'static) {
+        self.queue.push(Command::Track(Box::pin(request)));
+    }
+
+    /// The commands in the order they were asked for, leaving none behind.
+    pub fn take(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.queue)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Window {
+    Home,
+    Channel(String),
+    Chat(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum AppState {
+    Home,
+    SelectingGuild,
+    SelectingDM,
+    SelectingChannel(String),
+    Chatting(String),
+    EmojiSelection(String),
+    SelectingMessage(String),
+    Loading(Window),
+}
+
+#[derive(Debug)]
+pub enum CursorMove {
+    Left,
+    Right,
+    Home,
+    End,
+    WordLeft,
+    WordRight,
+}
+
+#[derive(Debug)]
+pub enum AppAction {
+    SigInt,
+    InputChar(char),
+    InputBackspace,
+    InputMoveCursor(CursorMove),
+    InputDeleteWord,
+    InputClearToStart,
+    ToggleSwitcher,
+    InputEscape,
+    InputSubmit,
+    InputTab,
+    SelectNext,
+    SelectPrevious,
+    SelectMessage,
+    DeleteMessage,
+    ScrollPageUp,
+    ScrollPageDown,
+    ScrollLineUp,
+    ScrollLineDown,
+    ApiUpdateMessages(Vec<Message>),
+    /// Messages of a channel posted or edited after the newest one loaded.
+    ApiUpdateNewerMessages(String, Vec<Message>),
+    /// Messages polled for the split pane's channel, and whether they are a full page.
+    ApiSplitMessages(String, Vec<Message>, bool),
+    ApiUpdateOlderMessages(Vec<Message>),
+    ApiReplaceMessage(Message),
+    ApiRemoveMessage(String),
+    ApiRestoreMessage(Message),
+    /// A message published to the channels following its announcement channel.
+    ApiPublished(Message),
+    /// The preview of the invite given to `/join-invite`.
+    ApiInvite(Result<Invite, String>),
+    /// The invite was accepted and the guild list refreshed with its guild.
+    ApiInviteAccepted(Invite),
+    ApiError(String),
+    /// The token was rejected; the app exits with an explanation.
+    Unauthorized,
+    /// Loading a channel's messages failed, possibly because it is gone or off-limits.
+    ChannelFailed(String, ApiError),
+    /// Reported by the poller after repeated network failures, and once it recovers.
+    ConnectionStatus(bool),
+    QueueMessage(Box<QueuedMessage>),
+    QueuedMessageSent,
+    /// Discord refused a message; its content goes back into the input or a draft.
+    SendFailed(Box<QueuedMessage>, ApiError),
+    OutboxFlushed,
+    OpenAttachPrompt,
+    /// Starts typing a search of the open channel, as Ctrl+F does.
+    OpenSearch,
+    /// Posts of a forum channel, for its post list.
+    ApiUpdateForumPosts(String, Result<Vec<Channel>, String>),
+    ApiForumPostCreated(Channel),
+    /// An upload ended; holds the id of its local copy and why it failed, if it did.
+    UploadFinished(String, Result<(), String>),
+    PreviewLoaded(Result<ImagePreview, String>),
+    /// Opens a channel fetched by id, as `rivetui open` asks at startup.
+    ApiOpenChannel(Channel),
+    ApiUpdateChannel(Vec<Channel>),
+    ApiUpdateEmojis(String, Vec<Emoji>),
+    /// Threads of a guild; the flag is set for archived ones loaded on demand.
+    ApiUpdateThreads(String, Vec<Channel>, bool),
+    LoadArchivedThreads,
+    /// Guilds loaded so far, in id order, and whether that is all of them.
+    ApiUpdateGuilds(Vec<Guild>, bool),
+    /// All the guilds again, fetched in the background to notice servers joined or left
+    /// elsewhere.
+    ApiRefreshGuilds(Vec<Guild>),
+    ApiUpdateDMs(Vec<DM>),
+    /// The member behind a profile, and the names of their roles, by user id.
+    ApiProfileMember(String, Result<(GuildMember, Vec<String>), String>),
+    /// A DM channel opened from a message, to switch to.
+    ApiDmOpened(DM),
+    ApiUpdateMember(String, GuildMember),
+    /// Full member list of a guild, for the sidebar.
+    ApiUpdateGuildMembers(String, Vec<GuildMember>),
+    ToggleMemberList,
+    ApiCacheGuildChannels(String, Vec<Channel>, Option<PermissionContext>),
+    ApiUpdateContext(Option<PermissionContext>),
+    GatewayMessage(Message),
+    /// Latest messages of channels other than the open one, checked for mentions.
+    ApiBackgroundMessages(Vec<Message>),
+    JumpToMention,
+    ToggleNotifications,
+    ToggleErrorLog,
+    TogglePins,
+    /// Opens or closes the help listing every key, F1 or `?`.
+    ToggleKeyHelp,
+    /// Switches the guild list to the next sort order.
+    CycleGuildSort,
+    CycleTimeFormat,
+    /// Switches the chat between compact and cozy messages.
+    ToggleDensity,
+    ToggleLinkHints,
+    /// Mutes or unmutes the selected guild or channel.
+    ToggleMute,
+    ToggleShowMuted,
+    /// Adds the selected guild to the favorites, or removes it.
+    ToggleFavorite,
+    /// Marks every channel of the guild whose channels are listed as read.
+    MarkGuildRead,
+    /// The translation of a message, by its id.
+    Translated(String, Result<String, String>),
+    /// Unread channels per guild, counted in the background.
+    UnreadCounts(HashMap<String, usize>),
+    /// Starts naming a new text channel, in the category of the selected row.
+    NewChannel,
+    /// Starts renaming the selected channel.
+    RenameChannel,
+    /// A channel just created or renamed, to show without fetching the list again.
+    ApiChannelSaved(Channel),
+    /// Opens the emoji manager of the guild whose channels are listed.
+    OpenEmojiManager,
+    /// The emojis of a guild, for the emoji manager.
+    ApiManagedEmojis(String, Result<Vec<Emoji>, ApiError>),
+    /// An emoji uploaded from the emoji manager, by guild id.
+    ApiEmojiCreated(String, Result<Emoji, ApiError>),
+    /// An emoji deleted from the emoji manager, by guild and emoji id.
+    ApiEmojiDeleted(String, String, Result<(), ApiError>),
+    /// The webhooks of a channel, for the `/webhook` picker.
+    ApiChannelWebhooks(String, Result<Vec<Webhook>, ApiError>),
+    /// Recalls an older or a newer sent message into the input.
+    HistoryPrevious,
+    HistoryNext,
+    /// A message made it to a channel, by channel id.
+    MessageSent(String, String),
+    MouseWheelUp,
+    MouseWheelDown,
+    /// A left click at a column and row of the terminal.
+    MouseClick(u16, u16),
+    /// Pinned messages of a channel, for the Ctrl+P overlay.
+    ApiUpdatePins(String, Result<Vec<Message>, String>),
+    /// Messages around a pinned or found one, which the chat then scrolls to.
+    ApiJumpToMessage(String, Vec<Message>),
+    /// Messages checked so far by the search for a query.
+    ApiSearchProgress(String, usize),
+    ApiSearchResults(SearchOutcome),
+    GatewayStatus(bool),
+    /// Everyone connected to a voice channel of a guild, replacing what was known.
+    GatewayVoiceStates(String, Vec<VoiceState>),
+    GatewayVoiceStateUpdate(String, VoiceState),
+    GatewayPresences(Vec<Presence>),
+    TypingStarted(User, String),
+    TransitionToChat(String),
+    TransitionToChannels(String),
+    TransitionToGuilds,
+    TransitionToDM,
+    TransitionToHome,
+    TransitionToLoading(Window),
+    EndLoading,
+    SelectEmoji,
+    Paste(String),
+    Tick,
+    /// The terminal was resized.
+    Resize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputMode {
+    Normal,
+    Insert,
+}
+
+/// An NSFW channel about to be opened, while its warning waits to be confirmed.
+#[derive(Debug, Clone)]
+pub struct GatedChannel {
+    pub channel_id: String,
+    pub name: String,
+    pub guild_id: Option<String>,
+}
+
+/// What the name typed in the channel list is for.
+#[derive(Debug, Clone)]
+pub enum ChannelEdit {
+    Create {
+        guild_id: String,
+        parent_id: Option<String>,
+    },
+    Rename {
+        channel_id: String,
+        channel_type: u8,
+    },
+}
+
+/// A channel name being typed in the input bar of the channel list.
+#[derive(Debug, Clone)]
+pub struct ChannelPrompt {
+    pub edit: ChannelEdit,
+    /// The filter of the list, put back once the prompt closes.
+    pub filter: String,
+}
+
+/// A message submitted while offline, sent once the connection is back. `message` is
+/// a local copy with an empty id, rendered in the chat until then.
+#[derive(Debug, Clone)]
+pub struct QueuedMessage {
+    pub message: Message,
+    pub message_reference: Option<String>,
+    pub allowed_mentions: AllowedMentions,
+    /// Set when it was submitted in webhook mode, to be sent through that webhook.
+    pub webhook: Option<ActiveWebhook>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypingIndicator {
+    pub user_id: String,
+    pub username: String,
+    pub channel_id: String,
+    pub started: Instant,
+}
+
+/// Where a selectable list was drawn, scrolled down by `offset` rows. Only its first
+/// `len` rows can be selected, not counting the `headings` rows between them.
+#[derive(Debug, Clone)]
+pub struct ListArea {
+    pub area: Rect,
+    pub offset: usize,
+    pub len: usize,
+    pub headings: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct App {
+    pub api_client: ApiClient,
+    pub state: AppState,
+    pub guilds: Vec<Guild>,
+    pub channels: Vec<Channel>,
+    pub messages: Vec<Message>,
+    pub custom_emojis: Vec<Emoji>,
+    /// Custom emojis per guild id, so re-entering a guild doesn't refetch them.
+    pub emoji_cache: HashMap<String, Vec<Emoji>>,
+    /// Known threads per guild, listed under their parent channel.
+    pub thread_cache: HashMap<String, Vec<Channel>>,
+    pub dms: Vec<DM>,
+    pub input: String,
+    pub selection_index: usize,
+    pub status_message: String,
+    pub terminal_height: usize,
+    pub terminal_width: usize,
+    pub emoji_map: Vec<(String, String)>,
+    pub emoji_filter: String,
+    /// Byte position where the emoji filter started (position of the ':')
+    pub emoji_filter_start: Option<usize>,
+    pub tick_count: usize,
+    pub context: Option<PermissionContext>,
+    pub mode: InputMode,
+    pub cursor_position: usize,
+    pub vim_mode: bool,
+    pub vim_state: Option<VimState>,
+    /// Whether the gateway is currently delivering messages, making polling unnecessary.
+    pub gateway_connected: bool,
+    /// Per guild, user id to the voice channel they are connected to.
+    pub voice_states: HashMap<String, HashMap<String, String>>,
+    /// Last known status per user id, and when it was received.
+    pub presences: HashMap<String, (Status, Instant)>,
+    /// Number of chat lines scrolled up from the newest message; 0 follows live messages.
+    pub scroll_offset: usize,
+    /// Set by the renderer when the oldest loaded message is visible.
+    pub chat_at_top: bool,
+    /// Newest message at the last render, to hold the view still when new ones arrive.
+    pub chat_newest_id: Option<String>,
+    /// Width the chat was last laid out at, to keep the scroll position across resizes.
+    pub chat_width: usize,
+    /// Messages laid out by earlier frames.
+    pub row_cache: RefCell<RowCache>,
+    /// Bumped whenever something messages are drawn with besides themselves changes, such
+    /// as the channels, roles, emojis and member names mentions resolve to, or the
+    /// aliases. The row cache starts over when it does.
+    pub rows_generation: u64,
+    /// Messages whose spoilers were revealed with `s` while selected.
+    pub revealed_spoilers: HashSet<String>,
+    /// Users whose messages collapse to a placeholder, persisted between sessions.
+    pub ignored: Vec<config::IgnoredUser>,
+    /// Names given to users with `/alias`, shown instead of theirs. Never sent to Discord.
+    pub aliases: HashMap<String, String>,
+    pub alias_list: Option<AliasList>,
+    /// Messages of ignored users shown anyway, revealed with `s` while selected.
+    pub revealed_ignored: HashSet<String>,
+    /// Author of the selected message awaiting a y/n confirmation before being ignored.
+    pub pending_ignore: Option<config::IgnoredUser>,
+    pub ignore_list: Option<IgnoreList>,
+    pub invite_prompt: Option<InvitePrompt>,
+    /// Hides spoilers and asks before NSFW channels, unless turned off in the config.
+    pub content_warnings: bool,
+    pub nsfw_prompt: Option<GatedChannel>,
+    /// The translation backend, when one is configured.
+    pub translation: Option<config::TranslationConfig>,
+    /// Translations by message id for the session, `None` while one loads.
+    pub translations: HashMap<String, Option<String>>,
+    /// NSFW channels whose warning was confirmed this session.
+    pub nsfw_confirmed: HashSet<String>,
+    pub history_exhausted: bool,
+    /// Last time we told Discord we are typing, to stay within one request per 8 seconds.
+    pub last_typing_sent: Option<Instant>,
+    pub typing: Vec<TypingIndicator>,
+    /// Message the next submitted input will reply to.
+    pub replying_to: Option<Message>,
+    /// Id of our own message currently loaded into the input for editing.
+    pub editing_message: Option<String>,
+    /// Who we are logged in as, checked before the client starts.
+    pub current_user: User,
+    /// Message awaiting a y/n confirmation before it is deleted.
+    pub pending_delete: Option<String>,
+    /// Quote of a message awaiting a y/n confirmation before it replaces the draft.
+    pub pending_quote: Option<String>,
+    /// Message awaiting a y/n confirmation before it is published.
+    pub pending_publish: Option<String>,
+    /// Shortcode typed after pressing `+` on a highlighted message.
+    pub reaction_input: Option<String>,
+    pub preview: Option<ImagePreview>,
+    pub preview_loading: bool,
+    /// Newest message id seen per channel, persisted between sessions.
+    pub read_markers: HashMap<String, String>,
+    /// Unread channels per guild, for the badges of the guild list. Counted in the
+    /// background whenever `unread_dirty` is set.
+    pub guild_unread: HashMap<String, usize>,
+    pub unread_dirty: bool,
+    /// Whether read markers are sent to Discord, from `sync_read_state` in the config.
+    pub sync_read_state: bool,
+    /// Last message acked per channel, and when.
+    pub acks: HashMap<String, (String, Instant)>,
+    /// Unsent input per channel id, persisted between sessions.
+    pub drafts: HashMap<String, config::Draft>,
+    /// Messages of recently left channels, and when each was left, shown at once when
+    /// they are opened again.
+    pub message_cache: HashMap<String, (Instant, Vec<Message>)>,
+    /// Ids of the muted guilds and channels, persisted between sessions. Muted channels are
+    /// only listed while `show_muted` is set.
+    pub muted: HashSet<String>,
+    pub show_muted: bool,
+    /// Favorite guilds, listed first, persisted between sessions.
+    pub pinned: config::Pinned,
+    /// The channel opened last, saved on every switch and on exit.
+    pub last_channel: Option<config::LastChannel>,
+    /// Per guild id, the channel last opened in it, selected again on coming back.
+    pub guild_channels: HashMap<String, String>,
+    /// The channel of the last session, while the y/n prompt to reopen it is up.
+    pub resume_prompt: Option<config::LastChannel>,
+    /// Messages sent per channel id, oldest first, for Alt+Up/Down.
+    pub input_history: HashMap<String, Vec<String>>,
+    pub history_recall: Option<Recall>,
+    pub persist_input_history: bool,
+    /// What sent messages may ping without asking, from the config.
+    pub allowed_mentions: AllowedMentions,
+    /// What the message being sent would ping beyond `allowed_mentions`, while the y/n
+    /// prompt to confirm it is up.
+    pub pending_pings: Option<Vec<String>>,
+    /// What the next message sent may ping, once the prompt was answered.
+    pub ping_override: Option<AllowedMentions>,
+    /// Order of the guild list, cycled with Ctrl+S.
+    pub guild_sort: config::GuildSort,
+    /// Format of message times, cycled with F3, and the custom one from the config.
+    pub time_format: config::TimeFormat,
+    pub custom_time_format: Option<String>,
+    pub density: config::Density,
+    /// When each guild was last opened, persisted for the recent sort.
+    pub guilds_opened: HashMap<String, DateTime<Utc>>,
+    /// Guild of the open channel, `None` in DMs.
+    pub chat_guild_id: Option<String>,
+    /// Per guild, user id to display name and role color.
+    pub member_cache: HashMap<String, HashMap<String, (String, Option<u32>)>>,
+    /// (guild id, user id) pairs already requested, so each member is fetched once.
+    pub member_requests: HashSet<(String, String)>,
+    /// Whether the member sidebar is open in the chat.
+    pub show_members: bool,
+    /// Full member lists fetched for the sidebar, per guild, and the guilds requested.
+    pub guild_members: HashMap<String, Vec<GuildMember>>,
+    pub member_list_requests: HashSet<String>,
+    /// User id, display name and role color of the members who can see the open channel.
+    pub member_list: Vec<(String, String, Option<u32>)>,
+    /// Grouped channels and permission context of every guild loaded so far.
+    pub channel_cache: HashMap<String, Vec<Channel>>,
+    pub context_cache: HashMap<String, PermissionContext>,
+    /// Guilds whose channels were requested for the quick switcher.
+    pub channel_requests: HashSet<String>,
+    pub switcher: Option<QuickSwitcher>,
+    pub pins: Option<PinViewer>,
+    pub forum: Option<ForumViewer>,
+    pub link_hints: Option<LinkHints>,
+    /// Channel and message that `/goto` scrolls to once the channel's messages load.
+    pub pending_jump: Option<(String, String)>,
+    pub split: Option<SplitPane>,
+    pub profile: Option<ProfileViewer>,
+    pub search: Option<MessageSearch>,
+    /// Caption typed before Ctrl+A, kept while the path of the file to attach is typed.
+    pub attach_prompt: Option<String>,
+    pub channel_prompt: Option<ChannelPrompt>,
+    pub emoji_manager: Option<EmojiManager>,
+    pub webhook_picker: Option<WebhookPicker>,
+    /// The webhook picked with `/webhook`: messages sent in its channel go through it
+    /// until `/webhook off`.
+    pub webhook: Option<ActiveWebhook>,
+    /// Local copies of messages whose file is still uploading, shown at the bottom of
+    /// their chat until the upload ends.
+    pub uploads: Vec<Message>,
+    /// Printed after the terminal is restored when the app has to quit on an error.
+    pub fatal_error: Option<String>,
+    pub offline: bool,
+    pub outbox: Vec<QueuedMessage>,
+    pub flushing_outbox: bool,
+    /// When the slowmode of each channel lets us send again, after a send it refused.
+    pub slowmode: HashMap<String, Instant>,
+    /// Why we can't send in each channel, after a send refused for permissions or a
+    /// timeout. Opening the channel again lifts it, to try once more.
+    pub send_blocked: HashMap<String, String>,
+    /// The last message was refused for its length, so the counter is highlighted.
+    pub length_rejected: bool,
+    /// Our presence as last set, `None` until it is.
+    pub presence: Option<OwnPresence>,
+    /// Hands presence changes to the gateway, which sends them again on reconnecting.
+    pub presence_tx: tokio::sync::watch::Sender<Option<OwnPresence>>,
+    /// Sends, edits, deletions and acks still running, which quitting waits for.
+    pub pending_requests: PendingRequests,
+    pub theme: Theme,
+    /// Recent mentions elsewhere, newest first, and how many are not acknowledged yet.
+    pub notifications: VecDeque<Notification>,
+    pub unacknowledged_mentions: usize,
+    /// Messages already notified, so polling doesn't report them twice.
+    pub notified_mentions: HashSet<String>,
+    /// Shows mentions and direct messages on the desktop, when turned on in the config.
+    pub desktop_notifier: Option<DesktopNotifier>,
+    /// Selection in the Ctrl+N overlay while it is open.
+    pub notification_overlay: Option<usize>,
+    /// Channels opened recently, newest first, polled for mentions while the gateway is down.
+    pub recent_channels: Vec<(String, Option<String>)>,
+    /// Errors reported by any task, newest first; the first one is shown in the status
+    /// bar since `error_toast`.
+    pub error_log: VecDeque<ErrorEntry>,
+    pub error_toast: Option<Instant>,
+    pub show_error_log: bool,
+    /// Whether the `/help` overlay listing the commands is open.
+    pub show_command_help: bool,
+    /// Scroll of the key help overlay, while it is open.
+    pub key_help: Option<usize>,
+    /// The list of the current screen as last drawn, for mouse clicks, and the last row
+    /// clicked, to tell double clicks.
+    pub list_area: Option<ListArea>,
+    pub last_click: Option<(Instant, usize)>,
+}
+
+impl App {
+    /// An app for `current_user` with the default settings and nothing loaded yet. The
+    /// caller puts the config and what was kept from the last run in place.
+    pub fn new(api_client: ApiClient, current_user: User) -> Self {
+        let config = config::Config::default();
+        Self {
+            api_client,
+            state: AppState::Home,
+            guilds: Vec::new(),
+            channels: Vec::new(),
+            messages: Vec::new(),
+            custom_emojis: Vec::new(),
+            emoji_cache: HashMap::new(),
+            thread_cache: HashMap::new(),
+            dms: Vec::new(),
+            input: String::new(),
+            selection_index: 0,
+            status_message: String::new(),
+            terminal_height: 20,
+            terminal_width: 80,
+            emoji_map: config.emoji_map,
+            emoji_filter: String::new(),
+            emoji_filter_start: None,
+            tick_count: 0,
+            context: None,
+            mode: InputMode::Normal,
+            cursor_position: 0,
+            vim_mode: config.vim_mode,
+            vim_state: config.vim_mode.then(VimState::default),
+            gateway_connected: false,
+            voice_states: HashMap::new(),
+            presences: HashMap::new(),
+            scroll_offset: 0,
+            chat_at_top: false,
+            chat_newest_id: None,
+            chat_width: 0,
+            row_cache: RefCell::default(),
+            rows_generation: 0,
+            revealed_spoilers: HashSet::new(),
+            ignored: Vec::new(),
+            aliases: HashMap::new(),
+            alias_list: None,
+            revealed_ignored: HashSet::new(),
+            pending_ignore: None,
+            ignore_list: None,
+            invite_prompt: None,
+            history_exhausted: false,
+            last_typing_sent: None,
+            typing: Vec::new(),
+            replying_to: None,
+            editing_message: None,
+            current_user,
+            pending_delete: None,
+            pending_publish: None,
+            pending_quote: None,
+            reaction_input: None,
+            preview: None,
+            preview_loading: false,
+            read_markers: HashMap::new(),
+            drafts: HashMap::new(),
+            message_cache: HashMap::new(),
+            guild_sort: config.guild_sort,
+            custom_time_format: None,
+            time_format: config.time_format,
+            density: config.density,
+            sync_read_state: config.sync_read_state,
+            guild_unread: HashMap::new(),
+            unread_dirty: true,
+            acks: HashMap::new(),
+            guilds_opened: HashMap::new(),
+            muted: HashSet::new(),
+            show_muted: false,
+            pinned: config::Pinned::default(),
+            last_channel: None,
+            guild_channels: HashMap::new(),
+            resume_prompt: None,
+            input_history: HashMap::new(),
+            history_recall: None,
+            persist_input_history: config.persist_input_history,
+            allowed_mentions: AllowedMentions::new(config.allowed_mentions),
+            pending_pings: None,
+            ping_override: None,
+            chat_guild_id: None,
+            member_cache: HashMap::new(),
+            member_requests: HashSet::new(),
+            show_members: false,
+            guild_members: HashMap::new(),
+            member_list_requests: HashSet::new(),
+            member_list: Vec::new(),
+            channel_cache: HashMap::new(),
+            context_cache: HashMap::new(),
+            channel_requests: HashSet::new(),
+            switcher: None,
+            pins: None,
+            forum: None,
+            link_hints: None,
+            pending_jump: None,
+            split: None,
+            profile: None,
+            search: None,
+            attach_prompt: None,
+            channel_prompt: None,
+            emoji_manager: None,
+            webhook_picker: None,
+            webhook: None,
+            content_warnings: config.content_warnings,
+            desktop_notifier: None,
+            translation: None,
+            translations: HashMap::new(),
+            nsfw_prompt: None,
+            nsfw_confirmed: HashSet::new(),
+            uploads: Vec::new(),
+            fatal_error: None,
+            offline: false,
+            outbox: Vec::new(),
+            flushing_outbox: false,
+            slowmode: HashMap::new(),
+            send_blocked: HashMap::new(),
+            length_rejected: false,
+            pending_requests: PendingRequests::default(),
+            presence: None,
+            presence_tx: watch::channel(None).0,
+            theme: Theme::default(),
+            notifications: VecDeque::new(),
+            unacknowledged_mentions: 0,
+            notified_mentions: HashSet::new(),
+            notification_overlay: None,
+            recent_channels: Vec::new(),
+            error_log: VecDeque::new(),
+            error_toast: None,
+            show_error_log: false,
+            show_command_help: false,
+            key_help: None,
+            list_area: None,
+            last_click: None,
+        }
+    }
+
+    pub fn is_ignored(&self, user_id: &str) -> bool {
+        self.ignored.iter().any(|user| user.id == user_id)
+    }
+
+    /// Has the messages laid out again on the next frame, after a change to what they
+    /// show that isn't part of the messages themselves.
+    pub fn invalidate_rows(&mut self) {
+        self.rows_generation = self.rows_generation.wrapping_add(1);
+    }
+
+    /// Unicode emojis, then custom emojis of the guild, whose name starts with what was
+    /// typed after the colon, in the order the completion lists them.
+    pub fn emoji_matches(&self) -> (Vec<&(String, String)>, Vec<&Emoji>) {
+        let unicode = self
+            .emoji_map
+            .iter()
+            .filter(|(name, _)| name.starts_with(&self.emoji_filter))
+            .collect();
+        let custom = self
+            .custom_emojis
+            .iter()
+            .filter(|emoji| emoji.name.starts_with(&self.emoji_filter))
+            .collect();
+        (unicode, custom)
+    }
+
+    pub fn alias(&self, user_id: &str) -> Option<&str> {
+        self.aliases.get(user_id).map(String::as_str)
+    }
+
+    /// How long until the slowmode of a channel lets us send again, if it still holds.
+    pub fn slowmode_left(&self, channel_id: &str) -> Option<Duration> {
+        self.slowmode
+            .get(channel_id)
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|left| !left.is_zero())
+    }
+
+    /// Rows of the channel list of a guild for the current filter, in display order.
+    /// `selection_index` in the channel list always indexes into this.
+    pub fn channel_rows(&self, guild_id: &str) -> Vec<&Channel> {
+        let threads = self
+            .thread_cache
+            .get(guild_id)
+            .map_or(&[][..], Vec::as_slice);
+        // While a channel is being named, the input holds the name, not the filter.
+        let filter = self
+            .channel_prompt
+            .as_ref()
+            .map_or(&self.input, |prompt| &prompt.filter);
+        Channel::visible_rows(
+            &self.channels,
+            threads,
+            self.context.as_ref(),
+            filter,
+            (!self.show_muted).then_some(&self.muted),
+        )
+    }
+}
+
+/// The status text while chatting.
+const CHAT_HELP: &str =
+    "Chatting in channel. Press Enter to send message, Esc to return to channels.";
+const TYPING_TIMEOUT: Duration = Duration::from_secs(10);
+/// Rows moved by Ctrl+Up and Ctrl+Down in the chat.
+const SCROLL_LINES: usize = 3;
+const CHANNEL_LIST_HELP: &str = "Select a channel to chat, F7 for a new channel, F8 to rename one, F10 for emojis. (Esc to return to Servers)";
+
+/// Whether the action comes straight from a key press, as opposed to a background task.
+fn is_keypress(action: &AppAction) -> bool {
+    matches!(
+        action,
+        AppAction::InputChar(_)
+            | AppAction::InputBackspace
+            | AppAction::InputMoveCursor(_)
+            | AppAction::InputDeleteWord
+            | AppAction::InputClearToStart
+            | AppAction::InputEscape
+            | AppAction::InputSubmit
+            | AppAction::InputTab
+            | AppAction::Paste(_)
+            | AppAction::SelectNext
+            | AppAction::SelectPrevious
+            | AppAction::SelectMessage
+            | AppAction::DeleteMessage
+            | AppAction::SelectEmoji
+            | AppAction::ToggleSwitcher
+            | AppAction::ToggleMemberList
+            | AppAction::ToggleNotifications
+            | AppAction::ToggleErrorLog
+            | AppAction::TogglePins
+            | AppAction::CycleGuildSort
+            | AppAction::ToggleKeyHelp
+            | AppAction::CycleTimeFormat
+            | AppAction::ToggleDensity
+            | AppAction::ToggleLinkHints
+            | AppAction::ToggleMute
+            | AppAction::ToggleShowMuted
+            | AppAction::ToggleFavorite
+            | AppAction::NewChannel
+            | AppAction::RenameChannel
+            | AppAction::MarkGuildRead
+            | AppAction::OpenEmojiManager
+            | AppAction::HistoryPrevious
+            | AppAction::HistoryNext
+            | AppAction::MouseClick(_, _)
+            | AppAction::OpenAttachPrompt
+            | AppAction::OpenSearch
+            | AppAction::JumpToMention
+            | AppAction::LoadArchivedThreads
+            | AppAction::ScrollPageUp
+            | AppAction::ScrollPageDown
+            | AppAction::ScrollLineUp
+            | AppAction::ScrollLineDown
+    )
+}
+
+/// Whether a popup over the screen takes the keys, and so the mouse wheel too.
+fn overlay_open(state: &App) -> bool {
+    state.switcher.is_some()
+        || state.notification_overlay.is_some()
+        || state.pins.is_some()
+        || state.search.is_some()
+        || state.forum.is_some()
+        || state.emoji_manager.is_some()
+        || state.webhook_picker.is_some()
+        || state.ignore_list.is_some()
+        || state.invite_prompt.is_some()
+        || state.alias_list.is_some()
+        || state.profile.is_some()
+        || state.show_error_log
+        || state.show_command_help
+        || state.key_help.is_some()
+}
+
+/// Applies an action to the app. Nothing outside it is touched: requests, saves and
+/// actions to handle next come back in `commands`, for the caller to carry out.
+pub fn apply_action(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<KeywordAction> {
+    let total_filtered_emojis = {
+        let (unicode, custom) = state.emoji_matches();
+        unicode.len() + custom.len()
+    };
+
+    // Any key dismisses the error toast, and still does what it normally does.
+    if state.error_toast.is_some() && is_keypress(&action) {
+        state.error_toast = None;
+    }
+
+    // The wheel scrolls the chat, and moves through lists anywhere else.
+    let in_chat = matches!(state.state, AppState::Chatting(_)) && !overlay_open(state);
+    let action = match action {
+        AppAction::MouseWheelUp if in_chat => AppAction::ScrollLineUp,
+        AppAction::MouseWheelDown if in_chat => AppAction::ScrollLineDown,
+        AppAction::MouseWheelUp => AppAction::SelectPrevious,
+        AppAction::MouseWheelDown => AppAction::SelectNext,
+        action => action,
+    };
+
+    // Pastes arrive whole, from the clipboard or the terminal's bracketed paste. Too long
+    // ones are refused rather than cut, as Discord would refuse the message anyway.
+    let action = match action {
+        AppAction::Paste(text) => {
+            let text = text.replace("\r\n", "\n").replace('\r', "\n");
+            let length = message_length(&state.input) + message_length(&text);
+            if length > MAX_MESSAGE_LENGTH {
+                state.status_message = format!(
+                    "Paste refused: the message would be {length} characters long, over the limit of {MAX_MESSAGE_LENGTH}."
+                );
+                return None;
+            }
+            AppAction::Paste(text)
+        }
+        action => action,
+    };
+
+    let action = if state.show_error_log {
+        handle_error_log_keys(state, action)?
+    } else {
+        action
+    };
+    let action = if state.show_command_help {
+        handle_command_help_keys(state, action)?
+    } else {
+        action
+    };
+    let action = if state.key_help.is_some() {
+        handle_key_help_keys(state, action)?
+    } else {
+        action
+    };
+
+    let action = if state.switcher.is_some() {
+        handle_switcher_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.notification_overlay.is_some() {
+        handle_notification_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.nsfw_prompt.is_some() {
+        handle_nsfw_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.pending_pings.is_some() {
+        handle_ping_keys(state, action)?
+    } else {
+        action
+    };
+    let action = if state.resume_prompt.is_some() {
+        handle_resume_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.profile.is_some() {
+        handle_profile_keys(state, action)?
+    } else {
+        action
+    };
+    let action = if state.link_hints.is_some() {
+        handle_link_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.pins.is_some() {
+        handle_pin_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.search.is_some() {
+        handle_search_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.attach_prompt.is_some() {
+        handle_attach_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.channel_prompt.is_some() {
+        handle_channel_prompt_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.forum.is_some() {
+        handle_forum_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.emoji_manager.is_some() {
+        handle_emoji_manager_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.webhook_picker.is_some() {
+        handle_webhook_picker_keys(state, action)?
+    } else {
+        action
+    };
+    let action = if state.ignore_list.is_some() {
+        handle_ignore_list_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.alias_list.is_some() {
+        handle_alias_list_keys(state, action, commands)?
+    } else {
+        action
+    };
+    let action = if state.invite_prompt.is_some() {
+        handle_invite_keys(state, action, commands)?
+    } else {
+        action
+    };
+
+    match action {
+        AppAction::ToggleSwitcher => open_switcher(state, commands),
+        AppAction::ToggleErrorLog => {
+            state.switcher = None;
+            state.notification_overlay = None;
+            state.pins = None;
+            state.show_error_log = true;
+        }
+        AppAction::ToggleNotifications => {
+            state.switcher = None;
+            state.pins = None;
+            state.notification_overlay = Some(0);
+            state.unacknowledged_mentions = 0;
+        }
+        AppAction::TogglePins => open_pins(state, commands),
+        AppAction::ToggleKeyHelp => state.key_help = Some(0),
+        AppAction::ToggleLinkHints => open_link_hints(state),
+        AppAction::ToggleMute => toggle_mute(state, commands),
+        AppAction::ToggleShowMuted => toggle_show_muted(state),
+        AppAction::ToggleFavorite => toggle_favorite(state, commands),
+        AppAction::MarkGuildRead => mark_guild_read(state, commands),
+        AppAction::UnreadCounts(counts) => state.guild_unread = counts,
+        AppAction::Translated(message_id, result) => match result {
+            Ok(translation) => {
+                state.translations.insert(message_id, Some(translation));
+                state.invalidate_rows();
+            }
+            Err(e) => {
+                state.translations.remove(&message_id);
+                state.invalidate_rows();
+                report_error(state, format!("Translation failed: {e}"));
+            }
+        },
+        AppAction::NewChannel => open_channel_prompt(state, false),
+        AppAction::OpenEmojiManager => open_emoji_manager(state, commands),
+        AppAction::ApiManagedEmojis(guild_id, result) => match result {
+            Ok(emojis) => {
+                if let Some(manager) = &mut state.emoji_manager
+                    && manager.guild_id == guild_id
+                {
+                    manager.emojis = Some(emojis.clone());
+                }
+                commands.dispatch(AppAction::ApiUpdateEmojis(guild_id, emojis));
+            }
+            Err(e) => emoji_request_failed(state, &guild_id, e, "load the emojis"),
+        },
+        AppAction::ApiEmojiCreated(guild_id, result) => match result {
+            Ok(emoji) => {
+                state.status_message = format!("Added :{}:.", emoji.name);
+                if let Some(manager) = &mut state.emoji_manager
+                    && manager.guild_id == guild_id
+                    && let Some(emojis) = &mut manager.emojis
+                {
+                    emojis.push(emoji);
+                    manager.selection_index = emojis.len() - 1;
+                    let emojis = emojis.clone();
+                    commands.dispatch(AppAction::ApiUpdateEmojis(guild_id, emojis));
+                }
+            }
+            Err(e) => emoji_request_failed(state, &guild_id, e, "upload the emoji"),
+        },
+        AppAction::ApiEmojiDeleted(guild_id, emoji_id, result) => match result {
+            Ok(()) => {
+                state.status_message = "Emoji deleted.".to_string();
+                if let Some(manager) = &mut state.emoji_manager
+                    && manager.guild_id == guild_id
+                    && let Some(emojis) = &mut manager.emojis
+                {
+                    emojis.retain(|e| e.id != emoji_id);
+                    manager.selection_index =
+                        manager.selection_index.min(emojis.len().saturating_sub(1));
+                    let emojis = emojis.clone();
+                    commands.dispatch(AppAction::ApiUpdateEmojis(guild_id, emojis));
+                }
+            }
+            Err(e) => emoji_request_failed(state, &guild_id, e, "delete the emoji"),
+        },
+        AppAction::ApiChannelWebhooks(channel_id, result) => {
+            if state
+                .webhook_picker
+                .as_ref()
+                .is_some_and(|picker| picker.channel_id == channel_id)
+            {
+                match result {
+                    Ok(webhooks) => {
+                        state.status_message = if webhooks.iter().any(|w| w.can_execute()) {
+                            "Pick a webhook to send through, Esc to send as yourself."
+                        } else {
+                            "No webhook of this channel can be posted through."
+                        }
+                        .to_string();
+                        if let Some(picker) = &mut state.webhook_picker {
+                            picker.webhooks = Some(webhooks);
+                        }
+                    }
+                    Err(ApiError::Forbidden(_)) => {
+                        state.webhook_picker = None;
+                        report_error(
+                            state,
+                            "You lack Manage Webhooks in this channel, so webhook mode stays off."
+                                .to_string(),
+                        );
+                    }
+                    Err(e) => {
+                        state.webhook_picker = None;
+                        report_error(state, format!("Failed to load webhooks: {e}"));
+                    }
+                }
+            }
+        }
+        AppAction::RenameChannel => open_channel_prompt(state, true),
+        AppAction::HistoryPrevious => recall_history(state, true),
+        AppAction::HistoryNext => recall_history(state, false),
+        AppAction::MessageSent(channel_id, content) => {
+            history::record(state.input_history.entry(channel_id).or_default(), content);
+            if state.persist_input_history {
+                let input_history = state.input_history.clone();
+                let tx_clone = commands.sender();
+                commands.spawn(async move {
+                    if let Err(e) = config::store_input_history(input_history).await {
+                        tx_clone
+                            .send(AppAction::ApiError(format!(
+                                "Failed to save the input history: {e}"
+                            )))
+                            .await
+                            .ok();
+                    }
+                });
+            }
+        }
+        AppAction::OpenAttachPrompt => open_attach_prompt(state),
+        AppAction::ApiUpdateForumPosts(channel_id, result) => {
+            if let Some(forum) = &mut state.forum
+                && forum.channel_id == channel_id
+            {
+                match result {
+                    Ok(posts) => forum.posts = Some(posts),
+                    Err(e) => {
+                        state.forum = None;
+                        report_error(state, format!("Failed to load posts: {e}"));
+                    }
+                }
+            }
+        }
+        AppAction::ApiForumPostCreated(post) => {
+            let guild_id = post
+                .guild_id
+                .clone()
+                .or_else(|| shown_guild_id(state).cloned())?;
+            state.forum = None;
+            state
+                .thread_cache
+                .entry(guild_id.clone())
+                .or_default()
+                .insert(0, post.clone());
+            open_channel(state, commands, post.id, &post.name, Some(guild_id));
+        }
+        AppAction::OpenSearch => {
+            if state.input.is_empty() && state.editing_message.is_none() {
+                open_search_prompt(state);
+            } else {
+                state.status_message =
+                    "Send or clear the input before searching, or use /search <text>.".to_string();
+            }
+        }
+        AppAction::UploadFinished(local_id, result) => {
+            state.uploads.retain(|message| message.id != local_id);
+            match result {
+                Ok(()) => {
+                    state.status_message = CHAT_HELP.to_string();
+                }
+                Err(e) => {
+                    state.status_message = e.clone();
+                    report_error(state, e);
+                }
+            }
+        }
+        // Outside the guild list, Ctrl+S opens or closes the split pane.
+        AppAction::CycleGuildSort if !matches!(state.state, AppState::SelectingGuild) => {
+            toggle_split(state, commands);
+        }
+        AppAction::CycleGuildSort => {
+            if matches!(state.state, AppState::SelectingGuild) {
+                // The selection follows the guild it was on into the new order.
+                let selected_id = filter_guilds(
+                    &state.guilds,
+                    &state.input,
+                    state.guild_sort,
+                    &state.guilds_opened,
+                    &state.muted,
+                    state.pinned.ids(PinKind::Guild),
+                )
+                .get(state.selection_index)
+                .map(|g| g.id.clone());
+                state.guild_sort = state.guild_sort.next();
+                state.selection_index = filter_guilds(
+                    &state.guilds,
+                    &state.input,
+                    state.guild_sort,
+                    &state.guilds_opened,
+                    &state.muted,
+                    state.pinned.ids(PinKind::Guild),
+                )
+                .iter()
+                .position(|g| Some(&g.id) == selected_id.as_ref())
+                .unwrap_or(0);
+                state.status_message = format!("Guilds sorted by {}", state.guild_sort.label());
+            }
+        }
+        AppAction::MouseClick(column, row) => {
+            click_list(state, commands, column, row);
+        }
+        // Turned into scrolling or selection before reaching here.
+        AppAction::MouseWheelUp | AppAction::MouseWheelDown => {}
+        AppAction::CycleTimeFormat => {
+            state.time_format = state.time_format.next(state.custom_time_format.as_deref());
+            state.status_message = format!("Times shown as {}", state.time_format.label());
+        }
+        AppAction::ToggleDensity => {
+            state.density = state.density.next();
+            state.status_message = format!("Messages shown {}", state.density.label());
+        }
+        AppAction::ApiUpdatePins(channel_id, result) => {
+            if let Some(pins) = &mut state.pins
+                && pins.channel_id == channel_id
+            {
+                match result {
+                    Ok(messages) => pins.messages = Some(messages),
+                    Err(e) => {
+                        state.pins = None;
+                        report_error(state, format!("Failed to load pins: {e}"));
+                    }
+                }
+            }
+        }
+        AppAction::ApiJumpToMessage(message_id, messages) => {
+            let channel_id = messages.first().map(|m| m.channel_id.clone())?;
+            if !matches!(&state.state, AppState::Chatting(id) if *id == channel_id) {
+                return None;
+            }
+
+            // The loaded history stays as the newest part of the chat, so scrolling down
+            // still leads to the latest messages. Messages around an older one are joined
+            // below it.
+            let loaded = std::mem::take(&mut state.messages);
+            state.messages = Message::merge_latest(messages, loaded);
+            request_members(state, commands);
+
+            if let Some(index) = state.messages.iter().position(|m| m.id == message_id) {
+                state.state = AppState::SelectingMessage(channel_id);
+                state.selection_index = index;
+                state.status_message =
+                    "Jumped to the message. Up/Down to move, Enter to reply, Esc to cancel."
+                        .to_string();
+            }
+        }
+        AppAction::ApiSearchProgress(query, checked) => {
+            if let Some(search) = &mut state.search
+                && search.query == query
+                && let SearchPhase::Searching(_) = search.phase
+            {
+                search.phase = SearchPhase::Searching(checked);
+                state.status_message =
+                    format!("Searching for \"{query}\"... {checked} messages checked.");
+            }
+        }
+        AppAction::ApiSearchResults(outcome) => {
+            let Some(search) = &mut state.search else {
+                return None;
+            };
+            if search.query != outcome.query || !matches!(search.phase, SearchPhase::Searching(_)) {
+                return None;
+            }
+
+            let count = outcome.matches.len();
+            let checked = outcome.checked;
+            search.phase = SearchPhase::Results(outcome.matches, 0);
+            let found = match count {
+                0 => format!("No messages matching \"{}\"", outcome.query),
+                1 => "1 match".to_string(),
+                count => format!("{count} matches"),
+            };
+
+            state.status_message = match outcome.error {
+                None if checked == SEARCH_PAGES * SEARCH_PAGE_SIZE => {
+                    format!("{found} in the last {checked} messages. Older ones weren't searched.")
+                }
+                None => format!("{found} in the whole channel."),
+                Some(ApiError::RateLimited { retry_after }) => format!(
+                    "{found} in the last {checked} messages. Rate limited, try again in {retry_after:.0}s to search further."
+                ),
+                Some(ApiError::Unauthorized) => {
+                    commands.dispatch(AppAction::Unauthorized);
+                    return None;
+                }
+                Some(e) => {
+                    report_error(state, format!("Search stopped: {e}"));
+                    format!("{found} in the last {checked} messages.")
+                }
+            };
+        }
+        AppAction::JumpToMention => {
+            if state.unacknowledged_mentions > 0
+                && let Some(notification) = state.notifications.front().cloned()
+            {
+                state.switcher = None;
+                state.unacknowledged_mentions = 0;
+                jump_to_notification(state, commands, notification);
+            }
+        }
+        AppAction::ApiBackgroundMessages(messages) => {
+            let open_channel_id = match &state.state {
+                AppState::Chatting(id)
+                | AppState::EmojiSelection(id)
+                | AppState::SelectingMessage(id) => Some(id.clone()),
+                _ => None,
+            };
+            let snowflake = |id: &str| id.parse::<u64>().unwrap_or(0);
+
+            for message in messages {
+                let read = state
+                    .read_markers
+                    .get(&message.channel_id)
+                    .is_some_and(|read| snowflake(read) >= message.snowflake());
+                if !read && open_channel_id.as_ref() != Some(&message.channel_id) {
+                    notify_mention(state, &message);
+                }
+            }
+        }
+        AppAction::ApiCacheGuildChannels(guild_id, channels, context) => {
+            let channels = Channel::filter_channels_by_categories(channels).unwrap_or_default();
+
+            // A chat opened before its guild was known, as from the command line, belongs
+            // to the guild listing it; adopt the list so Esc lands in the right guild.
+            if state.chat_guild_id.is_none()
+                && let Some(channel_id) = chat_channel_id(state)
+                && channels
+                    .iter()
+                    .flat_map(|c| std::iter::once(c).chain(c.children.iter().flatten()))
+                    .any(|c| c.id == *channel_id)
+            {
+                state.chat_guild_id = Some(guild_id.clone());
+            }
+            if state.chat_guild_id.as_ref() == Some(&guild_id) && state.channels.is_empty() {
+                state.channels = channels.clone();
+                state.context = context.clone();
+                state.invalidate_rows();
+            }
+
+            state.channel_cache.insert(guild_id.clone(), channels);
+            if let Some(context) = context {
+                state.context_cache.insert(guild_id, context);
+            }
+            store_list_cache(state, commands);
+        }
+        AppAction::ApiOpenChannel(channel) => {
+            match &channel.guild_id {
+                Some(guild_id) => request_guild_channels(state, commands, guild_id),
+                // Listed so that going back from it leads to the DM list.
+                None if !state.dms.iter().any(|dm| dm.id == channel.id) => {
+                    let dm = DM {
+                        id: channel.id.clone(),
+                        channel_type: channel.channel_type,
+                        recipients: channel.recipients.clone(),
+                    };
+                    state.dms.insert(0, dm);
+                }
+                None => {}
+            }
+
+            let name = if channel.name.is_empty() {
+                channel
+                    .recipients
+                    .iter()
+                    .map(|u| u.username.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            } else {
+                channel.name.clone()
+            };
+            // Not listed yet, so `open_channel` can't tell it is NSFW.
+            if state.content_warnings && channel.is_age_gated(None, &state.nsfw_confirmed) {
+                let gated = GatedChannel {
+                    channel_id: channel.id,
+                    name,
+                    guild_id: channel.guild_id,
+                };
+                gate_channel(state, gated);
+                return None;
+            }
+            open_channel(state, commands, channel.id, &name, channel.guild_id);
+        }
+        AppAction::SigInt => return Some(KeywordAction::Break),
+        AppAction::InputEscape => {
+            // In vim mode, Esc switches from Insert to Normal mode and returns early.
+            // In non-vim mode (or vim Normal mode), Esc triggers navigation (handled below).
+            if state.vim_mode && state.mode == InputMode::Insert {
+                state.mode = InputMode::Normal;
+                if let Some(c) = state.input[..state.cursor_position].chars().next_back()
+                    && c != '\n'
+                {
+                    state.cursor_position -= c.len_utf8();
+                }
+                vim::clamp_cursor(state);
+                return None;
+            }
+            // Navigation logic: clear the list filter first, then go back or quit
+            match &state.state {
+                AppState::SelectingGuild | AppState::SelectingChannel(_)
+                    if !state.input.is_empty() =>
+                {
+                    state.input.clear();
+                    state.cursor_position = 0;
+                    state.selection_index = 0;
+                }
+                AppState::Home | AppState::Loading(_) => return Some(KeywordAction::Break),
+                AppState::SelectingDM => {
+                    commands.dispatch(AppAction::TransitionToHome);
+                }
+                AppState::SelectingGuild => {
+                    commands.dispatch(AppAction::TransitionToHome);
+                }
+                AppState::SelectingChannel(_) => {
+                    commands.dispatch(AppAction::TransitionToGuilds);
+                }
+                AppState::SelectingMessage(_)
+                    if state.preview.is_some() || state.preview_loading =>
+                {
+                    state.preview = None;
+                    state.preview_loading = false;
+                    state.status_message = selection_help(state);
+                }
+                AppState::SelectingMessage(_) if state.pending_delete.is_some() => {
+                    state.pending_delete = None;
+                    state.status_message = "Deletion cancelled.".to_string();
+                }
+                AppState::SelectingMessage(_) if state.pending_quote.is_some() => {
+                    state.pending_quote = None;
+                    state.status_message = "Kept the draft.".to_string();
+                }
+                AppState::SelectingMessage(_) if state.pending_ignore.is_some() => {
+                    state.pending_ignore = None;
+                    state.status_message = "Not ignored.".to_string();
+                }
+                AppState::SelectingMessage(_) if state.pending_publish.is_some() => {
+                    state.pending_publish = None;
+                    state.status_message = "Not published.".to_string();
+                }
+                AppState::SelectingMessage(_) if state.reaction_input.is_some() => {
+                    state.reaction_input = None;
+                    state.status_message = "Reaction cancelled.".to_string();
+                }
+                AppState::SelectingMessage(channel_id) => {
+                    state.state = AppState::Chatting(channel_id.clone());
+                    state.selection_index = 0;
+                    state.status_message = CHAT_HELP.to_string();
+                }
+                AppState::Chatting(_) if state.editing_message.is_some() => {
+                    state.editing_message = None;
+                    state.input.clear();
+                    state.cursor_position = 0;
+                    state.status_message = CHAT_HELP.to_string();
+                }
+                AppState::Chatting(_) if state.replying_to.is_some() => {
+                    state.replying_to = None;
+                    state.status_message = CHAT_HELP.to_string();
+                }
+                AppState::Chatting(channel_id) => {
+                    let channel_id = channel_id.clone();
+                    stash_draft(state);
+                    store_drafts(state, commands);
+
+                    // The guild was recorded when the chat opened or is in the loaded
+                    // channel lists, so going back needs no request.
+                    let back = back_from_chat(state, &channel_id);
+                    commands.dispatch(back);
+                }
+                AppState::EmojiSelection(channel_id) => {
+                    commands.dispatch(AppAction::TransitionToChat(channel_id.clone()));
+                }
+            }
+        }
+        AppAction::Paste(text) => {
+            // Always insert text at cursor position, effectively treating it as insert mode operation
+            // but without necessarily switching mode if we want to be strict.
+            // However, standard behavior usually implies switching to insert or just inserting.
+            // Let's just insert.
+            let pos = state.cursor_position;
+            state.input.insert_str(pos, &text);
+            state.cursor_position += text.len();
+        }
+        AppAction::InputChar(c) => {
+            if let AppState::SelectingMessage(_) = state.state {
+                handle_message_selection_keys(state, c, commands);
+                return None;
+            }
+
+            let in_list = matches!(
+                state.state,
+                AppState::SelectingGuild | AppState::SelectingChannel(_)
+            );
+            if state.vim_mode
+                && state.mode == InputMode::Normal
+                && let Some(action) = keymap::vim_normal_action(c, in_list)
+            {
+                commands.dispatch(action);
+                return None;
+            }
+
+            if in_list && (!state.vim_mode || state.mode == InputMode::Insert) {
+                // The filter changed, so start again from the first match.
+                insert_char_at_cursor(state, c);
+                state.selection_index = 0;
+                return None;
+            }
+
+            if c == ':' && (!state.vim_mode || state.mode == InputMode::Insert) {
+                commands.dispatch(AppAction::SelectEmoji);
+                return None;
+            }
+
+            if !state.vim_mode || state.mode == InputMode::Insert {
+                insert_char_at_cursor(state, c);
+                if c == ' ' {
+                    expand_shortcodes_before_cursor(state);
+                }
+                notify_typing(state, commands);
+            } else {
+                vim::handle_vim_keys(state, c, commands);
+            }
+        }
+        AppAction::SelectMessage => {
+            if let AppState::Chatting(channel_id) = &state.state
+                && !state.messages.is_empty()
+            {
+                state.state = AppState::SelectingMessage(channel_id.clone());
+                state.selection_index = 0;
+                state.status_message = selection_help(state);
+            }
+        }
+        AppAction::DeleteMessage => {
+            if let AppState::SelectingMessage(_) = state.state
+                && let Some(message) = state.messages.get(state.selection_index)
+            {
+                let prompt = format!("Delete message from {}? (y/n)", message.author.username);
+                state.pending_delete = Some(message.id.clone());
+                state.status_message = prompt;
+            }
+        }
+        AppAction::SelectEmoji => {
+            if let AppState::EmojiSelection(channel_id) = &state.state.clone() {
+                // A closing ':' completes the shortcode typed so far.
+                insert_char_at_cursor(state, ':');
+                state.state = AppState::Chatting(channel_id.clone());
+                state.emoji_filter.clear();
+                state.emoji_filter_start = None;
+                state.selection_index = 0;
+                state.status_message = CHAT_HELP.to_string();
+                expand_shortcodes_before_cursor(state);
+            } else if let AppState::Chatting(channel_id) = &state.state.clone() {
+                let cursor_pos = std::cmp::min(state.cursor_position, state.input.len());
+                let is_start_of_emoji = cursor_pos == 0 || state.input[..cursor_pos].ends_with(' ');
+
+                if is_start_of_emoji {
+                    let pos = state.cursor_position;
+                    // Track where the emoji filter starts (position of the ':')
+                    state.emoji_filter_start = Some(pos);
+                    state.input.insert(pos, ':');
+                    state.cursor_position += ':'.len_utf8();
+                    let owned_channel_id = channel_id.clone();
+                    state.state = AppState::EmojiSelection(owned_channel_id);
+                    state.status_message =
+                        "Type to filter emoji. Tab to cycle, Enter to select, Esc to cancel."
+                            .to_string();
+                    state.emoji_filter.clear();
+                    state.selection_index = 0;
+                } else {
+                    let pos = state.cursor_position;
+                    state.input.insert(pos, ':');
+                    state.cursor_position += ':'.len_utf8();
+                }
+            }
+        }
+        AppAction::InputBackspace => {
+            if let Some(reaction) = &mut state.reaction_input {
+                reaction.pop();
+                let prompt = format!("React with :{reaction}");
+                state.status_message = prompt;
+                return None;
+            }
+            if state.vim_mode && state.mode == InputMode::Normal {
+                state.cursor_position = input::prev_grapheme(&state.input, state.cursor_position);
+                return None;
+            }
+            let current_state = state.state.clone();
+            match current_state {
+                AppState::Chatting(_) => {
+                    let pos = state.cursor_position;
+                    let start = input::prev_grapheme(&state.input, pos);
+                    state.input.drain(start..pos);
+                    state.cursor_position = start;
+                }
+                AppState::EmojiSelection(channel_id) => {
+                    let pos = state.cursor_position;
+                    if pos > 0 {
+                        let start = input::prev_grapheme(&state.input, pos);
+                        state.input.drain(start..pos);
+                        state.cursor_position = start;
+                        // Recompute emoji_filter based on the current input and emoji_filter_start.
+                        if let Some(start) = state.emoji_filter_start {
+                            // Position just after the ':' that started the emoji filter.
+                            let filter_start = start + ':'.len_utf8();
+                            if state.cursor_position <= start || filter_start > state.input.len() {
+                                // Cursor moved to or before the ':' (or indices are invalid);
+                                // clear the filter as we're no longer within the emoji filter.
+                                state.emoji_filter.clear();
+                            } else {
+                                let end = std::cmp::min(state.cursor_position, state.input.len());
+                                if filter_start <= end {
+                                    state.emoji_filter = state.input[filter_start..end].to_string();
+                                } else {
+                                    state.emoji_filter.clear();
+                                }
+                            }
+                        } else {
+                            // No known start of emoji filter; be conservative and clear it.
+                            state.emoji_filter.clear();
+                        }
+
+                        if state.emoji_filter.is_empty() {
+                            state.state = AppState::Chatting(channel_id.clone());
+                            state.emoji_filter_start = None;
+                            state.status_message = CHAT_HELP.to_string();
+                        }
+                        state.selection_index = 0;
+                    }
+                }
+                AppState::SelectingGuild | AppState::SelectingChannel(_) => {
+                    let pos = state.cursor_position;
+                    let start = input::prev_grapheme(&state.input, pos);
+                    state.input.drain(start..pos);
+                    state.cursor_position = start;
+                    state.selection_index = 0;
+                }
+                _ => {
+                    let pos = state.cursor_position;
+                    let start = input::prev_grapheme(&state.input, pos);
+                    state.input.drain(start..pos);
+                    state.cursor_position = start;
+                }
+            }
+        }
+        AppAction::InputMoveCursor(movement) => {
+            if state.pending_delete.is_some() || state.reaction_input.is_some() {
+                return None;
+            }
+            if matches!(movement, CursorMove::End)
+                && matches!(state.state, AppState::Chatting(_))
+                && state.scroll_offset > 0
+            {
+                // While scrolled back, End returns to following the newest messages.
+                state.scroll_offset = 0;
+                state.status_message = CHAT_HELP.to_string();
+                return None;
+            }
+            leave_emoji_selection(state);
+
+            let pos = state.cursor_position;
+            let line_start = state.input[..pos].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = state.input[pos..]
+                .find('\n')
+                .map_or(state.input.len(), |i| pos + i);
+
+            state.cursor_position = match movement {
+                CursorMove::Left => input::prev_grapheme(&state.input, pos),
+                CursorMove::Right => input::next_grapheme(&state.input, pos),
+                CursorMove::Home => line_start,
+                CursorMove::End => line_end,
+                CursorMove::WordLeft => input::prev_word(&state.input, pos),
+                CursorMove::WordRight => input::next_word(&state.input, pos),
+            };
+        }
+        AppAction::InputDeleteWord | AppAction::InputClearToStart => {
+            if state.pending_delete.is_some() || state.reaction_input.is_some() {
+                return None;
+            }
+            leave_emoji_selection(state);
+
+            let pos = state.cursor_position;
+            let start = match action {
+                AppAction::InputDeleteWord => input::prev_word(&state.input, pos),
+                _ => state.input[..pos].rfind('\n').map_or(0, |i| i + 1),
+            };
+            state.input.drain(start..pos);
+            state.cursor_position = start;
+        }
+        AppAction::InputTab => match state.state {
+            AppState::EmojiSelection(_) => {
+                move_selection(state, 1, total_filtered_emojis);
+            }
+            AppState::SelectingGuild => {
+                commands.dispatch(AppAction::TransitionToDM);
+            }
+            AppState::SelectingDM => {
+                commands.dispatch(AppAction::TransitionToGuilds);
+            }
+            _ => {}
+        },
+        AppAction::InputSubmit => {
+            // Without a completion to pick, Enter sends the message as usual.
+            if let AppState::EmojiSelection(channel_id) = &state.state.clone()
+                && total_filtered_emojis == 0
+            {
+                state.state = AppState::Chatting(channel_id.clone());
+                state.emoji_filter.clear();
+                state.emoji_filter_start = None;
+                state.selection_index = 0;
+            }
+            return input_submit(state, commands);
+        }
+        AppAction::SelectNext => move_selection(state, 1, total_filtered_emojis),
+        AppAction::SelectPrevious => {
+            if let AppState::Chatting(_) = state.state
+                && state.input.is_empty()
+            {
+                let own_message = state
+                    .messages
+                    .iter()
+                    .find(|m| m.author.id == state.current_user.id)
+                    .map(|m| (m.id.clone(), m.content.clone().unwrap_or_default()));
+
+                if let Some((message_id, content)) = own_message {
+                    state.cursor_position = content.len();
+                    state.input = content;
+                    state.editing_message = Some(message_id);
+                    state.status_message =
+                        "(editing) Press Enter to save, Esc to cancel.".to_string();
+                }
+                return None;
+            }
+            move_selection(state, -1, total_filtered_emojis)
+        }
+        AppAction::ScrollPageUp | AppAction::ScrollLineUp => {
+            if let AppState::Chatting(channel_id) = &state.state.clone() {
+                if !state.chat_at_top {
+                    let step = match action {
+                        AppAction::ScrollLineUp => SCROLL_LINES,
+                        _ => state.terminal_height.saturating_sub(2).max(1),
+                    };
+                    state.scroll_offset += step;
+                } else if state.history_exhausted {
+                    state.status_message = "Beginning of channel.".to_string();
+                } else if let Some(oldest) = state.messages.last() {
+                    let oldest_id = oldest.id.clone();
+                    let channel_id = channel_id.clone();
+                    let api_client_clone = state.api_client.clone();
+                    let tx_clone = commands.sender();
+
+                    state.status_message = "Loading older messages...".to_string();
+
+                    commands.spawn(async move {
+                        match api_client_clone
+                            .get_channel_messages(
+                                &channel_id,
+                                None,
+                                Some(oldest_id),
+                                None,
+                                Some(100),
+                            )
+                            .await
+                        {
+                            Ok(messages) => {
+                                tx_clone
+                                    .send(AppAction::ApiUpdateOlderMessages(messages))
+                                    .await
+                                    .ok();
+                            }
+                            Err(e) => {
+                                tx_clone
+                                    .send(AppAction::ApiError(format!(
+                                        "Failed to load older messages: {e}"
+                                    )))
+                                    .await
+                                    .ok();
+                            }
+                        }
+                    });
+                }
+            }
+        }
+        AppAction::ScrollPageDown | AppAction::ScrollLineDown => {
+            if let AppState::Chatting(_) = &state.state {
+                let step = match action {
+                    AppAction::ScrollLineDown => SCROLL_LINES,
+                    _ => state.terminal_height.saturating_sub(2).max(1),
+                };
+                state.scroll_offset = state.scroll_offset.saturating_sub(step);
+                if state.scroll_offset == 0 {
+                    state.status_message = CHAT_HELP.to_string();
+                }
+            }
+        }
+        AppAction::ApiUpdateMessages(new_messages) => {
+            let channel_id = new_messages.first().map(|m| m.channel_id.clone())?;
+
+            // A fetch may finish after its chat was left; it then only refreshes the cache.
+            if chat_channel_id(state) != Some(&channel_id) {
+                if let Some((_, cached)) = state.message_cache.get_mut(&channel_id) {
+                    *cached = Message::merge_latest(std::mem::take(cached), new_messages);
+                }
+                return None;
+            }
+
+            let message_id = new_messages[0].id.clone();
+            mark_read(state, &channel_id, &message_id, commands);
+            ack_latest(state, commands, &channel_id, &message_id);
+            if changes_loaded_rows(&state.messages, &new_messages) {
+                state.invalidate_rows();
+            }
+            let existing = std::mem::take(&mut state.messages);
+            state.messages = Message::merge_latest(existing, new_messages);
+            request_members(state, commands);
+
+            if let Some((_, message_id)) = state
+                .pending_jump
+                .take_if(|(jump_channel, _)| *jump_channel == channel_id)
+            {
+                jump_to_message(state, commands, channel_id, message_id);
+            }
+        }
+        AppAction::ApiUpdateNewerMessages(channel_id, newer_messages) => {
+            if chat_channel_id(state) != Some(&channel_id) {
+                return None;
+            }
+
+            if changes_loaded_rows(&state.messages, &newer_messages) {
+                state.invalidate_rows();
+            }
+            let existing = std::mem::take(&mut state.messages);
+            state.messages = Message::merge_newer(existing, newer_messages);
+            if let Some(message_id) = state.messages.first().map(|m| m.id.clone()) {
+                mark_read(state, &channel_id, &message_id, commands);
+                ack_latest(state, commands, &channel_id, &message_id);
+            }
+            request_members(state, commands);
+        }
+        AppAction::ApiReplaceMessage(message) => {
+            if let Some(existing) = state.messages.iter_mut().find(|m| m.id == message.id) {
+                *existing = message;
+                state.invalidate_rows();
+            }
+        }
+        AppAction::ApiInvite(result) => match result {
+            Ok(invite) => {
+                state.status_message = format!(
+                    "Join {}? y to join, n or Esc to cancel.",
+                    invite.guild_name()
+                );
+                state.invite_prompt = Some(InvitePrompt {
+                    invite,
+                    joined: false,
+                });
+            }
+            Err(e) => report_error(state, e),
+        },
+        AppAction::ApiInviteAccepted(invite) => {
+            state.status_message = format!(
+                "Joined {}. Enter to open it, Esc to stay here.",
+                invite.guild_name()
+            );
+            state.invite_prompt = Some(InvitePrompt {
+                invite,
+                joined: true,
+            });
+        }
+        AppAction::ApiPublished(message) => {
+            state.status_message = "Published the message to the following servers.".to_string();
+            if let Some(existing) = state.messages.iter_mut().find(|m| m.id == message.id) {
+                *existing = message;
+                state.invalidate_rows();
+            }
+        }
+        AppAction::ApiRemoveMessage(message_id) => {
+            if let Some(index) = state.messages.iter().position(|m| m.id == message_id) {
+                state.messages.remove(index);
+                if let AppState::SelectingMessage(_) = state.state {
+                    if index < state.selection_index {
+                        state.selection_index -= 1;
+                    }
+                    state.selection_index = state
+                        .selection_index
+                        .min(state.messages.len().saturating_sub(1));
+                }
+            }
+        }
+        AppAction::ApiRestoreMessage(message) => {
+            if !state.messages.iter().any(|m| m.id == message.id) {
+                let index = state
+                    .messages
+                    .iter()
+                    .position(|m| m.snowflake() < message.snowflake())
+                    .unwrap_or(state.messages.len());
+                state.messages.insert(index, message);
+            }
+        }
+        AppAction::ApiError(message) => report_error(state, message),
+        AppAction::Unauthorized => {
+            state.fatal_error = Some(state.api_client.token_type.rejected_message());
+            return Some(KeywordAction::Break);
+        }
+        AppAction::ChannelFailed(channel_id, e) => {
+            if matches!(e, ApiError::Forbidden(_) | ApiError::NotFound(_))
+                && let Some(split) = state.split.take_if(|split| split.channel_id == channel_id)
+            {
+                state.status_message =
+                    format!("Closed the split pane: {} is out of reach.", split.name);
+                if chat_channel_id(state) != Some(&channel_id) {
+                    return None;
+                }
+            }
+
+            let channel_name = state
+                .channels
+                .iter()
+                .flat_map(|c| c.children.iter().flatten())
+                .find(|c| c.id == channel_id)
+                .map(|c| format!("#{}", c.name))
+                .unwrap_or_else(|| "this channel".to_string());
+
+            match e {
+                ApiError::Unauthorized => {
+                    commands.dispatch(AppAction::Unauthorized);
+                }
+                ApiError::Forbidden(_) => {
+                    leave_channel(
+                        state,
+                        &channel_id,
+                        format!("You don't have access to {channel_name}."),
+                    );
+                }
+                ApiError::NotFound(_) => {
+                    let app = &mut *state;
+                    for channel in app
+                        .channels
+                        .iter_mut()
+                        .chain(app.channel_cache.values_mut().flatten())
+                    {
+                        if let Some(children) = &mut channel.children {
+                            children.retain(|c| c.id != channel_id);
+                        }
+                    }
+                    state.dms.retain(|d| d.id != channel_id);
+                    leave_channel(
+                        state,
+                        &channel_id,
+                        format!("{channel_name} no longer exists."),
+                    );
+                }
+                e => report_error(state, format!("Error loading chat: {e}")),
+            }
+        }
+        AppAction::PreviewLoaded(result) => {
+            // The preview may have been dismissed while it was downloading.
+            if state.preview_loading {
+                state.preview_loading = false;
+                match result {
+                    Ok(preview) => {
+                        state.status_message = "Previewing image. Esc to close.".to_string();
+                        state.preview = Some(preview);
+                    }
+                    Err(e) => report_error(state, format!("Failed to load preview: {e}")),
+                }
+            }
+        }
+        AppAction::ApiSplitMessages(channel_id, messages, full) => {
+            if let Some(split) = &mut state.split
+                && split.channel_id == channel_id
+            {
+                split.merge(messages, full);
+            }
+        }
+        AppAction::ApiUpdateOlderMessages(older_messages) => {
+            if older_messages.len() < 100 {
+                state.history_exhausted = true;
+            }
+
+            if older_messages.is_empty() {
+                state.status_message = "Beginning of channel.".to_string();
+            } else {
+                let existing = std::mem::take(&mut state.messages);
+                state.messages = Message::merge_older(existing, older_messages);
+                request_members(state, commands);
+                state.chat_at_top = false;
+                state.status_message =
+                    "Loaded older messages. PageDown to return to the latest.".to_string();
+            }
+        }
+        AppAction::ApiUpdateGuilds(new_guilds, complete) => {
+            update_guilds(state, new_guilds, complete, commands);
+        }
+        AppAction::ApiRefreshGuilds(new_guilds) => {
+            let known: HashSet<&str> = state.guilds.iter().map(|g| g.id.as_str()).collect();
+            let joined: Vec<&str> = new_guilds
+                .iter()
+                .filter(|g| !known.contains(g.id.as_str()))
+                .map(|g| g.name.as_str())
+                .collect();
+            let current: HashSet<&str> = new_guilds.iter().map(|g| g.id.as_str()).collect();
+            let left: Vec<&str> = state
+                .guilds
+                .iter()
+                .filter(|g| !current.contains(g.id.as_str()))
+                .map(|g| g.name.as_str())
+                .collect();
+
+            let mut changes = Vec::new();
+            if !joined.is_empty() {
+                changes.push(format!("Joined: {}", joined.join(", ")));
+            }
+            if !left.is_empty() {
+                changes.push(format!("Left: {}", left.join(", ")));
+            }
+            let changes = changes.join(" / ");
+
+            update_guilds(state, new_guilds, true, commands);
+            if !changes.is_empty() {
+                state.status_message = changes;
+            }
+        }
+        AppAction::ApiChannelSaved(channel) => {
+            let guild_id = channel.guild_id.clone()?;
+            state.status_message = format!("Saved #{}.", channel.name);
+
+            if channel.is_thread() {
+                if let Some(threads) = state.thread_cache.get_mut(&guild_id)
+                    && let Some(thread) = threads.iter_mut().find(|t| t.id == channel.id)
+                {
+                    *thread = channel;
+                }
+                return None;
+            }
+
+            // The list is grouped again with the channel in it, as if fetched anew.
+            let mut channels: Vec<Channel> = Vec::new();
+            for mut grouped in state
+                .channel_cache
+                .get(&guild_id)
+                .cloned()
+                .unwrap_or_default()
+            {
+                let children = grouped.children.take().unwrap_or_default();
+                if !grouped.id.is_empty() {
+                    channels.push(grouped);
+                }
+                channels.extend(children);
+            }
+            match channels.iter_mut().find(|c| c.id == channel.id) {
+                Some(existing) => *existing = channel,
+                None => channels.push(channel),
+            }
+            commands.dispatch(AppAction::ApiUpdateChannel(channels));
+        }
+        AppAction::ApiUpdateChannel(new_channels) => {
+            let guild_id = new_channels.iter().find_map(|c| c.guild_id.clone());
+            let channels = Channel::filter_channels_by_categories(new_channels).unwrap_or_default();
+            if let Some(guild_id) = &guild_id {
+                state
+                    .channel_cache
+                    .insert(guild_id.clone(), channels.clone());
+                store_list_cache(state, commands);
+            }
+
+            // A background refresh may land after the user moved on to another guild.
+            if guild_id.is_some() && shown_guild_id(state) != guild_id.as_ref() {
+                return None;
+            }
+
+            // Refreshing a list already on screen keeps the highlight on the same channel.
+            let refreshing = match &state.state {
+                AppState::SelectingChannel(guild_id) => {
+                    let rows = state.channel_rows(guild_id);
+                    Some(rows.get(state.selection_index).map(|c| c.id.clone()))
+                }
+                _ => None,
+            };
+
+            state.channels = channels;
+            state.invalidate_rows();
+
+            // Channels seen for the first time start out as read.
+            let unseen: Vec<(String, String)> = state
+                .channels
+                .iter()
+                .flat_map(|c| c.children.iter().flatten())
+                .filter(|c| !state.read_markers.contains_key(&c.id))
+                .filter_map(|c| Some((c.id.clone(), c.last_message_id.clone()?)))
+                .collect();
+            if !unseen.is_empty() {
+                state.read_markers.extend(unseen);
+                store_read_markers(state, commands);
+            }
+            if let Some(selected) = refreshing {
+                if let AppState::SelectingChannel(guild_id) = &state.state {
+                    let rows = state.channel_rows(guild_id);
+                    state.selection_index = rows
+                        .iter()
+                        .position(|c| Some(&c.id) == selected.as_ref())
+                        .unwrap_or(0);
+                }
+                return None;
+            }
+
+            let text_channels_count = state.channels.len();
+            if text_channels_count > 0 {
+                state.status_message =
+                    "Channels loaded. Select one to chat, Ctrl+T for archived threads. (Esc to return to Servers)".to_string();
+            } else {
+                state.status_message =
+                    "No text channels found. (Esc to return to Servers)".to_string();
+            }
+            state.selection_index = guild_id
+                .as_deref()
+                .map_or(0, |guild_id| remembered_channel_row(state, guild_id));
+        }
+        AppAction::ApiUpdateThreads(guild_id, threads, archived) => {
+            let known = state.thread_cache.entry(guild_id).or_default();
+            let count = threads.len();
+            for thread in threads {
+                match known.iter_mut().find(|t| t.id == thread.id) {
+                    Some(existing) => *existing = thread,
+                    None => known.push(thread),
+                }
+            }
+            // Most recently active first.
+            known.sort_by_key(|t| {
+                std::cmp::Reverse(
+                    t.last_message_id
+                        .as_ref()
+                        .and_then(|id| id.parse::<u64>().ok())
+                        .unwrap_or(0),
+                )
+            });
+
+            if archived {
+                state.status_message = if count == 0 {
+                    "No archived threads in this channel.".to_string()
+                } else {
+                    format!("Loaded {count} archived threads.")
+                };
+            }
+        }
+        AppAction::LoadArchivedThreads => {
+            let AppState::SelectingChannel(guild_id) = &state.state else {
+                return None;
+            };
+            let guild_id = guild_id.clone();
+            let rows = state.channel_rows(&guild_id);
+
+            // Threads of a thread do not exist; go to the thread's parent instead.
+            let channel_id = rows.get(state.selection_index).and_then(|c| {
+                if c.is_thread() {
+                    c.parent_id.clone()
+                } else {
+                    c.is_selectable().then(|| c.id.clone())
+                }
+            })?;
+
+            state.status_message = "Loading archived threads...".to_string();
+
+            let api_client_clone = state.api_client.clone();
+            let tx_clone = commands.sender();
+
+            commands.spawn(async move {
+                match api_client_clone
+                    .list_public_archived_threads(&channel_id)
+                    .await
+                {
+                    Ok(list) => {
+                        tx_clone
+                            .send(AppAction::ApiUpdateThreads(guild_id, list.threads, true))
+                            .await
+                            .ok();
+                    }
+                    Err(e) => {
+                        tx_clone
+                            .send(AppAction::ApiError(format!(
+                                "Failed to load archived threads: {e}"
+                            )))
+                            .await
+                            .ok();
+                    }
+                }
+            });
+        }
+        AppAction::ApiUpdateEmojis(guild_id, new_emojis) => {
+            if shown_guild_id(state) == Some(&guild_id) {
+                state.custom_emojis = new_emojis.clone();
+                state.invalidate_rows();
+            }
+            state.emoji_cache.insert(guild_id, new_emojis);
+        }
+        AppAction::ApiProfileMember(user_id, result) => {
+            if let Some(profile) = &mut state.profile
+                && profile.user.id == user_id
+            {
+                profile.member = Some(match result {
+                    Ok((member, roles)) => {
+                        profile.user = member.user.clone();
+                        MemberDetails::Loaded(member, roles)
+                    }
+                    Err(e) => MemberDetails::Failed(e),
+                });
+            }
+        }
+        AppAction::ApiDmOpened(dm) => {
+            let name = dm.get_name();
+            let dm_id = dm.id.clone();
+            if !state.dms.iter().any(|d| d.id == dm.id) {
+                state.dms.insert(0, dm);
+            }
+            open_channel(state, commands, dm_id, &name, None);
+        }
+        AppAction::ApiUpdateDMs(new_dms) => {
+            state.dms = new_dms;
+            let dms_count = state.dms.len();
+            if dms_count > 0 {
+                state.status_message =
+                    "DMs loaded. Select one to chat. (Esc to return to Home)".to_string();
+            } else {
+                state.status_message = "No DMs found. (Esc to return to Home)".to_string();
+            }
+            state.selection_index = 0;
+        }
+        AppAction::ApiUpdateGuildMembers(guild_id, members) => {
+            state.guild_members.insert(guild_id, members);
+            if state.show_members {
+                refresh_member_list(state);
+            }
+        }
+        AppAction::ToggleMemberList => {
+            if matches!(
+                state.state,
+                AppState::Chatting(_) | AppState::EmojiSelection(_) | AppState::SelectingMessage(_)
+            ) {
+                state.show_members = !state.show_members;
+                if state.show_members {
+                    request_member_list(state, commands);
+                    refresh_member_list(state);
+                }
+            }
+        }
+        AppAction::ApiUpdateMember(guild_id, member) => {
+            let color = state
+                .context
+                .as_ref()
+                .filter(|context| context.everyone_role_id == guild_id)
+                .and_then(|context| member.role_color(&context.all_guild_roles));
+
+            state.member_cache.entry(guild_id).or_default().insert(
+                member.user.id.clone(),
+                (member.display_name().to_string(), color),
+            );
+            state.invalidate_rows();
+        }
+        AppAction::ApiUpdateContext(new_context) => {
+            if let Some(context) = &new_context {
+                state
+                    .context_cache
+                    .insert(context.everyone_role_id.clone(), context.clone());
+                store_list_cache(state, commands);
+
+                if shown_guild_id(state) != Some(&context.everyone_role_id) {
+                    return None;
+                }
+            }
+            state.context = new_context;
+            state.invalidate_rows();
+        }
+        AppAction::GatewayMessage(message) => {
+            let in_channel = match &state.state {
+                AppState::Chatting(id)
+                | AppState::EmojiSelection(id)
+                | AppState::SelectingMessage(id) => *id == message.channel_id,
+                _ => false,
+            };
+
+            state
+                .typing
+                .retain(|t| t.user_id != message.author.id || t.channel_id != message.channel_id);
+
+            // The cached list too, which the unread badges of the guild list count from.
+            let app = &mut *state;
+            let cached = message
+                .guild_id
+                .as_ref()
+                .and_then(|guild_id| app.channel_cache.get_mut(guild_id));
+            for channels in [Some(&mut app.channels), cached].into_iter().flatten() {
+                if let Some(channel) = channels
+                    .iter_mut()
+                    .flat_map(|c| c.children.iter_mut().flatten())
+                    .find(|c| c.id == message.channel_id)
+                {
+                    channel.last_message_id = Some(message.id.clone());
+                    app.unread_dirty = true;
+                }
+            }
+
+            // The split pane shows its channel too, so it is read as messages arrive.
+            let in_split = match &mut state.split {
+                Some(split) if split.channel_id == message.channel_id => {
+                    split.push(message.clone());
+                    true
+                }
+                _ => false,
+            };
+
+            if in_channel || in_split {
+                let (channel_id, message_id) = (message.channel_id.clone(), message.id.clone());
+                mark_read(state, &channel_id, &message_id, commands);
+                ack_latest(state, commands, &channel_id, &message_id);
+            } else {
+                notify_mention(state, &message);
+            }
+
+            if in_channel && !state.messages.iter().any(|m| m.id == message.id) {
+                state.messages.insert(0, message);
+                // Keep the highlight on the same message as the list grows.
+                if let AppState::SelectingMessage(_) = state.state {
+                    state.selection_index += 1;
+                }
+                request_members(state, commands);
+            }
+        }
+        AppAction::GatewayPresences(presences) => {
+            let now = Instant::now();
+            state
+                .presences
+                .retain(|_, (_, received)| received.elapsed() < PRESENCE_TTL);
+            for presence in presences {
+                state
+                    .presences
+                    .insert(presence.user_id, (presence.status, now));
+            }
+        }
+        AppAction::GatewayVoiceStates(guild_id, voice_states) => {
+            let users = voice_states
+                .into_iter()
+                .filter_map(|voice_state| Some((voice_state.user_id, voice_state.channel_id?)))
+                .collect();
+            state.voice_states.insert(guild_id, users);
+        }
+        AppAction::GatewayVoiceStateUpdate(guild_id, voice_state) => {
+            let users = state.voice_states.entry(guild_id).or_default();
+            match voice_state.channel_id {
+                Some(channel_id) => {
+                    users.insert(voice_state.user_id, channel_id);
+                }
+                None => {
+                    users.remove(&voice_state.user_id);
+                }
+            }
+        }
+        AppAction::TypingStarted(user, channel_id) => {
+            let username = if user.username.is_empty() {
+                state
+                    .messages
+                    .iter()
+                    .map(|m| &m.author)
+                    .chain(state.dms.iter().flat_map(|d| d.recipients.iter()))
+                    .find(|u| u.id == user.id)
+                    .map(|u| u.username.clone())
+                    .unwrap_or_else(|| "Someone".to_string())
+            } else {
+                user.username
+            };
+
+            state
+                .typing
+                .retain(|t| t.user_id != user.id || t.channel_id != channel_id);
+            state.typing.push(TypingIndicator {
+                user_id: user.id,
+                username,
+                channel_id,
+                started: Instant::now(),
+            });
+        }
+        AppAction::GatewayStatus(connected) => {
+            state.gateway_connected = connected;
+            if connected {
+                state.offline = false;
+                flush_outbox(state, commands);
+            }
+        }
+        AppAction::ConnectionStatus(online) => {
+            state.offline = !online;
+            flush_outbox(state, commands);
+        }
+        AppAction::QueueMessage(queued) => {
+            state.outbox.push(*queued);
+        }
+        AppAction::QueuedMessageSent => {
+            if !state.outbox.is_empty() {
+                state.outbox.remove(0);
+            }
+        }
+        AppAction::SendFailed(queued, e) => {
+            let channel_id = queued.message.channel_id.clone();
+            match &e {
+                ApiError::SlowMode { retry_after } => {
+                    let wait = Duration::from_secs_f64(retry_after.max(0.0));
+                    state
+                        .slowmode
+                        .insert(channel_id.clone(), Instant::now() + wait);
+                }
+                ApiError::Forbidden(_) | ApiError::TimedOut => {
+                    state
+                        .send_blocked
+                        .insert(channel_id.clone(), format!("Can't send messages here: {e}"));
+                }
+                e if e.is_too_long() => state.length_rejected = true,
+                _ => {}
+            }
+            restore_failed_message(state, commands, *queued);
+            if matches!(e, ApiError::SlowMode { .. }) {
+                state.status_message =
+                    "Slowmode is on: the message was put back until you can send again."
+                        .to_string();
+            } else {
+                report_error(state, format!("Failed to send message: {e}"));
+            }
+        }
+        AppAction::OutboxFlushed => {
+            state.flushing_outbox = false;
+            // Messages queued while the previous batch was in flight.
+            flush_outbox(state, commands);
+        }
+        AppAction::TransitionToChannels(guild_id) => {
+            state.input = String::new();
+            state.cursor_position = 0;
+            state.selection_index = remembered_channel_row(state, &guild_id);
+            state.state = AppState::SelectingChannel(guild_id);
+            state.status_message =
+                "Select a server. Use arrows to navigate, Enter to select & Esc to quit"
+                    .to_string();
+        }
+        AppAction::TransitionToChat(channel_id) => {
+            // Check if we're coming from emoji selection before changing state
+            if let AppState::EmojiSelection(_) = &state.state {
+                // Remove the trailing ':' and filter text if canceling emoji selection
+                if let Some(start) = state.emoji_filter_start {
+                    let end = start + ':'.len_utf8() + state.emoji_filter.len();
+                    if state.input.is_char_boundary(start) && state.input.is_char_boundary(end) {
+                        state.input.drain(start..end);
+                        state.cursor_position = start;
+                    }
+                }
+                state.emoji_filter.clear();
+                state.emoji_filter_start = None;
+                state.selection_index = 0;
+            }
+            state.state = AppState::Chatting(channel_id.clone());
+            state.status_message = CHAT_HELP.to_string();
+            if state.show_members {
+                request_member_list(state, commands);
+                refresh_member_list(state);
+            }
+        }
+        AppAction::TransitionToGuilds => {
+            state.input = String::new();
+            state.cursor_position = 0;
+            state.state = AppState::SelectingGuild;
+            state.status_message =
+                "Select a server. Use arrows to navigate, Enter to select, Tab for DMs & Esc to quit"
+                    .to_string();
+            state.selection_index = 0;
+        }
+        AppAction::TransitionToDM => {
+            state.input = String::new();
+            state.cursor_position = 0;
+            state.state = AppState::SelectingDM;
+            state.status_message =
+                "Select a DM. Use arrows to navigate, Enter to select, Tab for servers & Esc to quit"
+                    .to_string();
+            state.selection_index = 0;
+        }
+        AppAction::TransitionToHome => {
+            state.input = String::new();
+            state.cursor_position = 0;
+            state.state = AppState::Home;
+            state.status_message = "Browse either DMs or Servers. Use arrows to navigate, Enter to select & Esc to quit".to_string();
+            state.selection_index = 0;
+        }
+        AppAction::TransitionToLoading(redirect_state) => {
+            state.state = AppState::Loading(redirect_state);
+            state.status_message = "Loading...".to_string();
+        }
+        AppAction::EndLoading => {
+            if let AppState::Loading(redirect) = &state.state.clone() {
+                match redirect {
+                    Window::Home => commands.dispatch(AppAction::TransitionToHome),
+                    Window::Channel(guild_id) => {
+                        commands.dispatch(AppAction::TransitionToChannels(guild_id.clone()))
+                    }
+                    Window::Chat(channel_id) => {
+                        commands.dispatch(AppAction::TransitionToChat(channel_id.clone()))
+                    }
+                };
+            }
+        }
+        AppAction::Resize => {
+            // Nothing to update here: the frame drawn after every action is laid out for the
+            // new size, and sets terminal_width and terminal_height from it.
+        }
+        AppAction::Tick => {
+            state.tick_count = state.tick_count.wrapping_add(1);
+            if state.error_toast.is_some() && !toast_visible(state.error_toast) {
+                state.error_toast = None;
+            }
+            state
+                .typing
+                .retain(|t| t.started.elapsed() < TYPING_TIMEOUT);
+            return Some(KeywordAction::Continue);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+impl App {
+    /// An app signed in as a test user. Its requests go to a port nothing listens on, so
+    /// a test can't reach Discord by accident.
+    pub fn for_tests() -> Self {
+        let api_client = ApiClient::new(
+            reqwest::Client::new(),
+            "test-token".to_string(),
+            "http://127.0.0.1:9".to_string(),
+        );
+        Self::new(api_client, User::for_tests("100", "tester"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    use super::{Command as Effect, *};
+    use crate::api::{
+        TokenType,
+        message::{Reaction, ReactionEmoji},
+    };
+
+    /// Applies `action` and the actions it dispatches, in order, as the main loop would.
+    /// Spawned tasks are returned unrun.
+    fn apply(state: &mut App, action: AppAction) -> Vec<Effect> {
+        let (tx_action, _rx_action) = mpsc::channel(16);
+        let mut commands = Commands::new(tx_action);
+        let mut queue = VecDeque::from([action]);
+        let mut effects = Vec::new();
+        while let Some(action) = queue.pop_front() {
+            apply_action(state, action, &mut commands);
+            for effect in commands.take() {
+                match effect {
+                    Effect::Dispatch(action) => queue.push_back(*action),
+                    effect => effects.push(effect),
+                }
+            }
+        }
+        effects
+    }
+
+    fn guild(id: &str, name: &str) -> Guild {
+        Guild {
+            id: id.to_string(),
+            name: name.to_string(),
+            icon: None,
+        }
+    }
+
+    fn text_channel(id: &str, name: &str, guild_id: &str) -> Channel {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": name,
+            "type": 0,
+            "guild_id": guild_id,
+            "parent_id": null,
+            "last_message_id": null,
+            "topic": null,
+            "message_count": null,
+            "children": null,
+        }))
+        .unwrap()
+    }
+
+    /// Everyone can view every channel of the guild.
+    fn open_context() -> PermissionContext {
+        serde_json::from_value(json!({
+            "user_id": "100",
+            "user_role_ids": ["g"],
+            "all_guild_roles": [{ "id": "g", "name": "@everyone", "permissions": "1024" }],
+            "everyone_role_id": "g",
+        }))
+        .unwrap()
+    }
+
+    /// A guild whose channels were loaded before, so entering it needs no request.
+    fn cache_guild(state: &mut App, guild_id: &str, channels: Vec<Channel>) {
+        state
+            .guilds
+            .push(guild(guild_id, &format!("Guild {guild_id}")));
+        state.channel_cache.insert(guild_id.to_string(), channels);
+        state
+            .context_cache
+            .insert(guild_id.to_string(), open_context());
+    }
+
+    fn chatting(guild_id: &str) -> App {
+        let mut state = App::for_tests();
+        state.vim_mode = false;
+        let channels = vec![text_channel("c1", "general", guild_id)];
+        cache_guild(&mut state, guild_id, channels);
+        state.state = AppState::Chatting("c1".to_string());
+        state
+    }
+
+    #[test]
+    fn goes_from_guild_to_channel_to_chat_and_back() {
+        let mut state = App::for_tests();
+        let channels = vec![
+            text_channel("c1", "general", "g1"),
+            text_channel("c2", "random", "g1"),
+        ];
+        cache_guild(&mut state, "g1", channels);
+
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(matches!(state.state, AppState::SelectingGuild));
+
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(matches!(&state.state, AppState::SelectingChannel(id) if id == "g1"));
+        assert_eq!(state.channels.len(), 2);
+
+        apply(&mut state, AppAction::SelectNext);
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(matches!(&state.state, AppState::Loading(Window::Chat(id)) if id == "c2"));
+        assert_eq!(state.chat_guild_id.as_deref(), Some("g1"));
+
+        apply(&mut state, AppAction::EndLoading);
+        assert!(matches!(&state.state, AppState::Chatting(id) if id == "c2"));
+
+        apply(&mut state, AppAction::InputEscape);
+        assert!(matches!(&state.state, AppState::SelectingChannel(id) if id == "g1"));
+        // Back on the channel that was open.
+        assert_eq!(state.selection_index, 1);
+
+        apply(&mut state, AppAction::InputEscape);
+        assert!(matches!(state.state, AppState::SelectingGuild));
+        apply(&mut state, AppAction::InputEscape);
+        assert!(matches!(state.state, AppState::Home));
+    }
+
+    #[test]
+    fn escape_finds_the_guild_of_a_chat_opened_without_it() {
+        // As on resume, where the chat opens before its guild is known.
+        let mut state = chatting("g1");
+        assert!(state.chat_guild_id.is_none());
+
+        apply(&mut state, AppAction::InputEscape);
+        assert!(matches!(&state.state, AppState::SelectingChannel(id) if id == "g1"));
+    }
+
+    #[test]
+    fn escape_from_an_unknown_channel_goes_to_the_guild_list() {
+        let mut state = chatting("g1");
+        state.state = AppState::Chatting("elsewhere".to_string());
+
+        apply(&mut state, AppAction::InputEscape);
+        assert!(matches!(state.state, AppState::SelectingGuild));
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut state = App::for_tests();
+        state.guilds = vec![guild("g1", "One"), guild("g2", "Two"), guild("g3", "Three")];
+        state.state = AppState::SelectingGuild;
+
+        apply(&mut state, AppAction::SelectPrevious);
+        assert_eq!(state.selection_index, 2);
+        apply(&mut state, AppAction::SelectNext);
+        assert_eq!(state.selection_index, 0);
+
+        state.state = AppState::Home;
+        apply(&mut state, AppAction::SelectPrevious);
+        assert_eq!(state.selection_index, 2);
+    }
+
+    #[test]
+    fn moving_through_empty_lists_keeps_the_selection() {
+        let mut state = App::for_tests();
+        for list in [
+            AppState::SelectingGuild,
+            AppState::SelectingDM,
+            AppState::SelectingChannel("g1".to_string()),
+            AppState::SelectingMessage("c1".to_string()),
+        ] {
+            state.state = list;
+            apply(&mut state, AppAction::SelectNext);
+            apply(&mut state, AppAction::SelectPrevious);
+            assert_eq!(state.selection_index, 0);
+            // Nothing to open either.
+            apply(&mut state, AppAction::InputSubmit);
+        }
+
+        // A filter matching no guild.
+        state.guilds = vec![guild("g1", "One")];
+        state.state = AppState::SelectingGuild;
+        state.input = "zzz".to_string();
+        apply(&mut state, AppAction::SelectNext);
+        assert_eq!(state.selection_index, 0);
+    }
+
+    fn channels(guild_id: &str, count: usize) -> Vec<Channel> {
+        (0..count)
+            .map(|i| text_channel(&format!("{guild_id}-{i}"), &format!("chan-{i}"), guild_id))
+            .collect()
+    }
+
+    #[test]
+    fn a_shrinking_channel_list_leaves_no_selection_past_its_end() {
+        let mut state = App::for_tests();
+        cache_guild(&mut state, "g1", channels("g1", 10));
+        state.channels = channels("g1", 10);
+        state.context = Some(open_context());
+        state.state = AppState::SelectingChannel("g1".to_string());
+        state.selection_index = 8;
+
+        // The refreshed list lost the selected channel.
+        apply(&mut state, AppAction::ApiUpdateChannel(channels("g1", 2)));
+        assert!(state.selection_index < state.channel_rows("g1").len());
+
+        // A list swapped under a stale selection, stepped through and opened.
+        state.channels = channels("g1", 10);
+        state.selection_index = 8;
+        state.channels = channels("g1", 2);
+        apply(&mut state, AppAction::SelectNext);
+        assert!(state.selection_index < 2);
+        state.selection_index = 8;
+        apply(&mut state, AppAction::SelectPrevious);
+        assert!(state.selection_index < 2);
+        state.selection_index = 8;
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(matches!(state.state, AppState::SelectingChannel(_)));
+    }
+
+    #[test]
+    fn edits_the_input_at_the_cursor() {
+        let mut state = chatting("g1");
+
+        for c in "helo world".chars() {
+            apply(&mut state, AppAction::InputChar(c));
+        }
+        assert_eq!(state.input, "helo world");
+        assert_eq!(state.cursor_position, state.input.len());
+
+        apply(&mut state, AppAction::InputMoveCursor(CursorMove::WordLeft));
+        apply(&mut state, AppAction::InputMoveCursor(CursorMove::Left));
+        apply(&mut state, AppAction::InputMoveCursor(CursorMove::Left));
+        apply(&mut state, AppAction::InputChar('l'));
+        assert_eq!(state.input, "hello world");
+
+        apply(&mut state, AppAction::InputMoveCursor(CursorMove::End));
+        apply(&mut state, AppAction::InputDeleteWord);
+        assert_eq!(state.input, "hello ");
+        apply(&mut state, AppAction::InputBackspace);
+        assert_eq!(state.input, "hello");
+
+        apply(&mut state, AppAction::InputMoveCursor(CursorMove::Home));
+        assert_eq!(state.cursor_position, 0);
+        apply(&mut state, AppAction::InputBackspace);
+        assert_eq!(state.input, "hello");
+
+        apply(&mut state, AppAction::InputMoveCursor(CursorMove::End));
+        apply(&mut state, AppAction::InputClearToStart);
+        assert!(state.input.is_empty());
+        assert_eq!(state.cursor_position, 0);
+    }
+
+    #[test]
+    fn backspace_removes_a_whole_grapheme() {
+        let mut state = chatting("g1");
+        state.input = "ok 👍🏽".to_string();
+        state.cursor_position = state.input.len();
+
+        apply(&mut state, AppAction::InputBackspace);
+        assert_eq!(state.input, "ok ");
+        assert_eq!(state.cursor_position, 3);
+    }
+
+    #[test]
+    fn typing_in_a_list_filters_it_from_the_top() {
+        let mut state = App::for_tests();
+        state.vim_mode = false;
+        state.guilds = vec![guild("g1", "Rust"), guild("g2", "Go")];
+        state.state = AppState::SelectingGuild;
+        state.selection_index = 1;
+
+        apply(&mut state, AppAction::InputChar('g'));
+        assert_eq!(state.input, "g");
+        assert_eq!(state.selection_index, 0);
+
+        // Esc clears the filter before leaving the list.
+        apply(&mut state, AppAction::InputEscape);
+        assert!(state.input.is_empty());
+        assert!(matches!(state.state, AppState::SelectingGuild));
+    }
+
+    #[test]
+    fn scrolls_back_by_lines_and_end_follows_again() {
+        let mut state = chatting("g1");
+        state.terminal_height = 20;
+
+        apply(&mut state, AppAction::ScrollLineUp);
+        apply(&mut state, AppAction::ScrollLineUp);
+        assert_eq!(state.scroll_offset, 2 * SCROLL_LINES);
+
+        apply(&mut state, AppAction::ScrollLineDown);
+        assert_eq!(state.scroll_offset, SCROLL_LINES);
+
+        apply(&mut state, AppAction::InputMoveCursor(CursorMove::End));
+        assert_eq!(state.scroll_offset, 0);
+    }
+
+    #[test]
+    fn scrolling_down_stops_at_the_newest_message() {
+        let mut state = chatting("g1");
+        state.terminal_height = 20;
+        state.scroll_offset = 1;
+
+        apply(&mut state, AppAction::ScrollPageDown);
+        assert_eq!(state.scroll_offset, 0);
+    }
+
+    #[test]
+    fn runs_slash_commands_instead_of_sending_them() {
+        let mut state = chatting("g1");
+        state.input = "/qiut".to_string();
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert!(effects.is_empty());
+        assert!(state.status_message.starts_with("Unknown command /qiut"));
+
+        state.input = "/quit".to_string();
+        let (tx_action, _rx_action) = mpsc::channel(16);
+        let mut commands = Commands::new(tx_action);
+        let outcome = apply_action(&mut state, AppAction::InputSubmit, &mut commands);
+        assert!(matches!(outcome, Some(KeywordAction::Break)));
+    }
+
+    #[test]
+    fn refuses_to_send_past_the_length_limit() {
+        let mut state = chatting("g1");
+        state.input = "a".repeat(MAX_MESSAGE_LENGTH + 1);
+
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert!(effects.is_empty());
+        assert_eq!(state.input.len(), MAX_MESSAGE_LENGTH + 1);
+        assert!(
+            state
+                .status_message
+                .starts_with("Message too long: 2001/2000")
+        );
+    }
+
+    #[test]
+    fn counts_the_length_after_expanding_shortcodes() {
+        let mut state = chatting("g1");
+        state.emoji_map = vec![("smile".to_string(), "😄".to_string())];
+        // 2100 bytes typed, 300 characters sent.
+        state.input = ":smile:".repeat(300);
+
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(!state.status_message.starts_with("Message too long"));
+        assert!(state.input.is_empty());
+    }
+
+    #[test]
+    fn recalls_only_messages_that_were_sent() {
+        let mut state = chatting("g1");
+        state.input = "lost".to_string();
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(state.input_history.is_empty());
+
+        apply(
+            &mut state,
+            AppAction::MessageSent("c1".to_string(), "sent".to_string()),
+        );
+        state.input = "draft".to_string();
+
+        apply(&mut state, AppAction::HistoryPrevious);
+        assert_eq!(state.input, "sent");
+        assert_eq!(state.cursor_position, "sent".len());
+        apply(&mut state, AppAction::HistoryNext);
+        assert_eq!(state.input, "draft");
+    }
+
+    #[test]
+    fn asks_before_pinging_everyone() {
+        let mut state = chatting("g1");
+        state.chat_guild_id = Some("g1".to_string());
+        state.input = "hey @everyone".to_string();
+
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert!(effects.is_empty());
+        assert_eq!(state.pending_pings, Some(vec!["@everyone".to_string()]));
+
+        // Esc goes back to editing, with the message kept.
+        apply(&mut state, AppAction::InputEscape);
+        assert!(state.pending_pings.is_none());
+        assert_eq!(state.input, "hey @everyone");
+
+        apply(&mut state, AppAction::InputSubmit);
+        let effects = apply(&mut state, AppAction::InputChar('n'));
+        assert!(!effects.is_empty());
+        assert!(state.pending_pings.is_none());
+        assert!(state.ping_override.is_none());
+        assert!(state.input.is_empty());
+    }
+
+    #[test]
+    fn sends_allowed_pings_without_asking() {
+        let mut state = chatting("g1");
+        state.chat_guild_id = Some("g1".to_string());
+        state.allowed_mentions = AllowedMentions::all();
+        state.input = "hey @everyone".to_string();
+
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert!(!effects.is_empty());
+        assert!(state.pending_pings.is_none());
+    }
+
+    #[test]
+    fn asks_before_opening_an_nsfw_channel_once_per_session() {
+        let mut state = App::for_tests();
+        let mut nsfw = text_channel("c1", "late-night", "g1");
+        nsfw.nsfw = true;
+        cache_guild(&mut state, "g1", vec![nsfw]);
+        state.content_warnings = true;
+        apply(&mut state, AppAction::InputSubmit);
+        apply(&mut state, AppAction::InputSubmit);
+
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert!(effects.is_empty());
+        assert!(state.nsfw_prompt.is_some());
+        assert!(matches!(state.state, AppState::SelectingChannel(_)));
+
+        apply(&mut state, AppAction::InputChar('n'));
+        assert!(state.nsfw_prompt.is_none());
+        assert!(matches!(state.state, AppState::SelectingChannel(_)));
+
+        apply(&mut state, AppAction::InputSubmit);
+        apply(&mut state, AppAction::InputChar('y'));
+        assert!(matches!(&state.state, AppState::Loading(Window::Chat(id)) if id == "c1"));
+
+        // Confirmed for the session.
+        apply(&mut state, AppAction::EndLoading);
+        apply(&mut state, AppAction::InputEscape);
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(state.nsfw_prompt.is_none());
+        assert!(matches!(&state.state, AppState::Loading(Window::Chat(id)) if id == "c1"));
+    }
+
+    #[test]
+    fn ignores_the_author_of_the_selected_message_once_confirmed() {
+        let mut state = chatting("g1");
+        state.messages = vec![Message::for_tests("m1", "hi").by("9", "troll")];
+        state.state = AppState::SelectingMessage("c1".to_string());
+        state.selection_index = 0;
+
+        apply(&mut state, AppAction::InputChar('x'));
+        assert!(state.pending_ignore.is_some());
+        apply(&mut state, AppAction::InputChar('n'));
+        assert!(state.pending_ignore.is_none());
+        assert!(!state.is_ignored("9"));
+
+        apply(&mut state, AppAction::InputChar('x'));
+        apply(&mut state, AppAction::InputChar('y'));
+        assert!(state.is_ignored("9"));
+        assert_eq!(state.ignored[0].name, "troll");
+    }
+
+    #[test]
+    fn goto_opens_the_linked_channel_and_jumps_to_the_message() {
+        let mut state = App::for_tests();
+        state.vim_mode = false;
+        let channels = vec![
+            text_channel("11", "general", "1"),
+            text_channel("12", "random", "1"),
+        ];
+        cache_guild(&mut state, "1", channels);
+        state.state = AppState::Chatting("11".to_string());
+        state.chat_guild_id = Some("1".to_string());
+
+        state.input = "/goto https://discord.com/channels/1/12/99".to_string();
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(matches!(&state.state, AppState::Loading(Window::Chat(id)) if id == "12"));
+        assert_eq!(
+            state.pending_jump,
+            Some(("12".to_string(), "99".to_string()))
+        );
+
+        state.state = AppState::Chatting("12".to_string());
+        state.input = "/goto 12".to_string();
+        apply(&mut state, AppAction::InputSubmit);
+        assert_eq!(state.status_message, "Already in this channel.");
+
+        // Any other bare id is a message of the open channel.
+        state.input = "/goto 404".to_string();
+        apply(&mut state, AppAction::InputSubmit);
+        assert_eq!(state.status_message, "Loading the message...");
+    }
+
+    #[test]
+    fn switches_between_compact_and_cozy_messages() {
+        let mut state = chatting("g1");
+        assert_eq!(state.density, config::Density::Compact);
+
+        apply(&mut state, AppAction::ToggleDensity);
+        assert_eq!(state.density, config::Density::Cozy);
+        assert_eq!(state.status_message, "Messages shown cozy");
+
+        apply(&mut state, AppAction::ToggleDensity);
+        assert_eq!(state.density, config::Density::Compact);
+    }
+
+    fn invite(channel: Option<&str>) -> Invite {
+        serde_json::from_value(json!({
+            "code": "abc123",
+            "guild": { "id": "g2", "name": "Rust" },
+            "channel": channel.map(|id| json!({ "id": id, "name": "welcome" })),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn previews_invites_before_joining() {
+        let mut state = chatting("g1");
+        state.input = "/join-invite discord.gg/abc123".to_string();
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert!(matches!(effects[..], [Effect::Spawn(_)]));
+        assert_eq!(state.status_message, "Loading the invite...");
+
+        apply(&mut state, AppAction::ApiInvite(Ok(invite(None))));
+        assert!(state.invite_prompt.is_some());
+        assert_eq!(
+            state.status_message,
+            "Join Rust? y to join, n or Esc to cancel."
+        );
+
+        apply(&mut state, AppAction::InputChar('n'));
+        assert!(state.invite_prompt.is_none());
+        assert_eq!(state.status_message, CHAT_HELP);
+    }
+
+    #[test]
+    fn joins_on_y_then_opens_the_invite_channel() {
+        let mut state = chatting("g1");
+        apply(&mut state, AppAction::ApiInvite(Ok(invite(Some("c9")))));
+
+        let effects = apply(&mut state, AppAction::InputChar('y'));
+        assert!(matches!(effects[..], [Effect::Track(_)]));
+        assert!(state.invite_prompt.is_none());
+        assert_eq!(state.status_message, "Joining Rust...");
+
+        apply(&mut state, AppAction::ApiInviteAccepted(invite(Some("c9"))));
+        assert!(
+            state
+                .invite_prompt
+                .as_ref()
+                .is_some_and(|prompt| prompt.joined)
+        );
+
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(state.invite_prompt.is_none());
+        assert_eq!(state.chat_guild_id.as_deref(), Some("g2"));
+        assert!(matches!(&state.state, AppState::Loading(Window::Chat(id)) if id == "c9"));
+    }
+
+    #[test]
+    fn bots_cannot_accept_invites() {
+        let mut state = chatting("g1");
+        state.api_client.token_type = TokenType::Bot;
+        state.input = "/join-invite abc123".to_string();
+
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert!(effects.is_empty());
+        assert!(
+            state
+                .status_message
+                .starts_with("Bots can't accept invites")
+        );
+    }
+
+    #[test]
+    fn reports_unknown_invites() {
+        let mut state = chatting("g1");
+        let error = format!("{}.", ApiError::UnknownInvite);
+        apply(&mut state, AppAction::ApiInvite(Err(error)));
+        assert!(state.invite_prompt.is_none());
+        assert_eq!(
+            state.error_log.front().map(|entry| entry.message.as_str()),
+            Some("This invite is invalid or has expired.")
+        );
+    }
+
+    #[test]
+    fn lays_out_loaded_messages_again_when_their_reactions_change() {
+        let loaded = vec![Message::for_tests("1", "hi")];
+        let mut refreshed = loaded.clone();
+        assert!(!changes_loaded_rows(&loaded, &refreshed));
+
+        refreshed[0].reactions = vec![Reaction {
+            emoji: ReactionEmoji {
+                id: None,
+                name: Some("👍".to_string()),
+            },
+            count: 1,
+            me: false,
+        }];
+        assert!(changes_loaded_rows(&loaded, &refreshed));
+        // Messages that weren't loaded yet have no rows to lay out again.
+        assert!(!changes_loaded_rows(&[], &refreshed));
+    }
+
+    #[test]
+    fn completes_unicode_then_custom_emojis() {
+        let complete = |index| {
+            let mut state = chatting("g1");
+            state.state = AppState::EmojiSelection("c1".to_string());
+            state.input = "hi :par".to_string();
+            state.cursor_position = state.input.len();
+            state.emoji_filter = "par".to_string();
+            state.emoji_filter_start = Some(3);
+            state.emoji_map = vec![
+                ("party".to_string(), "🎉".to_string()),
+                ("smile".to_string(), "😄".to_string()),
+            ];
+            state.custom_emojis = vec![Emoji {
+                id: "9".to_string(),
+                name: "parrot".to_string(),
+                animated: Some(true),
+                available: None,
+                user: None,
+            }];
+            state.selection_index = index;
+            apply(&mut state, AppAction::InputSubmit);
+            assert!(matches!(state.state, AppState::Chatting(_)));
+            state.input
+        };
+
+        assert_eq!(complete(0), "hi 🎉 ");
+        assert_eq!(complete(1), "hi <a:parrot:9> ");
+    }
+
+    #[test]
+    fn sets_and_removes_aliases() {
+        let mut state = chatting("g1");
+        state.input = "/alias 7 Bobby".to_string();
+        let generation = state.rows_generation;
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert_eq!(state.alias("7"), Some("Bobby"));
+        assert_eq!(state.status_message, "7 is now shown as Bobby.");
+        // Rows showing the old name are laid out again.
+        assert_ne!(state.rows_generation, generation);
+        // Saving the aliases is left to a spawned task.
+        assert!(matches!(effects[..], [Effect::Spawn(_)]));
+
+        state.input = "/alias remove 7".to_string();
+        apply(&mut state, AppAction::InputSubmit);
+        assert_eq!(state.alias("7"), None);
+        assert_eq!(state.status_message, "Removed the alias of 7.");
+
+        state.input = "/alias remove 7".to_string();
+        let effects = apply(&mut state, AppAction::InputSubmit);
+        assert_eq!(state.status_message, "7 has no alias.");
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn removes_aliases_from_their_list() {
+        let mut state = chatting("g1");
+        for (id, alias) in [("1", "carol"), ("2", "alice"), ("3", "bob")] {
+            state.aliases.insert(id.to_string(), alias.to_string());
+        }
+        state.input = "/aliases".to_string();
+        apply(&mut state, AppAction::InputSubmit);
+        assert!(state.alias_list.is_some());
+        assert_eq!(state.status_message, "3 aliases.");
+
+        apply(&mut state, AppAction::SelectNext);
+        apply(&mut state, AppAction::InputChar('d'));
+        assert_eq!(state.alias("3"), None);
+        assert_eq!(state.status_message, "Removed the alias bob.");
+
+        // Removing the last alias of the list moves the selection up.
+        apply(&mut state, AppAction::SelectNext);
+        apply(&mut state, AppAction::InputChar('d'));
+        assert_eq!(state.alias("1"), None);
+        assert_eq!(state.alias_list.as_ref().unwrap().selection_index, 0);
+
+        apply(&mut state, AppAction::InputEscape);
+        assert!(state.alias_list.is_none());
+    }
+
+    #[test]
+    fn starts_an_alias_from_a_profile() {
+        let mut state = chatting("g1");
+        state.profile = Some(ProfileViewer {
+            user: state.current_user.clone(),
+            member: None,
+        });
+
+        apply(&mut state, AppAction::InputChar('a'));
+        assert!(state.profile.is_none());
+        assert_eq!(state.input, "/alias 100 ");
+        assert_eq!(state.cursor_position, state.input.len());
+    }
+}
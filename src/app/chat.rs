@@ -0,0 +1,1102 @@
+//! Chatting in a channel: writing, sending and the read state of what comes in, and
+//! opening and leaving channels.
+
+use std::{collections::HashMap, path::PathBuf, time::Instant};
+
+use chrono::Utc;
+use tokio::time::Duration;
+
+use super::{
+    App, AppAction, AppState, CHAT_HELP, Commands, GatedChannel, QueuedMessage, Window,
+    is_keypress,
+    lists::{mark_guild_opened, remember_guild_channel, store_read_markers},
+    overlays::close_search,
+};
+use crate::{
+    api::{
+        ApiClient, ApiError, Channel, Message, TokenType, User,
+        message::{AllowedMentions, FileUpload, MAX_MESSAGE_LENGTH, MentionKind, message_length},
+    },
+    config::{self, Draft},
+    ui::{
+        history,
+        mentions::{MassMention, mass_mentions},
+        shortcodes::expand_shortcodes,
+        split::SplitPane,
+    },
+};
+
+/// Channels whose messages are kept after leaving them.
+const MESSAGE_CACHE_LIMIT: usize = 20;
+const TYPING_SEND_INTERVAL: Duration = Duration::from_secs(8);
+/// Shortest time between two read acks for the same channel.
+const ACK_INTERVAL: Duration = Duration::from_secs(5);
+/// Channels remembered for the mention poll.
+const RECENT_CHANNELS: usize = 5;
+/// Drafts kept at most; the one saved longest ago is dropped first.
+const DRAFT_LIMIT: usize = 100;
+
+/// Sends a typing notification for the current channel, at most once per `TYPING_SEND_INTERVAL`.
+pub(super) fn notify_typing(state: &mut App, commands: &mut Commands) {
+    let channel_id = match &state.state {
+        AppState::Chatting(id) | AppState::EmojiSelection(id) => id.clone(),
+        _ => return,
+    };
+
+    if state
+        .last_typing_sent
+        .is_some_and(|sent| sent.elapsed() < TYPING_SEND_INTERVAL)
+    {
+        return;
+    }
+
+    state.last_typing_sent = Some(Instant::now());
+    let api_client_clone = state.api_client.clone();
+
+    commands.spawn(async move {
+        api_client_clone.trigger_typing(&channel_id).await.ok();
+    });
+}
+
+/// Helper function to insert a character at the cursor position.
+/// Handles both emoji selection state and normal input state.
+pub(super) fn insert_char_at_cursor(state: &mut App, c: char) {
+    let current_state = state.state.clone();
+    match current_state {
+        AppState::EmojiSelection(channel_id) => {
+            let pos = state.cursor_position;
+            state.input.insert(pos, c);
+            state.cursor_position += c.len_utf8();
+            if c == ' ' {
+                state.state = AppState::Chatting(channel_id.clone());
+                state.emoji_filter.clear();
+                state.emoji_filter_start = None;
+            } else {
+                // Recompute emoji_filter based on the current input and emoji_filter_start.
+                if let Some(start) = state.emoji_filter_start {
+                    let filter_start = start + ':'.len_utf8();
+                    if state.cursor_position <= start || filter_start > state.input.len() {
+                        state.emoji_filter.clear();
+                    } else {
+                        let end = std::cmp::min(state.cursor_position, state.input.len());
+                        if filter_start <= end {
+                            state.emoji_filter = state.input[filter_start..end].to_string();
+                        } else {
+                            state.emoji_filter.clear();
+                        }
+                    }
+                } else {
+                    state.emoji_filter.clear();
+                }
+
+                if state.emoji_filter.is_empty() {
+                    state.state = AppState::Chatting(channel_id.clone());
+                    state.emoji_filter_start = None;
+                    state.status_message = CHAT_HELP.to_string();
+                }
+            }
+            state.selection_index = 0;
+        }
+        _ => {
+            let pos = state.cursor_position;
+            state.input.insert(pos, c);
+            state.cursor_position += c.len_utf8();
+        }
+    }
+}
+
+/// Expands completed `:shortcode:`s left of the cursor, keeping the cursor after them.
+pub(super) fn expand_shortcodes_before_cursor(state: &mut App) {
+    let cursor = state.cursor_position;
+    let expanded = expand_shortcodes(&state.input[..cursor], &state.emoji_map);
+
+    if expanded != state.input[..cursor] {
+        let rest = state.input.split_off(cursor);
+        state.cursor_position = expanded.len();
+        state.input = expanded + &rest;
+    }
+}
+
+/// Keeps the input of the open channel as its draft, or forgets the draft once the input
+/// is empty. Edits of sent messages and search queries are not drafts.
+pub fn stash_draft(state: &mut App) {
+    let channel_id = match &state.state {
+        AppState::Chatting(id) | AppState::EmojiSelection(id) | AppState::SelectingMessage(id) => {
+            id.clone()
+        }
+        _ => return,
+    };
+    if state.editing_message.is_some()
+        || state.search.as_ref().is_some_and(|s| s.is_prompt())
+        || state.attach_prompt.is_some()
+    {
+        return;
+    }
+
+    if state.input.trim().is_empty() {
+        state.drafts.remove(&channel_id);
+        return;
+    }
+
+    let draft = Draft {
+        text: state.input.clone(),
+        saved_at: Utc::now(),
+    };
+    state.drafts.insert(channel_id, draft);
+
+    if state.drafts.len() > DRAFT_LIMIT
+        && let Some(oldest) = state
+            .drafts
+            .iter()
+            .min_by_key(|(_, draft)| draft.saved_at)
+            .map(|(id, _)| id.clone())
+    {
+        state.drafts.remove(&oldest);
+    }
+}
+
+/// Writes the drafts to disk in the background.
+pub(super) fn store_drafts(state: &App, commands: &mut Commands) {
+    let drafts = state.drafts.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_drafts(drafts).await {
+            tx_clone
+                .send(AppAction::ApiError(format!("Failed to save drafts: {e}")))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Opens the channel of the last session again, staying on the list with an explanation
+/// when it can't be.
+pub fn resume_channel(
+    api_client: &ApiClient,
+    commands: &mut Commands,
+    last_channel: config::LastChannel,
+) {
+    let api_client_clone = api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let action = match api_client_clone.get_channel(&last_channel.channel_id).await {
+            Ok(channel) => AppAction::ApiOpenChannel(channel),
+            Err(ApiError::Unauthorized) => AppAction::Unauthorized,
+            Err(e @ (ApiError::Forbidden(_) | ApiError::NotFound(_))) => {
+                AppAction::ApiError(format!(
+                    "Could not reopen {}, it is gone or out of reach ({e}). Pick another channel.",
+                    last_channel.label()
+                ))
+            }
+            Err(e) => {
+                AppAction::ApiError(format!("Could not reopen {}: {e}", last_channel.label()))
+            }
+        };
+        tx_clone.send(action).await.ok();
+    });
+}
+
+/// Handles an action while the last session's channel is offered: `y` opens it, `n` or
+/// Esc dismisses it, and any other key dismisses it before doing what it does.
+pub(super) fn handle_resume_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    if !is_keypress(&action) {
+        return Some(action);
+    }
+    let last_channel = state.resume_prompt.take()?;
+
+    match action {
+        AppAction::InputChar('y' | 'Y') => {
+            state.status_message = format!("Reopening {}...", last_channel.label());
+            resume_channel(&state.api_client, commands, last_channel);
+            None
+        }
+        AppAction::InputChar('n' | 'N') | AppAction::InputEscape => None,
+        action => Some(action),
+    }
+}
+
+/// Names what `content` would ping beyond what the config allows, such as `@everyone` or
+/// `@moderators`. Nothing in DMs, where only users can be pinged.
+pub(super) fn unconfirmed_pings(state: &App, content: &str) -> Vec<String> {
+    if state.chat_guild_id.is_none() {
+        return Vec::new();
+    }
+    let roles = state
+        .context
+        .as_ref()
+        .map_or(&[][..], |context| context.all_guild_roles.as_slice());
+    let allows = |kind| state.allowed_mentions.allows(kind);
+
+    mass_mentions(content)
+        .into_iter()
+        .filter_map(|mention| match mention {
+            MassMention::Everyone | MassMention::Here if allows(MentionKind::Everyone) => None,
+            MassMention::Everyone => Some("@everyone".to_string()),
+            MassMention::Here => Some("@here".to_string()),
+            MassMention::Role(_) if allows(MentionKind::Roles) => None,
+            MassMention::Role(id) => {
+                let role = roles.iter().find(|role| role.id == id);
+                Some(format!(
+                    "@{}",
+                    role.map_or("unknown-role", |role| role.name.as_str())
+                ))
+            }
+        })
+        .collect()
+}
+
+/// Handles an action while a message waits for its pings to be confirmed: `y` sends it
+/// pinging everything it mentions, `n` sends it pinging only what the config allows, and
+/// Esc or any other key goes back to editing it.
+pub(super) fn handle_ping_keys(state: &mut App, action: AppAction) -> Option<AppAction> {
+    if !is_keypress(&action) {
+        return Some(action);
+    }
+    state.pending_pings.take()?;
+
+    match action {
+        AppAction::InputChar('y' | 'Y') => {
+            state.ping_override = Some(AllowedMentions::all());
+            Some(AppAction::InputSubmit)
+        }
+        AppAction::InputChar('n' | 'N') => {
+            state.ping_override = Some(state.allowed_mentions.clone());
+            Some(AppAction::InputSubmit)
+        }
+        AppAction::InputEscape => {
+            state.status_message = "Message not sent.".to_string();
+            None
+        }
+        action => Some(action),
+    }
+}
+
+/// Handles an action while an NSFW channel waits for its warning to be confirmed: `y`
+/// opens it, and keeps it confirmed for the session. `n` or Esc goes back, and any other
+/// key goes back before doing what it does.
+pub(super) fn handle_nsfw_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    if !is_keypress(&action) {
+        return Some(action);
+    }
+    let gated = state.nsfw_prompt.take()?;
+
+    match action {
+        AppAction::InputChar('y' | 'Y') => {
+            state.nsfw_confirmed.insert(gated.channel_id.clone());
+            open_channel(
+                state,
+                commands,
+                gated.channel_id,
+                &gated.name,
+                gated.guild_id,
+            );
+            None
+        }
+        AppAction::InputChar('n' | 'N') | AppAction::InputEscape => {
+            state.status_message = format!("Did not open #{}.", gated.name);
+            None
+        }
+        action => Some(action),
+    }
+}
+
+/// Puts an older or a newer message sent to the open channel in the input, and what was
+/// being typed back once past the newest.
+pub(super) fn recall_history(state: &mut App, older: bool) {
+    let AppState::Chatting(channel_id) = state.state.clone() else {
+        return;
+    };
+    let recall = state.history_recall.take();
+    let history = state
+        .input_history
+        .get(&channel_id)
+        .map_or(&[][..], Vec::as_slice);
+    let Some((input, recall)) = history::step(history, recall, &channel_id, &state.input, older)
+    else {
+        return;
+    };
+
+    state.input = input;
+    state.cursor_position = state.input.len();
+    state.history_recall = recall;
+}
+
+/// Channel whose chat is open or loading.
+pub(super) fn chat_channel_id(state: &App) -> Option<&String> {
+    match &state.state {
+        AppState::Chatting(id)
+        | AppState::EmojiSelection(id)
+        | AppState::SelectingMessage(id)
+        | AppState::Loading(Window::Chat(id)) => Some(id),
+        _ => None,
+    }
+}
+
+/// Keeps the loaded messages of the chat being left, forgetting the channel left longest
+/// ago past `MESSAGE_CACHE_LIMIT` channels.
+fn cache_messages(state: &mut App) {
+    let messages = std::mem::take(&mut state.messages);
+    let Some(channel_id) = messages.first().map(|m| m.channel_id.clone()) else {
+        return;
+    };
+    state
+        .message_cache
+        .insert(channel_id, (Instant::now(), messages));
+
+    if state.message_cache.len() > MESSAGE_CACHE_LIMIT
+        && let Some(oldest) = state
+            .message_cache
+            .iter()
+            .min_by_key(|(_, (left_at, _))| *left_at)
+            .map(|(id, _)| id.clone())
+    {
+        state.message_cache.remove(&oldest);
+    }
+}
+
+/// Guild whose channels are on screen: its channel list, or the chat of one of them.
+pub(super) fn shown_guild_id(state: &App) -> Option<&String> {
+    match &state.state {
+        AppState::SelectingChannel(guild_id) | AppState::Loading(Window::Channel(guild_id)) => {
+            Some(guild_id)
+        }
+        _ => state.chat_guild_id.as_ref(),
+    }
+}
+
+/// Records `message_id` as the newest message seen in `channel_id`.
+pub(super) fn mark_read(
+    state: &mut App,
+    channel_id: &str,
+    message_id: &str,
+    commands: &mut Commands,
+) {
+    let snowflake = |id: &str| id.parse::<u64>().unwrap_or(0);
+    let is_newer = state
+        .read_markers
+        .get(channel_id)
+        .is_none_or(|read| snowflake(read) < snowflake(message_id));
+
+    if is_newer {
+        state
+            .read_markers
+            .insert(channel_id.to_string(), message_id.to_string());
+        store_read_markers(state, commands);
+    }
+}
+
+/// Tells Discord the open channel was read up to `message_id`, when `sync_read_state` is
+/// on. Acks are sent at most once per `ACK_INTERVAL` per channel; a newer message seen in
+/// between is acked by the next fetch after it. Failures are only logged.
+pub(super) fn ack_latest(
+    state: &mut App,
+    commands: &mut Commands,
+    channel_id: &str,
+    message_id: &str,
+) {
+    if !state.sync_read_state || state.api_client.token_type != TokenType::User {
+        return;
+    }
+    if state
+        .acks
+        .get(channel_id)
+        .is_some_and(|(acked, sent_at)| acked == message_id || sent_at.elapsed() < ACK_INTERVAL)
+    {
+        return;
+    }
+
+    state.acks.insert(
+        channel_id.to_string(),
+        (message_id.to_string(), Instant::now()),
+    );
+    let api_client_clone = state.api_client.clone();
+    let (channel_id, message_id) = (channel_id.to_string(), message_id.to_string());
+
+    commands.track(async move {
+        if let Err(e) = api_client_clone.ack_message(&channel_id, &message_id).await {
+            tracing::warn!("Failed to mark {channel_id} as read: {e}");
+        }
+        Ok(())
+    });
+}
+
+/// Fetches the guild members behind any message authors not cached yet, one request
+/// per author, so their nicknames and role colors can be shown.
+pub(super) fn request_members(state: &mut App, commands: &mut Commands) {
+    let Some(guild_id) = state.chat_guild_id.clone() else {
+        return;
+    };
+
+    let cached = state.member_cache.get(&guild_id);
+    let mut user_ids: Vec<String> = state
+        .messages
+        .iter()
+        .map(|m| m.author.id.clone())
+        .filter(|id| cached.is_none_or(|members| !members.contains_key(id)))
+        .filter(|id| {
+            !state
+                .member_requests
+                .contains(&(guild_id.clone(), id.clone()))
+        })
+        .collect();
+    user_ids.sort();
+    user_ids.dedup();
+
+    if user_ids.is_empty() {
+        return;
+    }
+
+    for user_id in &user_ids {
+        state
+            .member_requests
+            .insert((guild_id.clone(), user_id.clone()));
+    }
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        for user_id in user_ids {
+            // Authors who left the guild (or webhooks) have no member object; their
+            // username is shown instead.
+            if let Ok(member) = api_client_clone.get_guild_member(&guild_id, &user_id).await {
+                tx_clone
+                    .send(AppAction::ApiUpdateMember(guild_id.clone(), member))
+                    .await
+                    .ok();
+            }
+        }
+    });
+}
+
+/// Drops the previous channel's history and returns the chat view to live-follow mode.
+fn reset_chat_view(state: &mut App) {
+    cache_messages(state);
+    state.scroll_offset = 0;
+    state.chat_at_top = false;
+    state.chat_newest_id = None;
+    state.history_exhausted = false;
+    state.replying_to = None;
+    state.editing_message = None;
+    state.pending_delete = None;
+    state.pending_quote = None;
+    state.pending_publish = None;
+    state.pending_pings = None;
+    state.ping_override = None;
+    state.reaction_input = None;
+    state.pins = None;
+    state.forum = None;
+    state.webhook_picker = None;
+    state.ignore_list = None;
+    state.pending_ignore = None;
+    if state.link_hints.take().is_some() {
+        state.invalidate_rows();
+    }
+    state.pending_jump = None;
+    state.length_rejected = false;
+    state.profile = None;
+    state.attach_prompt = None;
+    state.history_recall = None;
+    close_search(state);
+}
+
+/// A channel of a guild as last fetched, threads included.
+pub(super) fn known_channel<'a>(
+    state: &'a App,
+    guild_id: &str,
+    channel_id: &str,
+) -> Option<&'a Channel> {
+    let channels = state
+        .channel_cache
+        .get(guild_id)
+        .map_or(&[][..], Vec::as_slice);
+    let threads = state
+        .thread_cache
+        .get(guild_id)
+        .map_or(&[][..], Vec::as_slice);
+    channels
+        .iter()
+        .flat_map(|c| std::iter::once(c).chain(c.children.iter().flatten()))
+        .chain(threads)
+        .find(|c| c.id == channel_id)
+}
+
+/// Asks before opening an NSFW channel, staying where we are meanwhile.
+pub(super) fn gate_channel(state: &mut App, gated: GatedChannel) {
+    state.status_message = "y to view it, n or Esc to go back.".to_string();
+    state.nsfw_prompt = Some(gated);
+}
+
+/// Switches the chat view to `channel_id` and loads its latest messages. Messages cached
+/// from an earlier visit show right away while the fresh ones load. NSFW channels first
+/// wait for their warning to be confirmed.
+pub(super) fn open_channel(
+    state: &mut App,
+    commands: &mut Commands,
+    channel_id: String,
+    channel_name: &str,
+    guild_id: Option<String>,
+) {
+    let gated = state.content_warnings
+        && guild_id.as_ref().is_some_and(|guild_id| {
+            known_channel(state, guild_id, &channel_id).is_some_and(|channel| {
+                let parent = channel
+                    .parent_id
+                    .as_deref()
+                    .and_then(|id| known_channel(state, guild_id, id));
+                channel.is_age_gated(parent, &state.nsfw_confirmed)
+            })
+        });
+    if gated {
+        let gated = GatedChannel {
+            channel_id,
+            name: channel_name.to_string(),
+            guild_id,
+        };
+        gate_channel(state, gated);
+        return;
+    }
+
+    stash_draft(state);
+    store_drafts(state, commands);
+    reset_chat_view(state);
+    state.send_blocked.remove(&channel_id);
+
+    if let Some((_, messages)) = state.message_cache.remove(&channel_id) {
+        state.messages = messages;
+        commands.dispatch(AppAction::TransitionToChat(channel_id.clone()));
+    } else {
+        commands.dispatch(AppAction::TransitionToLoading(Window::Chat(
+            channel_id.clone(),
+        )));
+    }
+
+    state.input = state
+        .drafts
+        .get(&channel_id)
+        .map(|draft| draft.text.clone())
+        .unwrap_or_default();
+    state.cursor_position = state.input.len();
+    state.recent_channels.retain(|(id, _)| *id != channel_id);
+    state
+        .recent_channels
+        .insert(0, (channel_id.clone(), guild_id.clone()));
+    state.recent_channels.truncate(RECENT_CHANNELS);
+
+    if let Some(guild_id) = &guild_id {
+        mark_guild_opened(state, commands, guild_id);
+        remember_guild_channel(state, commands, guild_id, &channel_id);
+    }
+    let last_channel = config::LastChannel {
+        channel_id: channel_id.clone(),
+        guild_id: guild_id.clone(),
+        name: channel_name.to_string(),
+    };
+    state.last_channel = Some(last_channel.clone());
+    let tx_clone = commands.sender();
+    commands.spawn(async move {
+        if let Err(e) = config::store_last_channel(last_channel).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save the last channel: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+
+    if state.chat_guild_id != guild_id {
+        state.invalidate_rows();
+    }
+    state.chat_guild_id = guild_id;
+    state.status_message = format!("Loading messages for {channel_name}...");
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        match api_client_clone
+            .get_channel_messages(&channel_id, None, None, None, Some(100))
+            .await
+        {
+            Ok(messages) => {
+                tx_clone
+                    .send(AppAction::ApiUpdateMessages(messages))
+                    .await
+                    .ok();
+            }
+            Err(e) => {
+                tx_clone
+                    .send(AppAction::ChannelFailed(channel_id, e))
+                    .await
+                    .ok();
+            }
+        }
+
+        tx_clone.send(AppAction::EndLoading).await.ok();
+    });
+}
+
+/// Sends a message as submitted: through its webhook in webhook mode, where it cannot
+/// be a reply, or else as the user.
+pub(super) async fn deliver(
+    api_client: &ApiClient,
+    queued: &QueuedMessage,
+) -> Result<Message, ApiError> {
+    let message = &queued.message;
+    match &queued.webhook {
+        Some(active) => {
+            api_client
+                .execute_webhook(
+                    &active.webhook,
+                    message.content.clone(),
+                    Some(&active.username),
+                    active.avatar_url.as_deref(),
+                    &queued.allowed_mentions,
+                )
+                .await
+        }
+        None => {
+            api_client
+                .create_message(
+                    &message.channel_id,
+                    message.content.clone(),
+                    false,
+                    queued.message_reference.clone(),
+                    &queued.allowed_mentions,
+                )
+                .await
+        }
+    }
+}
+
+/// Sends the queued messages in order while online. Stops at the first network failure,
+/// leaving the rest queued for the next attempt.
+pub(super) fn flush_outbox(state: &mut App, commands: &mut Commands) {
+    if state.offline || state.flushing_outbox || state.outbox.is_empty() {
+        return;
+    }
+    state.flushing_outbox = true;
+
+    let outbox = state.outbox.clone();
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.track(async move {
+        let mut failures = Vec::new();
+        let mut unsent = outbox.len();
+        for queued in outbox {
+            match deliver(&api_client_clone, &queued).await {
+                Ok(_) => {
+                    unsent -= 1;
+                    let message = queued.message;
+                    tx_clone.send(AppAction::QueuedMessageSent).await.ok();
+                    tx_clone
+                        .send(AppAction::MessageSent(
+                            message.channel_id,
+                            message.content.unwrap_or_default(),
+                        ))
+                        .await
+                        .ok();
+                }
+                Err(ApiError::Network(_)) => {
+                    tx_clone.send(AppAction::ConnectionStatus(false)).await.ok();
+                    break;
+                }
+                Err(e) => {
+                    // Retrying would fail the same way; give it back so the queue moves on.
+                    unsent -= 1;
+                    let failure = format!("Failed to send message: {e}");
+                    tx_clone.send(AppAction::QueuedMessageSent).await.ok();
+                    tx_clone
+                        .send(AppAction::SendFailed(Box::new(queued), e))
+                        .await
+                        .ok();
+                    failures.push(failure);
+                }
+            }
+        }
+
+        tx_clone.send(AppAction::OutboxFlushed).await.ok();
+        if unsent > 0 {
+            failures.push(format!(
+                "{unsent} queued messages were not sent while offline"
+            ));
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("\n"))
+        }
+    });
+}
+
+/// Puts the content of a refused message back so it is never lost: into the input when
+/// its channel is open, otherwise into the channel's draft, ahead of any text there.
+pub(super) fn restore_failed_message(
+    state: &mut App,
+    commands: &mut Commands,
+    queued: QueuedMessage,
+) {
+    let QueuedMessage {
+        message,
+        message_reference,
+        ..
+    } = queued;
+    let content = message.content.unwrap_or_default();
+    if content.trim().is_empty() {
+        return;
+    }
+    let prepend = |text: &str| {
+        if text.trim().is_empty() {
+            content.clone()
+        } else {
+            format!("{content}\n{text}")
+        }
+    };
+
+    let open = matches!(&state.state,
+        AppState::Chatting(id) | AppState::EmojiSelection(id) | AppState::SelectingMessage(id)
+            if *id == message.channel_id)
+        && state.editing_message.is_none()
+        && !state.search.as_ref().is_some_and(|s| s.is_prompt())
+        && state.attach_prompt.is_none();
+    if open {
+        state.input = prepend(&state.input);
+        state.cursor_position = content.len();
+        if state.replying_to.is_none() {
+            state.replying_to = message_reference
+                .and_then(|id| state.messages.iter().find(|m| m.id == id).cloned());
+        }
+        return;
+    }
+
+    let text = state
+        .drafts
+        .get(&message.channel_id)
+        .map_or_else(|| content.clone(), |draft| prepend(&draft.text));
+    let draft = Draft {
+        text,
+        saved_at: Utc::now(),
+    };
+    state.drafts.insert(message.channel_id, draft);
+    store_drafts(state, commands);
+}
+
+/// Returns from a channel that can no longer be read to the list it was opened from.
+pub(super) fn leave_channel(state: &mut App, channel_id: &str, message: String) {
+    let in_channel = match &state.state {
+        AppState::Chatting(id)
+        | AppState::EmojiSelection(id)
+        | AppState::SelectingMessage(id)
+        | AppState::Loading(Window::Chat(id)) => id == channel_id,
+        _ => false,
+    };
+
+    if in_channel {
+        stash_draft(state);
+        state.state = match back_from_chat(state, channel_id) {
+            AppAction::TransitionToChannels(guild_id) => AppState::SelectingChannel(guild_id),
+            AppAction::TransitionToDM => AppState::SelectingDM,
+            _ => AppState::SelectingGuild,
+        };
+        state.input.clear();
+        state.cursor_position = 0;
+        state.selection_index = 0;
+        reset_chat_view(state);
+    }
+    state.message_cache.remove(channel_id);
+
+    state.status_message = message;
+}
+
+/// Pins the open chat into the split pane and goes back to the list it was opened from,
+/// so another channel can be opened next to it. With a pane already open, closes it.
+pub(super) fn toggle_split(state: &mut App, commands: &mut Commands) {
+    if state.split.take().is_some() {
+        state.status_message = "Closed the split pane.".to_string();
+        return;
+    }
+    let AppState::Chatting(channel_id) = &state.state else {
+        return;
+    };
+
+    let channel_id = channel_id.clone();
+    let guild_id = chat_guild(state, &channel_id);
+    let name = match &guild_id {
+        Some(guild_id) => {
+            known_channel(state, guild_id, &channel_id).map(|c| format!("#{}", c.name))
+        }
+        None => state
+            .dms
+            .iter()
+            .find(|d| d.id == channel_id)
+            .map(|d| d.get_name()),
+    };
+    let action = back_from_chat(state, &channel_id);
+    state.split = Some(SplitPane {
+        channel_id,
+        name: name.unwrap_or_else(|| "Pinned channel".to_string()),
+        messages: state.messages.clone(),
+    });
+
+    stash_draft(state);
+    store_drafts(state, commands);
+    commands.dispatch(action);
+}
+
+/// Whether a refreshed page changes how an already loaded message looks without it being
+/// edited, as new reactions or link embeds do.
+pub(super) fn changes_loaded_rows(loaded: &[Message], refreshed: &[Message]) -> bool {
+    let loaded: HashMap<&str, &Message> = loaded.iter().map(|m| (m.id.as_str(), m)).collect();
+    refreshed.iter().any(|message| {
+        loaded
+            .get(message.id.as_str())
+            .is_some_and(|loaded| !loaded.same_extras(message))
+    })
+}
+
+/// Starts typing the path of a file to attach. What was typed so far becomes its caption.
+pub(super) fn open_attach_prompt(state: &mut App) {
+    if !matches!(state.state, AppState::Chatting(_)) || state.editing_message.is_some() {
+        return;
+    }
+    let caption = std::mem::take(&mut state.input);
+    state.attach_prompt = Some(caption);
+    state.cursor_position = 0;
+    state.status_message =
+        "Attach a file: type its path, Enter to upload, Esc to cancel.".to_string();
+}
+
+/// Leaves the path prompt, bringing the caption back into the input.
+fn close_attach_prompt(state: &mut App) {
+    if let Some(caption) = state.attach_prompt.take() {
+        state.cursor_position = caption.len();
+        state.input = caption;
+    }
+    state.status_message = CHAT_HELP.to_string();
+}
+
+/// A path typed in the input bar, with `~/` standing for the home directory.
+pub(super) fn typed_path(typed: &str) -> PathBuf {
+    match typed.strip_prefix("~/").zip(dirs::home_dir()) {
+        Some((rest, home)) => home.join(rest),
+        None => PathBuf::from(typed),
+    }
+}
+
+/// Uploads the file at the typed path with the caption, showing a local copy of the
+/// message until the upload ends.
+fn start_upload(state: &mut App, commands: &mut Commands) {
+    let AppState::Chatting(channel_id) = &state.state else {
+        return;
+    };
+    let channel_id = channel_id.clone();
+    let typed = state.input.trim().to_string();
+    if typed.is_empty() {
+        close_attach_prompt(state);
+        return;
+    }
+    let path = typed_path(&typed);
+    let filename = path
+        .file_name()
+        .map_or_else(|| typed.clone(), |name| name.to_string_lossy().into_owned());
+
+    let caption = state.attach_prompt.take().unwrap_or_default();
+    let caption = expand_shortcodes(caption.trim(), &state.emoji_map);
+    state.input.clear();
+    state.cursor_position = 0;
+    if state.drafts.remove(&channel_id).is_some() {
+        store_drafts(state, commands);
+    }
+    let message_reference = state.replying_to.take().map(|m| m.id);
+    let allowed_mentions = state.allowed_mentions.clone();
+    state.status_message = format!("Uploading {filename}...");
+
+    let local_id = format!("upload-{}", Utc::now().timestamp_micros());
+    let echo = if caption.is_empty() {
+        format!("[uploading {filename}…]")
+    } else {
+        format!("[uploading {filename}…] {caption}")
+    };
+    let local_copy = Message {
+        id: local_id.clone(),
+        channel_id: channel_id.clone(),
+        guild_id: state.chat_guild_id.clone(),
+        author: state.current_user.clone(),
+        content: Some(echo),
+        timestamp: Some(Utc::now()),
+        edited_timestamp: None,
+        referenced_message: None,
+        reactions: Vec::new(),
+        attachments: Vec::new(),
+        embeds: Vec::new(),
+        mentions: Vec::new(),
+        sticker_items: Vec::new(),
+        flags: 0,
+        message_type: 0,
+    };
+    state.uploads.push(local_copy);
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.track(async move {
+        let result = match FileUpload::read(&path).await {
+            Ok(file) => api_client_clone
+                .create_message_with_attachments(
+                    &channel_id,
+                    (!caption.is_empty()).then_some(caption),
+                    message_reference,
+                    &allowed_mentions,
+                    vec![file],
+                )
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Failed to upload {filename}: {e}")),
+            Err(e) => Err(e),
+        };
+        tx_clone
+            .send(AppAction::UploadFinished(local_id, result.clone()))
+            .await
+            .ok();
+        result
+    });
+}
+
+/// Handles an action while the path of a file to attach is typed in the input bar.
+pub(super) fn handle_attach_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    match action {
+        AppAction::InputEscape => close_attach_prompt(state),
+        AppAction::InputChar(c) => insert_char_at_cursor(state, c),
+        AppAction::InputSubmit => start_upload(state, commands),
+        AppAction::InputBackspace
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::Paste(_) => return Some(action),
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// The guild of `channel_id` among the loaded channel lists, if any has it.
+pub(super) fn channel_guild(state: &App, channel_id: &str) -> Option<String> {
+    state
+        .channel_cache
+        .keys()
+        .find(|guild_id| known_channel(state, guild_id, channel_id).is_some())
+        .cloned()
+}
+
+/// The guild of a chat: the one it was opened in, or else the one whose loaded channels
+/// have it, for a guild channel opened before its guild was known, as on resume.
+fn chat_guild(state: &App, channel_id: &str) -> Option<String> {
+    state
+        .chat_guild_id
+        .clone()
+        .or_else(|| channel_guild(state, channel_id))
+}
+
+/// Where going back from a chat leads: the channel list of its guild, the DM list for a
+/// DM, and the guild list for a channel of a guild that isn't known.
+pub(super) fn back_from_chat(state: &App, channel_id: &str) -> AppAction {
+    match chat_guild(state, channel_id) {
+        Some(guild_id) => AppAction::TransitionToChannels(guild_id),
+        None if state.dms.iter().any(|dm| dm.id == channel_id) => AppAction::TransitionToDM,
+        None => AppAction::TransitionToGuilds,
+    }
+}
+
+/// Lines of a message quoted with `q` at most, and the characters they may take, leaving
+/// room for the answer.
+const QUOTE_MAX_LINES: usize = 10;
+const QUOTE_MAX_LENGTH: usize = MAX_MESSAGE_LENGTH / 2;
+
+/// Quotes `content` with Discord's `> ` on every line, followed by a blank line for the
+/// answer. A longer message is cut with an ellipsis and the number of lines left out.
+pub(super) fn quote_text(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut quote = String::new();
+    let mut quoted = 0;
+
+    for line in lines.iter().take(QUOTE_MAX_LINES) {
+        quoted += 1;
+        let room = QUOTE_MAX_LENGTH.saturating_sub(message_length(&quote) + "> \n".len());
+        if message_length(line) > room {
+            let kept: String = line.chars().take(room.saturating_sub(1)).collect();
+            quote.push_str(&format!("> {kept}…\n"));
+            break;
+        }
+        quote.push_str(&format!("> {line}\n"));
+    }
+
+    match lines.len() - quoted {
+        0 => {}
+        1 => quote.push_str("> … (1 more line)\n"),
+        omitted => quote.push_str(&format!("> … ({omitted} more lines)\n")),
+    }
+    quote.push('\n');
+    quote
+}
+
+/// Puts a quote in the input, in place of any draft, and goes back to the chat with the
+/// cursor after it.
+pub(super) fn start_quote(state: &mut App, quote: String) {
+    let AppState::SelectingMessage(channel_id) = state.state.clone() else {
+        return;
+    };
+    state.input = quote;
+    state.cursor_position = state.input.len();
+    state.editing_message = None;
+    state.state = AppState::Chatting(channel_id);
+    state.selection_index = 0;
+    state.status_message = "Quoting the message. Type your answer, Enter to send.".to_string();
+}
+
+/// Switches to the DM with `user`, asking Discord for the channel when there is none yet.
+pub(super) fn open_dm(state: &mut App, commands: &mut Commands, user: User) {
+    if state.current_user.id == user.id {
+        state.status_message = "That is your own message.".to_string();
+        return;
+    }
+
+    let existing = state
+        .dms
+        .iter()
+        .find(|dm| dm.recipients.len() == 1 && dm.recipients[0].id == user.id)
+        .map(|dm| (dm.id.clone(), dm.get_name()));
+    if let Some((dm_id, name)) = existing {
+        open_channel(state, commands, dm_id, &name, None);
+        return;
+    }
+
+    state.status_message = format!("Opening a DM with {}...", user.username);
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let action = match api_client_clone.create_dm(&user.id).await {
+            Ok(dm) => AppAction::ApiDmOpened(dm),
+            Err(e @ ApiError::CannotDm) => AppAction::ApiError(e.to_string()),
+            Err(e) => AppAction::ApiError(format!("Failed to open the DM: {e}")),
+        };
+        tx_clone.send(action).await.ok();
+    });
+}
@@ -0,0 +1,676 @@
+//! The guild, DM and channel lists, and moving through any list.
+
+use std::{collections::HashSet, time::Instant};
+
+use chrono::Utc;
+use ratatui::layout::{Margin, Position};
+use tokio::time::Duration;
+
+use super::{App, AppAction, AppState, CHAT_HELP, Commands, chat::known_channel};
+use crate::{
+    api::{Guild, Message, TokenType},
+    config::{self, PinKind},
+    ui::{
+        filter::filter_guilds,
+        ignore::{Collapse, collapse},
+    },
+};
+
+/// Longest time between the two clicks of a double click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Mutes or unmutes the guild or channel selected in the guild or channel list, and saves
+/// the mute list in the background.
+pub(super) fn toggle_mute(state: &mut App, commands: &mut Commands) {
+    let selected = match &state.state {
+        AppState::SelectingGuild => filter_guilds(
+            &state.guilds,
+            &state.input,
+            state.guild_sort,
+            &state.guilds_opened,
+            &state.muted,
+            state.pinned.ids(PinKind::Guild),
+        )
+        .get(state.selection_index)
+        .map(|g| (g.id.clone(), g.name.clone())),
+        AppState::SelectingChannel(guild_id) => state
+            .channel_rows(guild_id)
+            .get(state.selection_index)
+            .map(|c| (c.id.clone(), c.name.clone())),
+        _ => None,
+    };
+    let Some((id, name)) = selected else {
+        return;
+    };
+
+    state.unread_dirty = true;
+    if state.muted.remove(&id) {
+        state.status_message = format!("Unmuted {name}.");
+    } else {
+        state.muted.insert(id);
+        state.status_message = if state.show_muted {
+            format!("Muted {name}.")
+        } else {
+            format!("Muted {name}. Press F5 to show muted channels.")
+        };
+    }
+
+    let muted = state.muted.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_muted(muted).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save muted list: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Adds the selected guild to the favorites at the top of the guild list, or removes it,
+/// keeping it selected as it moves.
+pub(super) fn toggle_favorite(state: &mut App, commands: &mut Commands) {
+    if !matches!(state.state, AppState::SelectingGuild) {
+        return;
+    }
+    let Some((id, name)) = filter_guilds(
+        &state.guilds,
+        &state.input,
+        state.guild_sort,
+        &state.guilds_opened,
+        &state.muted,
+        state.pinned.ids(PinKind::Guild),
+    )
+    .get(state.selection_index)
+    .map(|g| (g.id.clone(), g.name.clone())) else {
+        return;
+    };
+
+    state.status_message = if state.pinned.toggle(PinKind::Guild, &id) {
+        format!("Added {name} to the favorites.")
+    } else {
+        format!("Removed {name} from the favorites.")
+    };
+    state.selection_index = filter_guilds(
+        &state.guilds,
+        &state.input,
+        state.guild_sort,
+        &state.guilds_opened,
+        &state.muted,
+        state.pinned.ids(PinKind::Guild),
+    )
+    .iter()
+    .position(|g| g.id == id)
+    .unwrap_or(0);
+
+    store_pinned(state, commands);
+}
+
+fn store_pinned(state: &App, commands: &mut Commands) {
+    let pinned = state.pinned.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_pinned(pinned).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save favorites: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Shows or hides the muted channels in the channel list.
+pub(super) fn toggle_show_muted(state: &mut App) {
+    if !matches!(state.state, AppState::SelectingChannel(_)) {
+        return;
+    }
+
+    state.show_muted = !state.show_muted;
+    state.selection_index = 0;
+    state.status_message = if state.show_muted {
+        "Showing muted channels.".to_string()
+    } else {
+        "Hiding muted channels.".to_string()
+    };
+}
+
+/// Records that a guild was opened, for the recent sort, and saves it in the background.
+pub(super) fn mark_guild_opened(state: &mut App, commands: &mut Commands, guild_id: &str) {
+    state.guilds_opened.insert(guild_id.to_string(), Utc::now());
+    let opened = state.guilds_opened.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_guilds_opened(opened).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save guild order: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Records `channel_id` as the channel last opened in `guild_id`, and saves it.
+pub(super) fn remember_guild_channel(
+    state: &mut App,
+    commands: &mut Commands,
+    guild_id: &str,
+    channel_id: &str,
+) {
+    state
+        .guild_channels
+        .insert(guild_id.to_string(), channel_id.to_string());
+    let channels = state.guild_channels.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_guild_channels(channels).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save the last channel of the server: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Row of the channel list of `guild_id` holding the channel last opened there, or the
+/// first row when it is gone. A thread is found through its parent channel.
+pub(super) fn remembered_channel_row(state: &App, guild_id: &str) -> usize {
+    let Some(channel_id) = state.guild_channels.get(guild_id) else {
+        return 0;
+    };
+    let rows = state.channel_rows(guild_id);
+    let row = |id: &String| rows.iter().position(|c| &c.id == id);
+
+    row(channel_id)
+        .or_else(|| {
+            known_channel(state, guild_id, channel_id)
+                .and_then(|channel| channel.parent_id.as_ref())
+                .and_then(row)
+        })
+        .unwrap_or(0)
+}
+
+/// Writes the read markers to disk in the background, and has the unread badges counted
+/// again.
+pub(super) fn store_read_markers(state: &mut App, commands: &mut Commands) {
+    state.unread_dirty = true;
+    let read_markers = state.read_markers.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_read_state(read_markers).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save read state: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Saves the guild and channel lists for the next startup, and has the unread badges
+/// counted again.
+pub(super) fn store_list_cache(state: &mut App, commands: &mut Commands) {
+    state.unread_dirty = true;
+    let cache = config::ListCache {
+        guilds: state.guilds.clone(),
+        channels: state.channel_cache.clone(),
+        contexts: state.context_cache.clone(),
+    };
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_list_cache(cache).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save the server list: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Replaces the guild list with `new_guilds`, keeping the highlight on the same guild.
+/// Once `complete`, what was cached for the guilds no longer in it is dropped.
+pub(super) fn update_guilds(
+    state: &mut App,
+    mut new_guilds: Vec<Guild>,
+    complete: bool,
+    commands: &mut Commands,
+) {
+    // Keep the highlight on the same guild as the list changes under it.
+    let selected = match state.state {
+        AppState::SelectingGuild => filter_guilds(
+            &state.guilds,
+            &state.input,
+            state.guild_sort,
+            &state.guilds_opened,
+            &state.muted,
+            state.pinned.ids(PinKind::Guild),
+        )
+        .get(state.selection_index)
+        .map(|g| g.id.clone()),
+        _ => None,
+    };
+
+    // Until the last page arrives, the known guilds past the loaded ones stay listed.
+    if !complete {
+        let snowflake = |id: &str| id.parse::<u64>().unwrap_or(0);
+        let loaded_up_to = new_guilds.last().map_or(0, |g| snowflake(&g.id));
+        new_guilds.extend(
+            state
+                .guilds
+                .iter()
+                .filter(|g| snowflake(&g.id) > loaded_up_to)
+                .cloned(),
+        );
+    }
+    state.guilds = new_guilds;
+
+    if let Some(selected) = selected {
+        state.selection_index = filter_guilds(
+            &state.guilds,
+            &state.input,
+            state.guild_sort,
+            &state.guilds_opened,
+            &state.muted,
+            state.pinned.ids(PinKind::Guild),
+        )
+        .iter()
+        .position(|g| g.id == selected)
+        .unwrap_or(0);
+    }
+
+    if let AppState::Loading(_) = state.state {
+        state.status_message =
+            "Select a server. Use arrows to navigate, Enter to select, Tab for DMs & Esc to quit."
+                .to_string();
+    }
+
+    if !complete {
+        return;
+    }
+
+    // Forget what was cached for guilds we have left.
+    let guild_ids: HashSet<String> = state.guilds.iter().map(|g| g.id.clone()).collect();
+    state.channel_cache.retain(|id, _| guild_ids.contains(id));
+    state.context_cache.retain(|id, _| guild_ids.contains(id));
+    state.emoji_cache.retain(|id, _| guild_ids.contains(id));
+    state.thread_cache.retain(|id, _| guild_ids.contains(id));
+    state.guild_members.retain(|id, _| guild_ids.contains(id));
+    if state
+        .pinned
+        .retain_known(PinKind::Guild, |id| guild_ids.contains(id))
+    {
+        store_pinned(state, commands);
+    }
+    store_list_cache(state, commands);
+}
+
+/// Marks every text channel of the listed guild as read up to its last message, on
+/// Discord too when `sync_read_state` is on.
+pub(super) fn mark_guild_read(state: &mut App, commands: &mut Commands) {
+    let AppState::SelectingChannel(guild_id) = state.state.clone() else {
+        return;
+    };
+    let unread: Vec<(String, String)> = state
+        .channels
+        .iter()
+        .flat_map(|c| c.children.iter().flatten())
+        .filter(|c| c.is_text() && c.has_unread(state.read_markers.get(&c.id)))
+        .filter_map(|c| Some((c.id.clone(), c.last_message_id.clone()?)))
+        .collect();
+    if unread.is_empty() {
+        state.status_message = "Nothing unread here.".to_string();
+        return;
+    }
+
+    state.status_message = match unread.len() {
+        1 => "Marked 1 channel as read.".to_string(),
+        count => format!("Marked {count} channels as read."),
+    };
+    state.read_markers.extend(unread.iter().cloned());
+    state.guild_unread.insert(guild_id, 0);
+    store_read_markers(state, commands);
+
+    if !state.sync_read_state || state.api_client.token_type != TokenType::User {
+        return;
+    }
+    let api_client_clone = state.api_client.clone();
+    commands.track(async move {
+        for (channel_id, message_id) in unread {
+            if let Err(e) = api_client_clone.ack_message(&channel_id, &message_id).await {
+                tracing::warn!("Failed to mark {channel_id} as read: {e}");
+            }
+        }
+        Ok(())
+    });
+}
+
+/// Fetches the full member list of the open guild for the sidebar, once per guild.
+pub(super) fn request_member_list(state: &mut App, commands: &mut Commands) {
+    let Some(guild_id) = state.chat_guild_id.clone() else {
+        return;
+    };
+    if state.guild_members.contains_key(&guild_id)
+        || !state.member_list_requests.insert(guild_id.clone())
+    {
+        return;
+    }
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        match api_client_clone.list_guild_members(&guild_id).await {
+            Ok(members) => {
+                tx_clone
+                    .send(AppAction::ApiUpdateGuildMembers(guild_id, members))
+                    .await
+                    .ok();
+            }
+            Err(e) => {
+                tx_clone
+                    .send(AppAction::ApiError(format!("Failed to load members: {e}")))
+                    .await
+                    .ok();
+            }
+        }
+    });
+}
+
+/// Rebuilds the sidebar with the members who can see the open channel, sorted by name.
+/// In DMs these are the recipients and ourselves.
+pub(super) fn refresh_member_list(state: &mut App) {
+    let channel_id = match &state.state {
+        AppState::Chatting(id) | AppState::EmojiSelection(id) | AppState::SelectingMessage(id) => {
+            id.clone()
+        }
+        _ => return,
+    };
+
+    let mut entries: Vec<(String, String, Option<u32>)> = match &state.chat_guild_id {
+        Some(guild_id) => {
+            // Threads follow the permissions of their parent channel.
+            let channel_id = state
+                .thread_cache
+                .get(guild_id)
+                .into_iter()
+                .flatten()
+                .find(|t| t.id == channel_id)
+                .and_then(|t| t.parent_id.clone())
+                .unwrap_or(channel_id);
+            let channel = state
+                .channels
+                .iter()
+                .flat_map(|c| c.children.iter().flatten())
+                .find(|c| c.id == channel_id);
+            let context = state
+                .context
+                .as_ref()
+                .filter(|context| &context.everyone_role_id == guild_id);
+
+            state
+                .guild_members
+                .get(guild_id)
+                .into_iter()
+                .flatten()
+                .filter(|member| match (channel, context) {
+                    (Some(channel), Some(context)) => {
+                        channel.is_readable(&context.for_member(member))
+                    }
+                    _ => true,
+                })
+                .map(|member| {
+                    let color =
+                        context.and_then(|context| member.role_color(&context.all_guild_roles));
+                    (
+                        member.user.id.clone(),
+                        member.display_name().to_string(),
+                        color,
+                    )
+                })
+                .collect()
+        }
+        None => state
+            .dms
+            .iter()
+            .filter(|dm| dm.id == channel_id)
+            .flat_map(|dm| dm.recipients.iter())
+            .chain([&state.current_user])
+            .map(|user| {
+                let name = user.global_name.as_ref().unwrap_or(&user.username);
+                (user.id.clone(), name.clone(), None)
+            })
+            .collect(),
+    };
+
+    entries.sort_by_key(|(_, name, _)| name.to_lowercase());
+    state.member_list = entries;
+}
+
+/// Closes the emoji popup once the cursor leaves the `:shortcode` being completed.
+pub(super) fn leave_emoji_selection(state: &mut App) {
+    if let AppState::EmojiSelection(channel_id) = &state.state {
+        state.state = AppState::Chatting(channel_id.clone());
+        state.emoji_filter.clear();
+        state.emoji_filter_start = None;
+        state.selection_index = 0;
+        state.status_message = CHAT_HELP.to_string();
+    }
+}
+
+/// Loads the channels and permission context of a guild not cached yet in the background,
+/// for a chat opened without going through its channel list.
+pub(super) fn request_guild_channels(state: &mut App, commands: &mut Commands, guild_id: &str) {
+    if state.channel_cache.contains_key(guild_id)
+        || !state.channel_requests.insert(guild_id.to_string())
+    {
+        return;
+    }
+
+    let api_client_clone = state.api_client.clone();
+    let user_id = state.current_user.id.clone();
+    let guild_id = guild_id.to_string();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let channels = api_client_clone
+            .get_guild_channels(&guild_id)
+            .await
+            .unwrap_or_default();
+        let context = api_client_clone
+            .get_permission_context(&guild_id, &user_id)
+            .await
+            .ok();
+
+        tx_clone
+            .send(AppAction::ApiCacheGuildChannels(
+                guild_id, channels, context,
+            ))
+            .await
+            .ok();
+    });
+}
+
+/// Makes `guild_id` the guild of the chat about to be opened, unless it already is. An
+/// uncached guild starts empty and adopts its channels once they are loaded.
+pub(super) fn enter_guild(state: &mut App, commands: &mut Commands, guild_id: &str) {
+    if state.chat_guild_id.as_deref() == Some(guild_id) {
+        return;
+    }
+    load_guild_cache(state, guild_id);
+    request_guild_channels(state, commands, guild_id);
+}
+
+/// Puts the cached channels, permission context and custom emojis of a guild in place,
+/// empty for what wasn't loaded yet. Returns whether its channels were cached.
+pub(super) fn load_guild_cache(state: &mut App, guild_id: &str) -> bool {
+    let cached_channels = state.channel_cache.get(guild_id).cloned();
+    let from_cache = cached_channels.is_some();
+    state.channels = cached_channels.unwrap_or_default();
+    state.context = state.context_cache.get(guild_id).cloned();
+    state.custom_emojis = state.emoji_cache.get(guild_id).cloned().unwrap_or_default();
+    state.invalidate_rows();
+    from_cache
+}
+
+pub(super) fn move_selection(state: &mut App, n: i32, total_filtered_emojis: usize) {
+    match state.state {
+        AppState::Home => {
+            if n < 0 {
+                state.selection_index = if state.selection_index == 0 {
+                    3 - n.unsigned_abs() as usize
+                } else {
+                    state.selection_index - n.unsigned_abs() as usize
+                };
+            } else {
+                state.selection_index = (state.selection_index + n.unsigned_abs() as usize) % 3;
+            }
+        }
+        AppState::SelectingDM if !state.dms.is_empty() => {
+            if n < 0 {
+                state.selection_index = if state.selection_index == 0 {
+                    state.dms.len() - n.unsigned_abs() as usize
+                } else {
+                    state.selection_index - n.unsigned_abs() as usize
+                };
+            } else {
+                state.selection_index =
+                    (state.selection_index + n.unsigned_abs() as usize) % state.dms.len();
+            }
+        }
+        AppState::SelectingGuild if !state.guilds.is_empty() => {
+            let len = filter_guilds(
+                &state.guilds,
+                &state.input,
+                state.guild_sort,
+                &state.guilds_opened,
+                &state.muted,
+                state.pinned.ids(PinKind::Guild),
+            )
+            .len();
+            if len == 0 {
+                return;
+            }
+
+            if n < 0 {
+                state.selection_index = if state.selection_index == 0 {
+                    len - n.unsigned_abs() as usize
+                } else {
+                    state.selection_index - n.unsigned_abs() as usize
+                };
+            } else {
+                state.selection_index = (state.selection_index + n.unsigned_abs() as usize) % len;
+            }
+        }
+        AppState::SelectingChannel(ref guild_id) if !state.channels.is_empty() => {
+            let rows = state.channel_rows(guild_id);
+
+            if !rows.iter().any(|c| c.is_selectable()) {
+                return;
+            }
+
+            // Category headers and forums are not selectable, so step over them.
+            let len = rows.len();
+            let mut index = state.selection_index.min(len - 1);
+            for _ in 0..n.unsigned_abs() {
+                loop {
+                    index = if n < 0 {
+                        (index + len - 1) % len
+                    } else {
+                        (index + 1) % len
+                    };
+                    if rows[index].is_selectable() {
+                        break;
+                    }
+                }
+            }
+
+            state.selection_index = index;
+        }
+        AppState::SelectingMessage(_) if !state.messages.is_empty() => {
+            // Messages are stored newest first, so moving up walks back in history.
+            let last = state.messages.len() - 1;
+            let mut index = if n < 0 {
+                (state.selection_index + n.unsigned_abs() as usize).min(last)
+            } else {
+                state
+                    .selection_index
+                    .saturating_sub(n.unsigned_abs() as usize)
+            };
+            // Messages of ignored users are only selected through the placeholder drawn for
+            // the newest of their run.
+            let messages: Vec<&Message> = state.messages.iter().collect();
+            while collapse(state, &messages, index) == Collapse::Hidden {
+                index = if n < 0 && index < last {
+                    index + 1
+                } else {
+                    index - 1
+                };
+            }
+            state.selection_index = index;
+        }
+        AppState::EmojiSelection(_) if total_filtered_emojis > 0 => {
+            if n < 0 {
+                state.selection_index = if state.selection_index == 0 {
+                    total_filtered_emojis - 1
+                } else {
+                    state.selection_index - 1
+                };
+            } else {
+                state.selection_index = (state.selection_index + 1) % total_filtered_emojis;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Selects the row of the screen's list under a click. A second click on the same row
+/// soon after activates it, like Enter.
+pub(super) fn click_list(state: &mut App, commands: &mut Commands, column: u16, row: u16) {
+    let Some(list) = state.list_area.clone() else {
+        return;
+    };
+    let inner = list.area.inner(Margin::new(1, 1));
+    if !inner.contains(Position::new(column, row)) {
+        return;
+    }
+    let row = list.offset + usize::from(row - inner.y);
+    if list.headings.contains(&row) {
+        return;
+    }
+    let index = row
+        - list
+            .headings
+            .iter()
+            .filter(|&&heading| heading < row)
+            .count();
+    if index >= list.len {
+        return;
+    }
+
+    let double_click = state
+        .last_click
+        .is_some_and(|(at, clicked)| clicked == index && at.elapsed() < DOUBLE_CLICK_INTERVAL);
+    state.selection_index = index;
+    if double_click {
+        state.last_click = None;
+        commands.dispatch(AppAction::InputSubmit);
+    } else {
+        state.last_click = Some((Instant::now(), index));
+    }
+}
@@ -0,0 +1,1386 @@
+//! The popups and prompts drawn over the rest of the app, and the keys they take.
+
+use std::{
+    io::{self, Write},
+    time::Instant,
+};
+
+use chrono::Local;
+
+use super::{
+    App, AppAction, AppState, CHANNEL_LIST_HELP, CHAT_HELP, ChannelEdit, ChannelPrompt, Commands,
+    chat::{chat_channel_id, insert_char_at_cursor, open_channel, typed_path},
+    is_keypress,
+    lists::enter_guild,
+    selection::jump_to_message,
+    submit::{store_aliases, store_ignored},
+};
+use crate::{
+    api::{
+        ApiError, Channel, Message, TokenType, User,
+        channel::{GUILD_TEXT, normalize_channel_name},
+        emoji::{MAX_EMOJI_BYTES, read_emoji_image, validate_emoji_name},
+        invite::Invite,
+        webhook::{ActiveWebhook, validate_webhook_username},
+    },
+    ui::{
+        aliases::sorted_aliases,
+        emojis::{EmojiManager, EmojiUpload},
+        errors::{ERROR_LOG_LIMIT, ErrorEntry},
+        forum::{ForumViewer, PostDraft},
+        notifications::{NOTIFICATION_LIMIT, Notification},
+        profile::{MemberDetails, ProfileViewer},
+        search::{
+            MessageSearch, SEARCH_PAGE_SIZE, SEARCH_PAGES, SearchOutcome, SearchPhase,
+            matches_query,
+        },
+        shortcodes::expand_shortcodes,
+        switcher::{QuickSwitcher, switcher_entries},
+        webhooks::WebhookPicker,
+    },
+};
+
+/// Lines moved by PageUp and PageDown in the key help.
+const KEY_HELP_PAGE: usize = 10;
+const EMOJIS_READ_ONLY: &str =
+    "You lack Manage Expressions here, so the emojis are only listed. Esc to close.";
+
+/// Opens the quick switcher and starts loading the channels of guilds not opened yet.
+pub(super) fn open_switcher(state: &mut App, commands: &mut Commands) {
+    state.switcher = Some(QuickSwitcher::default());
+
+    let missing: Vec<String> = state
+        .guilds
+        .iter()
+        .map(|g| g.id.clone())
+        .filter(|id| !state.channel_cache.contains_key(id) && !state.channel_requests.contains(id))
+        .collect();
+
+    if missing.is_empty() {
+        return;
+    }
+
+    state.channel_requests.extend(missing.iter().cloned());
+
+    let api_client_clone = state.api_client.clone();
+    let user_id = state.current_user.id.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        for guild_id in missing {
+            // A failed guild is cached as empty so the switcher stops waiting for it.
+            let channels = api_client_clone
+                .get_guild_channels(&guild_id)
+                .await
+                .unwrap_or_default();
+            let context = api_client_clone
+                .get_permission_context(&guild_id, &user_id)
+                .await
+                .ok();
+
+            tx_clone
+                .send(AppAction::ApiCacheGuildChannels(
+                    guild_id, channels, context,
+                ))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Shows an error in the status bar and keeps it in the Ctrl+E log.
+pub(super) fn report_error(state: &mut App, message: String) {
+    tracing::warn!("{message}");
+    state.error_log.push_front(ErrorEntry {
+        time: Local::now(),
+        message,
+    });
+    state.error_log.truncate(ERROR_LOG_LIMIT);
+    state.error_toast = Some(Instant::now());
+}
+
+/// Handles an action while the error log is open. It only closes again; everything else
+/// the keyboard sends is ignored.
+pub(super) fn handle_error_log_keys(state: &mut App, action: AppAction) -> Option<AppAction> {
+    match action {
+        AppAction::InputEscape | AppAction::ToggleErrorLog => {
+            state.show_error_log = false;
+        }
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Records a message mentioning us outside the open channel: rings the terminal bell and
+/// shows it in the status bar until acknowledged. Mentions and direct messages also get
+/// a desktop notification, when turned on.
+pub(super) fn notify_mention(state: &mut App, message: &Message) {
+    let user_id = state.current_user.id.clone();
+    let muted = state.muted.contains(&message.channel_id)
+        || message
+            .guild_id
+            .as_ref()
+            .is_some_and(|guild_id| state.muted.contains(guild_id));
+    let mentioned = message.mentions.iter().any(|m| m.id == user_id);
+    let in_dm = state.dms.iter().any(|dm| dm.id == message.channel_id);
+    if muted
+        || message.author.id == user_id
+        || state.is_ignored(&message.author.id)
+        || !(mentioned || in_dm)
+        || !state.notified_mentions.insert(message.id.clone())
+    {
+        return;
+    }
+
+    let channel_name = state
+        .channels
+        .iter()
+        .chain(state.channel_cache.values().flatten())
+        .flat_map(|c| c.children.iter().flatten())
+        .chain(state.thread_cache.values().flatten())
+        .find(|c| c.id == message.channel_id)
+        .map_or_else(|| "unknown".to_string(), |c| c.name.clone());
+    let guild_name = message.guild_id.as_ref().map(|guild_id| {
+        state
+            .guilds
+            .iter()
+            .find(|g| &g.id == guild_id)
+            .map_or_else(|| "unknown server".to_string(), |g| g.name.clone())
+    });
+
+    let author = &message.author.username;
+    let title = match &guild_name {
+        _ if in_dm => format!("{author} in a direct message"),
+        Some(guild_name) => format!("{author} in #{channel_name} ({guild_name})"),
+        None => format!("{author} in #{channel_name}"),
+    };
+    if let Some(notifier) = &mut state.desktop_notifier {
+        let content = message.content.as_deref().unwrap_or_default();
+        notifier.notify(&message.channel_id, title, content);
+    }
+    if !mentioned {
+        return;
+    }
+
+    state.notifications.push_front(Notification {
+        channel_id: message.channel_id.clone(),
+        channel_name,
+        guild_id: message.guild_id.clone(),
+        guild_name,
+        author: message.author.username.clone(),
+    });
+    state.notifications.truncate(NOTIFICATION_LIMIT);
+    state.unacknowledged_mentions += 1;
+
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x07").and_then(|()| stdout.flush()).ok();
+}
+
+/// Opens the channel of a notification, bringing along the channel list of its guild.
+pub(super) fn jump_to_notification(
+    state: &mut App,
+    commands: &mut Commands,
+    notification: Notification,
+) {
+    if let Some(guild_id) = &notification.guild_id {
+        enter_guild(state, commands, guild_id);
+    }
+
+    let name = match notification.guild_id {
+        Some(_) => notification.channel_name.clone(),
+        None => notification.author.clone(),
+    };
+    open_channel(
+        state,
+        commands,
+        notification.channel_id,
+        &name,
+        notification.guild_id,
+    );
+}
+
+/// Handles an action while the mentions overlay is open, like the quick switcher does.
+pub(super) fn handle_notification_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let len = state.notifications.len();
+    let selection_index = state.notification_overlay?;
+
+    match action {
+        AppAction::SelectNext if len > 0 => {
+            state.notification_overlay = Some((selection_index + 1) % len);
+        }
+        AppAction::SelectPrevious if len > 0 => {
+            state.notification_overlay = Some((selection_index + len - 1) % len);
+        }
+        AppAction::InputEscape | AppAction::ToggleNotifications => {
+            state.notification_overlay = None;
+        }
+        AppAction::InputSubmit => {
+            let notification = state.notifications.get(selection_index)?.clone();
+            state.notification_overlay = None;
+            jump_to_notification(state, commands, notification);
+        }
+        AppAction::SelectNext
+        | AppAction::SelectPrevious
+        | AppAction::InputChar(_)
+        | AppAction::InputBackspace
+        | AppAction::InputTab
+        | AppAction::Paste(_)
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::SelectMessage
+        | AppAction::DeleteMessage
+        | AppAction::SelectEmoji
+        | AppAction::LoadArchivedThreads
+        | AppAction::ToggleSwitcher
+        | AppAction::ToggleMemberList
+        | AppAction::ScrollPageUp
+        | AppAction::ScrollPageDown
+        | AppAction::ScrollLineUp
+        | AppAction::ScrollLineDown => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Turns the input bar into a search prompt for the open channel.
+pub(super) fn open_search_prompt(state: &mut App) {
+    let AppState::Chatting(channel_id) = &state.state else {
+        return;
+    };
+    state.search = Some(MessageSearch {
+        channel_id: channel_id.clone(),
+        query: String::new(),
+        phase: SearchPhase::Prompt,
+    });
+    state.status_message = "Search messages. Enter to search, Esc to cancel.".to_string();
+}
+
+/// Pages back through the history of the searched channel, up to `SEARCH_PAGES` pages,
+/// keeping the messages that contain the query.
+pub(super) fn start_search(state: &mut App, commands: &mut Commands) {
+    let query = state.input.trim().to_string();
+    let Some(search) = &mut state.search else {
+        return;
+    };
+    if query.is_empty() {
+        close_search(state);
+        state.status_message = CHAT_HELP.to_string();
+        return;
+    }
+
+    search.query = query.clone();
+    search.phase = SearchPhase::Searching(0);
+    let channel_id = search.channel_id.clone();
+    state.input.clear();
+    state.cursor_position = 0;
+    state.status_message = format!("Searching for \"{query}\"...");
+
+    let api_client = state.api_client.clone();
+    let tx_action = commands.sender();
+    commands.spawn(async move {
+        let mut outcome = SearchOutcome {
+            query,
+            matches: Vec::new(),
+            checked: 0,
+            error: None,
+        };
+        let mut before = None;
+
+        for _ in 0..SEARCH_PAGES {
+            let page = match api_client
+                .get_channel_messages(&channel_id, None, before, None, Some(SEARCH_PAGE_SIZE))
+                .await
+            {
+                Ok(page) => page,
+                Err(e) => {
+                    outcome.error = Some(e);
+                    break;
+                }
+            };
+
+            outcome.checked += page.len();
+            before = page.last().map(|m| m.id.clone());
+            let exhausted = page.len() < SEARCH_PAGE_SIZE;
+            outcome.matches.extend(
+                page.into_iter()
+                    .filter(|m| matches_query(m, &outcome.query)),
+            );
+
+            tx_action
+                .send(AppAction::ApiSearchProgress(
+                    outcome.query.clone(),
+                    outcome.checked,
+                ))
+                .await
+                .ok();
+
+            if exhausted {
+                break;
+            }
+        }
+
+        tx_action
+            .send(AppAction::ApiSearchResults(outcome))
+            .await
+            .ok();
+    });
+}
+
+/// Closes the search, dropping the query typed in the input bar if it wasn't submitted.
+pub(super) fn close_search(state: &mut App) {
+    if state.search.take().is_some_and(|search| search.is_prompt()) {
+        state.input.clear();
+        state.cursor_position = 0;
+    }
+}
+
+/// Starts typing in the input bar the name of a new text channel, in the category of the
+/// selected row, or with `rename` the new name of the selected channel. The filter of the
+/// list is kept aside meanwhile.
+pub(super) fn open_channel_prompt(state: &mut App, rename: bool) {
+    let AppState::SelectingChannel(guild_id) = state.state.clone() else {
+        return;
+    };
+    let opened = {
+        let rows = state.channel_rows(&guild_id);
+        if rename {
+            rows.get(state.selection_index)
+                .filter(|c| !c.id.is_empty())
+                .map(|c| {
+                    let edit = ChannelEdit::Rename {
+                        channel_id: c.id.clone(),
+                        channel_type: c.channel_type,
+                    };
+                    let status = format!(
+                        "Rename #{}: type the new name, Enter to save, Esc to cancel.",
+                        c.name
+                    );
+                    (edit, c.name.clone(), status)
+                })
+        } else {
+            // The category is the nearest one above the selected row.
+            let category = rows
+                .iter()
+                .take(state.selection_index + 1)
+                .rev()
+                .find(|c| c.is_category() && !c.id.is_empty());
+            let status = match category {
+                Some(category) => format!(
+                    "New text channel in {}: type its name, Enter to create, Esc to cancel.",
+                    category.name
+                ),
+                None => {
+                    "New text channel: type its name, Enter to create, Esc to cancel.".to_string()
+                }
+            };
+            let edit = ChannelEdit::Create {
+                guild_id,
+                parent_id: category.map(|c| c.id.clone()),
+            };
+            Some((edit, String::new(), status))
+        }
+    };
+    let Some((edit, name, status)) = opened else {
+        return;
+    };
+
+    let filter = std::mem::replace(&mut state.input, name);
+    state.cursor_position = state.input.len();
+    state.status_message = status;
+    state.channel_prompt = Some(ChannelPrompt { edit, filter });
+}
+
+/// Leaves the channel name prompt, bringing the filter of the list back.
+fn close_channel_prompt(state: &mut App) {
+    if let Some(prompt) = state.channel_prompt.take() {
+        state.cursor_position = prompt.filter.len();
+        state.input = prompt.filter;
+    }
+    state.status_message = CHANNEL_LIST_HELP.to_string();
+}
+
+/// Creates or renames the channel with the typed name, once it is one Discord accepts.
+fn submit_channel_prompt(state: &mut App, commands: &mut Commands) {
+    let Some(prompt) = &state.channel_prompt else {
+        return;
+    };
+    let channel_type = match &prompt.edit {
+        ChannelEdit::Create { .. } => GUILD_TEXT,
+        ChannelEdit::Rename { channel_type, .. } => *channel_type,
+    };
+    let name = match normalize_channel_name(&state.input, channel_type) {
+        Ok(name) => name,
+        Err(e) => {
+            state.status_message = e;
+            return;
+        }
+    };
+    let Some(prompt) = state.channel_prompt.take() else {
+        return;
+    };
+    state.cursor_position = prompt.filter.len();
+    state.input = prompt.filter;
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+    let edit = prompt.edit;
+    state.status_message = match &edit {
+        ChannelEdit::Create { .. } => format!("Creating #{name}..."),
+        ChannelEdit::Rename { .. } => format!("Renaming to {name}..."),
+    };
+
+    commands.track(async move {
+        let (result, failed) = match &edit {
+            ChannelEdit::Create {
+                guild_id,
+                parent_id,
+            } => (
+                api_client_clone
+                    .create_guild_channel(guild_id, &name, GUILD_TEXT, parent_id.as_deref())
+                    .await,
+                "create",
+            ),
+            ChannelEdit::Rename { channel_id, .. } => (
+                api_client_clone.modify_channel(channel_id, &name).await,
+                "rename",
+            ),
+        };
+        let (action, outcome) = match result {
+            Ok(channel) => (AppAction::ApiChannelSaved(channel), Ok(())),
+            Err(ApiError::Forbidden(_)) => {
+                let failure = "You lack Manage Channels here.".to_string();
+                (AppAction::ApiError(failure.clone()), Err(failure))
+            }
+            Err(e) => {
+                let failure = format!("Failed to {failed} the channel: {e}");
+                (AppAction::ApiError(failure.clone()), Err(failure))
+            }
+        };
+        tx_clone.send(action).await.ok();
+        outcome
+    });
+}
+
+/// Handles an action while a channel name is typed in the input bar of the channel list.
+pub(super) fn handle_channel_prompt_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    match action {
+        AppAction::InputEscape => close_channel_prompt(state),
+        AppAction::InputChar(c) => insert_char_at_cursor(state, c),
+        AppAction::InputSubmit => submit_channel_prompt(state, commands),
+        AppAction::InputBackspace
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::Paste(_) => return Some(action),
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Opens the emoji manager of the guild whose channels are listed and loads its emojis.
+/// Without Manage Expressions, as far as the roles tell, they are only listed.
+pub(super) fn open_emoji_manager(state: &mut App, commands: &mut Commands) {
+    let AppState::SelectingChannel(guild_id) = state.state.clone() else {
+        return;
+    };
+    let read_only = state
+        .context
+        .as_ref()
+        .filter(|context| context.everyone_role_id == guild_id)
+        .is_some_and(|context| !context.can_manage_expressions());
+
+    let filter = std::mem::take(&mut state.input);
+    state.cursor_position = 0;
+    state.status_message = if read_only {
+        EMOJIS_READ_ONLY.to_string()
+    } else {
+        "Emojis: u to upload one, d to delete the selected one, Esc to close.".to_string()
+    };
+    state.emoji_manager = Some(EmojiManager {
+        guild_id: guild_id.clone(),
+        emojis: None,
+        selection_index: 0,
+        read_only,
+        confirm_delete: false,
+        upload: None,
+        filter,
+    });
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+    commands.spawn(async move {
+        let result = api_client_clone.get_guild_emojis(&guild_id).await;
+        tx_clone
+            .send(AppAction::ApiManagedEmojis(guild_id, result))
+            .await
+            .ok();
+    });
+}
+
+/// Closes the emoji manager, bringing the filter of the channel list back.
+fn close_emoji_manager(state: &mut App) {
+    if let Some(manager) = state.emoji_manager.take() {
+        state.cursor_position = manager.filter.len();
+        state.input = manager.filter;
+    }
+    state.status_message = CHANNEL_LIST_HELP.to_string();
+}
+
+/// Uploads the image at the typed path as a new emoji, once it passes Discord's limits.
+fn upload_emoji(state: &mut App, commands: &mut Commands, name: String, typed: &str) {
+    let Some(manager) = &state.emoji_manager else {
+        return;
+    };
+    let guild_id = manager.guild_id.clone();
+    let path = typed_path(typed);
+    state.status_message = format!("Uploading :{name}:...");
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+    commands.track(async move {
+        let (action, outcome) = match read_emoji_image(&path).await {
+            Ok(image) => {
+                let result = api_client_clone
+                    .create_guild_emoji(&guild_id, &name, &image)
+                    .await;
+                let outcome = match &result {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("Failed to upload :{name}: {e}")),
+                };
+                (AppAction::ApiEmojiCreated(guild_id, result), outcome)
+            }
+            Err(e) => (AppAction::ApiError(e.clone()), Err(e)),
+        };
+        tx_clone.send(action).await.ok();
+        outcome
+    });
+}
+
+fn delete_emoji(state: &mut App, commands: &mut Commands) {
+    let Some(manager) = &state.emoji_manager else {
+        return;
+    };
+    let Some(emoji) = manager.selected() else {
+        return;
+    };
+    let (guild_id, emoji_id) = (manager.guild_id.clone(), emoji.id.clone());
+    state.status_message = format!("Deleting :{}:...", emoji.name);
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+    commands.track(async move {
+        let result = api_client_clone
+            .delete_guild_emoji(&guild_id, &emoji_id)
+            .await;
+        let outcome = match &result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("Failed to delete emoji {emoji_id}: {e}")),
+        };
+        tx_clone
+            .send(AppAction::ApiEmojiDeleted(guild_id, emoji_id, result))
+            .await
+            .ok();
+        outcome
+    });
+}
+
+/// Reports a failed request of the emoji manager. Missing permissions leave it read-only
+/// rather than closing it.
+pub(super) fn emoji_request_failed(state: &mut App, guild_id: &str, e: ApiError, what: &str) {
+    let Some(manager) = state
+        .emoji_manager
+        .as_mut()
+        .filter(|manager| manager.guild_id == guild_id)
+    else {
+        return;
+    };
+    match e {
+        ApiError::Forbidden(_) => {
+            manager.read_only = true;
+            manager.emojis.get_or_insert_default();
+            state.status_message = EMOJIS_READ_ONLY.to_string();
+        }
+        e => {
+            manager.emojis.get_or_insert_default();
+            report_error(state, format!("Failed to {what}: {e}"));
+        }
+    }
+}
+
+/// Handles an action while the emoji manager is open: moving through the emojis,
+/// deleting one after a y/n confirmation, or typing the name and image path of a new one.
+pub(super) fn handle_emoji_manager_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    if !is_keypress(&action) {
+        return Some(action);
+    }
+    let input = state.input.trim().to_string();
+    let manager = state.emoji_manager.as_mut()?;
+
+    if let Some(upload) = manager.upload.clone() {
+        match action {
+            AppAction::InputEscape => {
+                manager.upload = None;
+                state.input.clear();
+                state.cursor_position = 0;
+                state.status_message = "Upload cancelled.".to_string();
+            }
+            AppAction::InputChar(c) => insert_char_at_cursor(state, c),
+            AppAction::InputSubmit if input.is_empty() => {}
+            AppAction::InputSubmit => match upload {
+                EmojiUpload::Name => match validate_emoji_name(&input) {
+                    Ok(name) => {
+                        manager.upload = Some(EmojiUpload::Path(name));
+                        state.input.clear();
+                        state.cursor_position = 0;
+                    }
+                    Err(e) => state.status_message = e,
+                },
+                EmojiUpload::Path(name) => {
+                    manager.upload = None;
+                    state.input.clear();
+                    state.cursor_position = 0;
+                    upload_emoji(state, commands, name, &input);
+                }
+            },
+            AppAction::InputBackspace
+            | AppAction::InputMoveCursor(_)
+            | AppAction::InputDeleteWord
+            | AppAction::InputClearToStart
+            | AppAction::Paste(_) => return Some(action),
+            _ => {}
+        }
+        return None;
+    }
+
+    if manager.confirm_delete {
+        manager.confirm_delete = false;
+        if matches!(action, AppAction::InputChar('y' | 'Y')) {
+            delete_emoji(state, commands);
+        } else {
+            state.status_message = "Kept the emoji.".to_string();
+        }
+        return None;
+    }
+
+    let len = manager.emojis.as_ref().map_or(0, Vec::len);
+    match action {
+        AppAction::SelectNext if len > 0 => {
+            manager.selection_index = (manager.selection_index + 1).min(len - 1);
+        }
+        AppAction::SelectPrevious => {
+            manager.selection_index = manager.selection_index.saturating_sub(1);
+        }
+        AppAction::InputEscape => close_emoji_manager(state),
+        AppAction::InputChar('u') if !manager.read_only => {
+            manager.upload = Some(EmojiUpload::Name);
+            state.status_message = format!(
+                "New emoji: type its name, Enter, then the path of a PNG, JPEG or GIF up to {} KiB. Esc to cancel.",
+                MAX_EMOJI_BYTES / 1024
+            );
+        }
+        AppAction::InputChar('d') if !manager.read_only && manager.selected().is_some() => {
+            manager.confirm_delete = true;
+        }
+        _ => {}
+    }
+
+    None
+}
+
+/// Opens the post list of a forum channel and loads its active and archived posts.
+pub(super) fn open_forum(
+    state: &mut App,
+    commands: &mut Commands,
+    guild_id: String,
+    forum: &Channel,
+) {
+    state.forum = Some(ForumViewer {
+        channel_id: forum.id.clone(),
+        channel_name: forum.name.clone(),
+        guild_id: guild_id.clone(),
+        posts: None,
+        selection_index: 0,
+        draft: None,
+    });
+
+    let channel_id = forum.id.clone();
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let active = api_client_clone.get_active_guild_threads(&guild_id).await;
+        let archived = api_client_clone
+            .list_public_archived_threads(&channel_id)
+            .await;
+
+        let result = match (active, archived) {
+            (Ok(active), Ok(archived)) => {
+                let mut posts: Vec<Channel> = active
+                    .threads
+                    .into_iter()
+                    .filter(|t| t.parent_id.as_ref() == Some(&channel_id))
+                    .chain(archived.threads)
+                    .collect();
+                // Most recently active first.
+                posts.sort_by_key(|t| {
+                    std::cmp::Reverse(
+                        t.last_message_id
+                            .as_ref()
+                            .and_then(|id| id.parse::<u64>().ok())
+                            .unwrap_or(0),
+                    )
+                });
+                posts.dedup_by(|a, b| a.id == b.id);
+                Ok(posts)
+            }
+            (Err(e), _) | (_, Err(e)) => Err(e.to_string()),
+        };
+
+        tx_clone
+            .send(AppAction::ApiUpdateForumPosts(channel_id, result))
+            .await
+            .ok();
+    });
+}
+
+/// Sends the new post typed in the post list once its title and first message are in.
+fn create_forum_post(state: &mut App, commands: &mut Commands, channel_id: String, title: String) {
+    let content = expand_shortcodes(state.input.trim(), &state.emoji_map);
+    state.input.clear();
+    state.cursor_position = 0;
+    state.status_message = format!("Creating post \"{title}\"...");
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.track(async move {
+        match api_client_clone
+            .create_forum_post(&channel_id, title, content)
+            .await
+        {
+            Ok(post) => {
+                tx_clone
+                    .send(AppAction::ApiForumPostCreated(post))
+                    .await
+                    .ok();
+                Ok(())
+            }
+            Err(e) => {
+                let failure = format!("Failed to create the post: {e}");
+                tx_clone
+                    .send(AppAction::ApiError(failure.clone()))
+                    .await
+                    .ok();
+                Err(failure)
+            }
+        }
+    });
+}
+
+/// Handles an action while the post list of a forum is open. While a new post is typed,
+/// the input bar edits its title and then its first message.
+pub(super) fn handle_forum_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let input = state.input.trim().to_string();
+    let forum = state.forum.as_mut()?;
+
+    if let Some(draft) = &forum.draft {
+        match action {
+            AppAction::InputEscape => {
+                forum.draft = None;
+                state.input.clear();
+                state.cursor_position = 0;
+            }
+            AppAction::InputChar(c) => insert_char_at_cursor(state, c),
+            AppAction::InputSubmit if input.is_empty() => {}
+            AppAction::InputSubmit => match draft {
+                PostDraft::Title => {
+                    forum.draft = Some(PostDraft::Message(input));
+                    state.input.clear();
+                    state.cursor_position = 0;
+                }
+                PostDraft::Message(title) => {
+                    let (channel_id, title) = (forum.channel_id.clone(), title.clone());
+                    forum.draft = None;
+                    create_forum_post(state, commands, channel_id, title);
+                }
+            },
+            AppAction::InputBackspace
+            | AppAction::InputMoveCursor(_)
+            | AppAction::InputDeleteWord
+            | AppAction::InputClearToStart
+            | AppAction::Paste(_) => return Some(action),
+            action if is_keypress(&action) => {}
+            action => return Some(action),
+        }
+        return None;
+    }
+
+    let len = forum.posts.as_ref().map_or(0, Vec::len);
+    match action {
+        AppAction::SelectNext if len > 0 => {
+            forum.selection_index = (forum.selection_index + 1).min(len - 1);
+        }
+        AppAction::SelectPrevious => {
+            forum.selection_index = forum.selection_index.saturating_sub(1);
+        }
+        AppAction::InputEscape => state.forum = None,
+        AppAction::InputChar('n') => {
+            forum.draft = Some(PostDraft::Title);
+            state.input.clear();
+            state.cursor_position = 0;
+            state.status_message =
+                "New post: Enter after the title, then after the first message. Esc to cancel."
+                    .to_string();
+        }
+        AppAction::InputSubmit => {
+            let post = forum.posts.as_ref()?.get(forum.selection_index)?.clone();
+            let guild_id = forum.guild_id.clone();
+            state.forum = None;
+            open_channel(state, commands, post.id, &post.name, Some(guild_id));
+        }
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Handles an action while a search is typed, running or showing its results. The input
+/// bar edits the query as usual; other keys are ignored until the search is closed.
+pub(super) fn handle_search_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let input_empty = state.input.is_empty();
+    let search = state.search.as_mut()?;
+
+    match action {
+        AppAction::InputEscape => {
+            close_search(state);
+            state.status_message = CHAT_HELP.to_string();
+        }
+        AppAction::InputChar(c) if search.is_prompt() => insert_char_at_cursor(state, c),
+        AppAction::InputBackspace if search.is_prompt() && input_empty => {
+            close_search(state);
+            state.status_message = CHAT_HELP.to_string();
+        }
+        AppAction::InputSubmit if search.is_prompt() => start_search(state, commands),
+        AppAction::InputBackspace
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::Paste(_)
+            if search.is_prompt() =>
+        {
+            return Some(action);
+        }
+        AppAction::InputChar('n') | AppAction::SelectNext => {
+            if let SearchPhase::Results(matches, selection_index) = &mut search.phase
+                && !matches.is_empty()
+            {
+                *selection_index = (*selection_index + 1) % matches.len();
+            }
+        }
+        AppAction::InputChar('N') | AppAction::SelectPrevious => {
+            if let SearchPhase::Results(matches, selection_index) = &mut search.phase
+                && !matches.is_empty()
+            {
+                *selection_index = (*selection_index + matches.len() - 1) % matches.len();
+            }
+        }
+        AppAction::InputSubmit => {
+            if let SearchPhase::Results(matches, selection_index) = &search.phase {
+                let message = matches.get(*selection_index)?.clone();
+                state.search = None;
+                jump_to_message(state, commands, message.channel_id, message.id);
+            }
+        }
+        AppAction::ScrollPageUp
+        | AppAction::ScrollPageDown
+        | AppAction::ScrollLineUp
+        | AppAction::ScrollLineDown => return Some(action),
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Handles an action while the quick switcher is open. Actions the switcher does not
+/// consume are handed back for the regular reducer.
+pub(super) fn handle_switcher_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let query = state.switcher.as_ref()?.query.clone();
+    let len = switcher_entries(state, &query).len();
+    let switcher = state.switcher.as_mut()?;
+
+    match action {
+        AppAction::InputChar(c) => {
+            switcher.query.push(c);
+            switcher.selection_index = 0;
+        }
+        AppAction::InputBackspace => {
+            switcher.query.pop();
+            switcher.selection_index = 0;
+        }
+        AppAction::SelectNext if len > 0 => {
+            switcher.selection_index = (switcher.selection_index + 1) % len;
+        }
+        AppAction::SelectPrevious if len > 0 => {
+            switcher.selection_index = (switcher.selection_index + len - 1) % len;
+        }
+        AppAction::InputEscape | AppAction::ToggleSwitcher => {
+            state.switcher = None;
+        }
+        AppAction::InputSubmit => {
+            let index = switcher.selection_index;
+            let entry = switcher_entries(state, &query).into_iter().nth(index)?;
+            state.switcher = None;
+
+            // Jumping into another guild brings its channel list along, so Esc from the
+            // chat lands in the right place.
+            if let Some(guild_id) = &entry.guild_id {
+                enter_guild(state, commands, guild_id);
+            }
+
+            open_channel(
+                state,
+                commands,
+                entry.channel_id,
+                &entry.channel_name,
+                entry.guild_id,
+            );
+        }
+        AppAction::SelectNext
+        | AppAction::SelectPrevious
+        | AppAction::InputTab
+        | AppAction::Paste(_)
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::SelectMessage
+        | AppAction::DeleteMessage
+        | AppAction::SelectEmoji
+        | AppAction::LoadArchivedThreads
+        | AppAction::ScrollPageUp
+        | AppAction::ScrollPageDown
+        | AppAction::ScrollLineUp
+        | AppAction::ScrollLineDown => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Handles an action while the aliases are listed, like the ignore list does.
+pub(super) fn handle_alias_list_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let len = state.aliases.len();
+    let list = state.alias_list.as_mut()?;
+
+    match action {
+        AppAction::SelectNext if len > 0 => {
+            list.selection_index = (list.selection_index + 1).min(len - 1);
+        }
+        AppAction::SelectPrevious => {
+            list.selection_index = list.selection_index.saturating_sub(1);
+        }
+        AppAction::InputEscape => {
+            state.alias_list = None;
+            state.status_message = CHAT_HELP.to_string();
+        }
+        AppAction::InputChar('d') if len > 0 => {
+            let index = list.selection_index.min(len - 1);
+            list.selection_index = index.min(len.saturating_sub(2));
+            let user_id = sorted_aliases(state)[index].0.to_string();
+            if let Some(alias) = state.aliases.remove(&user_id) {
+                state.invalidate_rows();
+                state.status_message = format!("Removed the alias {alias}.");
+            }
+            store_aliases(state, commands);
+        }
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+    None
+}
+
+pub(super) fn handle_ignore_list_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let len = state.ignored.len();
+    let list = state.ignore_list.as_mut()?;
+
+    match action {
+        AppAction::SelectNext if len > 0 => {
+            list.selection_index = (list.selection_index + 1).min(len - 1);
+        }
+        AppAction::SelectPrevious => {
+            list.selection_index = list.selection_index.saturating_sub(1);
+        }
+        AppAction::InputEscape => {
+            state.ignore_list = None;
+            state.status_message = CHAT_HELP.to_string();
+        }
+        AppAction::InputChar('d') if len > 0 => {
+            let index = list.selection_index.min(len - 1);
+            list.selection_index = index.min(len.saturating_sub(2));
+            let user = state.ignored.remove(index);
+            state.status_message = format!("Stopped ignoring {}.", user.name);
+            store_ignored(state, commands);
+        }
+        AppAction::SelectNext
+        | AppAction::InputChar(_)
+        | AppAction::InputBackspace
+        | AppAction::InputTab
+        | AppAction::InputSubmit
+        | AppAction::Paste(_)
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::SelectMessage
+        | AppAction::DeleteMessage
+        | AppAction::SelectEmoji
+        | AppAction::LoadArchivedThreads
+        | AppAction::ToggleMemberList
+        | AppAction::ScrollPageUp
+        | AppAction::ScrollPageDown
+        | AppAction::ScrollLineUp
+        | AppAction::ScrollLineDown => {}
+        action => return Some(action),
+    }
+    None
+}
+
+/// Lists the webhooks of the open channel for `/webhook`, to send as `username` and
+/// with `avatar_url` through the one picked.
+pub(super) fn open_webhook_picker(
+    state: &mut App,
+    commands: &mut Commands,
+    username: Option<String>,
+    avatar_url: Option<String>,
+) {
+    let AppState::Chatting(channel_id) = state.state.clone() else {
+        return;
+    };
+    let username = match username.as_deref().map(validate_webhook_username) {
+        Some(Err(e)) => {
+            state.status_message = e;
+            return;
+        }
+        username => username.and_then(Result::ok),
+    };
+
+    state.status_message = "Loading the webhooks of this channel...".to_string();
+    state.webhook_picker = Some(WebhookPicker {
+        channel_id: channel_id.clone(),
+        webhooks: None,
+        selection_index: 0,
+        username,
+        avatar_url,
+    });
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+    commands.spawn(async move {
+        let result = api_client_clone.get_channel_webhooks(&channel_id).await;
+        tx_clone
+            .send(AppAction::ApiChannelWebhooks(channel_id, result))
+            .await
+            .ok();
+    });
+}
+
+/// Handles an action while the webhook picker is open.
+pub(super) fn handle_webhook_picker_keys(state: &mut App, action: AppAction) -> Option<AppAction> {
+    let picker = state.webhook_picker.as_mut()?;
+    let len = picker.webhooks.as_ref().map_or(0, Vec::len);
+
+    match action {
+        AppAction::SelectNext if len > 0 => {
+            picker.selection_index = (picker.selection_index + 1).min(len - 1);
+        }
+        AppAction::SelectPrevious => {
+            picker.selection_index = picker.selection_index.saturating_sub(1);
+        }
+        AppAction::InputEscape => {
+            state.webhook_picker = None;
+            state.status_message = CHAT_HELP.to_string();
+        }
+        AppAction::InputSubmit => {
+            let webhook = picker.selected()?.clone();
+            if !webhook.can_execute() {
+                state.status_message =
+                    format!("{} cannot be posted through.", webhook.display_name());
+                return None;
+            }
+            let picker = state.webhook_picker.take()?;
+            let username = picker
+                .username
+                .unwrap_or_else(|| webhook.display_name().to_string());
+            state.status_message = format!(
+                "Sending through {} as {username}. /webhook off to send as yourself.",
+                webhook.display_name()
+            );
+            state.webhook = Some(ActiveWebhook {
+                webhook,
+                channel_id: picker.channel_id,
+                username,
+                avatar_url: picker.avatar_url,
+            });
+        }
+        AppAction::SelectNext
+        | AppAction::InputChar(_)
+        | AppAction::InputBackspace
+        | AppAction::InputTab
+        | AppAction::Paste(_)
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::SelectMessage
+        | AppAction::DeleteMessage
+        | AppAction::SelectEmoji
+        | AppAction::LoadArchivedThreads
+        | AppAction::ToggleMemberList
+        | AppAction::ScrollPageUp
+        | AppAction::ScrollPageDown
+        | AppAction::ScrollLineUp
+        | AppAction::ScrollLineDown => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Loads the preview of an invite for `/join-invite`, shown in a popup to confirm joining.
+pub(super) fn preview_invite(state: &mut App, commands: &mut Commands, code: String) {
+    if state.api_client.token_type != TokenType::User {
+        state.status_message =
+            "Bots can't accept invites: add the bot with its OAuth2 link instead.".to_string();
+        return;
+    }
+    state.status_message = "Loading the invite...".to_string();
+
+    let api_client = state.api_client.clone();
+    let tx_action = commands.sender();
+    commands.spawn(async move {
+        let result = api_client.get_invite(&code).await.map_err(|e| match e {
+            ApiError::UnknownInvite => format!("{e}."),
+            e => format!("Failed to load the invite: {e}"),
+        });
+        tx_action.send(AppAction::ApiInvite(result)).await.ok();
+    });
+}
+
+/// Joins the guild of the previewed invite, then refreshes the guild list so it shows up.
+fn accept_invite(state: &mut App, commands: &mut Commands, invite: Invite) {
+    state.status_message = format!("Joining {}...", invite.guild_name());
+
+    let api_client = state.api_client.clone();
+    let tx_action = commands.sender();
+    commands.track(async move {
+        let failure = match api_client.accept_invite(&invite.code).await {
+            Ok(_) => {
+                match api_client.get_all_current_user_guilds().await {
+                    Ok(guilds) => {
+                        tx_action
+                            .send(AppAction::ApiRefreshGuilds(guilds))
+                            .await
+                            .ok();
+                    }
+                    // The background refresh picks the guild up later.
+                    Err(e) => tracing::warn!("Failed to refresh servers: {e}"),
+                }
+                tx_action
+                    .send(AppAction::ApiInviteAccepted(invite))
+                    .await
+                    .ok();
+                return Ok(());
+            }
+            Err(e @ ApiError::UnknownInvite) => format!("{e}."),
+            Err(e) => format!("Failed to join {}: {e}", invite.guild_name()),
+        };
+        tx_action
+            .send(AppAction::ApiError(failure.clone()))
+            .await
+            .ok();
+        Err(failure)
+    });
+}
+
+/// Handles an action while the invite popup is open: `y` joins, and once joined Enter
+/// opens the channel the invite leads to.
+pub(super) fn handle_invite_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let prompt = state.invite_prompt.as_ref()?;
+
+    match action {
+        AppAction::InputChar('y') | AppAction::InputSubmit if !prompt.joined => {
+            let invite = prompt.invite.clone();
+            state.invite_prompt = None;
+            accept_invite(state, commands, invite);
+        }
+        AppAction::InputSubmit => {
+            let invite = prompt.invite.clone();
+            state.invite_prompt = None;
+            let Some(channel) = invite.channel else {
+                state.status_message = format!("Joined {}.", invite.guild_name());
+                return None;
+            };
+            let guild_id = invite.guild.map(|guild| guild.id);
+            if let Some(guild_id) = &guild_id {
+                enter_guild(state, commands, guild_id);
+            }
+            let name = channel.name.unwrap_or_else(|| channel.id.clone());
+            open_channel(state, commands, channel.id, &name, guild_id);
+        }
+        AppAction::InputChar('n') | AppAction::InputEscape => {
+            state.invite_prompt = None;
+            state.status_message = CHAT_HELP.to_string();
+        }
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+    None
+}
+
+/// Handles an action while the key help is open: it scrolls, and Esc, `?` or F1 close it.
+pub(super) fn handle_key_help_keys(state: &mut App, action: AppAction) -> Option<AppAction> {
+    let scroll = state.key_help.as_mut()?;
+    match action {
+        AppAction::InputEscape | AppAction::InputChar('?') | AppAction::ToggleKeyHelp => {
+            state.key_help = None;
+        }
+        AppAction::SelectNext | AppAction::ScrollLineDown => *scroll += 1,
+        AppAction::SelectPrevious | AppAction::ScrollLineUp => {
+            *scroll = scroll.saturating_sub(1);
+        }
+        AppAction::ScrollPageDown => *scroll += KEY_HELP_PAGE,
+        AppAction::ScrollPageUp => *scroll = scroll.saturating_sub(KEY_HELP_PAGE),
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Handles an action while the `/help` overlay is open, which only Esc closes.
+pub(super) fn handle_command_help_keys(state: &mut App, action: AppAction) -> Option<AppAction> {
+    match action {
+        AppAction::InputEscape | AppAction::InputSubmit => state.show_command_help = false,
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Opens the profile of `user`. In a guild, their member details and roles are fetched
+/// in the background; until then, or if that fails, the profile shows what the message
+/// carried.
+pub(super) fn open_profile(state: &mut App, commands: &mut Commands, user: User) {
+    let guild_id = state.chat_guild_id.clone();
+    state.profile = Some(ProfileViewer {
+        user: user.clone(),
+        member: guild_id.as_ref().map(|_| MemberDetails::Loading),
+    });
+    let Some(guild_id) = guild_id else {
+        return;
+    };
+
+    let known_roles = state
+        .context
+        .as_ref()
+        .filter(|context| context.everyone_role_id == guild_id)
+        .map(|context| context.all_guild_roles.clone());
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let result = async {
+            let member = api_client_clone
+                .get_guild_member(&guild_id, &user.id)
+                .await?;
+            let mut roles = match known_roles {
+                Some(roles) => roles,
+                None => api_client_clone.get_guild_roles(&guild_id).await?,
+            };
+            // Highest role first, leaving out @everyone, whose id is the guild's.
+            roles.retain(|r| r.id != guild_id && member.roles.contains(&r.id));
+            roles.sort_by_key(|r| std::cmp::Reverse(r.position));
+            let names = roles.into_iter().map(|r| r.name).collect();
+            Ok::<_, ApiError>((member, names))
+        }
+        .await;
+
+        tx_clone
+            .send(AppAction::ApiProfileMember(
+                user.id,
+                result.map_err(|e| e.to_string()),
+            ))
+            .await
+            .ok();
+    });
+}
+
+/// Handles an action while a profile is open: Esc closes it, and `a` starts an alias for
+/// the user.
+pub(super) fn handle_profile_keys(state: &mut App, action: AppAction) -> Option<AppAction> {
+    match action {
+        AppAction::InputEscape => state.profile = None,
+        // Starts `/alias` for the user in the input, to type the name.
+        AppAction::InputChar('a') => {
+            let user_id = state.profile.as_ref()?.user.id.clone();
+            let channel_id = chat_channel_id(state)?.clone();
+            if !state.input.trim().is_empty() {
+                state.status_message = "Send or clear the draft first to set an alias.".to_string();
+                return None;
+            }
+            state.profile = None;
+            state.input = format!("/alias {user_id} ");
+            state.cursor_position = state.input.len();
+            state.editing_message = None;
+            state.state = AppState::Chatting(channel_id);
+            state.selection_index = 0;
+            state.status_message = "Type the alias, Enter to save it.".to_string();
+        }
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
@@ -0,0 +1,712 @@
+//! What can be done with the selected message.
+
+use super::{
+    App, AppAction, AppState, CHAT_HELP, Commands,
+    chat::{chat_channel_id, known_channel, open_dm, quote_text, start_quote},
+    is_keypress,
+    overlays::{open_profile, report_error},
+    submit::{goto, store_ignored},
+};
+use crate::{
+    Error,
+    api::{
+        ApiError, Channel,
+        message::{Reaction, ReactionEmoji},
+    },
+    clipboard,
+    config::IgnoredUser,
+    translate,
+    ui::{
+        draw::message_header_line,
+        keymap::{self, SelectionKey},
+        links::{GotoTarget, LinkHints, parse_permalink},
+        pins::PinViewer,
+        preview::ImagePreview,
+    },
+};
+
+/// Numbers the links of the loaded messages, to open one by typing its number.
+pub(super) fn open_link_hints(state: &mut App) {
+    if !matches!(state.state, AppState::Chatting(_)) {
+        return;
+    }
+
+    let hints = LinkHints::new(&state.messages);
+    if hints.links.is_empty() {
+        state.status_message = "No links in the loaded messages.".to_string();
+        return;
+    }
+    state.link_hints = Some(hints);
+    state.invalidate_rows();
+    state.status_message =
+        "Type a link number, then Enter to open it or y to copy it. Esc to cancel.".to_string();
+}
+
+fn close_link_hints(state: &mut App) {
+    state.link_hints = None;
+    state.invalidate_rows();
+    state.status_message = CHAT_HELP.to_string();
+}
+
+/// Opens `url` in the default browser. The opener runs on a blocking thread, as it may
+/// take a while to start.
+fn open_url(state: &mut App, commands: &mut Commands, url: String) {
+    state.status_message = format!("Opening {url}");
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let result = tokio::task::spawn_blocking({
+            let url = url.clone();
+            move || open::that(url)
+        })
+        .await;
+        let error = match result {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => e.to_string(),
+        };
+        tx_clone
+            .send(AppAction::ApiError(format!(
+                "Failed to open {url}: {error}"
+            )))
+            .await
+            .ok();
+    });
+}
+
+/// Handles an action while the link numbers are shown: digits pick a link, Enter opens
+/// it and `y` copies it. Discord links are opened here rather than in the browser.
+pub(super) fn handle_link_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let hints = state.link_hints.as_mut()?;
+
+    match action {
+        AppAction::InputChar(c) if c.is_ascii_digit() => {
+            hints.typed.push(c);
+            let typed = hints.typed.clone();
+            state.status_message = format!("Link {typed}: Enter to open, y to copy.");
+        }
+        AppAction::InputBackspace => {
+            hints.typed.pop();
+        }
+        AppAction::InputSubmit | AppAction::InputChar('y') => {
+            let Some(url) = hints.selected().map(str::to_string) else {
+                let typed = std::mem::take(&mut hints.typed);
+                state.status_message = format!("No link numbered {typed}.");
+                return None;
+            };
+            state.link_hints = None;
+            state.invalidate_rows();
+            if matches!(action, AppAction::InputSubmit) {
+                match parse_permalink(&url) {
+                    Some(link) => goto(state, commands, GotoTarget::Link(link)),
+                    None => open_url(state, commands, url),
+                }
+            } else {
+                match clipboard::copy(&url) {
+                    Ok(()) => state.status_message = format!("Copied {url}"),
+                    Err(e) => report_error(state, format!("Failed to copy: {e}")),
+                }
+            }
+        }
+        AppAction::InputEscape | AppAction::ToggleLinkHints => close_link_hints(state),
+        action if is_keypress(&action) => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Opens the pinned messages of the open channel and fetches them.
+pub(super) fn open_pins(state: &mut App, commands: &mut Commands) {
+    let AppState::Chatting(channel_id) = &state.state else {
+        return;
+    };
+    let channel_id = channel_id.clone();
+
+    state.switcher = None;
+    state.notification_overlay = None;
+    state.pins = Some(PinViewer {
+        channel_id: channel_id.clone(),
+        messages: None,
+        selection_index: 0,
+    });
+
+    let api_client = state.api_client.clone();
+    let tx_action = commands.sender();
+    commands.spawn(async move {
+        let result = api_client
+            .get_pinned_messages(&channel_id)
+            .await
+            .map_err(|e| e.to_string());
+        tx_action
+            .send(AppAction::ApiUpdatePins(channel_id, result))
+            .await
+            .ok();
+    });
+}
+
+/// Loads the messages around a pinned or found one, for the chat to scroll to it.
+pub(super) fn jump_to_message(
+    state: &mut App,
+    commands: &mut Commands,
+    channel_id: String,
+    message_id: String,
+) {
+    const AROUND_LIMIT: usize = 50;
+
+    state.status_message = "Loading the message...".to_string();
+
+    let api_client = state.api_client.clone();
+    let tx_action = commands.sender();
+    commands.spawn(async move {
+        match api_client
+            .get_channel_messages(
+                &channel_id,
+                Some(message_id.clone()),
+                None,
+                None,
+                Some(AROUND_LIMIT),
+            )
+            .await
+        {
+            Ok(messages) => {
+                tx_action
+                    .send(AppAction::ApiJumpToMessage(message_id, messages))
+                    .await
+                    .ok();
+            }
+            Err(e) => {
+                tx_action
+                    .send(AppAction::ApiError(format!(
+                        "Failed to load the message: {e}"
+                    )))
+                    .await
+                    .ok();
+            }
+        }
+    });
+}
+
+/// Handles an action while the pinned messages are shown, like the mentions overlay does.
+pub(super) fn handle_pin_keys(
+    state: &mut App,
+    action: AppAction,
+    commands: &mut Commands,
+) -> Option<AppAction> {
+    let pins = state.pins.as_mut()?;
+    let len = pins.messages.as_ref().map_or(0, Vec::len);
+
+    match action {
+        AppAction::SelectNext if len > 0 => {
+            pins.selection_index = (pins.selection_index + 1).min(len - 1);
+        }
+        AppAction::SelectPrevious => {
+            pins.selection_index = pins.selection_index.saturating_sub(1);
+        }
+        AppAction::InputEscape | AppAction::TogglePins => {
+            state.pins = None;
+        }
+        AppAction::InputSubmit => {
+            let pin = pins.messages.as_ref()?.get(pins.selection_index)?.clone();
+            state.pins = None;
+            jump_to_message(state, commands, pin.channel_id, pin.id);
+        }
+        AppAction::SelectNext
+        | AppAction::InputChar(_)
+        | AppAction::InputBackspace
+        | AppAction::InputTab
+        | AppAction::Paste(_)
+        | AppAction::InputMoveCursor(_)
+        | AppAction::InputDeleteWord
+        | AppAction::InputClearToStart
+        | AppAction::SelectMessage
+        | AppAction::DeleteMessage
+        | AppAction::SelectEmoji
+        | AppAction::LoadArchivedThreads
+        | AppAction::ToggleMemberList
+        | AppAction::ScrollPageUp
+        | AppAction::ScrollPageDown
+        | AppAction::ScrollLineUp
+        | AppAction::ScrollLineDown => {}
+        action => return Some(action),
+    }
+
+    None
+}
+
+/// Removes the message awaiting confirmation, restoring it if Discord refuses the deletion.
+pub(super) fn confirm_delete(state: &mut App, commands: &mut Commands) {
+    let Some(message_id) = state.pending_delete.take() else {
+        return;
+    };
+    let Some(index) = state.messages.iter().position(|m| m.id == message_id) else {
+        return;
+    };
+
+    let message = state.messages.remove(index);
+    let channel_id = message.channel_id.clone();
+
+    if state.messages.is_empty() {
+        state.state = AppState::Chatting(channel_id.clone());
+        state.selection_index = 0;
+    } else {
+        state.selection_index = state.selection_index.min(state.messages.len() - 1);
+    }
+    state.status_message = "Message deleted.".to_string();
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.track(async move {
+        if let Err(e) = api_client_clone
+            .delete_message(&channel_id, &message.id)
+            .await
+        {
+            let failure = format!("Failed to delete message: {e}");
+            tx_clone
+                .send(AppAction::ApiError(failure.clone()))
+                .await
+                .ok();
+            tx_clone
+                .send(AppAction::ApiRestoreMessage(message))
+                .await
+                .ok();
+            return Err(failure);
+        }
+        Ok(())
+    });
+}
+
+/// Asks to publish the selected message, if it is in an announcement channel and we may.
+/// Our own messages can be published without Manage Messages.
+fn request_publish(state: &mut App) {
+    let Some(message) = state.messages.get(state.selection_index) else {
+        return;
+    };
+    let channel = state
+        .chat_guild_id
+        .as_deref()
+        .and_then(|guild_id| known_channel(state, guild_id, &message.channel_id));
+    let may_publish = message.author.id == state.current_user.id
+        || channel
+            .zip(state.context.as_ref())
+            .is_some_and(|(channel, context)| channel.can_manage_messages(context));
+
+    let (status, message_id) = if !channel.is_some_and(Channel::is_announcement) {
+        (
+            "Only messages of announcement channels can be published.".to_string(),
+            None,
+        )
+    } else if message.is_crossposted() {
+        ("This message is already published.".to_string(), None)
+    } else if !may_publish {
+        (
+            "Publishing the messages of others needs Manage Messages.".to_string(),
+            None,
+        )
+    } else {
+        (
+            format!(
+                "Publish this message from {} to the following servers? (y/n)",
+                message.author.username
+            ),
+            Some(message.id.clone()),
+        )
+    };
+    state.status_message = status;
+    state.pending_publish = message_id;
+}
+
+/// Publishes a message of the open announcement channel, once confirmed.
+fn publish_message(state: &mut App, commands: &mut Commands, id: String) {
+    let Some(channel_id) = chat_channel_id(state).cloned() else {
+        return;
+    };
+    state.status_message = "Publishing...".to_string();
+
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.track(async move {
+        let failure = match api_client_clone.crosspost_message(&channel_id, &id).await {
+            Ok(message) => {
+                tx_clone.send(AppAction::ApiPublished(message)).await.ok();
+                return Ok(());
+            }
+            // Discord only allows a few publications per channel and hour.
+            Err(ApiError::RateLimited { retry_after }) => format!(
+                "Publishing is rate limited in this channel, try again in {} minutes.",
+                (retry_after / 60.0).ceil()
+            ),
+            Err(e) => format!("Failed to publish the message: {e}"),
+        };
+        tx_clone
+            .send(AppAction::ApiError(failure.clone()))
+            .await
+            .ok();
+        Err(failure)
+    });
+}
+
+/// Resolves a shortcode typed in the reaction prompt to the emoji's API name.
+fn resolve_reaction_emoji(state: &App, shortcode: &str) -> Option<String> {
+    let name = shortcode.trim().trim_matches(':');
+    if name.is_empty() {
+        return None;
+    }
+
+    if let Some((_, emoji)) = state.emoji_map.iter().find(|(n, _)| n == name) {
+        return Some(emoji.clone());
+    }
+    if let Some(emoji) = state.custom_emojis.iter().find(|e| e.name == name) {
+        return Some(format!("{}:{}", emoji.name, emoji.id));
+    }
+
+    // Allow an emoji character typed or pasted directly.
+    (!name.is_ascii()).then(|| name.to_string())
+}
+
+/// Reacts to the highlighted message with the prompted emoji, or removes our reaction if
+/// we already reacted with it.
+pub(super) fn toggle_reaction(state: &mut App, commands: &mut Commands) {
+    let Some(shortcode) = state.reaction_input.take() else {
+        return;
+    };
+    let Some(emoji) = resolve_reaction_emoji(state, &shortcode) else {
+        state.status_message = format!("Unknown emoji :{}:", shortcode.trim_matches(':'));
+        return;
+    };
+
+    let index = state.selection_index;
+    let Some(message) = state.messages.get_mut(index) else {
+        return;
+    };
+
+    let existing = message
+        .reactions
+        .iter()
+        .position(|r| r.emoji.api_name() == emoji);
+
+    let remove = match existing {
+        Some(i) if message.reactions[i].me => {
+            message.reactions[i].me = false;
+            message.reactions[i].count = message.reactions[i].count.saturating_sub(1);
+            if message.reactions[i].count == 0 {
+                message.reactions.remove(i);
+            }
+            true
+        }
+        Some(i) => {
+            message.reactions[i].me = true;
+            message.reactions[i].count += 1;
+            false
+        }
+        None => {
+            message.reactions.push(Reaction {
+                count: 1,
+                me: true,
+                emoji: ReactionEmoji::from_api_name(&emoji),
+            });
+            false
+        }
+    };
+
+    let channel_id = message.channel_id.clone();
+    let message_id = message.id.clone();
+    let api_client_clone = state.api_client.clone();
+    let tx_clone = commands.sender();
+    state.invalidate_rows();
+
+    state.status_message = if remove {
+        "Reaction removed.".to_string()
+    } else {
+        "Reaction added.".to_string()
+    };
+
+    commands.track(async move {
+        let result = if remove {
+            api_client_clone
+                .delete_own_reaction(&channel_id, &message_id, &emoji)
+                .await
+        } else {
+            api_client_clone
+                .create_reaction(&channel_id, &message_id, &emoji)
+                .await
+        };
+
+        if let Err(e) = result {
+            let failure = format!("Failed to update reaction: {e}");
+            tx_clone
+                .send(AppAction::ApiError(failure.clone()))
+                .await
+                .ok();
+            return Err(failure);
+        }
+        Ok(())
+    });
+}
+
+/// Previews the highlighted message's first image attachment, or shows the URL of
+/// its first attachment when none is an image.
+fn open_attachment(state: &mut App, commands: &mut Commands) {
+    let Some(message) = state.messages.get(state.selection_index) else {
+        return;
+    };
+
+    let image = message.attachments.iter().find(|a| {
+        a.content_type
+            .as_deref()
+            .is_some_and(|t| t.starts_with("image/"))
+    });
+
+    let Some(attachment) = image.cloned() else {
+        state.status_message = match message.attachments.first() {
+            Some(attachment) => format!("{}: {}", attachment.filename, attachment.url),
+            None => "This message has no attachments.".to_string(),
+        };
+        return;
+    };
+
+    state.preview_loading = true;
+    state.status_message = format!("Loading preview of {}...", attachment.filename);
+
+    let http_client = state.api_client.http_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let result = async {
+            let bytes = http_client
+                .get(&attachment.url)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+            let image =
+                tokio::task::spawn_blocking(move || image::load_from_memory(&bytes)).await??;
+            Ok::<_, Error>(ImagePreview::new(attachment.filename, image))
+        }
+        .await;
+
+        tx_clone
+            .send(AppAction::PreviewLoaded(result.map_err(|e| e.to_string())))
+            .await
+            .ok();
+    });
+}
+
+/// The help shown while selecting a message. `t` is only offered with a translation
+/// backend configured.
+pub(super) fn selection_help(state: &App) -> String {
+    let translate = if state.translation.is_some() {
+        " t to translate,"
+    } else {
+        ""
+    };
+    format!(
+        "Select a message. Up/Down to move, Enter to reply, o to open, s to reveal spoilers, y to copy, q to quote,{translate} i for the author's profile, d to DM them, x to ignore them, ? for every key, Esc to cancel."
+    )
+}
+
+/// Translates the selected message in the background. A translation is only fetched once
+/// per message and session.
+fn translate_selected(state: &mut App, commands: &mut Commands) {
+    let Some(config) = state.translation.clone() else {
+        return;
+    };
+    let Some(message) = state.messages.get(state.selection_index) else {
+        return;
+    };
+    let content = message.content.clone().unwrap_or_default();
+    if content.trim().is_empty() || state.translations.contains_key(&message.id) {
+        return;
+    }
+
+    let message_id = message.id.clone();
+    state.translations.insert(message_id.clone(), None);
+    state.invalidate_rows();
+    let http_client = state.api_client.http_client.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        let result = translate::translate(&http_client, &config, &content).await;
+        tx_clone
+            .send(AppAction::Translated(message_id, result))
+            .await
+            .ok();
+    });
+}
+
+pub(super) fn handle_message_selection_keys(state: &mut App, c: char, commands: &mut Commands) {
+    if state.pending_delete.is_some() {
+        if c == 'y' || c == 'Y' {
+            confirm_delete(state, commands);
+        } else {
+            state.pending_delete = None;
+            state.status_message = "Deletion cancelled.".to_string();
+        }
+        return;
+    }
+
+    if let Some(quote) = state.pending_quote.take() {
+        if c == 'y' || c == 'Y' {
+            start_quote(state, quote);
+        } else {
+            state.status_message = "Kept the draft.".to_string();
+        }
+        return;
+    }
+
+    if let Some(message_id) = state.pending_publish.take() {
+        if c == 'y' || c == 'Y' {
+            publish_message(state, commands, message_id);
+        } else {
+            state.status_message = "Not published.".to_string();
+        }
+        return;
+    }
+
+    if let Some(user) = state.pending_ignore.take() {
+        if c == 'y' || c == 'Y' {
+            state.status_message = format!(
+                "Ignoring {}. Their messages are collapsed; /ignore lists ignored users.",
+                user.name
+            );
+            state.ignored.push(user);
+            store_ignored(state, commands);
+        } else {
+            state.status_message = "Not ignored.".to_string();
+        }
+        return;
+    }
+
+    if let Some(reaction) = &mut state.reaction_input {
+        reaction.push(c);
+        let prompt = format!("React with :{reaction}");
+        state.status_message = prompt;
+        return;
+    }
+
+    let Some(key) = keymap::selection_key(c) else {
+        return;
+    };
+    match key {
+        SelectionKey::OpenAttachment => {
+            open_attachment(state, commands);
+        }
+        SelectionKey::ToggleSpoiler => {
+            let Some((id, author_id)) = state
+                .messages
+                .get(state.selection_index)
+                .map(|m| (m.id.clone(), m.author.id.clone()))
+            else {
+                return;
+            };
+            // Messages of ignored users are revealed first, their spoilers then still
+            // hidden.
+            let revealed = if state.is_ignored(&author_id) {
+                &mut state.revealed_ignored
+            } else {
+                &mut state.revealed_spoilers
+            };
+            if !revealed.remove(&id) {
+                revealed.insert(id);
+            }
+            state.invalidate_rows();
+        }
+        SelectionKey::React => {
+            state.reaction_input = Some(String::new());
+            state.status_message =
+                "React with an emoji shortcode. Enter to toggle, Esc to cancel.".to_string();
+        }
+        SelectionKey::Copy | SelectionKey::CopyWithHeader => {
+            if let Some(message) = state.messages.get(state.selection_index) {
+                let text = if key == SelectionKey::CopyWithHeader {
+                    message_header_line(state, message)
+                } else {
+                    message.content.clone().unwrap_or_default()
+                };
+                match clipboard::copy(&text) {
+                    Ok(()) => state.status_message = "Copied the message.".to_string(),
+                    Err(e) => report_error(state, format!("Failed to copy: {e}")),
+                }
+            }
+        }
+        SelectionKey::Quote => {
+            let Some(message) = state.messages.get(state.selection_index) else {
+                return;
+            };
+            let content = message.content.as_deref().unwrap_or_default();
+            if content.trim().is_empty() {
+                state.status_message = "This message has no text to quote.".to_string();
+                return;
+            }
+            let quote = quote_text(content);
+            if state.input.trim().is_empty() {
+                start_quote(state, quote);
+            } else {
+                state.pending_quote = Some(quote);
+                state.status_message = "Replace the draft with the quote? (y/n)".to_string();
+            }
+        }
+        SelectionKey::Translate => translate_selected(state, commands),
+        SelectionKey::Profile => {
+            if let Some(author) = state
+                .messages
+                .get(state.selection_index)
+                .map(|m| m.author.clone())
+            {
+                open_profile(state, commands, author);
+            }
+        }
+        SelectionKey::DmAuthor => {
+            if let Some(author) = state
+                .messages
+                .get(state.selection_index)
+                .map(|m| m.author.clone())
+            {
+                open_dm(state, commands, author);
+            }
+        }
+        SelectionKey::Ignore => {
+            let Some(author) = state
+                .messages
+                .get(state.selection_index)
+                .map(|m| m.author.clone())
+            else {
+                return;
+            };
+            if author.id == state.current_user.id {
+                state.status_message = "You can't ignore yourself.".to_string();
+            } else if state.is_ignored(&author.id) {
+                state.status_message = format!(
+                    "{} is already ignored. /ignore lists ignored users.",
+                    author.username
+                );
+            } else {
+                state.status_message = format!(
+                    "Ignore {}? Their messages will be collapsed. (y/n)",
+                    author.username
+                );
+                state.pending_ignore = Some(IgnoredUser {
+                    id: author.id,
+                    name: author.username,
+                });
+            }
+        }
+        SelectionKey::Publish => request_publish(state),
+        SelectionKey::Previous => {
+            commands.dispatch(AppAction::SelectPrevious);
+        }
+        SelectionKey::Next => {
+            commands.dispatch(AppAction::SelectNext);
+        }
+        SelectionKey::Help => {
+            commands.dispatch(AppAction::ToggleKeyHelp);
+        }
+    }
+}
@@ -0,0 +1,751 @@
+//! Enter, and the slash commands typed into the input.
+
+use super::{
+    App, AppAction, AppState, CHAT_HELP, Commands, KeywordAction, QueuedMessage, Window,
+    chat::{
+        channel_guild, chat_channel_id, deliver, flush_outbox, known_channel, open_channel,
+        store_drafts, unconfirmed_pings,
+    },
+    lists::{enter_guild, load_guild_cache, mark_guild_opened},
+    overlays::{open_forum, open_search_prompt, open_webhook_picker, preview_invite, start_search},
+    selection::{jump_to_message, toggle_reaction},
+};
+use crate::{
+    api::{
+        ApiError, Channel, DM, Message, TokenType,
+        message::{MAX_MESSAGE_LENGTH, message_length},
+    },
+    config::{self, PinKind},
+    ui::{
+        aliases::AliasList,
+        commands::{AliasChange, Command, StatusChange, parse_command},
+        filter::{filter_guilds, fuzzy_match},
+        ignore::IgnoreList,
+        links::GotoTarget,
+        presence::presence_label,
+        shortcodes::expand_shortcodes,
+    },
+};
+
+/// Runs a command typed in the chat, other than those sending a message.
+fn run_command(
+    state: &mut App,
+    commands: &mut Commands,
+    command: Command,
+) -> Option<KeywordAction> {
+    state.input.clear();
+    state.cursor_position = 0;
+    state.status_message = CHAT_HELP.to_string();
+
+    match command {
+        Command::Help => state.show_command_help = true,
+        Command::Quit => return Some(KeywordAction::Break),
+        Command::Join(name) => join_channel(state, commands, &name),
+        Command::Goto(target) => goto(state, commands, target),
+        Command::JoinInvite(code) => preview_invite(state, commands, code),
+        Command::Search(query) => {
+            open_search_prompt(state);
+            state.input = query;
+            start_search(state, commands);
+        }
+        Command::Clear => {
+            state.messages.clear();
+            state.scroll_offset = 0;
+            state.chat_at_top = false;
+            state.status_message = "Cleared the loaded messages.".to_string();
+        }
+        Command::Webhook(username, avatar_url) => {
+            open_webhook_picker(state, commands, username, avatar_url);
+        }
+        Command::Status(change) => set_presence(state, commands, change),
+        Command::Ignore => {
+            state.ignore_list = Some(IgnoreList::default());
+            state.status_message = if state.ignored.is_empty() {
+                "Nobody is ignored.".to_string()
+            } else {
+                format!("{} ignored users.", state.ignored.len())
+            };
+        }
+        Command::Alias(AliasChange::Set(user_id, alias)) => {
+            state.status_message = format!("{user_id} is now shown as {alias}.");
+            state.aliases.insert(user_id, alias);
+            state.invalidate_rows();
+            store_aliases(state, commands);
+        }
+        Command::Alias(AliasChange::Remove(user_id)) => {
+            if state.aliases.remove(&user_id).is_some() {
+                state.invalidate_rows();
+                state.status_message = format!("Removed the alias of {user_id}.");
+                store_aliases(state, commands);
+            } else {
+                state.status_message = format!("{user_id} has no alias.");
+            }
+        }
+        Command::Aliases => {
+            state.alias_list = Some(AliasList::default());
+            state.status_message = format!("{} aliases.", state.aliases.len());
+        }
+        Command::WebhookOff => {
+            state.status_message = match state.webhook.take() {
+                Some(active) => format!(
+                    "Webhook mode off, sending as yourself instead of {}.",
+                    active.username
+                ),
+                None => "Webhook mode is not on.".to_string(),
+            };
+        }
+        Command::Send(_) => {}
+    }
+    None
+}
+
+/// Splits the emoji a custom status starts with, as a `:shortcode:` or typed as is, from
+/// its text.
+fn split_status_emoji(state: &App, text: &str) -> (Option<String>, Option<String>) {
+    let (first, rest) = text
+        .split_once(char::is_whitespace)
+        .map_or((text, ""), |(first, rest)| (first, rest.trim()));
+    let shortcode = first
+        .strip_prefix(':')
+        .and_then(|name| name.strip_suffix(':'))
+        .and_then(|name| state.emoji_map.iter().find(|(n, _)| n == name))
+        .map(|(_, emoji)| emoji.clone());
+    let typed =
+        (!first.is_ascii() && !first.chars().any(char::is_alphanumeric)).then(|| first.to_string());
+
+    match shortcode.or(typed) {
+        Some(emoji) => (Some(emoji), (!rest.is_empty()).then(|| rest.to_string())),
+        None => (None, Some(text.to_string())),
+    }
+}
+
+/// Changes our presence for `/status`: through the gateway when it is up, which also
+/// sends it again after reconnecting, or else through the account settings.
+fn set_presence(state: &mut App, commands: &mut Commands, change: StatusChange) {
+    let mut presence = state.presence.clone().unwrap_or_default();
+    match change {
+        StatusChange::Set(status, text) => {
+            presence.status = status;
+            if let Some(text) = text {
+                (presence.emoji, presence.text) = split_status_emoji(state, &text);
+            }
+        }
+        StatusChange::ClearText => {
+            presence.text = None;
+            presence.emoji = None;
+        }
+    }
+    if let Err(e) = presence.validate() {
+        state.status_message = e;
+        return;
+    }
+
+    state.status_message = format!("Status set to {}.", presence_label(&presence));
+    state.presence = Some(presence.clone());
+    state.presence_tx.send_replace(Some(presence.clone()));
+
+    if state.gateway_connected {
+        return;
+    }
+    if state.api_client.token_type != TokenType::User {
+        state.status_message = format!(
+            "{} Bots set it through the gateway, once connected.",
+            state.status_message
+        );
+        return;
+    }
+    let api_client_clone = state.api_client.clone();
+    commands.track(async move {
+        api_client_clone
+            .update_presence_settings(&presence)
+            .await
+            .map_err(|e| format!("Failed to set the status: {e}"))
+    });
+}
+
+/// Saves the ignored users in the background.
+pub(super) fn store_ignored(state: &App, commands: &mut Commands) {
+    let ignored = state.ignored.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_ignored(ignored).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save the ignored users: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+pub(super) fn store_aliases(state: &App, commands: &mut Commands) {
+    let aliases = state.aliases.clone();
+    let tx_clone = commands.sender();
+
+    commands.spawn(async move {
+        if let Err(e) = config::store_aliases(aliases).await {
+            tx_clone
+                .send(AppAction::ApiError(format!(
+                    "Failed to save the aliases: {e}"
+                )))
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Opens the text channel of the current guild best matching `name`: the one named so,
+/// or else the first one whose name fuzzy-matches it.
+fn join_channel(state: &mut App, commands: &mut Commands, name: &str) {
+    let Some(guild_id) = state.chat_guild_id.clone() else {
+        state.status_message = "/join only works in a server channel".to_string();
+        return;
+    };
+
+    let channels = state
+        .channel_cache
+        .get(&guild_id)
+        .unwrap_or(&state.channels);
+    let context = state
+        .context_cache
+        .get(&guild_id)
+        .or(state.context.as_ref());
+    let candidates: Vec<&Channel> = channels
+        .iter()
+        .chain(channels.iter().flat_map(|c| c.children.iter().flatten()))
+        .filter(|c| c.is_text() && !c.is_thread())
+        .filter(|c| context.is_none_or(|context| c.is_readable(context)))
+        .collect();
+    let name = name.trim_start_matches('#');
+    let found = candidates
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(name))
+        .or_else(|| candidates.iter().find(|c| fuzzy_match(name, &c.name)))
+        .map(|c| (c.id.clone(), c.name.clone()));
+
+    match found {
+        Some((channel_id, channel_name)) => {
+            open_channel(state, commands, channel_id, &channel_name, Some(guild_id));
+        }
+        None => state.status_message = format!("No channel matching \"{name}\""),
+    }
+}
+
+/// Opens the channel `/goto` or a link number points at. A bare id that is no known
+/// channel is taken as a message of the open channel. With a message, the channel's
+/// messages around it are loaded and it is selected.
+pub(super) fn goto(state: &mut App, commands: &mut Commands, target: GotoTarget) {
+    let (channel_id, message_id, guild_id) = match target {
+        GotoTarget::Link(link) => (link.channel_id, link.message_id, link.guild_id),
+        GotoTarget::Id(id) => {
+            let is_channel =
+                channel_guild(state, &id).is_some() || state.dms.iter().any(|dm| dm.id == id);
+            match chat_channel_id(state) {
+                _ if is_channel => (id, None, None),
+                Some(channel_id) => (channel_id.clone(), Some(id), None),
+                None => {
+                    state.status_message = format!("No known channel with the id {id}.");
+                    return;
+                }
+            }
+        }
+    };
+    let guild_id = guild_id
+        .or_else(|| channel_guild(state, &channel_id))
+        .or_else(|| {
+            // An open guild channel isn't always in the cache yet.
+            (chat_channel_id(state) == Some(&channel_id))
+                .then(|| state.chat_guild_id.clone())
+                .flatten()
+        });
+
+    if chat_channel_id(state) == Some(&channel_id) {
+        match message_id {
+            Some(message_id) => jump_to_message(state, commands, channel_id, message_id),
+            None => state.status_message = "Already in this channel.".to_string(),
+        }
+        return;
+    }
+
+    let name = match &guild_id {
+        Some(guild_id) => known_channel(state, guild_id, &channel_id).map(|c| c.name.clone()),
+        None => state
+            .dms
+            .iter()
+            .find(|dm| dm.id == channel_id)
+            .map(|dm| dm.get_name()),
+    }
+    .unwrap_or_else(|| channel_id.clone());
+
+    if let Some(guild_id) = &guild_id {
+        enter_guild(state, commands, guild_id);
+    }
+
+    open_channel(state, commands, channel_id.clone(), &name, guild_id);
+    if let Some(message_id) = message_id
+        && state.nsfw_prompt.is_none()
+    {
+        state.pending_jump = Some((channel_id, message_id));
+    }
+}
+
+pub(super) fn input_submit(state: &mut App, commands: &mut Commands) -> Option<KeywordAction> {
+    match &state.state.clone() {
+        AppState::Loading(_) => {}
+        AppState::Home => match state.selection_index {
+            0 => {
+                commands.dispatch(AppAction::TransitionToGuilds);
+            }
+            1 => {
+                commands.dispatch(AppAction::TransitionToDM);
+            }
+            2 => {
+                return Some(KeywordAction::Break);
+            }
+            _ => {}
+        },
+        AppState::SelectingDM => {
+            let dms: Vec<&DM> = state
+                .dms
+                .iter()
+                .filter(|d| {
+                    d.get_name()
+                        .to_lowercase()
+                        .contains(&state.input.to_lowercase())
+                })
+                .collect();
+
+            if dms.is_empty() {
+                return Some(KeywordAction::Continue);
+            }
+
+            let selected_dm = &dms[state.selection_index];
+            let dm_id_clone = selected_dm.id.clone();
+            let selected_dm_name = selected_dm.get_name();
+
+            open_channel(state, commands, dm_id_clone, &selected_dm_name, None);
+        }
+        AppState::SelectingGuild => {
+            let guilds = filter_guilds(
+                &state.guilds,
+                &state.input,
+                state.guild_sort,
+                &state.guilds_opened,
+                &state.muted,
+                state.pinned.ids(PinKind::Guild),
+            );
+
+            let Some(selected_guild) = guilds.get(state.selection_index) else {
+                return Some(KeywordAction::Continue);
+            };
+            let guild_id_clone = selected_guild.id.clone();
+            let selected_guild_name = selected_guild.name.clone();
+            mark_guild_opened(state, commands, &guild_id_clone);
+
+            let tx_clone = commands.sender();
+
+            state.status_message = format!("Loading channels for {selected_guild_name}...");
+
+            let api_client_clone = state.api_client.clone();
+            let cached_emojis = state.emoji_cache.get(&guild_id_clone).cloned();
+            let current_user_id = state.current_user.id.clone();
+
+            // Channels known from before show up at once and are refreshed behind them.
+            let from_cache = load_guild_cache(state, &guild_id_clone);
+            if from_cache {
+                commands.dispatch(AppAction::TransitionToChannels(guild_id_clone.clone()));
+            }
+
+            commands.spawn(async move {
+                if !from_cache {
+                    tx_clone
+                        .send(AppAction::TransitionToLoading(Window::Channel(
+                            guild_id_clone.clone(),
+                        )))
+                        .await
+                        .ok();
+                }
+                // The context must be in place before the channels arrive, otherwise the
+                // list briefly renders with every channel treated as unreadable.
+                match api_client_clone
+                    .get_permission_context(&guild_id_clone, &current_user_id)
+                    .await
+                {
+                    Ok(context) => {
+                        tx_clone
+                            .send(AppAction::ApiUpdateContext(Some(context)))
+                            .await
+                            .ok();
+                    }
+                    Err(e) => {
+                        tx_clone
+                            .send(AppAction::ApiError(format!(
+                                "Failed to load permission context: {e}"
+                            )))
+                            .await
+                            .ok();
+                    }
+                }
+                match api_client_clone.get_guild_channels(&guild_id_clone).await {
+                    Ok(channels) => {
+                        tx_clone
+                            .send(AppAction::ApiUpdateChannel(channels))
+                            .await
+                            .ok();
+                    }
+                    Err(e) => {
+                        tx_clone
+                            .send(AppAction::ApiError(format!("Failed to load channels: {e}")))
+                            .await
+                            .ok();
+                    }
+                }
+                match api_client_clone
+                    .get_active_guild_threads(&guild_id_clone)
+                    .await
+                {
+                    Ok(list) => {
+                        tx_clone
+                            .send(AppAction::ApiUpdateThreads(
+                                guild_id_clone.clone(),
+                                list.threads,
+                                false,
+                            ))
+                            .await
+                            .ok();
+                    }
+                    Err(e) => {
+                        tx_clone
+                            .send(AppAction::ApiError(format!("Failed to load threads: {e}")))
+                            .await
+                            .ok();
+                    }
+                }
+                let emojis = match cached_emojis {
+                    Some(emojis) => Ok(emojis),
+                    None => api_client_clone.get_guild_emojis(&guild_id_clone).await,
+                };
+                match emojis {
+                    Ok(emojis) => {
+                        tx_clone
+                            .send(AppAction::ApiUpdateEmojis(guild_id_clone.clone(), emojis))
+                            .await
+                            .ok();
+                    }
+                    Err(e) => {
+                        tx_clone
+                            .send(AppAction::ApiError(format!(
+                                "Failed to load custom emojis: {e}"
+                            )))
+                            .await
+                            .ok();
+                    }
+                }
+
+                tx_clone.send(AppAction::EndLoading).await.ok();
+            });
+        }
+        AppState::SelectingChannel(guild_id) => {
+            let rows = state.channel_rows(guild_id);
+
+            let Some(selected_channel) = rows
+                .get(state.selection_index)
+                .filter(|c| c.is_selectable())
+            else {
+                return Some(KeywordAction::Continue);
+            };
+
+            if selected_channel.is_forum() {
+                let forum = (*selected_channel).clone();
+                open_forum(state, commands, guild_id.clone(), &forum);
+                return None;
+            }
+
+            // Voice and stage channels can't be joined from here, only peeked into.
+            if selected_channel.is_voice() {
+                let kind = if selected_channel.channel_type == 13 {
+                    "stage"
+                } else {
+                    "voice channel"
+                };
+                let connected = state
+                    .voice_states
+                    .get(guild_id)
+                    .map(|users| {
+                        users
+                            .values()
+                            .filter(|channel_id| **channel_id == selected_channel.id)
+                            .count()
+                    })
+                    .unwrap_or(0);
+                let message = if state.gateway_connected {
+                    format!(
+                        "{} is a {kind}, {connected} connected. Joining isn't supported.",
+                        selected_channel.name
+                    )
+                } else {
+                    format!(
+                        "{} is a {kind}. Joining isn't supported, and who is connected is unknown while offline.",
+                        selected_channel.name
+                    )
+                };
+                state.status_message = message;
+                return None;
+            }
+
+            let channel_info = (
+                selected_channel.id.clone(),
+                selected_channel.name.clone(),
+                selected_channel.guild_id.clone(),
+            );
+            let (channel_id_clone, selected_channel_name, guild_id) = channel_info;
+
+            open_channel(
+                state,
+                commands,
+                channel_id_clone,
+                &selected_channel_name,
+                guild_id,
+            );
+        }
+        AppState::EmojiSelection(channel_id) => {
+            let start_pos = state.emoji_filter_start?;
+            let end_pos = start_pos + ':'.len_utf8() + state.emoji_filter.len();
+
+            let picked = {
+                let (unicode, custom) = state.emoji_matches();
+                match state.selection_index.checked_sub(unicode.len()) {
+                    None => Some(unicode[state.selection_index].1.clone()),
+                    Some(custom_index) => custom.get(custom_index).map(|emoji| {
+                        let animated = if emoji.animated.unwrap_or(false) {
+                            "a"
+                        } else {
+                            ""
+                        };
+                        format!("<{animated}:{}:{}>", emoji.name, emoji.id)
+                    }),
+                }
+            };
+
+            if let Some(picked) = picked
+                && state.input.is_char_boundary(start_pos)
+                && state.input.is_char_boundary(end_pos)
+            {
+                state.input.drain(start_pos..end_pos);
+                state.input.insert_str(start_pos, &picked);
+                let mut pos = start_pos + picked.len();
+                state.input.insert(pos, ' ');
+                pos += ' '.len_utf8();
+
+                state.cursor_position = pos;
+            }
+
+            state.state = AppState::Chatting(channel_id.clone());
+            state.emoji_filter.clear();
+            state.emoji_filter_start = None;
+            state.selection_index = 0;
+            state.status_message = CHAT_HELP.to_string();
+        }
+        AppState::SelectingMessage(_)
+            if state.pending_delete.is_some()
+                || state.pending_quote.is_some()
+                || state.pending_ignore.is_some()
+                || state.pending_publish.is_some() => {}
+        AppState::SelectingMessage(_) if state.reaction_input.is_some() => {
+            toggle_reaction(state, commands);
+        }
+        AppState::SelectingMessage(channel_id) => {
+            if let Some(message) = state.messages.get(state.selection_index).cloned() {
+                state.status_message = format!(
+                    "Replying to {}. Press Enter to send, Esc to cancel.",
+                    message.author.username
+                );
+                state.replying_to = Some(message);
+            }
+            state.state = AppState::Chatting(channel_id.clone());
+            state.selection_index = 0;
+        }
+        AppState::Chatting(_) => {
+            let ping_override = state.ping_override.take();
+            if state.editing_message.is_none()
+                && let Some(command) = parse_command(&state.input)
+            {
+                match command {
+                    Ok(Command::Send(text)) => state.input = text,
+                    Ok(command) => return run_command(state, commands, command),
+                    Err(e) => {
+                        state.status_message = e;
+                        return None;
+                    }
+                }
+            }
+
+            let expanded = expand_shortcodes(&state.input, &state.emoji_map);
+            let length = message_length(&expanded);
+            if length > MAX_MESSAGE_LENGTH {
+                state.status_message = format!(
+                    "Message too long: {length}/{MAX_MESSAGE_LENGTH} characters. Shorten it to send it."
+                );
+                return None;
+            }
+
+            if state.editing_message.is_none()
+                && !state.input.trim().is_empty()
+                && let AppState::Chatting(channel_id) = &state.state
+            {
+                if let Some(notice) = state.send_blocked.get(channel_id) {
+                    state.status_message = format!("{notice}. Open the channel again to retry.");
+                    return None;
+                }
+                if let Some(left) = state.slowmode_left(channel_id) {
+                    state.status_message = format!(
+                        "Slowmode: you can send again in {}s.",
+                        left.as_secs_f64().ceil()
+                    );
+                    return None;
+                }
+            }
+            state.length_rejected = false;
+
+            if state.editing_message.is_none() && ping_override.is_none() {
+                let pings = unconfirmed_pings(state, &expanded);
+                if !pings.is_empty() {
+                    state.status_message =
+                        "y pings them, n sends without pinging, Esc goes back to editing."
+                            .to_string();
+                    state.pending_pings = Some(pings);
+                    return None;
+                }
+            }
+
+            let channel_id_clone = if let AppState::Chatting(id) = &state.state {
+                Some(id.clone())
+            } else {
+                None
+            };
+
+            let content = state.input.drain(..).collect::<String>();
+            let content = expand_shortcodes(&content, &state.emoji_map);
+            state.history_recall = None;
+            if let Some(channel_id) = &channel_id_clone
+                && state.drafts.remove(channel_id).is_some()
+            {
+                store_drafts(state, commands);
+            }
+            state.cursor_position = 0;
+            state.last_typing_sent = None;
+            let message_reference = state.replying_to.take().map(|m| m.id);
+
+            let message_data = if content.is_empty() || channel_id_clone.is_none() {
+                None
+            } else {
+                channel_id_clone.map(|id| (id, content))
+            };
+
+            if let Some(message_id) = state.editing_message.take() {
+                state.status_message = CHAT_HELP.to_string();
+
+                if let Some((channel_id_clone, content)) = message_data {
+                    let api_client_clone = state.api_client.clone();
+                    let tx_clone = commands.sender();
+
+                    commands.track(async move {
+                        match api_client_clone
+                            .edit_message(&channel_id_clone, &message_id, content)
+                            .await
+                        {
+                            Ok(message) => {
+                                tx_clone
+                                    .send(AppAction::ApiReplaceMessage(message))
+                                    .await
+                                    .ok();
+                                Ok(())
+                            }
+                            Err(e) => {
+                                let failure = format!("Failed to edit message: {e}");
+                                tx_clone
+                                    .send(AppAction::ApiError(failure.clone()))
+                                    .await
+                                    .ok();
+                                Err(failure)
+                            }
+                        }
+                    });
+                }
+            } else if let Some((channel_id_clone, content)) = message_data {
+                let webhook = state
+                    .webhook
+                    .clone()
+                    .filter(|webhook| webhook.channel_id == channel_id_clone);
+                let queued = QueuedMessage {
+                    message: Message {
+                        id: String::new(),
+                        channel_id: channel_id_clone,
+                        guild_id: state.chat_guild_id.clone(),
+                        author: state.current_user.clone(),
+                        content: Some(content),
+                        timestamp: Some(chrono::Utc::now()),
+                        edited_timestamp: None,
+                        referenced_message: None,
+                        reactions: Vec::new(),
+                        attachments: Vec::new(),
+                        embeds: Vec::new(),
+                        mentions: Vec::new(),
+                        sticker_items: Vec::new(),
+                        flags: 0,
+                        message_type: 0,
+                    },
+                    message_reference,
+                    allowed_mentions: ping_override
+                        .unwrap_or_else(|| state.allowed_mentions.clone()),
+                    webhook,
+                };
+
+                if state.offline || !state.outbox.is_empty() {
+                    // Keep the order: nothing overtakes messages still waiting to be sent.
+                    state.outbox.push(queued);
+                    flush_outbox(state, commands);
+                } else {
+                    let api_client_clone = state.api_client.clone();
+                    let tx_clone = commands.sender();
+
+                    commands.track(async move {
+                        match deliver(&api_client_clone, &queued).await {
+                            Ok(_) => {
+                                let message = &queued.message;
+                                tx_clone
+                                    .send(AppAction::MessageSent(
+                                        message.channel_id.clone(),
+                                        message.content.clone().unwrap_or_default(),
+                                    ))
+                                    .await
+                                    .ok();
+                                Ok(())
+                            }
+                            Err(e @ ApiError::Network(_)) => {
+                                let failure = format!("Failed to send message: {e}");
+                                tx_clone
+                                    .send(AppAction::QueueMessage(Box::new(queued)))
+                                    .await
+                                    .ok();
+                                tx_clone.send(AppAction::ConnectionStatus(false)).await.ok();
+                                Err(failure)
+                            }
+                            Err(e) => {
+                                let failure = format!("Failed to send message: {e}");
+                                tx_clone
+                                    .send(AppAction::SendFailed(Box::new(queued), e))
+                                    .await
+                                    .ok();
+                                Err(failure)
+                            }
+                        }
+                    });
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,27 @@
+//! The system clipboard. It is opened on first use and kept open, since on X11 copied
+//! text is only served while the clipboard that copied it is alive.
+
+use std::sync::Mutex;
+
+use arboard::Clipboard;
+
+static CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+fn with_clipboard<T>(
+    f: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>,
+) -> Result<T, String> {
+    let mut guard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        *guard = Some(Clipboard::new().map_err(|e| e.to_string())?);
+    }
+    let clipboard = guard.as_mut().expect("the clipboard was just opened");
+    f(clipboard).map_err(|e| e.to_string())
+}
+
+pub fn copy(text: &str) -> Result<(), String> {
+    with_clipboard(|clipboard| clipboard.set_text(text))
+}
+
+pub fn paste() -> Result<String, String> {
+    with_clipboard(|clipboard| clipboard.get_text())
+}
@@ -1,6 +1,33 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
+use chrono::{
+    DateTime, Utc,
+    format::{Item, StrftimeItems},
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    Error,
+    api::{Channel, Guild, channel::PermissionContext, message::MentionKind, user::OwnPresence},
+};
+
+pub const APP_NAME: &str = "rivetui";
 const DEFAULT_EMOJIS_JSON: &str = include_str!("../emojis.json");
+const READ_STATE_FILE: &str = "read_state.json";
+const LIST_CACHE_FILE: &str = "cache.json";
+const DRAFTS_FILE: &str = "drafts.json";
+const GUILDS_OPENED_FILE: &str = "guilds_opened.json";
+const GUILD_CHANNELS_FILE: &str = "guild_channels.json";
+const MUTED_FILE: &str = "muted.json";
+const IGNORED_FILE: &str = "ignored.json";
+const ALIASES_FILE: &str = "aliases.json";
+const PINNED_FILE: &str = "pinned.json";
+const LAST_CHANNEL_FILE: &str = "last_channel.json";
+const INPUT_HISTORY_FILE: &str = "input_history.json";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
@@ -8,13 +35,289 @@ pub struct Config {
     #[serde(default)]
     pub vim_mode: bool,
     pub emoji_map: Vec<(String, String)>,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    #[serde(default)]
+    pub guild_sort: GuildSort,
+    /// Marks viewed channels as read on Discord too, so other clients stop showing them
+    /// as unread. User accounts only.
+    #[serde(default)]
+    pub sync_read_state: bool,
+    #[serde(default)]
+    pub time_format: TimeFormat,
+    /// How messages are laid out at startup, until switched with F12.
+    #[serde(default)]
+    pub density: Density,
+    /// Captures the mouse for clicking and scrolling, at the cost of the terminal's own
+    /// text selection.
+    #[serde(default)]
+    pub mouse: bool,
+    /// Seconds between two fetches of the open channel while the gateway is down, from 1
+    /// to 60.
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    #[serde(default)]
+    pub resume: ResumeMode,
+    /// Keeps the messages recalled with Alt+Up/Down between sessions, which writes them
+    /// to disk.
+    #[serde(default)]
+    pub persist_input_history: bool,
+    /// What sent messages may ping: any of `users`, `roles` and `everyone`. Mentions of
+    /// anything else ask for confirmation before pinging.
+    #[serde(default = "default_allowed_mentions")]
+    pub allowed_mentions: Vec<MentionKind>,
+    /// Minutes between two fetches of the guild list in the background, or 0 to only
+    /// fetch it at startup.
+    #[serde(default = "default_guild_refresh_interval")]
+    pub guild_refresh_interval: u64,
+    /// Hides spoilers until revealed and asks before opening NSFW channels. Off shows
+    /// everything right away.
+    #[serde(default = "default_content_warnings")]
+    pub content_warnings: bool,
+    /// Where `t` sends messages to translate, in message selection. Without it, messages
+    /// are never sent anywhere but Discord.
+    #[serde(default)]
+    pub translation: Option<TranslationConfig>,
+    /// Status and custom status set when starting, until changed with `/status`.
+    #[serde(default)]
+    pub presence: Option<OwnPresence>,
+    /// Shows mentions and direct messages outside the open channel as desktop
+    /// notifications. Needs a build with the `desktop-notifications` feature.
+    #[serde(default)]
+    pub desktop_notifications: bool,
+    /// Puts the message text in desktop notifications. Off, they only say who wrote where.
+    #[serde(default = "default_notification_content")]
+    pub notification_content: bool,
+}
+
+/// How the translation backend is called.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationBackend {
+    /// `POST` of LibreTranslate's JSON to `url`, such as `https://libretranslate.com/translate`.
+    #[default]
+    LibreTranslate,
+    /// `GET` of `url` with `{text}`, `{target}` and `{key}` filled in, answering with the
+    /// bare translation.
+    Template,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TranslationConfig {
+    #[serde(default)]
+    pub backend: TranslationBackend,
+    pub url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Language code to translate to.
+    #[serde(default = "default_translation_target")]
+    pub target: String,
+}
+
+fn default_translation_target() -> String {
+    "en".to_string()
+}
+
+fn default_content_warnings() -> bool {
+    true
+}
+
+fn default_notification_content() -> bool {
+    true
+}
+
+fn default_guild_refresh_interval() -> u64 {
+    5
+}
+
+fn default_allowed_mentions() -> Vec<MentionKind> {
+    vec![MentionKind::Users]
+}
+
+fn default_poll_interval() -> u64 {
+    1
+}
+
+/// Order of the guild list: as the API returns it, by name, or most recently opened first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GuildSort {
+    #[default]
+    Api,
+    Alphabetical,
+    Recent,
+}
+
+impl GuildSort {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Api => Self::Alphabetical,
+            Self::Alphabetical => Self::Recent,
+            Self::Recent => Self::Api,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Api => "Discord order",
+            Self::Alphabetical => "A-Z",
+            Self::Recent => "Recent",
+        }
+    }
+}
+
+/// How the chat lays out messages: `compact` puts the time and author on the first line
+/// of each group, `cozy` gives each group a header line of its own with the messages
+/// indented below it, like Discord.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    #[default]
+    Compact,
+    Cozy,
+}
+
+impl Density {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Compact => Self::Cozy,
+            Self::Cozy => Self::Compact,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Compact => "compact",
+            Self::Cozy => "cozy",
+        }
+    }
+}
+
+/// Whether the channel open when rivetui was last closed is opened again on startup,
+/// or offered with a y/n prompt.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResumeMode {
+    Always,
+    #[default]
+    Ask,
+    Never,
+}
+
+/// How message times are shown: `"HH:MM"`, `"HH:MM:SS"`, `"relative"` ("2m ago"), or any
+/// other string as a strftime format such as `"%d/%m %H:%M"`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum TimeFormat {
+    Short,
+    #[default]
+    Long,
+    Relative,
+    Custom(String),
+}
+
+impl From<String> for TimeFormat {
+    fn from(format: String) -> Self {
+        match format.as_str() {
+            "HH:MM" => Self::Short,
+            "HH:MM:SS" => Self::Long,
+            "relative" => Self::Relative,
+            _ => Self::Custom(format),
+        }
+    }
+}
+
+impl From<TimeFormat> for String {
+    fn from(format: TimeFormat) -> Self {
+        match format {
+            TimeFormat::Short => "HH:MM".to_string(),
+            TimeFormat::Long => "HH:MM:SS".to_string(),
+            TimeFormat::Relative => "relative".to_string(),
+            TimeFormat::Custom(format) => format,
+        }
+    }
+}
+
+impl TimeFormat {
+    /// Refuses custom formats chrono can't render, as rendering them would panic.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Custom(format)
+                if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) =>
+            {
+                Err(format!("invalid time format \"{format}\""))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The format after this one, going through `custom` too when one is configured.
+    pub fn next(&self, custom: Option<&str>) -> Self {
+        match (self, custom) {
+            (Self::Short, _) => Self::Long,
+            (Self::Long, _) => Self::Relative,
+            (Self::Relative, Some(custom)) => Self::Custom(custom.to_string()),
+            (Self::Relative, None) | (Self::Custom(_), _) => Self::Short,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Short => "HH:MM",
+            Self::Long => "HH:MM:SS",
+            Self::Relative => "relative",
+            Self::Custom(format) => format,
+        }
+    }
+}
+
+/// Colors of the UI. `preset` picks a built-in theme ("default" or "solarized"), and any
+/// color set here overrides it. Colors are `#rrggbb` or one of the 16 ANSI names.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub preset: String,
+    pub selection: Option<String>,
+    pub border: Option<String>,
+    pub author: Option<String>,
+    pub timestamp: Option<String>,
+    pub mention_background: Option<String>,
+    pub status: Option<String>,
+    pub pending: Option<String>,
+    pub heading: Option<String>,
+    pub accent: Option<String>,
+    /// Colors authors are told apart by, each user always getting the same one. An empty
+    /// list colors every author with `author`.
+    pub author_palette: Option<Vec<String>>,
+    /// Whether the terminal shows `#rrggbb` colors, or only 256 colors they are brought
+    /// down to. Guessed from `COLORTERM` when unset.
+    pub truecolor: Option<bool>,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            preset: "default".to_string(),
+            selection: None,
+            border: None,
+            author: None,
+            timestamp: None,
+            mention_background: None,
+            status: None,
+            pending: None,
+            heading: None,
+            accent: None,
+            author_palette: None,
+            truecolor: None,
+        }
+    }
 }
 
 fn load_emojis() -> Vec<(String, String)> {
     match serde_json::from_str::<Vec<(String, String)>>(DEFAULT_EMOJIS_JSON) {
         Ok(map) => map,
         Err(e) => {
-            eprintln!("Error parsing emojis dictionary: {e}");
+            tracing::error!("Error parsing emojis dictionary: {e}");
             Vec::new()
         }
     }
@@ -26,25 +329,314 @@ impl Default for Config {
             version: 1,
             vim_mode: true,
             emoji_map: Vec::new(),
+            theme: ThemeConfig::default(),
+            guild_sort: GuildSort::default(),
+            sync_read_state: false,
+            time_format: TimeFormat::default(),
+            density: Density::default(),
+            mouse: false,
+            poll_interval: default_poll_interval(),
+            resume: ResumeMode::default(),
+            persist_input_history: false,
+            allowed_mentions: default_allowed_mentions(),
+            guild_refresh_interval: default_guild_refresh_interval(),
+            content_warnings: default_content_warnings(),
+            translation: None,
+            presence: None,
+            desktop_notifications: false,
+            notification_content: default_notification_content(),
         }
     }
 }
 
 pub fn load_config() -> Config {
-    let app_name = "rivetui";
+    let app_name = APP_NAME;
     match confy::load::<Config>(app_name, "config") {
         Ok(mut cfg) => {
             if cfg.emoji_map.is_empty() {
                 cfg.emoji_map = load_emojis();
                 if let Err(e) = confy::store::<Config>(app_name, "config", cfg.clone()) {
-                    eprintln!("Error storing config: {e}");
+                    tracing::warn!("Error storing config: {e}");
                 }
             }
             cfg
         }
         Err(e) => {
-            eprintln!("Error loading config: {e}");
+            tracing::warn!("Error loading config, using the defaults: {e}");
             Config::default()
         }
     }
 }
+
+/// Path of a state file kept next to the config file.
+fn state_path(file: &str) -> Option<PathBuf> {
+    let config_path = confy::get_configuration_file_path(APP_NAME, "config").ok()?;
+    Some(config_path.parent()?.join(file))
+}
+
+async fn write_state_file(file: &str, contents: Vec<u8>) -> Result<(), Error> {
+    let path = state_path(file).ok_or("Could not locate the config directory.")?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(path, contents).await?;
+    Ok(())
+}
+
+/// Loads the newest message id seen per channel. A missing or corrupted state file
+/// yields an empty map, so every channel starts out as read.
+/// Reads a JSON state file. A missing file yields the default value, and so does a
+/// corrupted one, which the next save then replaces.
+fn load_state_file<T: DeserializeOwned + Default>(file: &str) -> T {
+    let Some(contents) = state_path(file).and_then(|path| std::fs::read(path).ok()) else {
+        return T::default();
+    };
+
+    serde_json::from_slice(&contents).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring corrupted {file}: {e}");
+        T::default()
+    })
+}
+
+pub fn load_read_state() -> HashMap<String, String> {
+    load_state_file(READ_STATE_FILE)
+}
+
+pub async fn store_read_state(read_state: HashMap<String, String>) -> Result<(), Error> {
+    write_state_file(READ_STATE_FILE, serde_json::to_vec(&read_state)?).await
+}
+
+/// Guilds and their grouped channel lists from the last run, shown at startup while the
+/// fresh ones load.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ListCache {
+    pub guilds: Vec<Guild>,
+    pub channels: HashMap<String, Vec<Channel>>,
+    pub contexts: HashMap<String, PermissionContext>,
+}
+
+/// Loads the list cache. A missing or corrupted file yields an empty cache, which
+/// replaces it on the next save.
+pub fn load_list_cache() -> ListCache {
+    load_state_file(LIST_CACHE_FILE)
+}
+
+pub async fn store_list_cache(cache: ListCache) -> Result<(), Error> {
+    write_state_file(LIST_CACHE_FILE, serde_json::to_vec(&cache)?).await
+}
+
+/// Unsent input of a channel, kept while another channel is open and across runs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Draft {
+    pub text: String,
+    pub saved_at: DateTime<Utc>,
+}
+
+/// Loads the drafts per channel id. A missing or corrupted file yields no drafts.
+pub fn load_drafts() -> HashMap<String, Draft> {
+    load_state_file(DRAFTS_FILE)
+}
+
+pub async fn store_drafts(drafts: HashMap<String, Draft>) -> Result<(), Error> {
+    write_state_file(DRAFTS_FILE, serde_json::to_vec(&drafts)?).await
+}
+
+/// Loads when each guild was last opened, by guild id. A missing or corrupted file yields
+/// an empty map, leaving the recent sort in API order.
+pub fn load_guilds_opened() -> HashMap<String, DateTime<Utc>> {
+    load_state_file(GUILDS_OPENED_FILE)
+}
+
+pub async fn store_guilds_opened(opened: HashMap<String, DateTime<Utc>>) -> Result<(), Error> {
+    write_state_file(GUILDS_OPENED_FILE, serde_json::to_vec(&opened)?).await
+}
+
+/// Loads the channel last opened per guild id.
+pub fn load_guild_channels() -> HashMap<String, String> {
+    load_state_file(GUILD_CHANNELS_FILE)
+}
+
+pub async fn store_guild_channels(channels: HashMap<String, String>) -> Result<(), Error> {
+    write_state_file(GUILD_CHANNELS_FILE, serde_json::to_vec(&channels)?).await
+}
+
+/// Loads the ids of the muted guilds and channels. A missing or corrupted file mutes
+/// nothing.
+pub fn load_muted() -> HashSet<String> {
+    load_state_file(MUTED_FILE)
+}
+
+pub async fn store_muted(muted: HashSet<String>) -> Result<(), Error> {
+    write_state_file(MUTED_FILE, serde_json::to_vec(&muted)?).await
+}
+
+/// A user whose messages are collapsed, with the name they had when ignored, to recognize
+/// them in the list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IgnoredUser {
+    pub id: String,
+    pub name: String,
+}
+
+pub fn load_ignored() -> Vec<IgnoredUser> {
+    load_state_file(IGNORED_FILE)
+}
+
+pub async fn store_ignored(ignored: Vec<IgnoredUser>) -> Result<(), Error> {
+    write_state_file(IGNORED_FILE, serde_json::to_vec(&ignored)?).await
+}
+
+/// Loads the names given to users with `/alias`, per user id.
+pub fn load_aliases() -> HashMap<String, String> {
+    load_state_file(ALIASES_FILE)
+}
+
+pub async fn store_aliases(aliases: HashMap<String, String>) -> Result<(), Error> {
+    write_state_file(ALIASES_FILE, serde_json::to_vec(&aliases)?).await
+}
+
+/// The channel last opened, to offer it again on the next start.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LastChannel {
+    pub channel_id: String,
+    pub guild_id: Option<String>,
+    pub name: String,
+}
+
+impl LastChannel {
+    pub fn label(&self) -> String {
+        match self.guild_id {
+            Some(_) => format!("#{}", self.name),
+            None => format!("the DM with {}", self.name),
+        }
+    }
+}
+
+/// Loads the channel last opened. A missing or corrupted file offers none.
+pub fn load_last_channel() -> Option<LastChannel> {
+    load_state_file(LAST_CHANNEL_FILE)
+}
+
+pub async fn store_last_channel(last_channel: LastChannel) -> Result<(), Error> {
+    write_state_file(LAST_CHANNEL_FILE, serde_json::to_vec(&last_channel)?).await
+}
+
+/// Loads the sent messages per channel id. A missing or corrupted file yields none.
+pub fn load_input_history() -> HashMap<String, Vec<String>> {
+    load_state_file(INPUT_HISTORY_FILE)
+}
+
+pub async fn store_input_history(history: HashMap<String, Vec<String>>) -> Result<(), Error> {
+    write_state_file(INPUT_HISTORY_FILE, serde_json::to_vec(&history)?).await
+}
+
+/// What can be pinned to the top of its list.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PinKind {
+    Guild,
+}
+
+/// The ids pinned to the top of their list, by kind, in the order they were pinned.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Pinned(HashMap<PinKind, Vec<String>>);
+
+impl Pinned {
+    pub fn ids(&self, kind: PinKind) -> &[String] {
+        self.0.get(&kind).map_or(&[], Vec::as_slice)
+    }
+
+    /// Pins `id`, or unpins it when it already was. Returns whether it is now pinned.
+    pub fn toggle(&mut self, kind: PinKind, id: &str) -> bool {
+        let ids = self.0.entry(kind).or_default();
+        match ids.iter().position(|pinned| pinned == id) {
+            Some(index) => {
+                ids.remove(index);
+                false
+            }
+            None => {
+                ids.push(id.to_string());
+                true
+            }
+        }
+    }
+
+    /// Drops the ids of `kind` that are not `known` anymore, such as guilds left since
+    /// they were pinned. Returns whether any was dropped.
+    pub fn retain_known(&mut self, kind: PinKind, known: impl Fn(&str) -> bool) -> bool {
+        let Some(ids) = self.0.get_mut(&kind) else {
+            return false;
+        };
+        let len = ids.len();
+        ids.retain(|id| known(id));
+        ids.len() != len
+    }
+}
+
+/// Loads the pinned guilds. A missing or corrupted file pins nothing.
+pub fn load_pinned() -> Pinned {
+    load_state_file(PINNED_FILE)
+}
+
+pub async fn store_pinned(pinned: Pinned) -> Result<(), Error> {
+    write_state_file(PINNED_FILE, serde_json::to_vec(&pinned)?).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_time_formats_from_their_config_names() {
+        assert_eq!(TimeFormat::from("HH:MM".to_string()), TimeFormat::Short);
+        assert_eq!(TimeFormat::from("HH:MM:SS".to_string()), TimeFormat::Long);
+        assert_eq!(
+            TimeFormat::from("relative".to_string()),
+            TimeFormat::Relative
+        );
+        assert_eq!(
+            TimeFormat::from("%d/%m %H:%M".to_string()),
+            TimeFormat::Custom("%d/%m %H:%M".to_string())
+        );
+
+        for format in [TimeFormat::Short, TimeFormat::Relative] {
+            assert_eq!(TimeFormat::from(String::from(format.clone())), format);
+        }
+    }
+
+    #[test]
+    fn refuses_custom_formats_chrono_cannot_render() {
+        assert!(
+            TimeFormat::Custom("%Y-%m-%d %H:%M".to_string())
+                .validate()
+                .is_ok()
+        );
+        assert!(TimeFormat::Custom("%Q".to_string()).validate().is_err());
+        assert!(TimeFormat::Relative.validate().is_ok());
+    }
+
+    #[test]
+    fn cycles_time_formats_through_the_custom_one() {
+        let mut format = TimeFormat::Short;
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            format = format.next(Some("%H.%M"));
+            seen.push(format.label().to_string());
+        }
+        assert_eq!(seen, ["HH:MM:SS", "relative", "%H.%M", "HH:MM"]);
+
+        assert_eq!(TimeFormat::Relative.next(None), TimeFormat::Short);
+    }
+
+    #[test]
+    fn reads_density_from_lowercase_names() {
+        assert_eq!(
+            serde_json::from_str::<Density>(r#""cozy""#).unwrap(),
+            Density::Cozy
+        );
+        assert_eq!(Config::default().density, Density::Compact);
+        assert_eq!(Density::Compact.next().label(), "cozy");
+        assert_eq!(Density::Cozy.next(), Density::Compact);
+    }
+}
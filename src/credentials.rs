@@ -0,0 +1,49 @@
+//! Discord token kept in the OS keychain, under the `rivetui` service. Without the
+//! `keyring` feature nothing is ever stored.
+
+use crate::Error;
+
+#[cfg(feature = "keyring")]
+const SERVICE: &str = "rivetui";
+#[cfg(feature = "keyring")]
+const ACCOUNT: &str = "discord_token";
+
+#[cfg(feature = "keyring")]
+fn entry() -> Result<keyring::Entry, keyring::Error> {
+    keyring::Entry::new(SERVICE, ACCOUNT)
+}
+
+#[cfg(feature = "keyring")]
+pub fn load_token() -> Option<String> {
+    entry().and_then(|entry| entry.get_password()).ok()
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn load_token() -> Option<String> {
+    None
+}
+
+#[cfg(feature = "keyring")]
+pub fn store_token(token: &str) -> Result<(), Error> {
+    entry()?.set_password(token)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store_token(_token: &str) -> Result<(), Error> {
+    Err("rivetui was built without keyring support.".into())
+}
+
+/// Removes the stored token. Succeeds when there was none.
+#[cfg(feature = "keyring")]
+pub fn delete_token() -> Result<(), Error> {
+    match entry()?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn delete_token() -> Result<(), Error> {
+    Err("rivetui was built without keyring support.".into())
+}
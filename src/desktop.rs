@@ -0,0 +1,90 @@
+//! Desktop notifications for mentions and DMs, for when the terminal is in the
+//! background. Showing them needs the `desktop-notifications` feature; without it, or
+//! without a notification daemon, the first attempt is logged and the rest of the session
+//! goes without.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Shortest time between two notifications for the same channel.
+const CHANNEL_INTERVAL: Duration = Duration::from_secs(30);
+/// Longest message text shown in a notification, in characters.
+const MAX_BODY_LENGTH: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct DesktopNotifier {
+    /// Whether the message text is shown, or only who wrote where.
+    show_content: bool,
+    /// When each channel was last notified.
+    last_sent: HashMap<String, Instant>,
+    /// Set once showing a notification failed, for the rest of the session.
+    unavailable: Arc<AtomicBool>,
+}
+
+impl DesktopNotifier {
+    pub fn new(show_content: bool) -> Self {
+        Self {
+            show_content,
+            last_sent: HashMap::new(),
+            unavailable: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Shows a notification for a message of `channel_id`, unless one was shown for the
+    /// channel in the last `CHANNEL_INTERVAL`. It is shown in the background, as reaching
+    /// the daemon can take a while.
+    pub fn notify(&mut self, channel_id: &str, title: String, content: &str) {
+        if self.unavailable.load(Ordering::Relaxed) {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .last_sent
+            .get(channel_id)
+            .is_some_and(|sent| now.duration_since(*sent) < CHANNEL_INTERVAL)
+        {
+            return;
+        }
+        self.last_sent.insert(channel_id.to_string(), now);
+
+        let body = if !self.show_content {
+            "New message".to_string()
+        } else if content.chars().count() > MAX_BODY_LENGTH {
+            let truncated: String = content.chars().take(MAX_BODY_LENGTH - 1).collect();
+            format!("{truncated}…")
+        } else {
+            content.to_string()
+        };
+        let unavailable = self.unavailable.clone();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = show(&title, &body)
+                && !unavailable.swap(true, Ordering::Relaxed)
+            {
+                tracing::warn!("Desktop notifications are off for this session: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(feature = "desktop-notifications")]
+fn show(title: &str, body: &str) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .appname(crate::config::APP_NAME)
+        .summary(title)
+        .body(body)
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "desktop-notifications"))]
+fn show(_title: &str, _body: &str) -> Result<(), String> {
+    Err("rivetui was built without the desktop-notifications feature".to_string())
+}
@@ -0,0 +1,18 @@
+//! Rivet, a terminal UI Discord client. `main.rs` runs it; the modules are a library so
+//! that tests can drive them against a local server standing in for Discord.
+
+pub mod api;
+pub mod app;
+pub mod clipboard;
+pub mod config;
+pub mod credentials;
+pub mod desktop;
+pub mod logging;
+pub mod poll;
+pub mod queue;
+pub mod signals;
+pub mod tasks;
+pub mod translate;
+pub mod ui;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
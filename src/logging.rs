@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::EnvFilter;
+
+use crate::config::APP_NAME;
+
+/// Filter directives read from the environment, in the `RUST_LOG` syntax.
+const LOG_ENV: &str = "RIVET_LOG";
+const LOG_PREFIX: &str = "rivet";
+const LOG_SUFFIX: &str = "log";
+/// Daily log files kept before the oldest is deleted.
+const MAX_LOG_FILES: usize = 7;
+
+/// Directory of the log files, e.g. `~/.cache/rivetui` on Linux.
+fn log_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(APP_NAME))
+}
+
+/// Starts writing logs to a daily rotated file, filtered by `RIVET_LOG` or else at info
+/// level, or debug with `verbose`. Returns the guard that flushes the file when dropped,
+/// and the path of today's file. Logging stays off when the file can't be created.
+pub fn init(verbose: bool) -> Option<(WorkerGuard, PathBuf)> {
+    let dir = log_dir()?;
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir)
+        .ok()?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let default_level = if verbose { "debug" } else { "info" };
+    let filter = EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| {
+        EnvFilter::new(format!("{}={default_level}", env!("CARGO_CRATE_NAME")))
+    });
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(false)
+        .init();
+
+    // The appender names files after the UTC date.
+    let path = dir.join(format!(
+        "{LOG_PREFIX}.{}.{LOG_SUFFIX}",
+        Utc::now().format("%Y-%m-%d")
+    ));
+    Some((guard, path))
+}
@@ -1,165 +1,243 @@
-use std::{env, io, process, sync::Arc, time::Duration};
-
-use crossterm::{
-    cursor::SetCursorStyle,
-    event::EnableBracketedPaste,
-    execute,
-    terminal::{EnterAlternateScreen, enable_raw_mode},
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, Read, Write},
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
 };
+
+use clap::{Parser, Subcommand};
+use crossterm::{cursor::SetCursorStyle, execute};
 use ratatui::{Terminal, prelude::CrosstermBackend};
 use reqwest::Client;
 use tokio::{
-    sync::{
-        Mutex,
-        mpsc::{self},
-    },
+    sync::{Mutex, mpsc, watch},
     task::JoinHandle,
     time::{self},
 };
 
-use crate::{
-    api::{ApiClient, Channel, Emoji, Guild, Message, channel::PermissionContext, dm::DM},
-    signals::{restore_terminal, setup_ctrlc_handler},
-    ui::{draw_ui, handle_input_events, handle_keys_events, vim::VimState},
+use rivetui::{
+    Error,
+    api::{
+        ApiClient, ApiError, BOT_TOKEN_HINT, Channel, GUILD_PAGE_DELAY, GUILD_PAGE_SIZE, Guild,
+        Message, TokenType, User, api_base_url, gateway::run_gateway, message::AllowedMentions,
+    },
+    app::{
+        self, App, AppAction, AppState, Commands, InputMode, KeywordAction, Window, apply_action,
+    },
+    config,
+    config::{ResumeMode, TimeFormat},
+    credentials,
+    desktop::DesktopNotifier,
+    logging,
+    poll::{FULL_POLL_EVERY, poll_channel},
+    queue::{ACTION_CAPACITY, INPUT_CAPACITY, ParkedMessages, run_commands, send_or_park},
+    signals::{enter_terminal, restore_terminal, setup_ctrlc_handler, setup_panic_hook},
+    tasks::SHUTDOWN_GRACE,
+    ui::{draw_ui, handle_input_events, login::prompt_token, theme::Theme, vim::VimState},
 };
 
-mod api;
-mod config;
-mod signals;
-mod ui;
-
-const DISCORD_BASE_URL: &str = "https://discord.com/api/v10";
-
-pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
-
-#[derive(Debug)]
-pub enum KeywordAction {
-    Continue,
-    Break,
-}
-
-#[derive(Debug, Clone)]
-pub enum Window {
-    Home,
-    Guild,
-    DM,
-    Channel(String),
-    Chat(String),
-}
-
-#[derive(Debug, Clone)]
-pub enum AppState {
-    Home,
-    SelectingGuild,
-    SelectingDM,
-    SelectingChannel(String),
-    Chatting(String),
-    EmojiSelection(String),
-    Loading(Window),
-}
-
-#[derive(Debug)]
-pub enum AppAction {
-    SigInt,
-    InputChar(char),
-    InputBackspace,
-    InputEscape,
-    InputSubmit,
-    SelectNext,
-    SelectPrevious,
-    ApiUpdateMessages(Vec<Message>),
-    ApiUpdateChannel(Vec<Channel>),
-    ApiUpdateEmojis(Vec<Emoji>),
-    ApiUpdateGuilds(Vec<Guild>),
-    ApiUpdateDMs(Vec<DM>),
-    ApiUpdateContext(Option<PermissionContext>),
-    TransitionToChat(String),
-    TransitionToChannels(String),
-    TransitionToGuilds,
-    TransitionToDM,
-    TransitionToHome,
-    TransitionToLoading(Window),
-    EndLoading,
-    SelectEmoji,
-    Paste(String),
-    Tick,
+/// Bounds of the configured pause between two polls of the open channel, in seconds.
+const POLL_INTERVAL_RANGE: (u64, u64) = (1, 60);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Consecutive network errors after which the app considers itself offline.
+const OFFLINE_AFTER_FAILURES: u32 = 2;
+/// How often recently opened channels are checked for mentions while the gateway is down.
+const MENTION_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Upper bound between two frames, so animations keep running while no action arrives.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+/// First pause before checking the token again while Discord can't be reached at startup,
+/// doubled after each failure up to the maximum.
+const STARTUP_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_STARTUP_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// A terminal UI Discord client.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Use vim-style key bindings.
+    #[arg(long, global = true)]
+    vim: bool,
+    /// Log at debug level, unless RIVET_LOG says otherwise.
+    #[arg(long, global = true)]
+    verbose: bool,
+    /// Remove the token stored in the OS keychain and exit.
+    #[arg(long)]
+    logout: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum InputMode {
-    Normal,
-    Insert,
-}
-
-#[derive(Debug, Clone)]
-pub struct App {
-    api_client: ApiClient,
-    state: AppState,
-    guilds: Vec<Guild>,
-    channels: Vec<Channel>,
-    messages: Vec<Message>,
-    custom_emojis: Vec<Emoji>,
-    dms: Vec<DM>,
-    input: String,
-    selection_index: usize,
-    status_message: String,
-    terminal_height: usize,
-    terminal_width: usize,
-    emoji_map: Vec<(String, String)>,
-    emoji_filter: String,
-    /// Byte position where the emoji filter started (position of the ':')
-    emoji_filter_start: Option<usize>,
-    tick_count: usize,
-    context: Option<PermissionContext>,
-    mode: InputMode,
-    cursor_position: usize,
-    vim_mode: bool,
-    vim_state: Option<VimState>,
+#[derive(Subcommand)]
+enum Command {
+    /// Send a message and exit, without starting the TUI.
+    Send {
+        /// Id of the channel to send to.
+        #[arg(long)]
+        channel: String,
+        /// The message to send. Read from stdin when omitted.
+        #[arg(long)]
+        message: Option<String>,
+    },
+    /// Start the TUI in the chat of a channel.
+    Open {
+        /// Id of the channel to open.
+        #[arg(long)]
+        channel: String,
+    },
 }
 
-async fn run_app(token: String, config: config::Config) -> Result<(), Error> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
-    let backend = CrosstermBackend::new(stdout);
+/// Runs the client with a token and the user Discord says it belongs to. Without them,
+/// the user is first asked for a token in the terminal, which is then saved to the OS
+/// keychain. With a `start_channel`, the chat of that channel opens as soon as the client
+/// is up.
+async fn run_app(
+    login: Option<(String, User)>,
+    config: config::Config,
+    start_channel: Option<String>,
+) -> Result<(), Error> {
+    enter_terminal(config.mouse)?;
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
-    let vim_mode = config.vim_mode || env::args().any(|arg| arg == "--vim");
+    let mut status_message =
+        "Browse either DMs or Servers. Use arrows to navigate, Enter to select & Esc to quit"
+            .to_string();
+
+    let (token, current_user) = match login {
+        Some(login) => login,
+        None => {
+            let Some((token, user)) = prompt_token(&mut terminal).await? else {
+                return Ok(());
+            };
+            if let Err(e) = credentials::store_token(&token) {
+                status_message = format!("Token not saved to the keychain: {e}. {status_message}");
+            }
+            terminal.clear()?;
+            (token, user)
+        }
+    };
+
+    let vim_mode = config.vim_mode;
+    let poll_interval = Duration::from_secs(
+        config
+            .poll_interval
+            .clamp(POLL_INTERVAL_RANGE.0, POLL_INTERVAL_RANGE.1),
+    );
+    let guild_refresh_interval = (config.guild_refresh_interval > 0)
+        .then(|| Duration::from_secs(config.guild_refresh_interval * 60));
+
+    // A broken theme should not keep the client from starting.
+    let theme = Theme::from_config(&config.theme).unwrap_or_else(|e| {
+        status_message = format!("Theme ignored: {e}. {status_message}");
+        Theme::default()
+    });
+
+    let presence = config
+        .presence
+        .clone()
+        .filter(|presence| match presence.validate() {
+            Ok(()) => true,
+            Err(e) => {
+                status_message = format!("Presence ignored: {e} {status_message}");
+                false
+            }
+        });
+    let (presence_tx, presence_rx) = watch::channel(presence.clone());
+
+    let time_format = match config.time_format.validate() {
+        Ok(()) => config.time_format.clone(),
+        Err(e) => {
+            status_message = format!("Time format ignored: {e}. {status_message}");
+            TimeFormat::default()
+        }
+    };
+
+    // With the lists of the last run at hand, the UI is usable right away and refreshes
+    // them in the background.
+    let list_cache = config::load_list_cache();
+    let initial_state = if list_cache.guilds.is_empty() {
+        AppState::Loading(Window::Home)
+    } else {
+        AppState::Home
+    };
+
+    // A channel asked for on the command line wins over the one of the last session.
+    let last_channel = config::load_last_channel().filter(|_| start_channel.is_none());
+    let (resume_now, resume_prompt) = match config.resume {
+        ResumeMode::Always => (last_channel.clone(), None),
+        ResumeMode::Ask => (None, last_channel.clone()),
+        ResumeMode::Never => (None, None),
+    };
+
+    let api_client = ApiClient::new(Client::new(), token, api_base_url());
+    let gateway_token = api_client.auth_token.clone();
 
     let app_state = Arc::new(Mutex::new(App {
-        api_client: ApiClient::new(Client::new(), token.clone(), DISCORD_BASE_URL.to_string()),
-        state: AppState::Loading(Window::Home),
-        guilds: Vec::new(),
-        channels: Vec::new(),
-        messages: Vec::new(),
-        custom_emojis: Vec::new(),
-        dms: Vec::new(),
-        input: String::new(),
-        selection_index: 0,
-        status_message:
-            "Browse either DMs or Servers. Use arrows to navigate, Enter to select & Esc to quit"
-                .to_string(),
-        terminal_height: 20,
-        terminal_width: 80,
+        state: initial_state,
+        guilds: list_cache.guilds,
+        status_message,
         emoji_map: config.emoji_map,
-        emoji_filter: String::new(),
-        emoji_filter_start: None,
-        tick_count: 0,
-        context: None,
-        mode: InputMode::Normal,
-        cursor_position: 0,
         vim_mode,
         vim_state: if vim_mode {
             Some(VimState::default())
         } else {
             None
         },
+        ignored: config::load_ignored(),
+        aliases: config::load_aliases(),
+        read_markers: config::load_read_state(),
+        drafts: config::load_drafts(),
+        guild_sort: config.guild_sort,
+        custom_time_format: match &time_format {
+            TimeFormat::Custom(format) => Some(format.clone()),
+            _ => None,
+        },
+        time_format,
+        density: config.density,
+        sync_read_state: config.sync_read_state,
+        guilds_opened: config::load_guilds_opened(),
+        muted: config::load_muted(),
+        pinned: config::load_pinned(),
+        last_channel,
+        guild_channels: config::load_guild_channels(),
+        resume_prompt,
+        input_history: if config.persist_input_history {
+            config::load_input_history()
+        } else {
+            HashMap::new()
+        },
+        persist_input_history: config.persist_input_history,
+        allowed_mentions: AllowedMentions::new(config.allowed_mentions),
+        channel_cache: list_cache.channels,
+        context_cache: list_cache.contexts,
+        content_warnings: config.content_warnings,
+        desktop_notifier: config
+            .desktop_notifications
+            .then(|| DesktopNotifier::new(config.notification_content)),
+        translation: config.translation,
+        presence,
+        presence_tx,
+        theme,
+        ..App::new(api_client, current_user)
     }));
 
-    let (tx_action, mut rx_action) = mpsc::channel::<AppAction>(32);
+    let (tx_action, mut rx_action) = mpsc::channel::<AppAction>(ACTION_CAPACITY);
+    let (tx_input, mut rx_input) = mpsc::channel::<AppAction>(INPUT_CAPACITY);
+    let parked = Arc::new(ParkedMessages::default());
+
+    if let Some(last_channel) = resume_now {
+        let (api_client, pending_requests) = {
+            let state = app_state.lock().await;
+            (state.api_client.clone(), state.pending_requests.clone())
+        };
+        let mut commands = Commands::new(tx_action.clone());
+        app::resume_channel(&api_client, &mut commands, last_channel);
+        run_commands(commands.take(), &tx_action, &pending_requests).await;
+    }
     let (tx_shutdown, _) = tokio::sync::broadcast::channel::<()>(1);
 
-    let tx_input = tx_action.clone();
     let rx_shutdown_input = tx_shutdown.subscribe();
 
     let mut rx_shutdown_ticker = tx_shutdown.subscribe();
@@ -173,8 +251,7 @@ async fn run_app(token: String, config: config::Config) -> Result<(), Error> {
                     return;
                 }
                 _ = interval.tick() => {
-                    if let Err(e) = tx_ticker.send(AppAction::Tick).await {
-                        eprintln!("Failed to send tick action: {e}");
+                    if tx_ticker.send(AppAction::Tick).await.is_err() {
                         return;
                     }
                 }
@@ -183,19 +260,28 @@ async fn run_app(token: String, config: config::Config) -> Result<(), Error> {
     });
 
     let input_handle: JoinHandle<Result<(), io::Error>> = tokio::spawn(async move {
-        let res = handle_input_events(tx_input, rx_shutdown_input).await;
+        let res = handle_input_events(tx_input.clone(), rx_shutdown_input).await;
         if let Err(e) = &res {
-            eprintln!("Input Error: {e}");
+            tx_input
+                .send(AppAction::ApiError(format!("Input error: {e}")))
+                .await
+                .ok();
         }
         res
     });
 
+    let tx_gateway = tx_action.clone();
+    let rx_shutdown_gateway = tx_shutdown.subscribe();
+
+    let gateway_handle: JoinHandle<()> = tokio::spawn(async move {
+        run_gateway(gateway_token, tx_gateway, rx_shutdown_gateway, presence_rx).await;
+    });
+
     let api_state = Arc::clone(&app_state);
     let tx_api = tx_action.clone();
+    let parked_api = Arc::clone(&parked);
     let mut rx_shutdown_api = tx_shutdown.subscribe();
 
-    let mut interval = time::interval(Duration::from_secs(2));
-
     let api_handle: JoinHandle<()> = tokio::spawn(async move {
         let api_client_clone;
         {
@@ -203,68 +289,231 @@ async fn run_app(token: String, config: config::Config) -> Result<(), Error> {
             api_client_clone = state.api_client.clone();
         }
 
-        match api_client_clone.get_current_user_guilds().await {
-            Ok(guilds) => {
-                if let Err(e) = tx_api.send(AppAction::ApiUpdateGuilds(guilds)).await {
-                    eprintln!("Failed to send guild update action: {e}");
+        // Each page shows up as it arrives, so the first servers can be browsed while the
+        // rest load.
+        let mut guilds: Vec<Guild> = Vec::new();
+        loop {
+            let after = guilds.last().map(|g| g.id.clone());
+            match api_client_clone
+                .get_current_user_guilds(None, after, Some(GUILD_PAGE_SIZE))
+                .await
+            {
+                Ok(page) => {
+                    let complete = page.len() < GUILD_PAGE_SIZE;
+                    guilds.extend(page);
+                    tx_api
+                        .send(AppAction::ApiUpdateGuilds(guilds.clone(), complete))
+                        .await
+                        .ok();
+                    if complete {
+                        break;
+                    }
+                    time::sleep(GUILD_PAGE_DELAY).await;
+                }
+                Err(ApiError::Unauthorized) => {
+                    tx_api.send(AppAction::Unauthorized).await.ok();
+                    return;
+                }
+                Err(e) => {
+                    tx_api
+                        .send(AppAction::ApiError(format!("Failed to load servers: {e}")))
+                        .await
+                        .ok();
+                    break;
                 }
-            }
-            Err(e) => {
-                let mut state = api_state.lock().await;
-                state.status_message = format!("Failed to load servers. {e}");
             }
         }
 
         match api_client_clone.get_dms().await {
             Ok(dms) => {
-                if let Err(e) = tx_api.send(AppAction::ApiUpdateDMs(dms)).await {
-                    eprintln!("Failed to send DM update action: {e}");
-                }
+                tx_api.send(AppAction::ApiUpdateDMs(dms)).await.ok();
             }
             Err(e) => {
-                let mut state = api_state.lock().await;
-                state.status_message = format!("Failed to load DMs. {e}");
+                tx_api
+                    .send(AppAction::ApiError(format!("Failed to load DMs: {e}")))
+                    .await
+                    .ok();
             }
         }
 
         tx_api.send(AppAction::EndLoading).await.ok();
 
+        if let Some(channel_id) = start_channel {
+            match api_client_clone.get_channel(&channel_id).await {
+                Ok(channel) => {
+                    tx_api.send(AppAction::ApiOpenChannel(channel)).await.ok();
+                }
+                Err(e) => {
+                    tx_api
+                        .send(AppAction::ApiError(format!(
+                            "Could not open channel {channel_id}: {e}"
+                        )))
+                        .await
+                        .ok();
+                }
+            }
+        }
+
+        let mut network_failures: u32 = 0;
+        let mut last_mention_poll = Instant::now();
+        let mut last_guild_refresh = Instant::now();
+        // The polls since each watched channel was fully fetched.
+        let mut polls_since_full: HashMap<String, u32> = HashMap::new();
+
         loop {
+            // Back off exponentially while the network is down.
+            let delay = (poll_interval * 2u32.saturating_pow(network_failures))
+                .min(MAX_POLL_INTERVAL.max(poll_interval));
+
             tokio::select! {
                 _ = rx_shutdown_api.recv() => {
                     return;
                 }
 
-                _ = interval.tick() => {
-                    let current_channel_id = {
+                _ = time::sleep(delay) => {
+                    // The unread badges of the guild list are counted here, off the UI, once
+                    // anything they depend on changed.
+                    let unread_input = {
+                        let mut state = api_state.lock().await;
+                        if state.unread_dirty {
+                            state.unread_dirty = false;
+                            Some((
+                                state.channel_cache.clone(),
+                                state.context_cache.clone(),
+                                state.read_markers.clone(),
+                                state.muted.clone(),
+                            ))
+                        } else {
+                            None
+                        }
+                    };
+                    if let Some((channels, contexts, read_markers, muted)) = unread_input {
+                        let counts =
+                            Channel::unread_counts(&channels, &contexts, &read_markers, &muted);
+                        tx_api.send(AppAction::UnreadCounts(counts)).await.ok();
+                    }
+
+                    // The open chat and the split pane, whose channels are polled alike.
+                    let watched: Vec<(String, Option<String>, bool)> = {
                         let state = api_state.lock().await;
-                        match &state.state {
-                            AppState::Chatting(id) => Some(id.clone()),
+                        let newest = |messages: &[Message]| {
+                            messages
+                                .iter()
+                                .filter(|m| m.snowflake() > 0)
+                                .max_by_key(|m| m.snowflake())
+                                .map(|m| m.id.clone())
+                        };
+                        let chat = match &state.state {
+                            AppState::Chatting(id) | AppState::SelectingMessage(id) => {
+                                Some((id.clone(), newest(&state.messages), false))
+                            }
                             _ => None,
+                        };
+                        let split = state
+                            .split
+                            .as_ref()
+                            .map(|split| (split.channel_id.clone(), newest(&split.messages), true));
+                        if state.gateway_connected {
+                            Vec::new()
+                        } else {
+                            chat.into_iter().chain(split).collect()
                         }
                     };
-
-                    if let Some(channel_id) = current_channel_id {
-                        const MESSAGE_LIMIT: usize = 100;
-
-                        match api_client_clone.get_channel_messages(
-                            &channel_id,
-                            None,
-                            None,
-                            None,
-                            Some(MESSAGE_LIMIT),
+                    polls_since_full.retain(|id, _| watched.iter().any(|(watched_id, _, _)| watched_id == id));
+
+                    for (channel_id, newest_id, split) in watched {
+                        let polls = polls_since_full
+                            .entry(channel_id.clone())
+                            .or_insert(FULL_POLL_EVERY);
+
+                        match poll_channel(
+                            &api_client_clone,
+                            channel_id.clone(),
+                            newest_id,
+                            split,
+                            polls,
                         )
                         .await
                         {
-                            Ok(messages) => {
-                                if let Err(e) = tx_api.send(AppAction::ApiUpdateMessages(messages)).await {
-                                    eprintln!("Failed to send message update action: {e}");
+                            Ok(action) => {
+                                if network_failures >= OFFLINE_AFTER_FAILURES {
+                                    tx_api.send(AppAction::ConnectionStatus(true)).await.ok();
+                                }
+                                network_failures = 0;
+
+                                if let Some(action) = action
+                                    && !send_or_park(&tx_api, &parked_api, action).await
+                                {
                                     return;
                                 }
                             }
+                            Err(ApiError::Network(_)) => {
+                                network_failures = network_failures.saturating_add(1);
+                                if network_failures == OFFLINE_AFTER_FAILURES {
+                                    tx_api.send(AppAction::ConnectionStatus(false)).await.ok();
+                                }
+                                break;
+                            }
                             Err(e) => {
-                                api_state.lock().await.status_message = format!("Error loading chat: {e}");
+                                if network_failures >= OFFLINE_AFTER_FAILURES {
+                                    tx_api.send(AppAction::ConnectionStatus(true)).await.ok();
+                                }
+                                network_failures = 0;
+
+                                tx_api.send(AppAction::ChannelFailed(channel_id, e)).await.ok();
+                            }
+                        }
+                    }
+
+                    // Without the gateway, mentions elsewhere only show up by polling the
+                    // channels we were active in.
+                    if last_mention_poll.elapsed() >= MENTION_POLL_INTERVAL {
+                        last_mention_poll = Instant::now();
+
+                        let channels: Vec<(String, Option<String>)> = {
+                            let state = api_state.lock().await;
+                            if state.gateway_connected {
+                                Vec::new()
+                            } else {
+                                state.recent_channels.clone()
+                            }
+                        };
+
+                        let mut messages = Vec::new();
+                        for (channel_id, guild_id) in channels {
+                            if let Ok(latest) = api_client_clone
+                                .get_channel_messages(&channel_id, None, None, None, Some(10))
+                                .await
+                            {
+                                messages.extend(latest.into_iter().map(|mut message| {
+                                    message.guild_id = guild_id.clone();
+                                    message
+                                }));
+                            }
+                        }
+
+                        if !messages.is_empty() {
+                            tx_api.send(AppAction::ApiBackgroundMessages(messages)).await.ok();
+                        }
+                    }
+
+                    // Servers joined or left from another client only show up by fetching
+                    // the list again.
+                    if let Some(interval) = guild_refresh_interval
+                        && last_guild_refresh.elapsed() >= interval
+                    {
+                        last_guild_refresh = Instant::now();
+
+                        match api_client_clone.get_all_current_user_guilds().await {
+                            Ok(guilds) => {
+                                tx_api.send(AppAction::ApiRefreshGuilds(guilds)).await.ok();
+                            }
+                            Err(ApiError::Unauthorized) => {
+                                tx_api.send(AppAction::Unauthorized).await.ok();
+                                return;
                             }
+                            // The list on screen stays usable; the next refresh may work.
+                            Err(e) => tracing::warn!("Failed to refresh servers: {e}"),
                         }
                     }
                 }
@@ -272,32 +521,45 @@ async fn run_app(token: String, config: config::Config) -> Result<(), Error> {
         }
     });
 
+    let mut redraw = time::interval(REDRAW_INTERVAL);
+
     loop {
         {
-            let mut state_guard = app_state.lock().await;
+            // Held for the frame only: with the rows of messages cached, laying it out
+            // is quick, and nothing is awaited until the app is unlocked again.
+            let mut state = app_state.lock().await;
             terminal
                 .draw(|f| {
-                    draw_ui(f, &mut state_guard);
+                    draw_ui(f, &mut state);
                 })
                 .unwrap();
-
-            if !state_guard.vim_mode {
-                execute!(io::stdout(), SetCursorStyle::BlinkingBar).ok();
-            } else {
-                match state_guard.mode {
-                    InputMode::Normal => {
-                        execute!(io::stdout(), SetCursorStyle::BlinkingBlock).ok();
-                    }
-                    InputMode::Insert => {
-                        execute!(io::stdout(), SetCursorStyle::BlinkingBar).ok();
-                    }
-                }
-            }
+            let cursor = match (state.vim_mode, &state.mode) {
+                (true, InputMode::Normal) => SetCursorStyle::BlinkingBlock,
+                _ => SetCursorStyle::BlinkingBar,
+            };
+            drop(state);
+            execute!(io::stdout(), cursor).ok();
         }
-        if let Some(action) = rx_action.recv().await {
-            let state = app_state.lock().await;
-
-            match handle_keys_events(state, action, tx_action.clone()).await {
+        let action = tokio::select! {
+            biased;
+            Some(action) = rx_input.recv() => Some(action),
+            Some(action) = rx_action.recv() => Some(action),
+            messages = parked.next() => messages.map(AppAction::ApiUpdateMessages),
+            _ = redraw.tick() => None,
+        };
+        if let Some(action) = action {
+            if !matches!(action, AppAction::Tick) {
+                tracing::debug!(action = %summarize_action(&action), "handling action");
+            }
+            let (outcome, commands, pending_requests) = {
+                let mut state = app_state.lock().await;
+                let mut commands = Commands::new(tx_action.clone());
+                let outcome = apply_action(&mut state, action, &mut commands);
+                (outcome, commands.take(), state.pending_requests.clone())
+            };
+            run_commands(commands, &tx_action, &pending_requests).await;
+
+            match outcome {
                 Some(KeywordAction::Continue) => continue,
                 Some(KeywordAction::Break) => break,
                 None => {}
@@ -306,34 +568,209 @@ async fn run_app(token: String, config: config::Config) -> Result<(), Error> {
     }
 
     drop(rx_action);
+    drop(rx_input);
 
     let _ = tx_shutdown.send(());
+    // The poller may be in the middle of a request; what it fetches is not needed anymore.
+    api_handle.abort();
+
+    let _ = tokio::join!(input_handle, api_handle, ticker_handle, gateway_handle);
 
-    let _ = tokio::join!(input_handle, api_handle, ticker_handle);
+    // Sends and other changes still on their way get a moment to arrive, on the normal
+    // screen so it doesn't look frozen.
+    restore_terminal();
+    let pending_requests = app_state.lock().await.pending_requests.clone();
+    let (failures, unfinished) = pending_requests
+        .finish(SHUTDOWN_GRACE, |count| match count {
+            1 => eprintln!("Finishing 1 pending send..."),
+            count => eprintln!("Finishing {count} pending sends..."),
+        })
+        .await;
+    for failure in failures {
+        eprintln!("{failure}");
+    }
+    match unfinished {
+        0 => {}
+        1 => eprintln!("Gave up on 1 request that didn't finish in time."),
+        count => eprintln!("Gave up on {count} requests that didn't finish in time."),
+    }
+
+    // Keep what was being typed when quitting, and where, for the next run.
+    let (drafts, last_channel) = {
+        let mut state = app_state.lock().await;
+        app::stash_draft(&mut state);
+        (state.drafts.clone(), state.last_channel.clone())
+    };
+    if let Err(e) = config::store_drafts(drafts).await {
+        tracing::warn!("Failed to save drafts: {e}");
+    }
+    if let Some(last_channel) = last_channel
+        && let Err(e) = config::store_last_channel(last_channel).await
+    {
+        tracing::warn!("Failed to save the last channel: {e}");
+    }
+
+    if let Some(message) = app_state.lock().await.fatal_error.take() {
+        return Err(message.into());
+    }
 
     Ok(())
 }
 
+/// Debug representation of an action, cut short so message lists don't flood the log.
+fn summarize_action(action: &AppAction) -> String {
+    const MAX_LEN: usize = 200;
+
+    let mut summary = format!("{action:?}");
+    if let Some((end, _)) = summary.char_indices().nth(MAX_LEN) {
+        summary.truncate(end);
+        summary.push('…');
+    }
+    summary
+}
+
+/// Checks the token with Discord before the TUI starts, so a rejected token or an
+/// unreachable Discord is reported in the terminal rather than behind an empty client.
+/// While Discord can't be reached, it tries again after a countdown that grows with each
+/// failure.
+async fn check_token(token: &str) -> Result<User, Error> {
+    let api_client = ApiClient::new(Client::new(), token.to_string(), api_base_url());
+    let mut delay = STARTUP_RETRY_DELAY;
+
+    loop {
+        match api_client.get_current_user().await {
+            Ok(user) => return Ok(user),
+            Err(ApiError::Unauthorized) => {
+                return Err(api_client.token_type.rejected_message().into());
+            }
+            Err(e @ (ApiError::Network(_) | ApiError::RateLimited { .. })) => {
+                eprintln!("Could not reach Discord: {e}");
+            }
+            Err(ApiError::Http(status, _)) if status.is_server_error() => {
+                eprintln!("Discord is having trouble ({status}).");
+            }
+            Err(e) => return Err(format!("Could not check the token: {e}").into()),
+        }
+
+        for remaining in (1..=delay.as_secs()).rev() {
+            eprint!("\rRetrying in {remaining}s, Ctrl+C to quit... ");
+            io::stderr().flush().ok();
+            time::sleep(Duration::from_secs(1)).await;
+        }
+        eprintln!("\rRetrying now...                    ");
+        delay = (delay * 2).min(MAX_STARTUP_RETRY_DELAY);
+    }
+}
+
+/// Sends `message`, or stdin when it is omitted, to a channel without starting the TUI.
+async fn send_message(
+    token: String,
+    channel_id: &str,
+    message: Option<String>,
+) -> Result<(), Error> {
+    let content = match message {
+        Some(message) => message,
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            input.trim_end_matches(['\n', '\r']).to_string()
+        }
+    };
+
+    if content.trim().is_empty() {
+        return Err("the message is empty.".into());
+    }
+
+    let allowed_mentions = AllowedMentions::new(config::load_config().allowed_mentions);
+    let api_client = ApiClient::new(Client::new(), token, api_base_url());
+    match api_client
+        .create_message(channel_id, Some(content), false, None, &allowed_mentions)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(ApiError::Unauthorized) if api_client.token_type == TokenType::User => {
+            Err(format!("{}. {BOT_TOKEN_HINT}", ApiError::Unauthorized).into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     dotenvy::dotenv().ok();
     const ENV_TOKEN: &str = "DISCORD_TOKEN";
 
-    let token: String = env::var(ENV_TOKEN).unwrap_or_else(|_| {
+    let cli = Cli::parse();
+    let logging = logging::init(cli.verbose);
+
+    if cli.logout {
+        if let Err(e) = credentials::delete_token() {
+            eprintln!("Failed to remove the stored token: {e}");
+            process::exit(1);
+        }
+        println!("Removed the stored Discord token.");
+        return Ok(());
+    }
+
+    // The environment wins over the keychain for people who prefer env vars.
+    let token = env::var(ENV_TOKEN).ok().or_else(credentials::load_token);
+
+    let start_channel = match cli.command {
+        Some(Command::Send { channel, message }) => {
+            let Some(token) = token else {
+                eprintln!(
+                    "No Discord token found. Set DISCORD_TOKEN or log in by running rivetui."
+                );
+                process::exit(1);
+            };
+            if let Err(e) = send_message(token, &channel, message).await {
+                eprintln!("Failed to send message: {e}");
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Open { channel }) => Some(channel),
+        None => None,
+    };
+
+    if token.is_none() && !cfg!(feature = "keyring") {
         eprintln!("Env Error: DISCORD_TOKEN variable is missing.");
         process::exit(1);
-    });
+    }
 
     setup_ctrlc_handler();
+    setup_panic_hook();
 
-    let config = config::load_config();
+    let mut config = config::load_config();
+    config.vim_mode |= cli.vim;
 
-    if let Err(e) = run_app(token, config).await {
+    tracing::info!("starting rivetui {}", env!("CARGO_PKG_VERSION"));
+
+    let login = match token {
+        Some(token) => match check_token(&token).await {
+            Ok(user) => Some((token, user)),
+            Err(e) => {
+                tracing::error!("{e}");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if let Err(e) = run_app(login, config, start_channel).await {
+        tracing::error!("{e}");
         restore_terminal();
-        return Err(e);
+        eprintln!("{e}");
+        process::exit(1);
     }
 
     restore_terminal();
 
+    if let Some((guard, path)) = logging {
+        drop(guard);
+        println!("Logs were written to {}", path.display());
+    }
+
     Ok(())
 }
@@ -0,0 +1,191 @@
+//! Polling of the open chat and the split pane while the gateway is down. A channel is
+//! fetched whole when it opens and every `FULL_POLL_EVERY` polls after, and otherwise
+//! only for what came after its newest message.
+
+use crate::{
+    api::{ApiClient, ApiError},
+    app::AppAction,
+};
+
+/// Messages per poll, the most Discord allows.
+pub const POLL_LIMIT: usize = 100;
+/// Polls between two full fetches of the open channel, which catch deletions and edits of
+/// messages older than the newest.
+pub const FULL_POLL_EVERY: u32 = 30;
+
+/// Polls `channel_id` once. `newest_id` is its newest message loaded, and `polls` counts
+/// the polls since the last full fetch, `FULL_POLL_EVERY` for a channel just opened.
+/// Returns the action to send, if anything came in.
+pub async fn poll_channel(
+    api_client: &ApiClient,
+    channel_id: String,
+    newest_id: Option<String>,
+    split: bool,
+    polls: &mut u32,
+) -> Result<Option<AppAction>, ApiError> {
+    // Once a channel is loaded, only what came after its newest message is asked for,
+    // which is usually nothing.
+    let after = newest_id.filter(|_| *polls < FULL_POLL_EVERY);
+    let full = after.is_none();
+
+    let messages = api_client
+        .get_channel_messages(&channel_id, None, None, after, Some(POLL_LIMIT))
+        .await?;
+
+    // A full page after the newest message may not reach the present, so the next poll
+    // fetches the latest page.
+    *polls = if full {
+        0
+    } else if messages.len() >= POLL_LIMIT {
+        FULL_POLL_EVERY
+    } else {
+        *polls + 1
+    };
+
+    Ok(if !full && messages.is_empty() {
+        None
+    } else if split {
+        Some(AppAction::ApiSplitMessages(channel_id, messages, full))
+    } else if full {
+        Some(AppAction::ApiUpdateMessages(messages))
+    } else {
+        Some(AppAction::ApiUpdateNewerMessages(channel_id, messages))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path, query_param, query_param_is_missing},
+    };
+
+    use super::*;
+
+    fn page(count: usize) -> ResponseTemplate {
+        let messages: Vec<_> = (0..count)
+            .map(|i| {
+                json!({
+                    "id": (600 - i).to_string(),
+                    "channel_id": "10",
+                    "author": { "id": "1", "username": "someone" },
+                    "content": "hi",
+                    "timestamp": "2024-01-01T00:00:00Z",
+                    "edited_timestamp": null,
+                })
+            })
+            .collect();
+        ResponseTemplate::new(200).set_body_json(messages)
+    }
+
+    /// A server answering full fetches with `full` messages and fetches after message 500
+    /// with `newer`, each expected `times` times.
+    async fn server(full: (usize, u64), newer: (usize, u64)) -> (MockServer, ApiClient) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10/messages"))
+            .and(query_param_is_missing("after"))
+            .and(query_param("limit", "100"))
+            .respond_with(page(full.0))
+            .expect(full.1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/channels/10/messages"))
+            .and(query_param("after", "500"))
+            .and(query_param("limit", "100"))
+            .respond_with(page(newer.0))
+            .expect(newer.1)
+            .mount(&server)
+            .await;
+        let api_client = ApiClient::new(Client::new(), "token".to_string(), server.uri());
+        (server, api_client)
+    }
+
+    fn newest() -> Option<String> {
+        Some("500".to_string())
+    }
+
+    #[tokio::test]
+    async fn fetches_a_channel_just_opened_whole() {
+        let (_server, api_client) = server((3, 1), (0, 0)).await;
+        let mut polls = FULL_POLL_EVERY;
+
+        let action = poll_channel(&api_client, "10".into(), newest(), false, &mut polls)
+            .await
+            .unwrap();
+        assert!(matches!(action, Some(AppAction::ApiUpdateMessages(m)) if m.len() == 3));
+        assert_eq!(polls, 0);
+    }
+
+    #[tokio::test]
+    async fn then_asks_only_for_newer_messages() {
+        let (_server, api_client) = server((0, 0), (0, 2)).await;
+        let mut polls = 0;
+
+        for expected in 1..=2 {
+            let action = poll_channel(&api_client, "10".into(), newest(), false, &mut polls)
+                .await
+                .unwrap();
+            assert!(action.is_none());
+            assert_eq!(polls, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn hands_newer_messages_over_as_such() {
+        let (_server, api_client) = server((0, 0), (2, 1)).await;
+        let mut polls = 0;
+
+        let action = poll_channel(&api_client, "10".into(), newest(), false, &mut polls)
+            .await
+            .unwrap();
+        assert!(
+            matches!(action, Some(AppAction::ApiUpdateNewerMessages(id, m)) if id == "10" && m.len() == 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn refreshes_whole_every_so_often() {
+        let (_server, api_client) = server((1, 1), (0, 1)).await;
+        let mut polls = FULL_POLL_EVERY - 1;
+
+        poll_channel(&api_client, "10".into(), newest(), false, &mut polls)
+            .await
+            .unwrap();
+        assert_eq!(polls, FULL_POLL_EVERY);
+        poll_channel(&api_client, "10".into(), newest(), false, &mut polls)
+            .await
+            .unwrap();
+        assert_eq!(polls, 0);
+    }
+
+    #[tokio::test]
+    async fn refreshes_whole_after_a_gap_of_a_full_page() {
+        let (_server, api_client) = server((100, 1), (100, 1)).await;
+        let mut polls = 0;
+
+        poll_channel(&api_client, "10".into(), newest(), false, &mut polls)
+            .await
+            .unwrap();
+        // The newer messages may not reach the present.
+        assert_eq!(polls, FULL_POLL_EVERY);
+        poll_channel(&api_client, "10".into(), newest(), false, &mut polls)
+            .await
+            .unwrap();
+        assert_eq!(polls, 0);
+    }
+
+    #[tokio::test]
+    async fn fetches_an_empty_channel_whole() {
+        let (_server, api_client) = server((0, 1), (0, 0)).await;
+        let mut polls = 5;
+
+        let action = poll_channel(&api_client, "10".into(), None, true, &mut polls)
+            .await
+            .unwrap();
+        assert!(matches!(action, Some(AppAction::ApiSplitMessages(_, m, true)) if m.is_empty()));
+    }
+}
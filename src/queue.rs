@@ -0,0 +1,193 @@
+//! How actions reach the main loop. Keys and mouse input have a channel of their own,
+//! read before anything else, so a burst of updates from the API never holds typing up.
+//! Everything else shares a second, larger channel, which the reducer itself also sends
+//! follow-up actions into while the main loop waits on it, so it must rarely fill up.
+
+use std::sync::{Mutex, PoisonError};
+
+use tokio::sync::{
+    Notify,
+    mpsc::{Sender, error::TrySendError},
+};
+
+use crate::{
+    api::Message,
+    app::{AppAction, Command},
+    tasks::PendingRequests,
+};
+
+/// Room for keys and mouse input not handled yet.
+pub const INPUT_CAPACITY: usize = 64;
+/// Room for the other actions not handled yet.
+pub const ACTION_CAPACITY: usize = 1024;
+
+/// The latest full page of the open chat polled while the action channel was full. A
+/// newer page replaces one not handled yet, as it holds all of it and more.
+#[derive(Debug, Default)]
+pub struct ParkedMessages {
+    messages: Mutex<Option<Vec<Message>>>,
+    notify: Notify,
+}
+
+impl ParkedMessages {
+    fn park(&self, messages: Vec<Message>) {
+        *self.messages.lock().unwrap_or_else(PoisonError::into_inner) = Some(messages);
+        self.notify.notify_one();
+    }
+
+    /// Waits for a page to be parked, and takes it.
+    pub async fn next(&self) -> Option<Vec<Message>> {
+        self.notify.notified().await;
+        self.messages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
+
+/// Sends an action from the poller without waiting when the channel is full: full pages
+/// of messages are parked instead, and only the other actions wait for room. `false`
+/// once the main loop is gone.
+pub async fn send_or_park(
+    tx_action: &Sender<AppAction>,
+    parked: &ParkedMessages,
+    action: AppAction,
+) -> bool {
+    match tx_action.try_send(action) {
+        Ok(()) => true,
+        Err(TrySendError::Full(AppAction::ApiUpdateMessages(messages))) => {
+            parked.park(messages);
+            true
+        }
+        Err(TrySendError::Full(action)) => tx_action.send(action).await.is_ok(),
+        Err(TrySendError::Closed(_)) => false,
+    }
+}
+
+/// Carries out what applying an action asked for. The app is unlocked by then, so the
+/// tasks using it aren't held up while a dispatched action waits for room.
+pub async fn run_commands(
+    commands: Vec<Command>,
+    tx_action: &Sender<AppAction>,
+    pending_requests: &PendingRequests,
+) {
+    for command in commands {
+        match command {
+            Command::Dispatch(action) => {
+                tx_action.send(*action).await.ok();
+            }
+            Command::Spawn(task) => {
+                tokio::spawn(task);
+            }
+            Command::Track(request) => pending_requests.spawn(request),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+    use ratatui::{Terminal, backend::TestBackend};
+    use tokio::{
+        sync::{Mutex as AsyncMutex, mpsc},
+        time::{self, Duration},
+    };
+
+    use super::*;
+    use crate::{
+        app::{App, AppState, Commands, KeywordAction, apply_action},
+        ui::draw_ui,
+    };
+
+    const UPDATES: usize = 10_000;
+    const KEYS: usize = 200;
+
+    /// Runs the main loop as `run_app` does, drawing every action, while a poller floods
+    /// it with pages and ticks and keys are typed alongside.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn keys_stay_responsive_under_a_flood_of_updates() {
+        let mut app = App::for_tests();
+        app.vim_mode = false;
+        app.state = AppState::Chatting("c1".to_string());
+        let app_state = Arc::new(AsyncMutex::new(app));
+        let (tx_action, mut rx_action) = mpsc::channel(ACTION_CAPACITY);
+        let (tx_input, mut rx_input) = mpsc::channel(INPUT_CAPACITY);
+        let parked = Arc::new(ParkedMessages::default());
+        let typed_at = Arc::new(Mutex::new(VecDeque::new()));
+
+        let poller_state = Arc::clone(&app_state);
+        let tx_poller = tx_action.clone();
+        let parked_poller = Arc::clone(&parked);
+        tokio::spawn(async move {
+            for i in 0..UPDATES {
+                // The poller reads the app between requests too.
+                drop(poller_state.lock().await);
+                let action = if i % 2 == 0 {
+                    AppAction::ApiUpdateMessages(vec![Message::for_tests(
+                        &i.to_string(),
+                        &format!("update {i}"),
+                    )])
+                } else {
+                    AppAction::Tick
+                };
+                assert!(send_or_park(&tx_poller, &parked_poller, action).await);
+            }
+            // Handled after all the rest, which is how the loop knows the flood is over.
+            tx_poller.send(AppAction::SigInt).await.unwrap();
+        });
+
+        let typist_typed_at = Arc::clone(&typed_at);
+        tokio::spawn(async move {
+            for _ in 0..KEYS {
+                typist_typed_at.lock().unwrap().push_back(Instant::now());
+                tx_input.send(AppAction::InputChar('a')).await.unwrap();
+                time::sleep(Duration::from_millis(1)).await;
+            }
+        });
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let mut typed = 0;
+        let mut flooded = false;
+        let mut slowest = Duration::ZERO;
+        let main_loop = async {
+            while typed < KEYS || !flooded {
+                let mut state = app_state.lock().await;
+                terminal.draw(|f| draw_ui(f, &mut state)).unwrap();
+                drop(state);
+
+                let action = tokio::select! {
+                    biased;
+                    Some(action) = rx_input.recv() => action,
+                    Some(action) = rx_action.recv() => action,
+                    Some(messages) = parked.next() => AppAction::ApiUpdateMessages(messages),
+                };
+                if matches!(action, AppAction::InputChar(_)) {
+                    let sent = typed_at.lock().unwrap().pop_front().unwrap();
+                    slowest = slowest.max(sent.elapsed());
+                    typed += 1;
+                }
+
+                let (outcome, commands, pending_requests) = {
+                    let mut state = app_state.lock().await;
+                    let mut commands = Commands::new(tx_action.clone());
+                    let outcome = apply_action(&mut state, action, &mut commands);
+                    (outcome, commands.take(), state.pending_requests.clone())
+                };
+                run_commands(commands, &tx_action, &pending_requests).await;
+                if matches!(outcome, Some(KeywordAction::Break)) {
+                    flooded = true;
+                }
+            }
+        };
+        time::timeout(Duration::from_secs(120), main_loop)
+            .await
+            .expect("the main loop stalled");
+
+        assert_eq!(app_state.lock().await.input, "a".repeat(KEYS));
+        assert!(
+            slowest < Duration::from_millis(500),
+            "a key waited {slowest:?}"
+        );
+    }
+}
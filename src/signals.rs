@@ -1,33 +1,72 @@
-use std::sync::Once;
-use std::{io, process};
+//! Puts the terminal back the way it was however rivetui exits: normally, on Ctrl+C or a
+//! termination request (closing the console on Windows), or on a panic.
 
-use crossterm::terminal::disable_raw_mode;
-use crossterm::{event::DisableBracketedPaste, execute, terminal::LeaveAlternateScreen};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{io, panic, process};
 
-static INIT: Once = Once::new();
+use crossterm::terminal::{EnterAlternateScreen, disable_raw_mode, enable_raw_mode};
+use crossterm::{
+    cursor::SetCursorStyle,
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    execute,
+    terminal::LeaveAlternateScreen,
+};
 
+/// Set while the terminal is in raw mode on the alternate screen.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Switches the terminal to raw mode on the alternate screen, with bracketed paste, and
+/// mouse capture when `mouse` is set.
+pub fn enter_terminal(mouse: bool) -> io::Result<()> {
+    ACTIVE.store(true, Ordering::SeqCst);
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
+    if mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    Ok(())
+}
+
+/// Undoes `enter_terminal`. Only the first call after it does anything, so the Ctrl+C
+/// handler, the panic hook and the normal exit can all call it.
 pub fn restore_terminal() {
-    INIT.call_once(|| {
-        eprintln!("\nAttempting terminal cleanup...");
+    restore_to(&mut io::stdout());
+}
 
-        match disable_raw_mode() {
-            Ok(_) => (),
-            Err(e) => eprintln!("Failed to disable raw mode: {e}"),
-        }
+/// `restore_terminal`, writing the escape sequences to `out`.
+fn restore_to(out: &mut impl io::Write) {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
 
-        let mut stdout = io::stdout();
-        match execute!(stdout, LeaveAlternateScreen, DisableBracketedPaste) {
-            Ok(_) => (),
-            Err(e) => eprintln!("Failed to leave alternate screen: {e}"),
-        }
+    match disable_raw_mode() {
+        Ok(_) => (),
+        Err(e) => eprintln!("Failed to disable raw mode: {e}"),
+    }
 
-        match execute!(stdout, crossterm::cursor::Show) {
-            Ok(_) => (),
-            Err(e) => eprintln!("Failed to show cursor: {e}"),
-        }
-    });
+    match execute!(
+        out,
+        LeaveAlternateScreen,
+        DisableBracketedPaste,
+        DisableMouseCapture
+    ) {
+        Ok(_) => (),
+        Err(e) => eprintln!("Failed to leave alternate screen: {e}"),
+    }
+
+    match execute!(
+        out,
+        SetCursorStyle::DefaultUserShape,
+        crossterm::cursor::Show
+    ) {
+        Ok(_) => (),
+        Err(e) => eprintln!("Failed to show cursor: {e}"),
+    }
 }
 
+/// Restores the terminal on Ctrl+C, and on the termination requests the `ctrlc` crate
+/// handles: SIGTERM and SIGHUP on Unix, closing the console or logging off on Windows.
 pub fn setup_ctrlc_handler() {
     if let Err(e) = ctrlc::set_handler(move || {
         restore_terminal();
@@ -36,3 +75,46 @@ pub fn setup_ctrlc_handler() {
         eprintln!("Error setting Ctrl-C handler: {e}");
     }
 }
+
+/// Restores the terminal before a panic message is printed, as it would be lost on the
+/// alternate screen. A panic while the TUI is up then exits, even in a background task:
+/// the TUI can't go on drawing in a restored terminal.
+pub fn setup_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let was_active = ACTIVE.load(Ordering::SeqCst);
+        restore_terminal();
+        default_hook(info);
+        if was_active {
+            process::exit(101);
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One test, as they would share `ACTIVE`.
+    #[test]
+    fn restores_the_terminal_once() {
+        let mut out = Vec::new();
+        restore_to(&mut out);
+        assert!(out.is_empty());
+
+        // As after `enter_terminal`, without touching the terminal running the tests.
+        ACTIVE.store(true, Ordering::SeqCst);
+        restore_to(&mut out);
+        let written = String::from_utf8(out).unwrap();
+        assert!(
+            written.contains("\x1b[?1049l"),
+            "leaves the alternate screen"
+        );
+        assert!(written.ends_with("\x1b[?25h"), "shows the cursor");
+
+        // The panic hook and the normal exit may both get here.
+        let mut out = Vec::new();
+        restore_to(&mut out);
+        assert!(out.is_empty());
+    }
+}
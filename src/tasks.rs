@@ -0,0 +1,173 @@
+//! Short requests changing something on Discord, such as sending a message, run in the
+//! background but tracked, so that quitting right after one waits for it instead of
+//! dropping it halfway.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    task::{JoinError, JoinSet},
+    time::Instant,
+};
+
+/// How long quitting waits for the pending requests.
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+/// The requests still running. Each ends with what failed, if anything, for the report
+/// printed when quitting waited on it.
+#[derive(Debug, Clone, Default)]
+pub struct PendingRequests {
+    tasks: Arc<Mutex<JoinSet<Result<(), String>>>>,
+}
+
+impl PendingRequests {
+    /// Runs `task` in the background, like `tokio::spawn`, until it ends or quitting gives
+    /// up on it.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let Ok(mut tasks) = self.tasks.lock() else {
+            return;
+        };
+        // Those already done were reported on screen.
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(task);
+    }
+
+    /// Waits up to `timeout` for the requests still running, after telling `announce` how
+    /// many there are, if any. Returns what failed, and how many didn't end in time.
+    pub async fn finish(
+        &self,
+        timeout: Duration,
+        announce: impl FnOnce(usize),
+    ) -> (Vec<String>, usize) {
+        let Some(mut tasks) = self
+            .tasks
+            .lock()
+            .ok()
+            .map(|mut tasks| std::mem::take(&mut *tasks))
+        else {
+            return (Vec::new(), 0);
+        };
+
+        let mut failures = Vec::new();
+        let mut record = |result: Result<Result<(), String>, JoinError>| match result {
+            Ok(Ok(())) => {}
+            Ok(Err(failure)) => failures.push(failure),
+            Err(e) => failures.push(format!("A request stopped: {e}")),
+        };
+
+        while let Some(result) = tasks.try_join_next() {
+            record(result);
+        }
+        if !tasks.is_empty() {
+            announce(tasks.len());
+        }
+
+        let deadline = Instant::now() + timeout;
+        while let Ok(Some(result)) = tokio::time::timeout_at(deadline, tasks.join_next()).await {
+            record(result);
+        }
+        (failures, tasks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Client;
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, method, path},
+    };
+
+    use super::*;
+    use crate::{
+        api::ApiClient,
+        app::{App, AppAction, AppState, Commands, apply_action},
+        queue::{ACTION_CAPACITY, run_commands},
+    };
+
+    #[tokio::test]
+    async fn reports_failures_and_requests_given_up_on() {
+        let pending = PendingRequests::default();
+        pending.spawn(async { Ok(()) });
+        pending.spawn(async { Err("Failed to send message: boom".to_string()) });
+        pending.spawn(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        });
+
+        let mut announced = 0;
+        let (failures, unfinished) = pending
+            .finish(Duration::from_millis(100), |count| announced = count)
+            .await;
+        assert_eq!(failures, ["Failed to send message: boom"]);
+        assert_eq!(unfinished, 1);
+        assert!(announced >= 1);
+    }
+
+    #[tokio::test]
+    async fn announces_nothing_without_requests() {
+        let (failures, unfinished) = PendingRequests::default()
+            .finish(SHUTDOWN_GRACE, |_| panic!("nothing to wait for"))
+            .await;
+        assert!(failures.is_empty());
+        assert_eq!(unfinished, 0);
+    }
+
+    /// Quitting right after sending, while Discord takes its time to answer.
+    #[tokio::test]
+    async fn a_message_sent_just_before_quitting_still_arrives() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/channels/10/messages"))
+            .and(body_partial_json(json!({ "content": "bye" })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_delay(Duration::from_millis(500))
+                    .set_body_json(json!({
+                        "id": "11",
+                        "channel_id": "10",
+                        "author": { "id": "100", "username": "tester" },
+                        "content": "bye",
+                        "timestamp": "2024-01-01T00:00:00Z",
+                        "edited_timestamp": null,
+                    })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut state = App::for_tests();
+        state.api_client = ApiClient::new(Client::new(), "token".to_string(), server.uri());
+        state.vim_mode = false;
+        state.state = AppState::Chatting("10".to_string());
+        state.input = "bye".to_string();
+        state.cursor_position = state.input.len();
+
+        let (tx_action, mut rx_action) = mpsc::channel(ACTION_CAPACITY);
+        let mut action = Some(AppAction::InputSubmit);
+        while let Some(next) = action {
+            let mut commands = Commands::new(tx_action.clone());
+            apply_action(&mut state, next, &mut commands);
+            run_commands(commands.take(), &tx_action, &state.pending_requests).await;
+            action = rx_action.try_recv().ok();
+        }
+        assert!(state.input.is_empty());
+
+        let (failures, unfinished) = state.pending_requests.finish(SHUTDOWN_GRACE, |_| {}).await;
+        assert!(failures.is_empty(), "{failures:?}");
+        assert_eq!(unfinished, 0);
+        // Discord's answer came back before quitting went on.
+        let sent = std::iter::from_fn(|| rx_action.try_recv().ok())
+            .any(|action| matches!(action, AppAction::MessageSent(_, content) if content == "bye"));
+        assert!(sent);
+        server.verify().await;
+    }
+}
@@ -0,0 +1,75 @@
+//! Translation of messages through the backend set in the `translation` section of the
+//! config. Without one, nothing here is ever called.
+
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::config::{TranslationBackend, TranslationConfig};
+
+/// Response of LibreTranslate's `POST /translate`.
+#[derive(Deserialize)]
+struct LibreTranslation {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+/// Percent-encodes `text` for a query string.
+fn encode_component(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Translates `text` to the configured target language.
+pub async fn translate(
+    http_client: &Client,
+    config: &TranslationConfig,
+    text: &str,
+) -> Result<String, String> {
+    let request = match config.backend {
+        TranslationBackend::LibreTranslate => {
+            http_client.post(&config.url).json(&serde_json::json!({
+                "q": text,
+                "source": "auto",
+                "target": config.target,
+                "format": "text",
+                "api_key": config.api_key,
+            }))
+        }
+        TranslationBackend::Template => {
+            let url = config
+                .url
+                .replace("{target}", &encode_component(&config.target))
+                .replace(
+                    "{key}",
+                    &encode_component(config.api_key.as_deref().unwrap_or_default()),
+                )
+                .replace("{text}", &encode_component(text));
+            http_client.get(url)
+        }
+    };
+
+    let response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    match config.backend {
+        TranslationBackend::LibreTranslate => response
+            .json::<LibreTranslation>()
+            .await
+            .map(|translation| translation.translated_text)
+            .map_err(|e| format!("unexpected response: {e}")),
+        TranslationBackend::Template => response
+            .text()
+            .await
+            .map(|text| text.trim().to_string())
+            .map_err(|e| e.to_string()),
+    }
+}
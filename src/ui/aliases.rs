@@ -0,0 +1,110 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::{app::App, ui::sanitize::sanitize_line};
+
+/// Follows aliases wherever they stand in for a name, so they are never taken for the
+/// name the user chose on Discord.
+pub const ALIAS_MARKER: char = '†';
+
+/// The `/aliases` overlay listing the aliases, to remove some.
+#[derive(Debug, Clone, Default)]
+pub struct AliasList {
+    pub selection_index: usize,
+}
+
+/// The aliases as user id and alias, sorted by alias, the order the overlay lists them in.
+pub fn sorted_aliases(app: &App) -> Vec<(&str, &str)> {
+    let mut aliases: Vec<(&str, &str)> = app
+        .aliases
+        .iter()
+        .map(|(id, alias)| (id.as_str(), alias.as_str()))
+        .collect();
+    aliases.sort_by_key(|(id, alias)| (alias.to_lowercase(), *id));
+    aliases
+}
+
+/// How a name is shown once `alias` stands in for it.
+pub fn alias_label(alias: &str) -> String {
+    format!("{alias}{ALIAS_MARKER}")
+}
+
+pub fn draw_alias_list(f: &mut Frame, area: Rect, app: &App, list: &AliasList) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Aliases (d to remove, Esc to close)",
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let aliases = sorted_aliases(app);
+    let (items, selected) = if aliases.is_empty() {
+        (
+            vec![
+                ListItem::new("No aliases. Set one with /alias <user id> <name>.")
+                    .style(Style::default().fg(Color::DarkGray)),
+            ],
+            None,
+        )
+    } else {
+        let items = aliases
+            .into_iter()
+            .map(|(id, alias)| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(alias_label(&sanitize_line(alias))),
+                    Span::styled(format!("  {id}"), Style::default().fg(Color::DarkGray)),
+                ]))
+            })
+            .collect();
+        (items, Some(list.selection_index))
+    };
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default().with_selected(selected);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_stateful_widget(list, popup_rect, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_aliases_by_name_ignoring_case() {
+        let mut app = App::for_tests();
+        for (id, alias) in [("1", "carol"), ("2", "Bob"), ("3", "alice"), ("4", "bob")] {
+            app.aliases.insert(id.to_string(), alias.to_string());
+        }
+
+        assert_eq!(
+            sorted_aliases(&app),
+            [("3", "alice"), ("2", "Bob"), ("4", "bob"), ("1", "carol")]
+        );
+    }
+
+    #[test]
+    fn marks_aliases_as_such() {
+        assert_eq!(alias_label("Bob"), "Bob†");
+    }
+}
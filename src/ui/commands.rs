@@ -0,0 +1,392 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+
+use crate::{
+    api::{invite::parse_invite_code, user::OwnStatus},
+    app::App,
+    ui::links::{GotoTarget, parse_goto},
+};
+
+const SHRUG: &str = r"¯\_(ツ)_/¯";
+const TABLEFLIP: &str = "(╯°□°)╯︵ ┻━┻";
+
+/// Usage and description of every command, for `/help`.
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("/help", "List the commands"),
+    ("/quit", "Exit rivetui"),
+    (
+        "/join <channel>",
+        "Open a channel of the current server by name",
+    ),
+    (
+        "/join-invite <invite>",
+        "Preview an invite link or code, and join its server",
+    ),
+    (
+        "/goto <link or id>",
+        "Open a message link, or a channel or message by id",
+    ),
+    ("/search <text>", "Search the history of this channel"),
+    ("/clear", "Clear the loaded messages of this channel"),
+    ("/me <text>", "Send the text in italics"),
+    ("/shrug [text]", "Send the text followed by ¯\\_(ツ)_/¯"),
+    (
+        "/tableflip [text]",
+        "Send the text followed by (╯°□°)╯︵ ┻━┻",
+    ),
+    (
+        "/webhook [name] [avatar url]",
+        "Send through a webhook of this channel, as name if given",
+    ),
+    ("/webhook off", "Send as yourself again"),
+    (
+        "/status <status> [text]",
+        "Set online, idle, dnd or invisible, with a custom status",
+    ),
+    ("/status clear", "Remove the custom status"),
+    ("/ignore", "List the ignored users, to stop ignoring some"),
+    (
+        "/alias <user id> <name>",
+        "Show a user under a name of your own, kept on this computer",
+    ),
+    ("/alias remove <user id>", "Show the user's own name again"),
+    ("/aliases", "List the aliases, to remove some"),
+    ("//text", "Send text starting with a single /"),
+];
+
+/// A command typed in the input bar instead of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Quit,
+    Join(String),
+    /// Previews the invite with this code, to join its guild.
+    JoinInvite(String),
+    /// Opens the channel of a link or id, scrolled to its message if it has one.
+    Goto(GotoTarget),
+    Search(String),
+    Clear,
+    /// Opens the webhook picker, with the name and avatar URL to send as, if any.
+    Webhook(Option<String>, Option<String>),
+    WebhookOff,
+    Status(StatusChange),
+    /// Opens the list of ignored users.
+    Ignore,
+    Alias(AliasChange),
+    /// Opens the list of aliases.
+    Aliases,
+    /// Sends the text as a message, once the command made it.
+    Send(String),
+}
+
+/// What `/alias` does, to the user with this id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AliasChange {
+    Set(String, String),
+    Remove(String),
+}
+
+/// What `/status` changes of our presence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusChange {
+    /// The status, and a new custom status when text follows it.
+    Set(OwnStatus, Option<String>),
+    ClearText,
+}
+
+/// Reads the input as a command when it starts with `/`. `None` means it is a plain
+/// message, which `//` also forces, minus the first `/`. An unknown command or a missing
+/// argument is an error, so a mistyped command never ends up in the chat.
+pub fn parse_command(input: &str) -> Option<Result<Command, String>> {
+    let rest = input.strip_prefix('/')?;
+    if let Some(text) = rest.strip_prefix('/') {
+        return Some(Ok(Command::Send(format!("/{text}"))));
+    }
+
+    let (name, argument) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(name, argument)| (name, argument.trim()));
+    let required = |usage: &str| {
+        if argument.is_empty() {
+            Err(format!("Usage: {usage}"))
+        } else {
+            Ok(argument.to_string())
+        }
+    };
+
+    let command = match name {
+        "help" => Ok(Command::Help),
+        "quit" => Ok(Command::Quit),
+        "join" => required("/join <channel>").map(Command::Join),
+        "join-invite" => required("/join-invite <invite link or code>").and_then(|argument| {
+            parse_invite_code(&argument)
+                .map(Command::JoinInvite)
+                .ok_or_else(|| format!("\"{argument}\" is not an invite link or code."))
+        }),
+        "goto" => required("/goto <link or id>").and_then(|argument| {
+            parse_goto(&argument)
+                .map(Command::Goto)
+                .ok_or_else(|| format!("\"{argument}\" is not a Discord link or id."))
+        }),
+        "search" => required("/search <text>").map(Command::Search),
+        "clear" => Ok(Command::Clear),
+        "webhook" if argument == "off" => Ok(Command::WebhookOff),
+        "webhook" => Ok(webhook_command(argument)),
+        "status" if argument == "clear" => Ok(Command::Status(StatusChange::ClearText)),
+        "status" => required("/status <online|idle|dnd|invisible> [text]")
+            .and_then(|argument| status_command(&argument)),
+        "ignore" => Ok(Command::Ignore),
+        "alias" => {
+            required("/alias <user id> <name>").and_then(|argument| alias_command(&argument))
+        }
+        "aliases" => Ok(Command::Aliases),
+        "me" => required("/me <text>").map(|text| Command::Send(format!("_{text}_"))),
+        "shrug" => Ok(Command::Send(with_suffix(argument, SHRUG))),
+        "tableflip" => Ok(Command::Send(with_suffix(argument, TABLEFLIP))),
+        _ => Err(format!(
+            "Unknown command /{name}, see /help. Start with // to send it as a message."
+        )),
+    };
+    Some(command)
+}
+
+/// Splits the argument of `/webhook` into the name to send as and, when it ends with a
+/// link, the avatar URL.
+fn webhook_command(argument: &str) -> Command {
+    let (name, avatar_url) = match argument.rsplit_once(char::is_whitespace) {
+        Some((name, url)) if url.starts_with("https://") || url.starts_with("http://") => {
+            (name.trim(), Some(url.to_string()))
+        }
+        _ if argument.starts_with("https://") || argument.starts_with("http://") => {
+            ("", Some(argument.to_string()))
+        }
+        _ => (argument, None),
+    };
+    let name = (!name.is_empty()).then(|| name.to_string());
+    Command::Webhook(name, avatar_url)
+}
+
+fn alias_command(argument: &str) -> Result<Command, String> {
+    let (user, name) = argument
+        .split_once(char::is_whitespace)
+        .map_or((argument, ""), |(user, name)| (user, name.trim()));
+    if user == "remove" {
+        let user_id = parse_user_id(name)
+            .ok_or_else(|| "Usage: /alias remove <user id or mention>".to_string())?;
+        return Ok(Command::Alias(AliasChange::Remove(user_id)));
+    }
+
+    let user_id =
+        parse_user_id(user).ok_or_else(|| format!("\"{user}\" is not a user id or mention."))?;
+    if name.is_empty() {
+        return Err("Usage: /alias <user id> <name>".to_string());
+    }
+    Ok(Command::Alias(AliasChange::Set(user_id, name.to_string())))
+}
+
+/// Reads a user id, bare or as the `<@id>` markup of a mention.
+fn parse_user_id(text: &str) -> Option<String> {
+    let id = text
+        .strip_prefix("<@")
+        .and_then(|rest| rest.strip_suffix('>'))
+        .map_or(text, |id| id.strip_prefix('!').unwrap_or(id));
+    (!id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())).then(|| id.to_string())
+}
+
+fn status_command(argument: &str) -> Result<Command, String> {
+    let (word, text) = argument
+        .split_once(char::is_whitespace)
+        .map_or((argument, ""), |(word, text)| (word, text.trim()));
+    let status = OwnStatus::parse(word)
+        .ok_or_else(|| format!("Unknown status \"{word}\": use online, idle, dnd or invisible."))?;
+    let text = (!text.is_empty()).then(|| text.to_string());
+    Ok(Command::Status(StatusChange::Set(status, text)))
+}
+
+fn with_suffix(text: &str, suffix: &str) -> String {
+    if text.is_empty() {
+        suffix.to_string()
+    } else {
+        format!("{text} {suffix}")
+    }
+}
+
+pub fn draw_command_help(f: &mut Frame, area: Rect, app: &App) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (COMMANDS.len() as u16 + 2).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Commands (Esc to close)",
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let usage_width = COMMANDS
+        .iter()
+        .map(|(usage, _)| usage.len())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = COMMANDS
+        .iter()
+        .map(|(usage, description)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{usage:<usage_width$}  "),
+                    Style::default().fg(Color::LightCyan),
+                ),
+                Span::raw(*description),
+            ])
+        })
+        .collect();
+
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(Paragraph::new(lines).block(block), popup_rect);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Command, String> {
+        parse_command(input).expect("a command")
+    }
+
+    #[test]
+    fn leaves_plain_messages_alone() {
+        assert_eq!(parse_command("hello /quit"), None);
+        assert_eq!(parse_command(""), None);
+    }
+
+    #[test]
+    fn parses_commands_and_their_arguments() {
+        assert_eq!(parse("/help"), Ok(Command::Help));
+        assert_eq!(parse("/quit"), Ok(Command::Quit));
+        assert_eq!(parse("/clear"), Ok(Command::Clear));
+        assert_eq!(
+            parse("/join   off-topic  "),
+            Ok(Command::Join("off-topic".to_string()))
+        );
+    }
+
+    #[test]
+    fn builds_the_text_of_sending_commands() {
+        assert_eq!(parse("/me waves"), Ok(Command::Send("_waves_".to_string())));
+        assert_eq!(parse("/shrug"), Ok(Command::Send(SHRUG.to_string())));
+        assert_eq!(
+            parse("/tableflip no"),
+            Ok(Command::Send(format!("no {TABLEFLIP}")))
+        );
+        assert_eq!(
+            parse("//quit is a command"),
+            Ok(Command::Send("/quit is a command".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_missing_arguments() {
+        assert!(
+            parse("/qiut")
+                .unwrap_err()
+                .contains("Unknown command /qiut")
+        );
+        assert_eq!(parse("/join"), Err("Usage: /join <channel>".to_string()));
+        assert_eq!(parse("/me  "), Err("Usage: /me <text>".to_string()));
+    }
+
+    #[test]
+    fn lists_every_command_in_the_help() {
+        for name in ["help", "quit", "join", "clear", "me", "shrug", "tableflip"] {
+            assert!(
+                COMMANDS
+                    .iter()
+                    .any(|(usage, _)| usage.starts_with(&format!("/{name}"))),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_goto() {
+        assert_eq!(
+            parse("/goto 42"),
+            Ok(Command::Goto(GotoTarget::Id("42".to_string())))
+        );
+        assert_eq!(
+            parse("/goto https://example.com"),
+            Err("\"https://example.com\" is not a Discord link or id.".to_string())
+        );
+        assert_eq!(parse("/goto"), Err("Usage: /goto <link or id>".to_string()));
+    }
+
+    #[test]
+    fn parses_join_invite() {
+        assert_eq!(
+            parse("/join-invite https://discord.gg/abc123"),
+            Ok(Command::JoinInvite("abc123".to_string()))
+        );
+        assert_eq!(
+            parse("/join-invite https://example.com/x"),
+            Err("\"https://example.com/x\" is not an invite link or code.".to_string())
+        );
+        assert_eq!(
+            parse("/join-invite"),
+            Err("Usage: /join-invite <invite link or code>".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_aliases_of_ids_and_mentions() {
+        let set = |id: &str, name: &str| {
+            Ok(Command::Alias(AliasChange::Set(
+                id.to_string(),
+                name.to_string(),
+            )))
+        };
+        assert_eq!(
+            parse("/alias 42 Bob the Builder"),
+            set("42", "Bob the Builder")
+        );
+        assert_eq!(parse("/alias <@42> Bob"), set("42", "Bob"));
+        assert_eq!(parse("/alias <@!42> Bob"), set("42", "Bob"));
+        assert_eq!(
+            parse("/alias remove <@42>"),
+            Ok(Command::Alias(AliasChange::Remove("42".to_string())))
+        );
+        assert_eq!(parse("/aliases"), Ok(Command::Aliases));
+    }
+
+    #[test]
+    fn refuses_aliases_without_an_id_or_name() {
+        assert_eq!(
+            parse("/alias bob Bob"),
+            Err("\"bob\" is not a user id or mention.".to_string())
+        );
+        assert_eq!(
+            parse("/alias 42"),
+            Err("Usage: /alias <user id> <name>".to_string())
+        );
+        assert_eq!(
+            parse("/alias remove"),
+            Err("Usage: /alias remove <user id or mention>".to_string())
+        );
+        assert_eq!(
+            parse("/alias"),
+            Err("Usage: /alias <user id> <name>".to_string())
+        );
+        assert_eq!(parse_user_id("<@>"), None);
+    }
+}
@@ -1,29 +1,1020 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Local, TimeDelta, TimeZone, Utc};
 use ratatui::{
+    layout::Rect,
     style::{Color, Style, Stylize},
-    text::Span,
-    widgets::{BorderType, Clear, List, ListItem, ListState},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
 };
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 use crate::{
-    App, AppState,
-    api::{Channel, DM, Emoji, Guild, Message},
+    api::{
+        Channel, DM, Guild, Message,
+        message::{MAX_MESSAGE_LENGTH, message_length},
+    },
+    app::{App, AppState, ListArea},
+    config::{Density, PinKind, TimeFormat},
+    ui::{
+        aliases::{alias_label, draw_alias_list},
+        commands::draw_command_help,
+        emojis::{EmojiManager, draw_emoji_manager},
+        errors::{draw_error_log, error_segment},
+        filter::filter_guilds,
+        forum::draw_forum,
+        ignore::{Collapse, collapse, draw_ignore_list, placeholder_line},
+        invite::draw_invite_prompt,
+        keymap::draw_key_help,
+        links::{Permalink, parse_permalink},
+        markdown::{MdLine, MdSpan, parse_markdown},
+        mentions::{Segment, parse_mentions},
+        notifications::draw_notifications,
+        pins::draw_pins,
+        presence::{own_presence_segment, presence_span},
+        preview::draw_preview,
+        profile::draw_profile,
+        rows::RowKey,
+        sanitize::{sanitize, sanitize_line},
+        search::draw_search,
+        shortcodes::expand_shortcodes,
+        split::draw_split_pane,
+        switcher::draw_switcher,
+        system::system_text,
+        theme::author_color,
+        webhooks::draw_webhook_picker,
+        wrap::wrap_line,
+    },
 };
 
+const CODE_BACKGROUND: Color = Color::Rgb(45, 45, 45);
+
+/// Messages from the same author sent closer together than this share one header.
+const GROUP_WINDOW: TimeDelta = TimeDelta::minutes(5);
+/// The same in cozy mode, where Discord keeps groups together a little longer.
+const COZY_GROUP_WINDOW: TimeDelta = TimeDelta::minutes(7);
+
+/// Indent of the messages under their header line in cozy mode.
+const COZY_INDENT: &str = "  ";
+
+/// Shown instead of the time of a message without a readable timestamp.
+pub const UNKNOWN_TIME: &str = "??:??";
+
+/// Frames of the loading spinners, one per tick.
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Between the segments of the status bar.
+const STATUS_SEPARATOR: &str = " │ ";
+
+/// Messages longer than this show their length next to the limit in the input title.
+const LENGTH_COUNTER_FROM: usize = 1800;
+
+/// Below this size only a placeholder is drawn.
+const MIN_WIDTH: u16 = 10;
+const MIN_HEIGHT: u16 = 5;
+
+/// Colors of the guild badges, picked from the guild id.
+const BADGE_COLORS: [Color; 6] = [
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+];
+
+/// Cuts `text` to at most `width` terminal columns, ending it with `…` when shortened.
+/// Wide characters such as CJK and emoji count for two columns, and characters made of
+/// several code points, like flags or emoji with skin tones, are never split.
+fn truncate_to_width(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if used + grapheme_width + 1 > width {
+            break;
+        }
+        used += grapheme_width;
+        truncated.push_str(grapheme);
+    }
+    if width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// Two-letter badge standing in for a guild's icon, e.g. `[GN]` for "Guild Name", in a
+/// color derived from the guild id so it stays the same between runs.
+fn guild_badge(guild: &Guild) -> Span<'static> {
+    let mut initials: String = guild
+        .name
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .take(2)
+        .collect();
+    if initials.chars().count() < 2 {
+        initials = guild
+            .name
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .take(2)
+            .collect();
+    }
+    let initials = initials.to_uppercase();
+
+    let hash = guild.id.bytes().fold(0u64, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(u64::from(b))
+    });
+    let color = BADGE_COLORS[(hash % BADGE_COLORS.len() as u64) as usize];
+
+    Span::styled(
+        format!("[{initials:<2}] "),
+        Style::default().fg(color).bold(),
+    )
+}
+
+/// Topic of a guild channel, if it is in the loaded channel list and has one.
+fn channel_topic<'a>(app: &'a App, channel_id: &str) -> Option<&'a str> {
+    app.channels
+        .iter()
+        .chain(
+            app.channels
+                .iter()
+                .flat_map(|c| c.children.iter().flatten()),
+        )
+        .find(|c| c.id == channel_id)
+        .and_then(|c| c.topic.as_deref())
+        .filter(|topic| !topic.trim().is_empty())
+}
+
+/// Display name and color for a message author, from the member cache of the open
+/// guild. Uncached authors fall back to their username, and authors without a role color
+/// get their color of the theme's palette. An alias replaces the name, marked as one.
+fn author_style(app: &App, message: &Message) -> (String, Color) {
+    let palette_color =
+        author_color(&message.author.id, &app.theme.author_palette).unwrap_or(app.theme.author);
+    let alias = app.alias(&message.author.id);
+    let (name, color) = app
+        .chat_guild_id
+        .as_ref()
+        .and_then(|guild_id| app.member_cache.get(guild_id))
+        .and_then(|members| members.get(&message.author.id))
+        .map(|(name, color)| {
+            let color = color.map_or(palette_color, |c| {
+                Color::Rgb((c >> 16) as u8, (c >> 8) as u8, c as u8)
+            });
+            (sanitize_line(alias.unwrap_or(name)), color)
+        })
+        .unwrap_or_else(|| {
+            (
+                sanitize_line(alias.unwrap_or(&message.author.username)),
+                palette_color,
+            )
+        });
+    let name = match alias {
+        Some(_) => alias_label(&name),
+        None => name.into_owned(),
+    };
+    (name, color)
+}
+
+/// The unread channels of a guild after its name, or `(?)` while its channels were never
+/// fetched. Muted guilds show none.
+fn unread_badge(app: &App, guild: &Guild) -> Span<'static> {
+    if app.muted.contains(&guild.id) {
+        return Span::raw("");
+    }
+    if !app.channel_cache.contains_key(&guild.id) {
+        return Span::styled(" (?)", Style::default().fg(Color::DarkGray));
+    }
+    match app.guild_unread.get(&guild.id) {
+        Some(&count) if count > 0 => Span::styled(
+            format!(" ({count})"),
+            Style::default().fg(Color::White).bold(),
+        ),
+        _ => Span::raw(""),
+    }
+}
+
+/// A message on one line as `[time] author: content`, with its raw content.
+pub fn message_header_line(app: &App, message: &Message) -> String {
+    format!(
+        "[{}] {}: {}",
+        format_time(message.timestamp, &TimeFormat::Long, Utc::now(), &Local),
+        author_style(app, message).0,
+        message.content.as_deref().unwrap_or_default(),
+    )
+}
+
+/// How a Discord link into the open guild is shown: `#channel → jump`. Links elsewhere
+/// stay as they are.
+fn permalink_label(app: &App, link: &Permalink) -> Option<String> {
+    if link.guild_id.is_none() || link.guild_id != app.chat_guild_id {
+        return None;
+    }
+    let name = app
+        .channels
+        .iter()
+        .flat_map(|c| std::iter::once(c).chain(c.children.iter().flatten()))
+        .find(|c| c.id == link.channel_id)
+        .map_or("unknown-channel", |c| c.name.as_str());
+    Some(format!("#{name} → jump"))
+}
+
+/// Splits `text` into spans with user, role and channel mentions resolved to `@username`,
+/// `@role` and `#channel-name`, custom emojis shown as `:name:` and links into the open
+/// guild shown as `#channel → jump`.
+fn content_spans(app: &App, message: &Message, text: &str) -> Vec<Span<'static>> {
+    parse_mentions(text)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => {
+                Span::styled(text.to_string(), Style::default().fg(Color::White))
+            }
+            Segment::Link(url, link) => match permalink_label(app, &link) {
+                Some(label) => Span::styled(label, Style::default().fg(Color::LightCyan).bold()),
+                None => Span::styled(url.to_string(), Style::default().fg(Color::White)),
+            },
+            Segment::User(id) => {
+                let name = match app.alias(id) {
+                    Some(alias) => alias_label(alias),
+                    None => message
+                        .mentions
+                        .iter()
+                        .find(|u| u.id == id)
+                        .map_or("unknown-user", |u| u.username.as_str())
+                        .to_string(),
+                };
+                Span::styled(
+                    format!("@{name}"),
+                    Style::default().fg(Color::LightMagenta).bold(),
+                )
+            }
+            Segment::Channel(id) => {
+                let name = app
+                    .channels
+                    .iter()
+                    .flat_map(|c| c.children.iter().flatten())
+                    .find(|c| c.id == id)
+                    .map_or("unknown-channel", |c| c.name.as_str());
+                Span::styled(
+                    format!("#{name}"),
+                    Style::default().fg(Color::LightCyan).bold(),
+                )
+            }
+            Segment::Role(id) => {
+                let role = app
+                    .context
+                    .as_ref()
+                    .and_then(|context| context.all_guild_roles.iter().find(|r| r.id == id));
+                let name = role.map_or("unknown-role", |r| r.name.as_str());
+                let color = match role {
+                    Some(role) if role.color != 0 => {
+                        let c = role.color;
+                        Color::Rgb((c >> 16) as u8, (c >> 8) as u8, c as u8)
+                    }
+                    _ => Color::LightMagenta,
+                };
+                Span::styled(format!("@{name}"), Style::default().fg(color).bold())
+            }
+            Segment::Emoji { name, id, .. } => {
+                // The guild's emoji list has the current name when the emoji was renamed.
+                let name = app
+                    .custom_emojis
+                    .iter()
+                    .chain(app.emoji_cache.values().flatten())
+                    .find(|e| e.id == id)
+                    .map_or(name, |e| e.name.as_str());
+                Span::styled(format!(":{name}:"), Style::default().fg(Color::LightYellow))
+            }
+        })
+        .collect()
+}
+
+fn spoilers_revealed(app: &App, message: &Message) -> bool {
+    !app.content_warnings || app.revealed_spoilers.contains(&message.id)
+}
+
+/// Renders the markdown of a message into the spans of each line. Spoilers stay hidden
+/// behind `░` until revealed, and a code block opening the message moves below the header.
+fn markdown_lines(app: &App, message: &Message, show_header: bool) -> Vec<Vec<Span<'static>>> {
+    let revealed = spoilers_revealed(app, message);
+    let parsed = parse_markdown(&sanitize(message_content(message)));
+    let code_first = matches!(parsed.first(), Some(MdLine::Code(_)));
+
+    let lines = parsed.into_iter().map(|line| match line {
+        MdLine::Code(code) => vec![Span::styled(
+            format!("  {code}"),
+            Style::default().fg(Color::White).bg(CODE_BACKGROUND),
+        )],
+        MdLine::Text(spans) => spans
+            .into_iter()
+            .flat_map(|span| markdown_span(app, message, span, revealed))
+            .collect(),
+    });
+
+    if code_first && show_header {
+        std::iter::once(Vec::new()).chain(lines).collect()
+    } else {
+        lines.collect()
+    }
+}
+
+fn markdown_span(app: &App, message: &Message, span: MdSpan, revealed: bool) -> Vec<Span<'static>> {
+    let md = span.style;
+
+    if md.spoiler && !revealed {
+        let width = UnicodeWidthStr::width(span.text.as_str());
+        return vec![Span::styled(
+            "░".repeat(width),
+            Style::default().fg(Color::DarkGray),
+        )];
+    }
+
+    let mut style = Style::default();
+    if md.bold {
+        style = style.bold();
+    }
+    if md.italic {
+        style = style.italic();
+    }
+    if md.underline {
+        style = style.underlined();
+    }
+    if md.strikethrough {
+        style = style.crossed_out();
+    }
+
+    if md.code {
+        return vec![Span::styled(
+            span.text,
+            style.fg(Color::White).bg(CODE_BACKGROUND),
+        )];
+    }
+
+    content_spans(app, message, &span.text)
+        .into_iter()
+        .map(|s| s.patch_style(style))
+        .collect()
+}
+
+fn message_content(message: &Message) -> &str {
+    match message.content.as_deref() {
+        Some(content) if !content.is_empty() => content,
+        _ if !message.attachments.is_empty()
+            || !message.embeds.is_empty()
+            || !message.sticker_items.is_empty() =>
+        {
+            ""
+        }
+        _ => "(*non-text*)",
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Lines describing a message's stickers, attachments and embeds, shown below its content.
+/// Spoiler attachments only show their size until `revealed`.
+fn attachment_lines(message: &Message, revealed: bool) -> Vec<(String, Color)> {
+    const DESCRIPTION_CHARS: usize = 200;
+
+    let mut lines = Vec::new();
+
+    for sticker in &message.sticker_items {
+        lines.push((format!("[sticker: {}]", sticker.name), Color::LightMagenta));
+    }
+
+    for attachment in &message.attachments {
+        if attachment.is_spoiler() && !revealed {
+            lines.push((
+                format!("[spoiler attachment ({})]", format_size(attachment.size)),
+                Color::DarkGray,
+            ));
+            continue;
+        }
+        lines.push((
+            format!(
+                "[attachment: {} ({})] {}",
+                attachment.filename,
+                format_size(attachment.size),
+                attachment.url
+            ),
+            Color::LightGreen,
+        ));
+    }
+
+    for embed in &message.embeds {
+        if let Some(title) = &embed.title {
+            lines.push((format!("  │ {title}"), Color::LightCyan));
+        }
+
+        if let Some(description) = &embed.description {
+            let mut truncated: String = description.chars().take(DESCRIPTION_CHARS).collect();
+            if description.chars().count() > DESCRIPTION_CHARS {
+                truncated.push('…');
+            }
+            for line in truncated.lines() {
+                lines.push((format!("  │ {line}"), Color::Gray));
+            }
+        }
+
+        if let Some(url) = &embed.url {
+            lines.push((format!("  │ {url}"), Color::DarkGray));
+        }
+    }
+
+    lines
+}
+
+/// The numbered links of a message while the Ctrl+L link numbers are shown.
+fn link_lines(app: &App, message: &Message) -> Vec<(String, Color)> {
+    let Some(hints) = &app.link_hints else {
+        return Vec::new();
+    };
+    hints
+        .of_message(&message.id)
+        .map(|(number, url)| {
+            let label = parse_permalink(url)
+                .and_then(|link| permalink_label(app, &link))
+                .unwrap_or_else(|| url.to_string());
+            (format!("  [{number}] {label}"), Color::LightBlue)
+        })
+        .collect()
+}
+
+/// One-line summary of the message being replied to, shown above the reply.
+fn reply_preview(message: &Message) -> Option<String> {
+    const PREVIEW_CHARS: usize = 60;
+
+    let referenced = message.referenced_message.as_ref()?;
+    let content = referenced.content.as_deref().unwrap_or("(*non-text*)");
+    let first_line = content.lines().next().unwrap_or("");
+
+    let mut preview: String = first_line.chars().take(PREVIEW_CHARS).collect();
+    if first_line.chars().count() > PREVIEW_CHARS || content.lines().count() > 1 {
+        preview.push('…');
+    }
+
+    Some(format!(
+        "↳ replying to {}: {preview}",
+        referenced.author.username
+    ))
+}
+
+/// Label of the separator drawn above `message` when it is the first of its day, with
+/// `previous` being the message right before it.
+fn day_separator(previous: Option<&Message>, message: &Message) -> Option<String> {
+    let local_day = |m: &Message| m.timestamp.map(|t| t.with_timezone(&Local).date_naive());
+    let day = local_day(message)?;
+    if previous.is_some_and(|p| local_day(p) == Some(day)) {
+        return None;
+    }
+
+    let today = Local::now().date_naive();
+    let label = if day == today {
+        "Today".to_string()
+    } else if today.pred_opt() == Some(day) {
+        "Yesterday".to_string()
+    } else {
+        day.format("%Y-%m-%d").to_string()
+    };
+
+    Some(format!("──── {label} ────"))
+}
+
+/// Remembers where the list of the screen was drawn and how far it was scrolled, so
+/// clicks can be mapped to its first `len` rows, past the `headings` rows.
+fn track_list(app: &mut App, area: Rect, state: &ListState, len: usize, headings: Vec<usize>) {
+    app.list_area = Some(ListArea {
+        area,
+        offset: state.offset(),
+        len,
+        headings,
+    });
+}
+
+/// When `message` was sent, in the time format picked in the config or with F3.
+fn message_time(app: &App, message: &Message) -> String {
+    format_time(message.timestamp, &app.time_format, Utc::now(), &Local)
+}
+
+/// `timestamp` in `format` as seen in the timezone `tz`, relative to `now` for the
+/// relative format. A missing timestamp shows as `??:??` whatever the format.
+fn format_time<Tz: TimeZone>(
+    timestamp: Option<DateTime<Utc>>,
+    format: &TimeFormat,
+    now: DateTime<Utc>,
+    tz: &Tz,
+) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let Some(timestamp) = timestamp else {
+        return UNKNOWN_TIME.to_string();
+    };
+    let local = timestamp.with_timezone(tz);
+    match format {
+        TimeFormat::Short => local.format("%H:%M").to_string(),
+        TimeFormat::Long => local.format("%H:%M:%S").to_string(),
+        TimeFormat::Relative => relative_time(now - timestamp),
+        TimeFormat::Custom(format) => local.format(format).to_string(),
+    }
+}
+
+/// How long ago something happened, in its largest whole unit: "now", "5m ago", "3h ago"
+/// or "2d ago".
+fn relative_time(elapsed: TimeDelta) -> String {
+    match elapsed {
+        elapsed if elapsed < TimeDelta::minutes(1) => "now".to_string(),
+        elapsed if elapsed < TimeDelta::hours(1) => format!("{}m ago", elapsed.num_minutes()),
+        elapsed if elapsed < TimeDelta::days(1) => format!("{}h ago", elapsed.num_hours()),
+        elapsed => format!("{}d ago", elapsed.num_days()),
+    }
+}
+
+/// Whether `message` is shown without its `[time] author:` header, continuing the one of
+/// `previous` like Discord does for quick successive messages.
+fn continues_group(previous: Option<&Message>, message: &Message, density: Density) -> bool {
+    let window = match density {
+        Density::Compact => GROUP_WINDOW,
+        Density::Cozy => COZY_GROUP_WINDOW,
+    };
+    previous.is_some_and(|previous| {
+        previous.author.id == message.author.id
+            && message
+                .timestamp
+                .zip(previous.timestamp)
+                .is_some_and(|(sent, previous_sent)| sent - previous_sent < window)
+            && message.referenced_message.is_none()
+            && [previous, message]
+                .iter()
+                .all(|m| system_text(m, "").is_none())
+            && day_separator(Some(previous), message).is_none()
+    })
+}
+
+/// The lines of `message` as the chat shows them, with its `[time] author:` header when
+/// `show_header` is set. In compact mode the header starts the first line; in cozy mode
+/// it is a line of its own after a blank one, and the message is indented below it.
+pub fn message_lines(
+    app: &App,
+    message: &Message,
+    show_header: bool,
+    density: Density,
+    line_style: Style,
+) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    let cozy = density == Density::Cozy;
+
+    let formatted_time = message_time(app, message);
+
+    let (author_name, author_color) = author_style(app, message);
+    let author = format!(" {author_name}: ");
+
+    // System messages, such as joins and pins, are a dim line of their own without the
+    // author header.
+    let system = system_text(message, &author_name).map(|text| sanitize_line(&text).into_owned());
+    let content_lines = match &system {
+        Some(text) => vec![vec![Span::styled(
+            format!(" {text}"),
+            Style::default().fg(Color::DarkGray).italic(),
+        )]],
+        None => markdown_lines(app, message, show_header && !cozy),
+    };
+
+    if cozy && show_header {
+        lines.push(Line::default().style(line_style));
+    }
+    if let Some(preview) = reply_preview(message) {
+        lines.push(
+            Line::from(Span::styled(
+                preview,
+                Style::default().fg(Color::Gray).italic(),
+            ))
+            .style(line_style),
+        );
+    }
+    if cozy && show_header && system.is_none() {
+        lines.push(
+            Line::from(vec![
+                Span::styled(
+                    author_name.clone(),
+                    Style::default().fg(author_color).bold(),
+                ),
+                Span::styled(
+                    format!("  {formatted_time}"),
+                    Style::default().fg(app.theme.timestamp),
+                ),
+            ])
+            .style(line_style),
+        );
+    }
+
+    let line_count = content_lines.len();
+    for (i, line_content) in content_lines.into_iter().enumerate() {
+        let mut spans = vec![];
+
+        if cozy {
+            spans.push(Span::raw(COZY_INDENT));
+        } else if i == 0 && show_header {
+            spans.push(Span::styled(
+                format!("[{formatted_time}]"),
+                Style::default().fg(app.theme.timestamp),
+            ));
+            if system.is_none() {
+                spans.push(Span::styled(
+                    author.clone(),
+                    Style::default().fg(author_color),
+                ));
+            }
+        }
+
+        spans.extend(line_content);
+
+        if i + 1 == line_count && message.edited_timestamp.is_some() {
+            spans.push(Span::styled(
+                " (edited)",
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if i + 1 == line_count && message.is_crossposted() {
+            spans.push(Span::styled(
+                " 📣 published",
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if i + 1 == line_count && message.id.is_empty() {
+            spans.push(Span::styled(
+                " (sending…)",
+                Style::default().fg(app.theme.pending).italic(),
+            ));
+        }
+        lines.push(Line::from(spans).style(line_style));
+    }
+
+    if let Some(translation) = app.translations.get(&message.id) {
+        let text = translation.as_deref().unwrap_or("translating…");
+        for line in text.lines() {
+            lines.push(
+                Line::from(Span::styled(
+                    format!("  ⇄ {line}"),
+                    Style::default().fg(Color::DarkGray),
+                ))
+                .style(line_style),
+            );
+        }
+    }
+
+    for (line, color) in attachment_lines(message, spoilers_revealed(app, message))
+        .into_iter()
+        .chain(link_lines(app, message))
+    {
+        lines.push(Line::from(Span::styled(line, Style::default().fg(color))).style(line_style));
+    }
+
+    if !message.reactions.is_empty() {
+        let mut spans = vec![Span::raw("  ")];
+        for reaction in &message.reactions {
+            let color = if reaction.me {
+                Color::LightYellow
+            } else {
+                Color::Gray
+            };
+            spans.push(Span::styled(
+                format!("[{} {}]", reaction.emoji.display(), reaction.count),
+                Style::default().fg(color),
+            ));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Line::from(spans).style(line_style));
+    }
+
+    // Names, file names and the like come from Discord as much as the content does.
+    for span in lines.iter_mut().flat_map(|line| line.spans.iter_mut()) {
+        if let Cow::Owned(sanitized) = sanitize_line(&span.content) {
+            span.content = sanitized.into();
+        }
+    }
+
+    lines
+}
+
+/// Key of the rows of `message` in the row cache. Everything else they show, such as
+/// the names mentions resolve to, bumps `App::rows_generation` when it changes.
+fn row_key(
+    app: &App,
+    message: &Message,
+    separator: Option<&str>,
+    show_header: bool,
+    collapse: Collapse,
+    width: usize,
+) -> RowKey {
+    RowKey {
+        message_id: message.id.clone(),
+        edited_timestamp: message.edited_timestamp.clone(),
+        width,
+        time_format: app.time_format.clone(),
+        relative_minute: (app.time_format == TimeFormat::Relative)
+            .then(|| Utc::now().timestamp() / 60),
+        density: app.density,
+        separator: separator.map(str::to_string),
+        show_header,
+        collapse,
+    }
+}
+
+/// Lays out the message at `index` of the newest-first `messages` at `width` columns, or
+/// takes its rows from the row cache, and hands them to `f` with the number of rows of
+/// the day separator above it. Messages of ignored users collapsed into the placeholder
+/// of a newer one have no rows.
+fn with_rows<T>(
+    app: &App,
+    messages: &[&Message],
+    index: usize,
+    width: usize,
+    f: impl FnOnce(usize, &[Line<'static>]) -> T,
+) -> T {
+    let collapse = collapse(app, messages, index);
+    if collapse == Collapse::Hidden {
+        return f(0, &[]);
+    }
+    let message = messages[index];
+    let previous = messages.get(index + 1).copied();
+    let separator = day_separator(previous, message);
+    let show_header = !continues_group(previous, message, app.density);
+    let key = row_key(
+        app,
+        message,
+        separator.as_deref(),
+        show_header,
+        collapse,
+        width,
+    );
+
+    let mut cache = app.row_cache.borrow_mut();
+    let (separator_rows, rows) = cache.get_or_insert_with(key, || {
+        let separator: Vec<Line<'static>> = separator
+            .map(|separator| {
+                Line::from(Span::styled(
+                    separator,
+                    Style::default().fg(Color::DarkGray),
+                ))
+                .centered()
+            })
+            .iter()
+            .flat_map(|line| wrap_line(line, width))
+            .collect();
+        let separator_rows = separator.len();
+        let lines = match collapse {
+            Collapse::Placeholder(count) => vec![placeholder_line(count)],
+            _ => message_lines(app, message, show_header, app.density, Style::default()),
+        };
+        let rows = separator
+            .into_iter()
+            .chain(lines.iter().flat_map(|line| wrap_line(line, width)))
+            .collect();
+        (separator_rows, rows)
+    });
+    f(*separator_rows, rows)
+}
+
+/// The rows of the message at `index` of the newest-first `messages`, with the day
+/// separator above it if any, wrapped at `width` columns. The chat draws exactly these
+/// rows and measures messages by them.
+pub fn message_rows(
+    app: &App,
+    messages: &[&Message],
+    index: usize,
+    width: usize,
+    line_style: Style,
+) -> Vec<Line<'static>> {
+    with_rows(app, messages, index, width, |separator_rows, rows| {
+        let mut rows = rows.to_vec();
+        for row in rows.iter_mut().skip(separator_rows) {
+            row.style = line_style;
+        }
+        rows
+    })
+}
+
+/// Rows taken by the message at `index` of the newest-first `messages`.
+fn message_height(app: &App, messages: &[&Message], index: usize, width: usize) -> usize {
+    with_rows(app, messages, index, width, |_, rows| rows.len())
+}
+
+/// The newest messages needed to fill `rows` rows of the chat.
+struct ChatWindow {
+    /// How many messages, from the newest, are part of the window.
+    count: usize,
+    /// Total rows taken by those messages.
+    height: usize,
+    /// Rows of the selected message, counted from the bottom of the chat.
+    selected_range: Option<(usize, usize)>,
+}
+
+/// Walks `messages` from the newest until `rows` rows are filled, going further when needed
+/// to reach the selected message.
+fn chat_window(
+    app: &App,
+    messages: &[&Message],
+    width: usize,
+    rows: usize,
+    selected_id: Option<&String>,
+) -> ChatWindow {
+    let mut window = ChatWindow {
+        count: 0,
+        height: 0,
+        selected_range: None,
+    };
+
+    for (index, message) in messages.iter().enumerate() {
+        let height = message_height(app, messages, index, width);
+
+        if selected_id == Some(&message.id) {
+            window.selected_range = Some((window.height, window.height + height));
+        }
+
+        window.count += 1;
+        window.height += height;
+
+        if window.height >= rows && (selected_id.is_none() || window.selected_range.is_some()) {
+            break;
+        }
+    }
+
+    window
+}
+
+/// Describes who is typing in the channel currently open, collapsing crowds to a count.
+fn typing_text(app: &App) -> Option<String> {
+    let channel_id = match &app.state {
+        AppState::Chatting(id) | AppState::EmojiSelection(id) | AppState::SelectingMessage(id) => {
+            id
+        }
+        _ => return None,
+    };
+
+    let names: Vec<Cow<str>> = app
+        .typing
+        .iter()
+        .filter(|t| &t.channel_id == channel_id)
+        .map(|t| sanitize_line(&t.username))
+        .collect();
+
+    match names.as_slice() {
+        [] => None,
+        [name] => Some(format!("{name} is typing...")),
+        [first, second] => Some(format!("{first} and {second} are typing...")),
+        _ => Some(format!("{} people are typing...", names.len())),
+    }
+}
+
+/// Maps a scroll offset laid out at `old_width` to `new_width`, keeping the message at the
+/// bottom of the view in place, proportionally when it is partly visible.
+fn rescale_offset(app: &App, messages: &[&Message], old_width: usize, new_width: usize) -> usize {
+    let mut old_rows = 0;
+    let mut new_rows = 0;
+
+    for index in 0..messages.len() {
+        let old_height = message_height(app, messages, index, old_width);
+        let new_height = message_height(app, messages, index, new_width);
+
+        if old_rows + old_height > app.scroll_offset {
+            let into = app.scroll_offset - old_rows;
+            return new_rows + into * new_height / old_height.max(1);
+        }
+
+        old_rows += old_height;
+        new_rows += new_height;
+    }
+
+    new_rows
+}
+
+fn draw_member_list(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let loading = app
+        .chat_guild_id
+        .as_ref()
+        .is_some_and(|guild_id| !app.guild_members.contains_key(guild_id));
+
+    let title = if loading {
+        "Members (loading...)".to_string()
+    } else {
+        format!("Members ({})", app.member_list.len())
+    };
+
+    let items: Vec<ListItem> = app
+        .member_list
+        .iter()
+        .map(|(user_id, name, color)| {
+            // Ignored members are dimmed, whatever their role color.
+            let color = match *color {
+                _ if app.is_ignored(user_id) => Color::DarkGray,
+                Some(color) => Color::Rgb((color >> 16) as u8, (color >> 8) as u8, color as u8),
+                None => Color::Reset,
+            };
+            let name = match app.alias(user_id) {
+                Some(alias) => alias_label(&sanitize_line(alias)),
+                None => sanitize_line(name).into_owned(),
+            };
+            ListItem::new(Line::from(vec![
+                presence_span(app, user_id),
+                Span::styled(name, Style::default().fg(color)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items).block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Double)
+            .border_style(Style::default().fg(app.theme.border)),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(list, area);
+}
+
 pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
     use ratatui::layout::{Constraint, Direction, Layout};
-    use ratatui::text::{Line, Text};
-    use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+    use ratatui::text::Text;
+    use ratatui::widgets::{Paragraph, Wrap};
 
     let area = f.area();
+    app.list_area = None;
+    app.row_cache
+        .get_mut()
+        .prepare((area.width, area.height), app.rows_generation);
+
+    // Too small for any of the views; wait for the terminal to grow back.
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        app.terminal_width = area.width as usize;
+        app.terminal_height = area.height as usize;
+        f.render_widget(
+            Paragraph::new("Too small")
+                .style(Style::default().fg(Color::LightRed))
+                .wrap(Wrap { trim: true }),
+            area,
+        );
+        return;
+    }
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Percentage(90), Constraint::Percentage(10)].as_ref())
         .split(area);
 
-    app.terminal_height = chunks[0].height as usize;
-    app.terminal_width = chunks[0].width as usize;
+    // A channel pinned with Ctrl+S takes the right half; everything else is drawn in the
+    // left one, which the input bar belongs to.
+    let (content_area, split_area) = if app.split.is_some() {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[0]);
+        (columns[0], Some(columns[1]))
+    } else {
+        (chunks[0], None)
+    };
+
+    // The member sidebar takes the right of the chat; the input bar stays full width.
+    let in_chat = matches!(
+        app.state,
+        AppState::Chatting(_) | AppState::EmojiSelection(_) | AppState::SelectingMessage(_)
+    );
+    let (main_area, sidebar_area) = if app.show_members && in_chat {
+        let sidebar_width = (content_area.width / 5).min(30);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(sidebar_width)])
+            .split(content_area);
+        (columns[0], Some(columns[1]))
+    } else {
+        (content_area, None)
+    };
+
+    app.terminal_height = main_area.height as usize;
+    app.terminal_width = main_area.width as usize;
 
     let max_height = app.terminal_height.saturating_sub(2);
     let max_width = app.terminal_width.saturating_sub(2) as u16;
@@ -37,10 +1028,9 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
                     Constraint::Length(3),
                     Constraint::Min(0),
                 ])
-                .split(chunks[0])[1];
+                .split(content_area)[1];
 
-            let spinner = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-            let symbol = spinner[app.tick_count % spinner.len()];
+            let symbol = SPINNER[app.tick_count % SPINNER.len()];
 
             let loading_text = Line::from(vec![
                 Span::styled("Loading", Style::default().fg(Color::LightCyan)),
@@ -52,7 +1042,7 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
                 .alignment(ratatui::layout::Alignment::Center)
                 .block(Block::default().borders(Borders::NONE));
 
-            f.render_widget(Clear, chunks[0]);
+            f.render_widget(Clear, content_area);
             f.render_widget(loading_paragraph, loading_area);
         }
         AppState::Home => {
@@ -72,16 +1062,18 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
                             Style::default().fg(Color::Yellow),
                         ))
                         .borders(Borders::ALL)
-                        .border_type(BorderType::Double),
+                        .border_type(BorderType::Double)
+                        .border_style(Style::default().fg(app.theme.border)),
                 )
-                .highlight_style(Style::default().reversed())
+                .highlight_style(app.theme.selection)
                 .highlight_symbol(">> ");
 
             app.selection_index = app.selection_index.min(options.len().saturating_sub(1));
 
             let mut state = ListState::default().with_selected(Some(app.selection_index));
-            f.render_widget(Clear, chunks[0]);
-            f.render_stateful_widget(list, chunks[0], &mut state);
+            f.render_widget(Clear, content_area);
+            f.render_stateful_widget(list, content_area, &mut state);
+            track_list(app, content_area, &state, options.len(), Vec::new());
         }
         AppState::SelectingDM => {
             let filter_text = app.input.to_lowercase();
@@ -107,8 +1099,25 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
                         _ => Color::LightRed,
                     };
 
-                    ListItem::new(format!("{char} {}", d.get_name()))
-                        .style(Style::default().fg(color))
+                    let draft = if app.drafts.contains_key(&d.id) {
+                        " ✎"
+                    } else {
+                        ""
+                    };
+
+                    // Only one-to-one DMs have a single status to show.
+                    let presence = match d.recipients.as_slice() {
+                        [recipient] if d.channel_type == 1 => presence_span(app, &recipient.id),
+                        _ => Span::raw("  "),
+                    };
+
+                    ListItem::new(Line::from(vec![
+                        presence,
+                        Span::styled(
+                            format!("{char} {}{draft}", sanitize_line(&d.get_name())),
+                            Style::default().fg(color),
+                        ),
+                    ]))
                 })
                 .collect();
 
@@ -123,26 +1132,30 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
                             Style::default().fg(Color::Yellow),
                         ))
                         .borders(Borders::ALL)
-                        .border_type(BorderType::Double),
+                        .border_type(BorderType::Double)
+                        .border_style(Style::default().fg(app.theme.border)),
                 )
-                .highlight_style(Style::default().reversed())
+                .highlight_style(app.theme.selection)
                 .highlight_symbol(">> ");
 
             let mut state = ListState::default().with_selected(Some(app.selection_index));
-            f.render_widget(Clear, chunks[0]);
-            f.render_stateful_widget(list, chunks[0], &mut state);
+            f.render_widget(Clear, content_area);
+            f.render_stateful_widget(list, content_area, &mut state);
+            track_list(app, content_area, &state, num_filtered, Vec::new());
         }
         AppState::SelectingGuild => {
-            let filter_text = app.input.to_lowercase();
-
-            let filtered_guilds: Vec<&Guild> = app
-                .guilds
-                .iter()
-                .filter(|g| g.name.to_lowercase().contains(&filter_text))
-                .collect();
+            let favorites = app.pinned.ids(PinKind::Guild);
+            let filtered_guilds = filter_guilds(
+                &app.guilds,
+                &app.input,
+                app.guild_sort,
+                &app.guilds_opened,
+                &app.muted,
+                favorites,
+            );
 
             let mut count = 0;
-            let items: Vec<ListItem> = filtered_guilds
+            let mut items: Vec<ListItem> = filtered_guilds
                 .iter()
                 .map(|g| {
                     let color = if count % 2 == 0 {
@@ -153,115 +1166,208 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
 
                     count += 1;
 
-                    ListItem::new(g.name.as_str()).style(Style::default().fg(color))
+                    let color = if app.muted.contains(&g.id) {
+                        Color::DarkGray
+                    } else {
+                        color
+                    };
+
+                    let star = if favorites.contains(&g.id) {
+                        "★ "
+                    } else {
+                        ""
+                    };
+                    ListItem::new(Line::from(vec![
+                        Span::raw(star),
+                        guild_badge(g),
+                        Span::raw(sanitize_line(&g.name)),
+                        unread_badge(app, g),
+                    ]))
+                    .style(Style::default().fg(color))
                 })
                 .collect();
 
             let num_filtered = items.len();
             app.selection_index = app.selection_index.min(num_filtered.saturating_sub(1));
 
+            // The favorites come first, under a heading and above a separator. Neither
+            // can be selected, so the selection is shifted past them.
+            let favorite_count = filtered_guilds
+                .iter()
+                .take_while(|g| favorites.contains(&g.id))
+                .count();
+            let mut headings = Vec::new();
+            let mut selected = app.selection_index;
+            if favorite_count > 0 {
+                headings.push(0);
+                items.insert(
+                    0,
+                    ListItem::new("★ Favorites").style(Style::default().fg(Color::Yellow).bold()),
+                );
+                selected += 1;
+                if favorite_count < num_filtered {
+                    headings.push(favorite_count + 1);
+                    items.insert(
+                        favorite_count + 1,
+                        ListItem::new(
+                            "─".repeat(usize::from(content_area.width.saturating_sub(5))),
+                        )
+                        .style(Style::default().fg(Color::DarkGray)),
+                    );
+                    if app.selection_index >= favorite_count {
+                        selected += 1;
+                    }
+                }
+            }
+
             let list = List::new(items)
                 .block(
                     Block::default()
                         .title(Span::styled(
-                            "Rivet Client - Guilds",
+                            format!(
+                                "Rivet Client - Guilds ({}, Ctrl+S to change)",
+                                app.guild_sort.label()
+                            ),
                             Style::default().fg(Color::Yellow),
                         ))
                         .borders(Borders::ALL)
-                        .border_type(BorderType::Double),
+                        .border_type(BorderType::Double)
+                        .border_style(Style::default().fg(app.theme.border)),
                 )
-                .highlight_style(Style::default().reversed())
+                .highlight_style(app.theme.selection)
                 .highlight_symbol(">> ");
 
-            let mut state = ListState::default().with_selected(Some(app.selection_index));
-            f.render_widget(Clear, chunks[0]);
-            f.render_stateful_widget(list, chunks[0], &mut state);
+            let mut state = ListState::default().with_selected(Some(selected));
+            f.render_widget(Clear, content_area);
+            f.render_stateful_widget(list, content_area, &mut state);
+            track_list(app, content_area, &state, num_filtered, headings);
         }
         AppState::SelectingChannel(guild_id) => {
-            let filter_text = app.input.to_lowercase();
-
             let permission_context = &app.context;
 
-            let mut list_items: Vec<ListItem> = Vec::new();
-
-            let should_display_channel_content = |c: &Channel| {
-                let is_readable = permission_context
-                    .as_ref()
-                    .is_some_and(|context| c.is_readable(context));
-
-                is_readable
-                    && (filter_text.is_empty() || c.name.to_lowercase().contains(&filter_text))
+            let get_channel_style = |channel_type: u8| -> (char, Color) {
+                match channel_type {
+                    15 => ('', Color::LightYellow),
+                    13 => ('󱝉', Color::LightRed),
+                    5 => ('', Color::LightGreen),
+                    4 => ('', Color::Gray),
+                    2 => ('', Color::LightCyan),
+                    0 => ('', Color::LightBlue),
+                    _ => ('', Color::LightMagenta),
+                }
             };
 
-            app.channels
-                .iter()
-                .filter(|c| {
-                    if c.children.is_none() && c.channel_type != 4 {
-                        return should_display_channel_content(c);
-                    }
-
-                    if c.channel_type == 4 {
-                        if filter_text.is_empty() || c.name.to_lowercase().contains(&filter_text) {
-                            return true;
-                        }
-
-                        if let Some(children) = &c.children {
-                            return children.iter().any(should_display_channel_content);
-                        }
-                    }
+            // Same rows as `App::channel_rows`, borrowed field by field so the selection
+            // can still be clamped below.
+            let threads = app
+                .thread_cache
+                .get(guild_id)
+                .map_or(&[][..], Vec::as_slice);
+            let rows = Channel::visible_rows(
+                &app.channels,
+                threads,
+                permission_context.as_ref(),
+                &app.input,
+                (!app.show_muted).then_some(&app.muted),
+            );
 
-                    false
+            let voice_states = app.voice_states.get(guild_id);
+            let connected = |channel_id: &String| {
+                voice_states.map_or(0, |users| {
+                    users.values().filter(|c| *c == channel_id).count()
                 })
-                .for_each(|c| {
-                    let get_channel_style = |channel_type: u8| -> (char, Color) {
-                        match channel_type {
-                            15 => ('', Color::LightYellow),
-                            13 => ('󱝉', Color::LightRed),
-                            5 => ('', Color::LightGreen),
-                            4 => ('', Color::Gray),
-                            2 => ('', Color::LightCyan),
-                            0 => ('', Color::LightBlue),
-                            _ => ('', Color::LightMagenta),
-                        }
-                    };
+            };
 
-                    if c.channel_type == 4 {
+            // Inside the borders and the highlight symbol.
+            let topic_width = content_area.width.saturating_sub(5) as usize;
+            let mut list_items: Vec<ListItem> =
+                rows.iter()
+                    .map(|c| {
                         let (char, color) = get_channel_style(c.channel_type);
-                        list_items.push(
-                            ListItem::new(format!("{char} {}", c.name))
-                                .style(Style::default().fg(color)),
-                        );
-
-                        if let Some(children) = &c.children {
-                            children
-                                .iter()
-                                .filter(|c| should_display_channel_content(c))
-                                .for_each(|child| {
-                                    let (char, color) = get_channel_style(child.channel_type);
-
-                                    list_items.push(
-                                        ListItem::new(format!("  {char} {}", child.name))
-                                            .style(Style::default().fg(color)),
-                                    );
-                                });
+                        let unread = c.has_unread(app.read_markers.get(&c.id));
+                        let connected = if c.is_voice() { connected(&c.id) } else { 0 };
+                        let name = sanitize_line(&c.name);
+                        let name = match connected {
+                            0 => name.into_owned(),
+                            count => format!("{name} ({count})"),
+                        };
+                        let name = if app.drafts.contains_key(&c.id) {
+                            format!("{name} ✎")
+                        } else {
+                            name
+                        };
+                        if c.is_category() {
+                            return ListItem::new(format!("{char} {}", name.to_uppercase()))
+                                .style(Style::default().fg(color).bold());
                         }
-                    } else {
-                        let (char, color) = get_channel_style(c.channel_type);
-                        list_items.push(
-                            ListItem::new(format!("{char} {}", c.name))
-                                .style(Style::default().fg(color)),
-                        );
-                    }
-                });
+                        if c.is_thread() {
+                            let marker = if unread { "●" } else { " " };
+                            let style = Style::default().fg(Color::Gray);
+                            return ListItem::new(format!("{marker}   └ {name}"))
+                                .style(if unread { style.bold() } else { style });
+                        }
+
+                        let (label, style) = if unread {
+                            (
+                                format!("● {char} {name}"),
+                                Style::default().fg(color).bold(),
+                            )
+                        } else {
+                            (format!("  {char} {name}"), Style::default().fg(color))
+                        };
+
+                        // The topic fills what is left of the row after the name.
+                        let available = topic_width.saturating_sub(label.width() + 3);
+                        let topic = c
+                            .topic
+                            .as_deref()
+                            .filter(|topic| !topic.trim().is_empty() && available > 1)
+                            .map(|topic| {
+                                Span::styled(
+                                    format!(
+                                        " — {}",
+                                        truncate_to_width(
+                                            &sanitize_line(&topic.replace('\n', " ")),
+                                            available
+                                        )
+                                    ),
+                                    Style::default().fg(Color::DarkGray),
+                                )
+                            });
+
+                        let mut spans = vec![Span::raw(label)];
+                        spans.extend(topic);
+                        ListItem::new(Line::from(spans)).style(style)
+                    })
+                    .collect();
+
+            // Muted channels only show up while they are not hidden, dimmed.
+            for (item, channel) in list_items.iter_mut().zip(&rows) {
+                if app.muted.contains(&channel.id) {
+                    *item = item.clone().style(Style::default().fg(Color::DarkGray));
+                }
+            }
 
             let num_filtered = list_items.len();
             app.selection_index = app.selection_index.min(num_filtered.saturating_sub(1));
 
+            // Filtering can leave the cursor on a header; move it to the next channel.
+            if rows
+                .get(app.selection_index)
+                .is_some_and(|c| !c.is_selectable())
+                && let Some(index) = rows
+                    .iter()
+                    .skip(app.selection_index)
+                    .position(|c| c.is_selectable())
+            {
+                app.selection_index += index;
+            }
+
             let hidden_items: Vec<ListItem> = app
                 .channels
                 .iter()
                 .flat_map(|c| {
-                    if c.channel_type == 4 {
+                    if c.is_category() {
                         let mut items: Vec<&Channel> = Vec::new();
 
                         if let Some(children) = &c.children {
@@ -294,7 +1400,8 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
 
                     let color = Color::DarkGray;
 
-                    ListItem::new(format!(" {char} {}", c.name)).style(Style::default().fg(color))
+                    ListItem::new(format!(" {char} {}", sanitize_line(&c.name)))
+                        .style(Style::default().fg(color))
                 })
                 .collect();
 
@@ -311,185 +1418,209 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
                     Block::default()
                         .title(Span::styled(title, Style::default().fg(Color::Yellow)))
                         .borders(Borders::ALL)
-                        .border_type(BorderType::Double),
+                        .border_type(BorderType::Double)
+                        .border_style(Style::default().fg(app.theme.border)),
                 )
-                .highlight_style(Style::default().reversed())
+                .highlight_style(app.theme.selection)
                 .highlight_symbol(">> ");
 
             let mut state = ListState::default().with_selected(Some(app.selection_index));
-            f.render_widget(Clear, chunks[0]);
-            f.render_stateful_widget(list, chunks[0], &mut state);
+            f.render_widget(Clear, content_area);
+            f.render_stateful_widget(list, content_area, &mut state);
+            track_list(app, content_area, &state, num_filtered, Vec::new());
         }
-        AppState::Chatting(_) | AppState::EmojiSelection(_) => {
+        AppState::Chatting(channel_id)
+        | AppState::EmojiSelection(channel_id)
+        | AppState::SelectingMessage(channel_id) => {
+            let channel_id = channel_id.clone();
             if max_width == 0 {
                 return;
             }
 
-            let mut messages_to_render: Vec<Message> = Vec::new();
-            let mut current_height = 0;
-
-            for message in app.messages.iter() {
-                let formatted_text = format!(
-                    "[{}] {}: {}",
-                    message
-                        .timestamp
-                        .split('T')
-                        .next()
-                        .unwrap_or("")
-                        .to_string()
-                        + " "
-                        + message
-                            .timestamp
-                            .split('T')
-                            .nth(1)
-                            .unwrap_or("")
-                            .split('.')
-                            .next()
-                            .unwrap_or(""),
-                    message.author.username,
-                    message.content.as_deref().unwrap_or("(*non-text*)")
-                );
-
-                let text_lines: Vec<&str> = formatted_text.split('\n').collect();
-                let mut estimated_height = 0;
-
-                let safe_max_width = max_width.saturating_sub(4);
-                for line in text_lines {
-                    let width = UnicodeWidthStr::width(line);
-
-                    if width == 0 || safe_max_width == 0 {
-                        estimated_height += 1;
-                        continue;
-                    }
+            let selected_id = match &app.state {
+                AppState::SelectingMessage(_) => {
+                    app.messages.get(app.selection_index).map(|m| m.id.clone())
+                }
+                _ => None,
+            };
 
-                    let mut current_line_width = 0;
-                    let mut first_word = true;
+            // Messages waiting to be sent go below the loaded history, newest first like it.
+            let queued: Vec<Message> = app
+                .outbox
+                .iter()
+                .rev()
+                .filter(|queued| queued.message.channel_id == channel_id)
+                .map(|queued| queued.message.clone())
+                .collect();
+            let uploading = app
+                .uploads
+                .iter()
+                .rev()
+                .filter(|message| message.channel_id == channel_id);
+            let all_messages: Vec<&Message> = uploading
+                .chain(queued.iter())
+                .chain(app.messages.iter())
+                .collect();
+            let text_width = max_width.saturating_sub(4) as usize;
 
-                    for word in line.split(' ') {
-                        let word_width = UnicodeWidthStr::width(word);
-                        let space_width = if first_word { 0 } else { 1 };
+            let previous_width = if app.chat_width == 0 {
+                text_width
+            } else {
+                app.chat_width
+            };
 
-                        if current_line_width + space_width + word_width <= safe_max_width as usize
-                        {
-                            current_line_width += space_width + word_width;
-                        } else {
-                            if current_line_width > 0 {
-                                estimated_height += 1;
-                            }
-
-                            if word_width > safe_max_width as usize {
-                                let chunks = word_width.div_ceil(safe_max_width as usize);
-                                estimated_height += chunks.saturating_sub(1);
-                                current_line_width = word_width % safe_max_width as usize;
-                                if current_line_width == 0 {
-                                    current_line_width = safe_max_width as usize;
-                                }
-                            } else {
-                                current_line_width = word_width;
-                            }
-                        }
-                        first_word = false;
-                    }
-                    if current_line_width > 0 {
-                        estimated_height += 1;
-                    }
-                }
+            // While scrolled back, keep the same lines in view when new messages arrive by
+            // growing the offset by their height.
+            let newest_id = all_messages.first().map(|m| m.id.clone());
+            if app.scroll_offset > 0
+                && let Some(previous) = &app.chat_newest_id
+                && let Some(added) = all_messages.iter().position(|m| &m.id == previous)
+            {
+                app.scroll_offset += (0..added)
+                    .map(|index| message_height(app, &all_messages, index, previous_width))
+                    .sum::<usize>();
+            }
+            app.chat_newest_id = newest_id;
 
-                messages_to_render.push(message.clone());
-                current_height += estimated_height;
+            // When the width changes, e.g. as the member list opens or closes, the same
+            // message stays at the bottom of the view.
+            if app.scroll_offset > 0 && previous_width != text_width {
+                app.scroll_offset = rescale_offset(app, &all_messages, previous_width, text_width);
+            }
+            app.chat_width = text_width;
+
+            let window = chat_window(
+                app,
+                &all_messages,
+                text_width,
+                max_height + app.scroll_offset,
+                selected_id.as_ref(),
+            );
+            let current_height = window.height;
 
-                if current_height >= max_height {
-                    break;
+            if let Some((start, end)) = window.selected_range {
+                if start < app.scroll_offset {
+                    app.scroll_offset = start;
+                } else if end > app.scroll_offset + max_height {
+                    app.scroll_offset = end.saturating_sub(max_height);
                 }
             }
 
-            messages_to_render.reverse();
+            // Every loaded message fits inside the window: clamp the offset to the oldest one.
+            if window.count == all_messages.len() {
+                let max_offset = current_height.saturating_sub(max_height);
+                app.scroll_offset = app.scroll_offset.min(max_offset);
+                app.chat_at_top = app.scroll_offset == max_offset;
+            } else {
+                app.chat_at_top = false;
+            }
 
             let mut final_content: Vec<Line> = Vec::new();
 
-            for message in messages_to_render.into_iter() {
-                let formatted_time = format!(
-                    " {}]",
-                    message
-                        .timestamp
-                        .split('T')
-                        .nth(1)
-                        .unwrap_or("")
-                        .split('.')
-                        .next()
-                        .unwrap_or(""),
-                );
-
-                let formatted_date = message
-                    .timestamp
-                    .split('T')
-                    .next()
-                    .unwrap_or("")
-                    .to_string();
-
-                let author = format!(" {}: ", message.author.username);
-
-                let content = message
-                    .content
-                    .clone()
-                    .unwrap_or("(*non-text*)".to_string());
-
-                let content_lines: Vec<&str> = content.split('\n').collect();
-
-                for (i, line_content) in content_lines.iter().enumerate() {
-                    let mut spans = vec![];
-
-                    if i == 0 {
-                        spans.push(Span::styled(
-                            "[".to_string(),
-                            Style::default().fg(Color::LightBlue),
-                        ));
-                        spans.push(Span::styled(
-                            formatted_date.clone(),
-                            Style::default().fg(Color::LightCyan),
-                        ));
-                        spans.push(Span::styled(
-                            formatted_time.clone(),
-                            Style::default().fg(Color::LightBlue),
-                        ));
-                        spans.push(Span::styled(
-                            author.clone(),
-                            Style::default().fg(Color::Yellow),
-                        ));
-                    }
+            for index in (0..window.count).rev() {
+                let message = all_messages[index];
+
+                let mentions_me = message.mentions.iter().any(|m| m.id == app.current_user.id);
+
+                let line_style = if selected_id.as_ref() == Some(&message.id) {
+                    app.theme.selection
+                } else if mentions_me {
+                    Style::default().bg(app.theme.mention_background)
+                } else {
+                    Style::default()
+                };
+
+                final_content.extend(message_rows(
+                    app,
+                    &all_messages,
+                    index,
+                    text_width,
+                    line_style,
+                ));
+            }
 
-                    spans.push(Span::styled(
-                        line_content.to_string(),
-                        Style::default().fg(Color::White),
-                    ));
-                    final_content.push(Line::from(spans));
-                }
+            let scroll_offset = current_height
+                .saturating_sub(max_height)
+                .saturating_sub(app.scroll_offset);
+
+            const CHAT_TITLE: &str = "Rivet Client - Chatting";
+            let mut title = vec![Span::styled(CHAT_TITLE, Style::default().fg(Color::Yellow))];
+            let available = (max_width as usize).saturating_sub(CHAT_TITLE.width() + 3);
+            if let Some(topic) = channel_topic(app, &channel_id)
+                && available > 1
+            {
+                title.push(Span::styled(
+                    format!(
+                        " — {}",
+                        truncate_to_width(&sanitize_line(&topic.replace('\n', " ")), available)
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ));
             }
 
-            let scroll_offset = if current_height > max_height {
-                current_height.saturating_sub(max_height)
-            } else {
-                0
-            };
+            let mut block = Block::default()
+                .title(Line::from(title))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Double)
+                .border_style(Style::default().fg(app.theme.border));
+
+            if app.scroll_offset > 0 {
+                block = block.title_bottom(
+                    Line::from(Span::styled(
+                        " — scrolled, End to jump to latest — ",
+                        Style::default().fg(Color::LightYellow),
+                    ))
+                    .centered(),
+                );
+            }
 
+            // Already wrapped by `message_rows`, the same way they were measured.
             let paragraph = Paragraph::new(final_content)
-                .block(
-                    Block::default()
-                        .title(Span::styled(
-                            "Rivet Client - Chatting",
-                            Style::default().fg(Color::Yellow),
-                        ))
-                        .borders(Borders::ALL)
-                        .border_type(BorderType::Double),
-                )
-                .wrap(Wrap { trim: false })
+                .block(block)
                 .scroll((scroll_offset as u16, 0));
 
-            f.render_widget(Clear, chunks[0]);
-            f.render_widget(paragraph, chunks[0]);
+            f.render_widget(Clear, main_area);
+            f.render_widget(paragraph, main_area);
+
+            if let Some(sidebar_area) = sidebar_area {
+                draw_member_list(f, sidebar_area, app);
+            }
         }
     };
 
+    if let (Some(split), Some(split_area)) = (&app.split, split_area) {
+        draw_split_pane(f, split_area, app, split);
+    }
+
+    if let Some(preview) = &mut app.preview {
+        draw_preview(f, main_area, preview, app.theme.border);
+    } else if app.preview_loading {
+        let symbol = SPINNER[app.tick_count % SPINNER.len()];
+
+        let popup_rect = ratatui::layout::Rect {
+            x: main_area.x + main_area.width.saturating_sub(30) / 2,
+            y: main_area.y + main_area.height.saturating_sub(3) / 2,
+            width: main_area.width.min(30),
+            height: main_area.height.min(3),
+        };
+
+        f.render_widget(Clear, popup_rect);
+        f.render_widget(
+            Paragraph::new(Line::from(Span::styled(
+                format!("Loading preview {symbol}"),
+                Style::default().fg(Color::LightCyan),
+            )))
+            .alignment(ratatui::layout::Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double)
+                    .border_style(Style::default().fg(app.theme.border)),
+            ),
+            popup_rect,
+        );
+    }
+
     if let AppState::EmojiSelection(_) = &app.state {
         let input_area = chunks[1];
         let emoji_popup_height = 8;
@@ -499,25 +1630,14 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
             y: input_area.y.saturating_sub(emoji_popup_height + 1),
             width: input_area.width.saturating_sub(2),
             height: emoji_popup_height,
-        };
+        }
+        .intersection(area);
 
         f.render_widget(Clear, popup_rect);
 
         let mut filtered_items: Vec<ListItem> = Vec::new();
 
-        let app_clone = app.clone();
-
-        let filtered_unicode: Vec<&(String, String)> = app_clone
-            .emoji_map
-            .iter()
-            .filter(|(name, _)| name.starts_with(&app.emoji_filter))
-            .collect();
-
-        let filtered_custom: Vec<&Emoji> = app_clone
-            .custom_emojis
-            .iter()
-            .filter(|e| e.name.starts_with(&app.emoji_filter))
-            .collect();
+        let (filtered_unicode, filtered_custom) = app.emoji_matches();
 
         for (name, char) in filtered_unicode.iter() {
             filtered_items.push(ListItem::new(Line::from(vec![
@@ -550,9 +1670,10 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
                             Style::default().fg(Color::Yellow),
                         ))
                         .borders(Borders::ALL)
-                        .border_type(BorderType::Double),
+                        .border_type(BorderType::Double)
+                        .border_style(Style::default().fg(app.theme.border)),
                 )
-                .highlight_style(Style::default().reversed())
+                .highlight_style(app.theme.selection)
                 .highlight_symbol(">> ");
 
             let mut state = ListState::default().with_selected(Some(app.selection_index));
@@ -562,26 +1683,735 @@ pub fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
         }
     }
 
-    f.render_widget(
-        Paragraph::new(app.input.as_str()).block(
-            Block::default()
-                .title(Span::styled(
-                    format!("Input: {}", app.status_message),
-                    Style::default().fg(Color::Yellow),
-                ))
-                .borders(Borders::ALL)
-                .border_type(BorderType::Double),
+    // The status bar is the input's title: help │ activity │ error. Everything past the
+    // help text stays whole, and the help text is cut to whatever width is left.
+    let mut activity: Vec<Span> = Vec::new();
+
+    let requests = app.api_client.requests_in_flight();
+    if requests > 0 {
+        let label = match requests {
+            1 => "1 request".to_string(),
+            count => format!("{count} requests"),
+        };
+        activity.push(Span::styled(
+            format!("{} {label}", SPINNER[app.tick_count % SPINNER.len()]),
+            Style::default().fg(Color::LightCyan),
+        ));
+    }
+
+    if let Some(retry_in) = app.api_client.rate_limiter.retry_in() {
+        activity.push(Span::styled(
+            format!("rate limited, retrying in {:.1}s", retry_in.as_secs_f64()),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+
+    if app.offline {
+        activity.push(Span::styled(
+            format!("offline, {} queued, retrying", app.outbox.len()),
+            Style::default().fg(Color::LightRed).bold(),
+        ));
+    }
+
+    if app.unacknowledged_mentions > 0
+        && let Some(notification) = app.notifications.front()
+    {
+        let more = match app.unacknowledged_mentions {
+            1 => String::new(),
+            count => format!(" (+{} more)", count - 1),
+        };
+        activity.push(Span::styled(
+            format!("{}{more}, Ctrl+G to jump", notification.label()),
+            Style::default().fg(Color::LightYellow).bold(),
+        ));
+    }
+
+    if let Some(webhook) = &app.webhook
+        && matches!(&app.state, AppState::Chatting(id) | AppState::SelectingMessage(id)
+            if *id == webhook.channel_id)
+    {
+        activity.push(Span::styled(
+            format!(
+                "webhook mode: as {} via {}",
+                sanitize_line(&webhook.username),
+                sanitize_line(webhook.webhook.display_name())
+            ),
+            Style::default().fg(Color::LightMagenta).bold(),
+        ));
+    }
+
+    let chat_id = match &app.state {
+        AppState::Chatting(id) | AppState::SelectingMessage(id) => Some(id.as_str()),
+        _ => None,
+    };
+    let send_blocked = chat_id.and_then(|id| app.send_blocked.get(id));
+    if let Some(notice) = send_blocked {
+        activity.push(Span::styled(
+            sanitize_line(notice),
+            Style::default().fg(Color::LightRed).bold(),
+        ));
+    } else if let Some(left) = chat_id.and_then(|id| app.slowmode_left(id)) {
+        activity.push(Span::styled(
+            format!("slowmode: {}s", left.as_secs_f64().ceil()),
+            Style::default().fg(Color::LightYellow).bold(),
+        ));
+    }
+
+    if let Some(typing) = typing_text(app) {
+        activity.push(Span::styled(
+            typing,
+            Style::default().fg(Color::Gray).italic(),
+        ));
+    }
+
+    // Shortcodes only ever shorten the input, so short inputs skip expanding them. After
+    // Discord refused a message for its length, the counter stays up and highlighted.
+    if matches!(app.state, AppState::Chatting(_))
+        && (app.length_rejected || app.input.len() > LENGTH_COUNTER_FROM)
+    {
+        let length = message_length(&expand_shortcodes(&app.input, &app.emoji_map));
+        if app.length_rejected || length > LENGTH_COUNTER_FROM {
+            let style = if app.length_rejected {
+                Style::default().fg(Color::Black).bg(Color::LightRed)
+            } else if length > MAX_MESSAGE_LENGTH {
+                Style::default().fg(Color::LightRed)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            activity.push(Span::styled(
+                format!("{length}/{MAX_MESSAGE_LENGTH}"),
+                style.bold(),
+            ));
+        }
+    }
+
+    activity.extend(error_segment(app));
+    activity.extend(own_presence_segment(app));
+
+    let separator = Span::styled(STATUS_SEPARATOR, Style::default().fg(app.theme.border));
+    let segments_width: usize = activity
+        .iter()
+        .map(|span| span.width() + STATUS_SEPARATOR.width())
+        .sum();
+    let help_width = usize::from(chunks[1].width.saturating_sub(2)).saturating_sub(segments_width);
+
+    let mut title = vec![Span::styled(
+        truncate_to_width(
+            &format!("Input: {}", sanitize_line(&app.status_message)),
+            help_width,
         ),
-        chunks[1],
-    );
+        Style::default().fg(app.theme.status),
+    )];
+    for segment in activity {
+        title.push(separator.clone());
+        title.push(segment);
+    }
+
+    let input_text = match (&app.pending_delete, &app.reaction_input) {
+        (Some(_), _) => Text::from(Span::styled(
+            sanitize_line(&app.status_message),
+            Style::default().fg(Color::LightRed).bold(),
+        )),
+        (None, Some(reaction)) => Text::from(Span::styled(
+            format!("React with :{reaction}"),
+            Style::default().fg(Color::LightYellow),
+        )),
+        (None, None)
+            if app.pending_quote.is_some()
+                || app.pending_ignore.is_some()
+                || app.pending_publish.is_some() =>
+        {
+            Text::from(Span::styled(
+                sanitize_line(&app.status_message),
+                Style::default().fg(Color::LightYellow).bold(),
+            ))
+        }
+        (None, None) if let Some(pings) = &app.pending_pings => Text::from(Span::styled(
+            format!("Ping {}? (y/n)", pings.join(", ")),
+            Style::default().fg(Color::LightYellow).bold(),
+        )),
+        (None, None) if let Some(gated) = &app.nsfw_prompt => Text::from(Span::styled(
+            format!(
+                "NSFW channel #{} — press y to view (n to go back)",
+                sanitize_line(&gated.name)
+            ),
+            Style::default().fg(Color::LightYellow).bold(),
+        )),
+        (None, None)
+            if let Some(emoji) = app
+                .emoji_manager
+                .as_ref()
+                .filter(|manager| manager.confirm_delete)
+                .and_then(EmojiManager::selected) =>
+        {
+            Text::from(Span::styled(
+                format!("Delete :{}:? (y/n)", sanitize_line(&emoji.name)),
+                Style::default().fg(Color::LightYellow).bold(),
+            ))
+        }
+        (None, None) if let Some(last_channel) = &app.resume_prompt => Text::from(Span::styled(
+            format!("Reopen {}? (y/n)", last_channel.label()),
+            Style::default().fg(Color::LightYellow).bold(),
+        )),
+        (None, None)
+            if app.attach_prompt.is_some()
+                || app.channel_prompt.is_some()
+                || app
+                    .emoji_manager
+                    .as_ref()
+                    .is_some_and(|manager| manager.upload.is_some()) =>
+        {
+            Text::from(Span::styled(
+                app.input.as_str(),
+                Style::default().fg(Color::LightCyan),
+            ))
+        }
+        (None, None) if app.search.as_ref().is_some_and(|search| search.is_prompt()) => Text::from(
+            Span::styled(app.input.as_str(), Style::default().fg(Color::LightYellow)),
+        ),
+        (None, None) if send_blocked.is_some() => Text::from(Span::styled(
+            app.input.as_str(),
+            Style::default().fg(Color::DarkGray),
+        )),
+        (None, None) => Text::from(app.input.as_str()),
+    };
 
     let input_before_cursor = &app.input[..app.cursor_position];
     let cursor_lines = input_before_cursor.split('\n').count();
     let cursor_y = chunks[1].y + cursor_lines as u16;
 
     let current_line_start = input_before_cursor.rfind('\n').map(|i| i + 1).unwrap_or(0);
-    let cursor_x =
-        chunks[1].x + 1 + UnicodeWidthStr::width(&input_before_cursor[current_line_start..]) as u16;
+    let cursor_column = UnicodeWidthStr::width(&input_before_cursor[current_line_start..]) as u16;
+
+    // Scroll long lines horizontally so the cursor always stays inside the box.
+    let visible_width = chunks[1].width.saturating_sub(2);
+    let scroll_x = cursor_column.saturating_sub(visible_width.saturating_sub(1));
+
+    f.render_widget(
+        Paragraph::new(input_text).scroll((0, scroll_x)).block(
+            Block::default()
+                .title(Line::from(title))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Double)
+                .border_style(Style::default().fg(app.theme.border)),
+        ),
+        chunks[1],
+    );
+
+    let cursor_x = chunks[1].x + 1 + cursor_column - scroll_x;
 
     f.set_cursor_position((cursor_x, cursor_y));
+
+    if let Some(switcher) = &app.switcher {
+        draw_switcher(f, f.area(), app, switcher);
+    }
+
+    if app.show_error_log {
+        draw_error_log(f, f.area(), app);
+    }
+
+    if let Some(forum) = &app.forum {
+        draw_forum(f, f.area(), app, forum);
+    }
+
+    if let Some(manager) = &app.emoji_manager {
+        draw_emoji_manager(f, f.area(), app, manager);
+    }
+    if let Some(picker) = &app.webhook_picker {
+        draw_webhook_picker(f, f.area(), app, picker);
+    }
+    if let Some(list) = &app.ignore_list {
+        draw_ignore_list(f, f.area(), app, list);
+    }
+    if let Some(list) = &app.alias_list {
+        draw_alias_list(f, f.area(), app, list);
+    }
+    if let Some(prompt) = &app.invite_prompt {
+        draw_invite_prompt(f, f.area(), app, prompt);
+    }
+
+    if let Some(profile) = &app.profile {
+        draw_profile(f, f.area(), app, profile);
+    }
+
+    if app.show_command_help {
+        draw_command_help(f, f.area(), app);
+    }
+
+    if let Some(selection_index) = app.notification_overlay {
+        draw_notifications(f, f.area(), app, selection_index);
+    }
+
+    if let Some(pins) = &app.pins {
+        draw_pins(f, f.area(), app, pins);
+    }
+
+    if let Some(search) = &app.search {
+        draw_search(f, f.area(), app, search);
+    }
+
+    if let Some(scroll) = app.key_help {
+        app.key_help = Some(draw_key_help(f, f.area(), app, scroll));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use chrono::FixedOffset;
+    use ratatui::{Terminal, backend::TestBackend};
+    use serde_json::json;
+
+    use super::*;
+    use crate::api::{
+        Emoji,
+        message::{Attachment, StickerItem},
+    };
+
+    fn category_with(id: &str, name: &str) -> Channel {
+        serde_json::from_value(json!({
+            "id": "cat",
+            "name": "Text",
+            "type": 4,
+            "guild_id": "g1",
+            "parent_id": null,
+            "last_message_id": null,
+            "topic": null,
+            "message_count": null,
+            "children": [{
+                "id": id,
+                "name": name,
+                "type": 0,
+                "guild_id": "g1",
+                "parent_id": "cat",
+                "last_message_id": null,
+                "topic": null,
+                "message_count": null,
+                "children": null,
+            }],
+        }))
+        .unwrap()
+    }
+
+    fn text(spans: &[Span]) -> String {
+        spans.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    /// An app chatting in a channel with `count` one-line messages, newest first.
+    fn chatting(count: u64) -> App {
+        let mut app = App::for_tests();
+        app.state = AppState::Chatting("c1".to_string());
+        app.messages = (1..=count)
+            .rev()
+            .map(|id| Message::for_tests(&id.to_string(), &format!("message {id}")))
+            .collect();
+        app
+    }
+
+    fn draw(app: &mut App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let frame = terminal.draw(|f| draw_ui(f, app)).unwrap();
+        frame
+            .buffer
+            .content()
+            .chunks(80)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>() + "\n")
+            .collect()
+    }
+
+    #[test]
+    fn resolves_user_and_channel_mentions() {
+        let mut app = App::for_tests();
+        app.channels = vec![category_with("42", "general")];
+        let message = Message::for_tests("1", "hi <@7>, see <#42>").mentioning("7", "bob");
+
+        let spans = content_spans(&app, &message, message_content(&message));
+        assert_eq!(text(&spans), "hi @bob, see #general");
+        assert_eq!(spans[1].style.fg, Some(Color::LightMagenta));
+        assert_eq!(spans[3].style.fg, Some(Color::LightCyan));
+    }
+
+    #[test]
+    fn falls_back_for_unknown_mentions() {
+        let app = App::for_tests();
+        let message = Message::for_tests("1", "<@7> <#42>");
+
+        let spans = content_spans(&app, &message, message_content(&message));
+        assert_eq!(text(&spans), "@unknown-user #unknown-channel");
+    }
+
+    #[test]
+    fn keeps_the_view_still_when_messages_arrive_while_scrolled() {
+        let mut app = chatting(60);
+        draw(&mut app);
+        app.scroll_offset = 5;
+        let before = draw(&mut app);
+        assert!(!before.contains("message 60"));
+
+        app.messages
+            .insert(0, Message::for_tests("61", "message 61"));
+        let after = draw(&mut app);
+
+        assert_eq!(app.scroll_offset, 6);
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn follows_new_messages_when_not_scrolled() {
+        let mut app = chatting(60);
+        draw(&mut app);
+
+        app.messages
+            .insert(0, Message::for_tests("61", "message 61"));
+        let screen = draw(&mut app);
+
+        assert_eq!(app.scroll_offset, 0);
+        assert!(screen.contains("message 61"));
+    }
+
+    #[test]
+    fn stops_scrolling_at_the_oldest_message() {
+        let mut app = chatting(30);
+        draw(&mut app);
+        app.scroll_offset = 1000;
+        let screen = draw(&mut app);
+
+        assert!(app.chat_at_top);
+        assert!(app.scroll_offset < 1000);
+        assert!(screen.contains("message 1 "));
+    }
+
+    #[test]
+    fn resolves_roles_and_custom_emojis() {
+        let mut app = App::for_tests();
+        app.context = Some(
+            serde_json::from_value(json!({
+                "user_id": "100",
+                "user_role_ids": [],
+                "all_guild_roles": [
+                    { "id": "5", "name": "mods", "permissions": "0", "color": 0xff0000 },
+                ],
+                "everyone_role_id": "g1",
+            }))
+            .unwrap(),
+        );
+        app.custom_emojis = vec![Emoji {
+            id: "12".to_string(),
+            name: "renamed".to_string(),
+            animated: None,
+            available: None,
+            user: None,
+        }];
+        let message = Message::for_tests("1", "<@&5> <:old:12> <a:gone:99> <@&6>");
+
+        let spans = content_spans(&app, &message, message_content(&message));
+        assert_eq!(text(&spans), "@mods :renamed: :gone: @unknown-role");
+        assert_eq!(spans[0].style.fg, Some(Color::Rgb(255, 0, 0)));
+    }
+
+    #[test]
+    fn shows_stickers_as_text() {
+        let mut message = Message::for_tests("1", "");
+        message.sticker_items = vec![StickerItem {
+            name: "Wumpus".to_string(),
+        }];
+
+        assert_eq!(message_content(&message), "");
+        assert_eq!(attachment_lines(&message, true)[0].0, "[sticker: Wumpus]");
+    }
+
+    fn guild(id: &str, name: &str) -> Guild {
+        Guild {
+            id: id.to_string(),
+            name: name.to_string(),
+            icon: None,
+        }
+    }
+
+    #[test]
+    fn truncates_to_terminal_columns() {
+        assert_eq!(truncate_to_width("short", 10), "short");
+        assert_eq!(truncate_to_width("a long topic", 7), "a long…");
+        // Wide characters take two columns and are never split.
+        assert_eq!(truncate_to_width("日本語のトピック", 7), "日本語…");
+        assert_eq!(truncate_to_width("日本語", 6), "日本語");
+        assert_eq!(truncate_to_width("abc", 0), "");
+    }
+
+    #[test]
+    fn truncates_emoji_whole() {
+        assert_eq!(truncate_to_width("🎉🎉🎉 party", 6), "🎉🎉…");
+        // A family is one character of several code points joined together.
+        let family = "👨\u{200d}👩\u{200d}👧";
+        assert_eq!(
+            truncate_to_width(&format!("{family}{family} reunion"), 4),
+            format!("{family}…")
+        );
+        // Combining accents stay with their letter.
+        assert_eq!(truncate_to_width("cafe\u{301}s ouverts", 5), "cafe\u{301}…");
+        // Flags are pairs of regional indicators.
+        assert_eq!(truncate_to_width("🇫🇷🇯🇵 trip", 4), "🇫🇷…");
+    }
+
+    #[test]
+    fn badges_guilds_with_their_initials() {
+        assert_eq!(guild_badge(&guild("1", "Guild Name")).content, "[GN] ");
+        assert_eq!(guild_badge(&guild("1", "rust")).content, "[RU] ");
+        assert_eq!(guild_badge(&guild("1", "The Rust Lang")).content, "[TR] ");
+        assert_eq!(guild_badge(&guild("1", "x")).content, "[X ] ");
+    }
+
+    #[test]
+    fn colors_badges_by_guild_id() {
+        let first = guild_badge(&guild("81384788765712384", "A"));
+        assert_eq!(
+            first.style.fg,
+            guild_badge(&guild("81384788765712384", "Renamed")).style.fg
+        );
+
+        let colors: HashSet<_> = (0..50)
+            .map(|id| guild_badge(&guild(&id.to_string(), "A")).style.fg)
+            .collect();
+        assert!(colors.len() > 1);
+    }
+
+    #[test]
+    fn finds_topics_of_channels_in_categories() {
+        let mut app = App::for_tests();
+        let mut category = category_with("42", "general");
+        category.children.as_mut().unwrap()[0].topic = Some("Say hi".to_string());
+        app.channels = vec![category];
+
+        assert_eq!(channel_topic(&app, "42"), Some("Say hi"));
+        assert_eq!(channel_topic(&app, "cat"), None);
+        assert_eq!(channel_topic(&app, "missing"), None);
+    }
+
+    #[test]
+    fn counts_characters_near_the_limit() {
+        let mut app = chatting(1);
+        app.input = "a".repeat(LENGTH_COUNTER_FROM);
+        assert!(!draw(&mut app).contains("/2000"));
+
+        app.input = "日".repeat(1900);
+        assert!(draw(&mut app).contains("1900/2000"));
+    }
+
+    #[test]
+    fn formats_relative_times_in_their_largest_unit() {
+        assert_eq!(relative_time(TimeDelta::seconds(59)), "now");
+        assert_eq!(relative_time(TimeDelta::minutes(2)), "2m ago");
+        assert_eq!(relative_time(TimeDelta::minutes(61)), "1h ago");
+        assert_eq!(relative_time(TimeDelta::hours(49)), "2d ago");
+    }
+
+    #[test]
+    fn formats_times_in_the_given_timezone() {
+        let time = |text: &str| text.parse::<DateTime<Utc>>().ok();
+        let paris = FixedOffset::east_opt(3600).unwrap();
+        let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+        let now = time("2024-01-02T00:10:00Z").unwrap();
+        let format = |timestamp, format: &TimeFormat, tz: &FixedOffset| {
+            format_time(timestamp, format, now, tz)
+        };
+        // Late on New Year's Eve in UTC, already the next day in Paris.
+        let sent = time("2023-12-31T23:30:15Z");
+
+        assert_eq!(format(sent, &TimeFormat::Short, &paris), "00:30");
+        assert_eq!(format(sent, &TimeFormat::Long, &paris), "00:30:15");
+        assert_eq!(format(sent, &TimeFormat::Long, &new_york), "18:30:15");
+        let custom = TimeFormat::Custom("%Y-%m-%d %H:%M".to_string());
+        assert_eq!(format(sent, &custom, &paris), "2024-01-01 00:30");
+        assert_eq!(format(sent, &custom, &new_york), "2023-12-31 18:30");
+        // Exactly midnight belongs to the day it starts.
+        let midnight = time("2024-01-01T23:00:00Z");
+        assert_eq!(format(midnight, &custom, &paris), "2024-01-02 00:00");
+
+        // Relative times count across midnight, whatever the timezone.
+        let before_midnight = time("2024-01-01T23:50:00Z");
+        assert_eq!(
+            format(before_midnight, &TimeFormat::Relative, &paris),
+            "20m ago"
+        );
+        assert_eq!(format(sent, &TimeFormat::Relative, &paris), "1d ago");
+    }
+
+    #[test]
+    fn shows_unknown_times_as_question_marks() {
+        let now = Utc::now();
+        for format in [
+            TimeFormat::Short,
+            TimeFormat::Long,
+            TimeFormat::Relative,
+            TimeFormat::Custom("%Y-%m-%d".to_string()),
+        ] {
+            assert_eq!(format_time(None, &format, now, &Utc), "??:??");
+        }
+
+        let mut app = chatting(1);
+        app.messages[0].timestamp = None;
+        assert!(draw(&mut app).contains("[??:??]"));
+    }
+
+    #[test]
+    fn hides_spoiler_attachments_until_revealed() {
+        let mut message = Message::for_tests("1", "");
+        message.attachments = vec![Attachment {
+            filename: "SPOILER_ending.png".to_string(),
+            url: "https://cdn.example/ending.png".to_string(),
+            content_type: Some("image/png".to_string()),
+            size: 2048,
+        }];
+
+        let hidden = &attachment_lines(&message, false)[0].0;
+        assert!(hidden.starts_with("[spoiler attachment ("));
+        assert!(!hidden.contains("ending"));
+        assert!(
+            attachment_lines(&message, true)[0]
+                .0
+                .contains("SPOILER_ending.png")
+        );
+    }
+
+    #[test]
+    fn draws_control_characters_as_symbols() {
+        let mut app = chatting(0);
+        app.messages = vec![Message::for_tests("1", "\x1b[2J\x1b[31mgotcha")];
+
+        let screen = draw(&mut app);
+        assert!(screen.contains("␛[2J␛[31mgotcha"));
+        assert!(!screen.contains('\x1b'));
+    }
+
+    #[test]
+    fn keys_cached_rows_by_what_they_show() {
+        let mut app = App::for_tests();
+        let mut message = Message::for_tests("1", "hello");
+        let key = |app: &App, message: &Message, width| {
+            row_key(app, message, None, true, Collapse::Shown, width)
+        };
+        let original = key(&app, &message, 80);
+
+        // Typing changes nothing the rows depend on.
+        app.input = "typing".to_string();
+        assert_eq!(key(&app, &message, 80), original);
+
+        assert_ne!(key(&app, &message, 60), original);
+        message.content = Some("hello, edited".to_string());
+        message.edited_timestamp = Some("2024-01-01T12:05:00Z".to_string());
+        assert_ne!(key(&app, &message, 80), original);
+    }
+
+    #[test]
+    fn labels_links_into_the_open_guild() {
+        let mut app = App::for_tests();
+        app.chat_guild_id = Some("1".to_string());
+        app.channels = vec![category_with("42", "general")];
+        let message = Message::for_tests(
+            "1",
+            "look https://discord.com/channels/1/42/7 and https://discord.com/channels/2/5",
+        );
+
+        let spans = content_spans(&app, &message, message_content(&message));
+        assert_eq!(
+            text(&spans),
+            "look #general → jump and https://discord.com/channels/2/5"
+        );
+    }
+
+    #[test]
+    fn keeps_groups_together_longer_in_cozy_mode() {
+        let previous = Message::for_tests("1", "first");
+        let mut next = Message::for_tests("2", "second");
+        next.timestamp = previous.timestamp.map(|sent| sent + TimeDelta::minutes(6));
+
+        assert!(!continues_group(Some(&previous), &next, Density::Compact));
+        assert!(continues_group(Some(&previous), &next, Density::Cozy));
+
+        next.timestamp = previous.timestamp.map(|sent| sent + TimeDelta::minutes(8));
+        assert!(!continues_group(Some(&previous), &next, Density::Cozy));
+        assert!(!continues_group(None, &next, Density::Cozy));
+    }
+
+    #[test]
+    fn gives_cozy_groups_a_header_line() {
+        let app = App::for_tests();
+        let message = Message::for_tests("1", "hello");
+        let lines = |show_header, density| -> Vec<String> {
+            message_lines(&app, &message, show_header, density, Style::default())
+                .iter()
+                .map(|line| text(&line.spans))
+                .collect()
+        };
+        let time = message_time(&app, &message);
+
+        assert_eq!(
+            lines(true, Density::Cozy),
+            [
+                "".to_string(),
+                format!("alice  {time}"),
+                "  hello".to_string()
+            ]
+        );
+        assert_eq!(lines(false, Density::Cozy), ["  hello"]);
+        assert!(lines(true, Density::Compact)[0].starts_with(&format!("[{time}]")));
+    }
+
+    #[test]
+    fn keys_cached_rows_by_density() {
+        let mut app = App::for_tests();
+        let message = Message::for_tests("1", "hello");
+        let compact = row_key(&app, &message, None, true, Collapse::Shown, 80);
+        app.density = Density::Cozy;
+        assert_ne!(
+            row_key(&app, &message, None, true, Collapse::Shown, 80),
+            compact
+        );
+    }
+
+    #[test]
+    fn shows_aliases_for_authors_and_mentions() {
+        let mut app = App::for_tests();
+        app.aliases.insert("2".to_string(), "Ally".to_string());
+        app.aliases.insert("7".to_string(), "Bobby".to_string());
+        let message = Message::for_tests("1", "hi <@7>").mentioning("7", "bob");
+
+        assert_eq!(author_style(&app, &message).0, "Ally†");
+        let spans = content_spans(&app, &message, message_content(&message));
+        assert_eq!(text(&spans), "hi @Bobby†");
+
+        app.aliases.clear();
+        assert_eq!(author_style(&app, &message).0, "alice");
+    }
+
+    #[test]
+    fn draws_renamed_channels_once_the_rows_are_invalidated() {
+        let mut app = chatting(0);
+        app.chat_guild_id = Some("1".to_string());
+        app.channels = vec![category_with("42", "general")];
+        app.messages = vec![Message::for_tests(
+            "1",
+            "see https://discord.com/channels/1/42/7",
+        )];
+        assert!(draw(&mut app).contains("#general"));
+
+        app.channels = vec![category_with("42", "lobby")];
+        app.invalidate_rows();
+        let screen = draw(&mut app);
+        assert!(screen.contains("#lobby"));
+        assert!(!screen.contains("#general"));
+    }
+
+    #[test]
+    fn keys_relative_times_by_the_minute() {
+        let mut app = App::for_tests();
+        let message = Message::for_tests("1", "hello");
+        let key = row_key(&app, &message, None, true, Collapse::Shown, 80);
+        assert_eq!(key.relative_minute, None);
+
+        app.time_format = TimeFormat::Relative;
+        let key = row_key(&app, &message, None, true, Collapse::Shown, 80);
+        assert!(key.relative_minute.is_some());
+    }
 }
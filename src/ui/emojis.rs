@@ -0,0 +1,111 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::{api::Emoji, app::App, ui::sanitize::sanitize_line};
+
+/// Where a new emoji stands while it is typed in the input bar.
+#[derive(Debug, Clone)]
+pub enum EmojiUpload {
+    Name,
+    /// The name is set; the path of the image is being typed.
+    Path(String),
+}
+
+/// The emojis of a guild, opened from its channel list to upload and delete them.
+#[derive(Debug, Clone)]
+pub struct EmojiManager {
+    pub guild_id: String,
+    /// `None` until they are loaded.
+    pub emojis: Option<Vec<Emoji>>,
+    pub selection_index: usize,
+    /// Without Manage Expressions, the emojis are only listed.
+    pub read_only: bool,
+    pub confirm_delete: bool,
+    pub upload: Option<EmojiUpload>,
+    /// The filter of the channel list, kept aside while the input bar is used here.
+    pub filter: String,
+}
+
+impl EmojiManager {
+    pub fn selected(&self) -> Option<&Emoji> {
+        self.emojis.as_ref()?.get(self.selection_index)
+    }
+}
+
+fn emoji_row(emoji: &Emoji) -> ListItem<'static> {
+    let mut spans = vec![
+        Span::styled(
+            format!(":{}:", sanitize_line(&emoji.name)),
+            Style::default().fg(Color::LightYellow),
+        ),
+        Span::styled(
+            format!("  {}", emoji.id),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ];
+    if emoji.animated == Some(true) {
+        spans.push(Span::styled(
+            "  animated",
+            Style::default().fg(Color::LightCyan),
+        ));
+    }
+    if emoji.available == Some(false) {
+        spans.push(Span::styled(
+            "  unavailable",
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    if let Some(user) = &emoji.user {
+        spans.push(Span::raw(format!("  by {}", sanitize_line(&user.username))));
+    }
+    ListItem::new(Line::from(spans))
+}
+
+pub fn draw_emoji_manager(f: &mut Frame, area: Rect, app: &App, manager: &EmojiManager) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let title = match &manager.upload {
+        None if manager.read_only => "Emojis (read-only, Esc to close)".to_string(),
+        None => "Emojis (u to upload, d to delete, Esc to close)".to_string(),
+        Some(EmojiUpload::Name) => "New emoji: type its name".to_string(),
+        Some(EmojiUpload::Path(name)) => format!("New emoji :{name}: type the path of its image"),
+    };
+    let block = Block::default()
+        .title(Span::styled(title, Style::default().fg(Color::Yellow)))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let empty_item =
+        |text: &'static str| vec![ListItem::new(text).style(Style::default().fg(Color::DarkGray))];
+    let (items, selected) = match &manager.emojis {
+        None => (empty_item("Loading emojis..."), None),
+        Some(emojis) if emojis.is_empty() => (empty_item("No custom emojis yet"), None),
+        Some(emojis) => (
+            emojis.iter().map(emoji_row).collect(),
+            Some(manager.selection_index),
+        ),
+    };
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default().with_selected(selected);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_stateful_widget(list, popup_rect, &mut state);
+}
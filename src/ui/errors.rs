@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::Span,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem},
+};
+
+use crate::app::App;
+
+/// Errors kept for the Ctrl+E log.
+pub const ERROR_LOG_LIMIT: usize = 50;
+/// How long an error stays on screen unless a key is pressed first.
+pub const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct ErrorEntry {
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+/// The newest error for the status bar, while its toast has not expired.
+pub fn error_segment(app: &App) -> Option<Span<'static>> {
+    let entry = app.error_log.front()?;
+    if !toast_visible(app.error_toast) {
+        return None;
+    }
+
+    Some(Span::styled(
+        format!(" {} ", entry.message),
+        Style::default().fg(Color::White).bg(Color::Red).bold(),
+    ))
+}
+
+/// Lists the last errors, newest first.
+pub fn draw_error_log(f: &mut Frame, area: Rect, app: &App) {
+    let width = (area.width * 4 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Errors (Esc to close)",
+            Style::default().fg(Color::LightRed),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let items: Vec<ListItem> = if app.error_log.is_empty() {
+        vec![ListItem::new("No errors").style(Style::default().fg(Color::DarkGray))]
+    } else {
+        app.error_log
+            .iter()
+            .map(|entry| {
+                ListItem::new(format!(
+                    "[{}] {}",
+                    entry.time.format("%H:%M:%S"),
+                    entry.message
+                ))
+            })
+            .collect()
+    };
+
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(List::new(items).block(block), popup_rect);
+}
+
+/// Whether the toast shown at `shown_at` is still on screen.
+pub fn toast_visible(shown_at: Option<Instant>) -> bool {
+    shown_at.is_some_and(|shown_at| shown_at.elapsed() < TOAST_DURATION)
+}
@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+
+use crate::{api::Guild, config::GuildSort};
+
+/// Whether every character of `query` appears in `candidate` in the same order,
+/// ignoring case. An empty query matches everything.
+pub fn fuzzy_match(query: &str, candidate: &str) -> bool {
+    let mut candidate = candidate.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| candidate.any(|c| c == q))
+}
+
+/// The guilds shown in the guild list for the current filter, in display order, with the
+/// `favorites` first and the `muted` ones last. Every lookup by selection index must go through here so it agrees
+/// with what was drawn.
+pub fn filter_guilds<'a>(
+    guilds: &'a [Guild],
+    query: &str,
+    sort: GuildSort,
+    opened: &HashMap<String, DateTime<Utc>>,
+    muted: &HashSet<String>,
+    favorites: &[String],
+) -> Vec<&'a Guild> {
+    let mut filtered: Vec<&Guild> = guilds
+        .iter()
+        .filter(|g| fuzzy_match(query, &g.name))
+        .collect();
+
+    // Both sorts are stable, so ties keep the API order.
+    match sort {
+        GuildSort::Api => {}
+        GuildSort::Alphabetical => filtered.sort_by_cached_key(|g| collation_key(&g.name)),
+        GuildSort::Recent => {
+            filtered.sort_by_key(|g| std::cmp::Reverse(opened.get(&g.id).copied()));
+        }
+    }
+    // Stable too, so an unmuted or unpinned guild goes back to its place.
+    filtered.sort_by_key(|g| (!favorites.contains(&g.id), muted.contains(&g.id)));
+    filtered
+}
+
+/// Sort key comparing names by letter before case or accents, so "éclair" sorts among
+/// the "e"s and "apple" next to "Apple".
+fn collation_key(name: &str) -> (String, String) {
+    let base = name
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(strip_accent)
+        .collect();
+    (base, name.to_lowercase())
+}
+
+/// The unaccented letter for the common Latin-1 and Latin Extended-A accented letters.
+fn strip_accent(c: char) -> char {
+    match c {
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è'..='ë' | 'ē' | 'ė' | 'ę' | 'ě' => 'e',
+        'ğ' => 'g',
+        'ì'..='ï' | 'ī' | 'į' | 'ı' => 'i',
+        'ł' | 'ľ' => 'l',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò'..='ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ŕ' | 'ř' => 'r',
+        'ś' | 'š' | 'ş' => 's',
+        'ť' | 'ţ' => 't',
+        'ù'..='ü' | 'ū' | 'ů' | 'ű' | 'ų' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guild(id: &str, name: &str) -> Guild {
+        Guild {
+            id: id.to_string(),
+            name: name.to_string(),
+            icon: None,
+        }
+    }
+
+    fn names(guilds: &[&Guild]) -> Vec<String> {
+        guilds.iter().map(|g| g.name.clone()).collect()
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        assert!(fuzzy_match("rst", "Rust Lang"));
+        assert!(fuzzy_match("RL", "rust lang"));
+        assert!(fuzzy_match("", "anything"));
+        assert!(!fuzzy_match("tsr", "Rust"));
+        assert!(!fuzzy_match("rustt", "Rust"));
+    }
+
+    #[test]
+    fn filters_guilds_in_api_order() {
+        let guilds = [
+            guild("1", "Rust"),
+            guild("2", "Gaming"),
+            guild("3", "rustaceans"),
+        ];
+
+        let filtered = filter_guilds(
+            &guilds,
+            "rs",
+            GuildSort::Api,
+            &HashMap::new(),
+            &HashSet::new(),
+            &[],
+        );
+        assert_eq!(names(&filtered), ["Rust", "rustaceans"]);
+
+        let all = filter_guilds(
+            &guilds,
+            "",
+            GuildSort::Api,
+            &HashMap::new(),
+            &HashSet::new(),
+            &[],
+        );
+        assert_eq!(all.len(), 3);
+    }
+
+    #[test]
+    fn filters_to_nothing() {
+        let guilds = [guild("1", "Rust")];
+        let filtered = filter_guilds(
+            &guilds,
+            "xyz",
+            GuildSort::Api,
+            &HashMap::new(),
+            &HashSet::new(),
+            &[],
+        );
+        assert!(filtered.is_empty());
+    }
+}
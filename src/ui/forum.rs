@@ -0,0 +1,85 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::Span,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::{api::Channel, app::App, ui::sanitize::sanitize_line};
+
+/// Where a new post stands while it is typed in the input bar.
+#[derive(Debug, Clone)]
+pub enum PostDraft {
+    Title,
+    /// The title is set; the first message is being typed.
+    Message(String),
+}
+
+/// The list of posts of a forum channel, opened with Enter on the forum.
+#[derive(Debug, Clone)]
+pub struct ForumViewer {
+    pub channel_id: String,
+    pub channel_name: String,
+    pub guild_id: String,
+    /// Most recently active post first; `None` until they are loaded.
+    pub posts: Option<Vec<Channel>>,
+    pub selection_index: usize,
+    pub draft: Option<PostDraft>,
+}
+
+fn post_label(post: &Channel) -> String {
+    let name = sanitize_line(&post.name);
+    match post.message_count {
+        Some(1) => format!("{name} (1 reply)"),
+        Some(count) => format!("{name} ({count} replies)"),
+        None => name.into_owned(),
+    }
+}
+
+pub fn draw_forum(f: &mut Frame, area: Rect, app: &App, forum: &ForumViewer) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let channel_name = sanitize_line(&forum.channel_name);
+    let title = match &forum.draft {
+        None => format!("#{channel_name} (Enter to open, n for a new post, Esc to close)"),
+        Some(PostDraft::Title) => format!("New post in #{channel_name}: type its title"),
+        Some(PostDraft::Message(title)) => format!("New post \"{title}\": type its first message"),
+    };
+    let block = Block::default()
+        .title(Span::styled(title, Style::default().fg(Color::Yellow)))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let empty_item =
+        |text: &'static str| vec![ListItem::new(text).style(Style::default().fg(Color::DarkGray))];
+    let (items, selected) = match &forum.posts {
+        None => (empty_item("Loading posts..."), None),
+        Some(posts) if posts.is_empty() => (empty_item("No posts yet"), None),
+        Some(posts) => (
+            posts
+                .iter()
+                .map(|post| ListItem::new(post_label(post)))
+                .collect(),
+            Some(forum.selection_index),
+        ),
+    };
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default().with_selected(selected);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_stateful_widget(list, popup_rect, &mut state);
+}
@@ -0,0 +1,121 @@
+/// Messages remembered per channel for Alt+Up/Down.
+pub const HISTORY_LIMIT: usize = 50;
+
+/// Where Alt+Up/Down recall stands in the history of a channel.
+#[derive(Debug, Clone)]
+pub struct Recall {
+    pub channel_id: String,
+    /// The entry in the input, oldest first.
+    pub index: usize,
+    /// What was typed before recalling, put back when going past the newest entry.
+    pub typed: String,
+}
+
+/// Adds a sent message to the history of its channel, unless it repeats the last one.
+pub fn record(history: &mut Vec<String>, text: String) {
+    if history.last() == Some(&text) {
+        return;
+    }
+    history.push(text);
+    if history.len() > HISTORY_LIMIT {
+        history.remove(0);
+    }
+}
+
+/// Steps through `history` from `recall`, to an older entry or a newer one. Returns the
+/// text for the input and where recall then stands: `None` once past the newest entry,
+/// with the text typed before. Entries edited in the input are left as they were sent.
+pub fn step(
+    history: &[String],
+    recall: Option<Recall>,
+    channel_id: &str,
+    input: &str,
+    older: bool,
+) -> Option<(String, Option<Recall>)> {
+    let (index, typed) = match recall {
+        Some(recall) if recall.channel_id == channel_id => (Some(recall.index), recall.typed),
+        _ => (None, input.to_string()),
+    };
+
+    let index = match (index, older) {
+        _ if history.is_empty() => return None,
+        (None, true) => history.len() - 1,
+        (None, false) => return None,
+        (Some(index), true) => index.saturating_sub(1),
+        (Some(index), false) if index + 1 < history.len() => index + 1,
+        (Some(_), false) => return Some((typed, None)),
+    };
+
+    let recall = Recall {
+        channel_id: channel_id.to_string(),
+        index,
+        typed,
+    };
+    Some((history[index].clone(), Some(recall)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn records_without_repeating_the_last_entry() {
+        let mut entries = Vec::new();
+        record(&mut entries, "a".to_string());
+        record(&mut entries, "a".to_string());
+        record(&mut entries, "b".to_string());
+        record(&mut entries, "a".to_string());
+        assert_eq!(entries, ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn keeps_the_newest_entries_up_to_the_limit() {
+        let mut entries = Vec::new();
+        for i in 0..HISTORY_LIMIT + 10 {
+            record(&mut entries, i.to_string());
+        }
+        assert_eq!(entries.len(), HISTORY_LIMIT);
+        assert_eq!(entries[0], "10");
+        assert_eq!(entries.last().unwrap(), &(HISTORY_LIMIT + 9).to_string());
+    }
+
+    #[test]
+    fn steps_back_and_forth_and_restores_what_was_typed() {
+        let entries = history(&["first", "second"]);
+
+        let (input, recall) = step(&entries, None, "c1", "draft", true).unwrap();
+        assert_eq!(input, "second");
+        let (input, recall) = step(&entries, recall, "c1", &input, true).unwrap();
+        assert_eq!(input, "first");
+        // The oldest entry stays.
+        let (input, recall) = step(&entries, recall, "c1", &input, true).unwrap();
+        assert_eq!(input, "first");
+
+        let (input, recall) = step(&entries, recall, "c1", &input, false).unwrap();
+        assert_eq!(input, "second");
+        let (input, recall) = step(&entries, recall, "c1", &input, false).unwrap();
+        assert_eq!(input, "draft");
+        assert!(recall.is_none());
+    }
+
+    #[test]
+    fn does_nothing_without_history_or_going_newer_first() {
+        assert!(step(&[], None, "c1", "draft", true).is_none());
+        assert!(step(&history(&["a"]), None, "c1", "draft", false).is_none());
+    }
+
+    #[test]
+    fn starts_over_in_another_channel() {
+        let entries = history(&["a", "b"]);
+        let (_, recall) = step(&entries, None, "c1", "", true).unwrap();
+        let (_, recall) = step(&entries, recall, "c1", "", true).unwrap();
+
+        let (input, recall) = step(&entries, recall, "c2", "typed in c2", true).unwrap();
+        assert_eq!(input, "b");
+        assert_eq!(recall.unwrap().typed, "typed in c2");
+    }
+}
@@ -0,0 +1,183 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::{api::Message, app::App, ui::sanitize::sanitize_line};
+
+/// The `/ignore` overlay listing the ignored users, to remove some.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreList {
+    pub selection_index: usize,
+}
+
+/// How a message is drawn once ignored users are taken into account. Consecutive
+/// messages of ignored users share a single placeholder, drawn in place of the newest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Collapse {
+    Shown,
+    /// The newest message of a run of this many collapsed messages.
+    Placeholder(usize),
+    /// Part of a run whose placeholder is drawn for a newer message.
+    Hidden,
+}
+
+/// Whether `message` is collapsed: its author is ignored and it wasn't revealed.
+pub fn is_collapsed(app: &App, message: &Message) -> bool {
+    app.is_ignored(&message.author.id) && !app.revealed_ignored.contains(&message.id)
+}
+
+/// How the message at `index` of the newest-first `messages` is drawn.
+pub fn collapse(app: &App, messages: &[&Message], index: usize) -> Collapse {
+    if !is_collapsed(app, messages[index]) {
+        return Collapse::Shown;
+    }
+    if index > 0 && is_collapsed(app, messages[index - 1]) {
+        return Collapse::Hidden;
+    }
+    let run = messages[index..]
+        .iter()
+        .take_while(|message| is_collapsed(app, message))
+        .count();
+    Collapse::Placeholder(run)
+}
+
+/// The line drawn instead of `count` collapsed messages.
+pub fn placeholder_line(count: usize) -> Line<'static> {
+    let text = if count == 1 {
+        "— 1 message from blocked user —".to_string()
+    } else {
+        format!("— {count} messages from blocked users —")
+    };
+    Line::from(Span::styled(
+        text,
+        Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::ITALIC),
+    ))
+}
+
+pub fn draw_ignore_list(f: &mut Frame, area: Rect, app: &App, list: &IgnoreList) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Ignored users (d to stop ignoring, Esc to close)",
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let (items, selected) = if app.ignored.is_empty() {
+        (
+            vec![
+                ListItem::new(
+                    "Nobody is ignored. Press x on a selected message to ignore its author.",
+                )
+                .style(Style::default().fg(Color::DarkGray)),
+            ],
+            None,
+        )
+    } else {
+        let items = app
+            .ignored
+            .iter()
+            .map(|user| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(sanitize_line(&user.name).into_owned()),
+                    Span::styled(
+                        format!("  {}", user.id),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect();
+        (items, Some(list.selection_index))
+    };
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default().with_selected(selected);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_stateful_widget(list, popup_rect, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IgnoredUser;
+
+    fn message(id: &str, author_id: &str) -> Message {
+        Message::for_tests(id, "hi").by(author_id, "someone")
+    }
+
+    fn ignoring(user_id: &str) -> App {
+        let mut app = App::for_tests();
+        app.ignored.push(IgnoredUser {
+            id: user_id.to_string(),
+            name: "troll".to_string(),
+        });
+        app
+    }
+
+    #[test]
+    fn collapses_a_run_of_ignored_messages_into_one_placeholder() {
+        let app = ignoring("9");
+        // Newest first.
+        let messages = [
+            message("5", "1"),
+            message("4", "9"),
+            message("3", "9"),
+            message("2", "1"),
+            message("1", "9"),
+        ];
+        let messages: Vec<&Message> = messages.iter().collect();
+
+        let collapsed: Vec<Collapse> = (0..messages.len())
+            .map(|index| collapse(&app, &messages, index))
+            .collect();
+        assert_eq!(
+            collapsed,
+            [
+                Collapse::Shown,
+                Collapse::Placeholder(2),
+                Collapse::Hidden,
+                Collapse::Shown,
+                Collapse::Placeholder(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn shows_revealed_messages() {
+        let mut app = ignoring("9");
+        let ignored = message("4", "9");
+        assert!(is_collapsed(&app, &ignored));
+
+        app.revealed_ignored.insert("4".to_string());
+        assert!(!is_collapsed(&app, &ignored));
+        assert!(!is_collapsed(&app, &message("5", "1")));
+    }
+
+    #[test]
+    fn counts_the_collapsed_messages_in_the_placeholder() {
+        let text = |count| placeholder_line(count).spans[0].content.to_string();
+        assert_eq!(text(1), "— 1 message from blocked user —");
+        assert_eq!(text(3), "— 3 messages from blocked users —");
+    }
+}
@@ -0,0 +1,35 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Byte offset of the grapheme boundary before `pos`, so emojis and other multi-codepoint
+/// clusters are stepped over as a whole.
+pub fn prev_grapheme(input: &str, pos: usize) -> usize {
+    input[..pos]
+        .grapheme_indices(true)
+        .next_back()
+        .map_or(0, |(i, _)| i)
+}
+
+/// Byte offset of the grapheme boundary after `pos`.
+pub fn next_grapheme(input: &str, pos: usize) -> usize {
+    input[pos..]
+        .graphemes(true)
+        .next()
+        .map_or(pos, |g| pos + g.len())
+}
+
+/// Start of the word before `pos`, skipping any whitespace in between.
+pub fn prev_word(input: &str, pos: usize) -> usize {
+    let before = input[..pos].trim_end();
+    before.rfind(char::is_whitespace).map_or(0, |i| {
+        i + before[i..].chars().next().map_or(1, char::len_utf8)
+    })
+}
+
+/// End of the word after `pos`, skipping any whitespace in between.
+pub fn next_word(input: &str, pos: usize) -> usize {
+    let after = &input[pos..];
+    let word_start = after.len() - after.trim_start().len();
+    after[word_start..]
+        .find(char::is_whitespace)
+        .map_or(input.len(), |i| pos + word_start + i)
+}
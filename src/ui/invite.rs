@@ -0,0 +1,82 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+};
+
+use crate::{api::invite::Invite, app::App, ui::sanitize::sanitize_line};
+
+/// The `/join-invite` popup: the preview of an invite, then once joined the offer to
+/// open the guild.
+#[derive(Debug, Clone)]
+pub struct InvitePrompt {
+    pub invite: Invite,
+    pub joined: bool,
+}
+
+pub fn draw_invite_prompt(f: &mut Frame, area: Rect, app: &App, prompt: &InvitePrompt) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = 9.min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let invite = &prompt.invite;
+    let title = if prompt.joined {
+        "Joined (Enter to open it, Esc to stay here)"
+    } else {
+        "Invite (y to join, n or Esc to cancel)"
+    };
+    let block = Block::default()
+        .title(Span::styled(title, Style::default().fg(Color::Yellow)))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let mut lines = vec![Line::from(
+        Span::raw(sanitize_line(invite.guild_name()).into_owned()).bold(),
+    )];
+    let counts = match (
+        invite.approximate_member_count,
+        invite.approximate_presence_count,
+    ) {
+        (Some(members), Some(online)) => Some(format!("{members} members, {online} online")),
+        (Some(members), None) => Some(format!("{members} members")),
+        _ => None,
+    };
+    if let Some(counts) = counts {
+        lines.push(Line::from(Span::styled(
+            counts,
+            Style::default().fg(Color::Gray),
+        )));
+    }
+    if let Some(name) = invite.channel.as_ref().and_then(|c| c.name.as_deref()) {
+        lines.push(Line::from(Span::styled(
+            format!("#{}", sanitize_line(name)),
+            Style::default().fg(Color::LightCyan),
+        )));
+    }
+    if let Some(inviter) = &invite.inviter {
+        lines.push(Line::from(Span::styled(
+            format!("Invited by {}", sanitize_line(&inviter.username)),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines.push(Line::from(Span::styled(
+        format!("discord.gg/{}", invite.code),
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        popup_rect,
+    );
+}
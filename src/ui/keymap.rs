@@ -0,0 +1,662 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+
+use crate::{
+    app::{App, AppAction, CursorMove},
+    clipboard,
+    ui::theme::Theme,
+};
+
+/// Where a binding does something, to group them in the help overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyContext {
+    Global,
+    /// The guild, DM and channel lists.
+    Lists,
+    Chat,
+    MessageSelection,
+}
+
+/// A key and what it does, as the input handler reads it and the help overlay lists it.
+#[derive(Debug, Clone, Copy)]
+pub struct Binding<A> {
+    pub key: KeyCode,
+    pub modifiers: KeyModifiers,
+    pub context: KeyContext,
+    pub action: A,
+    pub description: &'static str,
+}
+
+/// Keys turned into an action as they are pressed.
+pub type KeyBinding = Binding<fn() -> AppAction>;
+
+/// What a key does while a message is selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKey {
+    OpenAttachment,
+    ToggleSpoiler,
+    React,
+    Copy,
+    CopyWithHeader,
+    Quote,
+    Translate,
+    Profile,
+    DmAuthor,
+    Ignore,
+    Publish,
+    Previous,
+    Next,
+    Help,
+}
+
+const fn key(
+    key: KeyCode,
+    modifiers: KeyModifiers,
+    context: KeyContext,
+    action: fn() -> AppAction,
+    description: &'static str,
+) -> KeyBinding {
+    Binding {
+        key,
+        modifiers,
+        context,
+        action,
+        description,
+    }
+}
+
+const fn ctrl(
+    c: char,
+    context: KeyContext,
+    action: fn() -> AppAction,
+    description: &'static str,
+) -> KeyBinding {
+    key(
+        KeyCode::Char(c),
+        KeyModifiers::CONTROL,
+        context,
+        action,
+        description,
+    )
+}
+
+const fn plain(
+    code: KeyCode,
+    context: KeyContext,
+    action: fn() -> AppAction,
+    description: &'static str,
+) -> KeyBinding {
+    key(code, KeyModifiers::NONE, context, action, description)
+}
+
+fn paste_clipboard() -> AppAction {
+    match clipboard::paste() {
+        Ok(text) => AppAction::Paste(text),
+        Err(e) => AppAction::ApiError(format!("Failed to paste: {e}")),
+    }
+}
+
+/// Every key the input handler knows besides typed characters, which go to the input.
+pub const KEYS: &[KeyBinding] = &[
+    plain(
+        KeyCode::F(1),
+        KeyContext::Global,
+        || AppAction::ToggleKeyHelp,
+        "Show this help",
+    ),
+    ctrl('c', KeyContext::Global, || AppAction::SigInt, "Quit"),
+    plain(
+        KeyCode::Esc,
+        KeyContext::Global,
+        || AppAction::InputEscape,
+        "Go back, or close a popup",
+    ),
+    plain(
+        KeyCode::Enter,
+        KeyContext::Global,
+        || AppAction::InputSubmit,
+        "Open the selection, or send the message",
+    ),
+    plain(
+        KeyCode::Up,
+        KeyContext::Global,
+        || AppAction::SelectPrevious,
+        "Select the previous row",
+    ),
+    plain(
+        KeyCode::Down,
+        KeyContext::Global,
+        || AppAction::SelectNext,
+        "Select the next row",
+    ),
+    ctrl(
+        'k',
+        KeyContext::Global,
+        || AppAction::ToggleSwitcher,
+        "Quick switcher",
+    ),
+    ctrl(
+        'g',
+        KeyContext::Global,
+        || AppAction::JumpToMention,
+        "Jump to the latest mention",
+    ),
+    ctrl(
+        'n',
+        KeyContext::Global,
+        || AppAction::ToggleNotifications,
+        "Recent mentions",
+    ),
+    ctrl(
+        'e',
+        KeyContext::Global,
+        || AppAction::ToggleErrorLog,
+        "Error log",
+    ),
+    ctrl(
+        's',
+        KeyContext::Global,
+        || AppAction::CycleGuildSort,
+        "Cycle the guild sort, elsewhere pin the chat to a split pane",
+    ),
+    plain(
+        KeyCode::F(3),
+        KeyContext::Global,
+        || AppAction::CycleTimeFormat,
+        "Cycle the time format",
+    ),
+    plain(
+        KeyCode::F(12),
+        KeyContext::Chat,
+        || AppAction::ToggleDensity,
+        "Switch between compact and cozy messages",
+    ),
+    ctrl(
+        'v',
+        KeyContext::Global,
+        paste_clipboard,
+        "Paste the clipboard",
+    ),
+    plain(
+        KeyCode::Tab,
+        KeyContext::Lists,
+        || AppAction::InputTab,
+        "Switch between servers and DMs",
+    ),
+    ctrl(
+        't',
+        KeyContext::Lists,
+        || AppAction::LoadArchivedThreads,
+        "Load the archived threads of the channel",
+    ),
+    plain(
+        KeyCode::F(4),
+        KeyContext::Lists,
+        || AppAction::ToggleMute,
+        "Mute or unmute",
+    ),
+    plain(
+        KeyCode::F(5),
+        KeyContext::Lists,
+        || AppAction::ToggleShowMuted,
+        "Show or hide the muted channels",
+    ),
+    plain(
+        KeyCode::F(6),
+        KeyContext::Lists,
+        || AppAction::ToggleFavorite,
+        "Add or remove a favorite server",
+    ),
+    plain(
+        KeyCode::F(7),
+        KeyContext::Lists,
+        || AppAction::NewChannel,
+        "Create a text channel",
+    ),
+    plain(
+        KeyCode::F(8),
+        KeyContext::Lists,
+        || AppAction::RenameChannel,
+        "Rename the channel",
+    ),
+    plain(
+        KeyCode::F(9),
+        KeyContext::Lists,
+        || AppAction::MarkGuildRead,
+        "Mark the server as read",
+    ),
+    plain(
+        KeyCode::F(10),
+        KeyContext::Lists,
+        || AppAction::OpenEmojiManager,
+        "Manage the server's emojis",
+    ),
+    ctrl(
+        'r',
+        KeyContext::Chat,
+        || AppAction::SelectMessage,
+        "Select a message",
+    ),
+    ctrl(
+        'p',
+        KeyContext::Chat,
+        || AppAction::TogglePins,
+        "Pinned messages",
+    ),
+    ctrl(
+        'a',
+        KeyContext::Chat,
+        || AppAction::OpenAttachPrompt,
+        "Attach a file",
+    ),
+    ctrl(
+        'f',
+        KeyContext::Chat,
+        || AppAction::OpenSearch,
+        "Search the channel",
+    ),
+    ctrl(
+        'l',
+        KeyContext::Chat,
+        || AppAction::ToggleLinkHints,
+        "Number the links to open or copy one",
+    ),
+    plain(
+        KeyCode::F(2),
+        KeyContext::Chat,
+        || AppAction::ToggleMemberList,
+        "Member list",
+    ),
+    key(
+        KeyCode::Up,
+        KeyModifiers::ALT,
+        KeyContext::Chat,
+        || AppAction::HistoryPrevious,
+        "Recall the previous sent message",
+    ),
+    key(
+        KeyCode::Down,
+        KeyModifiers::ALT,
+        KeyContext::Chat,
+        || AppAction::HistoryNext,
+        "Recall the next sent message",
+    ),
+    plain(
+        KeyCode::PageUp,
+        KeyContext::Chat,
+        || AppAction::ScrollPageUp,
+        "Scroll up a page",
+    ),
+    plain(
+        KeyCode::PageDown,
+        KeyContext::Chat,
+        || AppAction::ScrollPageDown,
+        "Scroll down a page",
+    ),
+    key(
+        KeyCode::Up,
+        KeyModifiers::CONTROL,
+        KeyContext::Chat,
+        || AppAction::ScrollLineUp,
+        "Scroll up a few lines",
+    ),
+    key(
+        KeyCode::Down,
+        KeyModifiers::CONTROL,
+        KeyContext::Chat,
+        || AppAction::ScrollLineDown,
+        "Scroll down a few lines",
+    ),
+    plain(
+        KeyCode::Backspace,
+        KeyContext::Chat,
+        || AppAction::InputBackspace,
+        "Delete the character before the cursor",
+    ),
+    ctrl(
+        'w',
+        KeyContext::Chat,
+        || AppAction::InputDeleteWord,
+        "Delete the word before the cursor",
+    ),
+    ctrl(
+        'u',
+        KeyContext::Chat,
+        || AppAction::InputClearToStart,
+        "Delete up to the start of the input",
+    ),
+    plain(
+        KeyCode::Left,
+        KeyContext::Chat,
+        || AppAction::InputMoveCursor(CursorMove::Left),
+        "Move the cursor left",
+    ),
+    plain(
+        KeyCode::Right,
+        KeyContext::Chat,
+        || AppAction::InputMoveCursor(CursorMove::Right),
+        "Move the cursor right",
+    ),
+    key(
+        KeyCode::Left,
+        KeyModifiers::CONTROL,
+        KeyContext::Chat,
+        || AppAction::InputMoveCursor(CursorMove::WordLeft),
+        "Move the cursor a word left",
+    ),
+    key(
+        KeyCode::Right,
+        KeyModifiers::CONTROL,
+        KeyContext::Chat,
+        || AppAction::InputMoveCursor(CursorMove::WordRight),
+        "Move the cursor a word right",
+    ),
+    plain(
+        KeyCode::Home,
+        KeyContext::Chat,
+        || AppAction::InputMoveCursor(CursorMove::Home),
+        "Move the cursor to the start",
+    ),
+    plain(
+        KeyCode::End,
+        KeyContext::Chat,
+        || AppAction::InputMoveCursor(CursorMove::End),
+        "Move the cursor to the end",
+    ),
+    ctrl(
+        'd',
+        KeyContext::MessageSelection,
+        || AppAction::DeleteMessage,
+        "Delete the message",
+    ),
+];
+
+/// Keys of vim normal mode, where typing doesn't filter the lists. Those of the lists
+/// context only work there.
+pub const VIM_NORMAL_KEYS: &[KeyBinding] = &[
+    plain(
+        KeyCode::Char('?'),
+        KeyContext::Global,
+        || AppAction::ToggleKeyHelp,
+        "Show this help",
+    ),
+    plain(
+        KeyCode::Char('m'),
+        KeyContext::Lists,
+        || AppAction::ToggleMute,
+        "Mute or unmute",
+    ),
+    plain(
+        KeyCode::Char('M'),
+        KeyContext::Lists,
+        || AppAction::ToggleShowMuted,
+        "Show or hide the muted channels",
+    ),
+    plain(
+        KeyCode::Char('f'),
+        KeyContext::Lists,
+        || AppAction::ToggleFavorite,
+        "Add or remove a favorite server",
+    ),
+    plain(
+        KeyCode::Char('n'),
+        KeyContext::Lists,
+        || AppAction::NewChannel,
+        "Create a text channel",
+    ),
+    plain(
+        KeyCode::Char('r'),
+        KeyContext::Lists,
+        || AppAction::RenameChannel,
+        "Rename the channel",
+    ),
+    plain(
+        KeyCode::Char('A'),
+        KeyContext::Lists,
+        || AppAction::MarkGuildRead,
+        "Mark the server as read",
+    ),
+    plain(
+        KeyCode::Char('e'),
+        KeyContext::Lists,
+        || AppAction::OpenEmojiManager,
+        "Manage the server's emojis",
+    ),
+];
+
+const fn selection(
+    c: char,
+    action: SelectionKey,
+    description: &'static str,
+) -> Binding<SelectionKey> {
+    Binding {
+        key: KeyCode::Char(c),
+        modifiers: KeyModifiers::NONE,
+        context: KeyContext::MessageSelection,
+        action,
+        description,
+    }
+}
+
+/// Keys of the selected message.
+pub const SELECTION_KEYS: &[Binding<SelectionKey>] = &[
+    selection('k', SelectionKey::Previous, "Select the previous message"),
+    selection('j', SelectionKey::Next, "Select the next message"),
+    selection('y', SelectionKey::Copy, "Copy the text"),
+    selection(
+        'Y',
+        SelectionKey::CopyWithHeader,
+        "Copy as [time] author: text",
+    ),
+    selection('q', SelectionKey::Quote, "Quote it in the input"),
+    selection('+', SelectionKey::React, "React with an emoji"),
+    selection('o', SelectionKey::OpenAttachment, "Open the attachment"),
+    selection(
+        's',
+        SelectionKey::ToggleSpoiler,
+        "Reveal or hide spoilers, or a blocked user's message",
+    ),
+    selection('t', SelectionKey::Translate, "Translate it, when set up"),
+    selection('i', SelectionKey::Profile, "Show the author's profile"),
+    selection('d', SelectionKey::DmAuthor, "Open a DM with the author"),
+    selection('x', SelectionKey::Ignore, "Ignore the author"),
+    selection(
+        'P',
+        SelectionKey::Publish,
+        "Publish it to the following servers, in announcement channels",
+    ),
+    selection('?', SelectionKey::Help, "Show this help"),
+];
+
+/// The action of a pressed key, if any. Control takes precedence over Alt, and Alt
+/// keys without a binding of their own act as without it.
+pub fn key_action(event: &KeyEvent) -> Option<AppAction> {
+    let modifiers = if event.modifiers.contains(KeyModifiers::CONTROL) {
+        KeyModifiers::CONTROL
+    } else if event.modifiers.contains(KeyModifiers::ALT) {
+        KeyModifiers::ALT
+    } else {
+        KeyModifiers::NONE
+    };
+    let find = |modifiers: KeyModifiers| {
+        KEYS.iter()
+            .find(|binding| binding.key == event.code && binding.modifiers == modifiers)
+    };
+
+    let binding = match modifiers {
+        KeyModifiers::ALT => find(KeyModifiers::ALT).or_else(|| find(KeyModifiers::NONE)),
+        modifiers => find(modifiers),
+    };
+    match (binding, event.code) {
+        (Some(binding), _) => Some((binding.action)()),
+        (None, KeyCode::Char(c)) if modifiers != KeyModifiers::CONTROL => {
+            Some(AppAction::InputChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// The action of a character typed in vim normal mode, in a list or not, if any.
+pub fn vim_normal_action(c: char, in_list: bool) -> Option<AppAction> {
+    VIM_NORMAL_KEYS
+        .iter()
+        .filter(|binding| in_list || binding.context != KeyContext::Lists)
+        .find(|binding| binding.key == KeyCode::Char(c))
+        .map(|binding| (binding.action)())
+}
+
+pub fn selection_key(c: char) -> Option<SelectionKey> {
+    SELECTION_KEYS
+        .iter()
+        .find(|binding| binding.key == KeyCode::Char(c))
+        .map(|binding| binding.action)
+}
+
+/// How a key is written in the help, such as `Ctrl+R` or `Alt+Up`.
+pub fn key_label(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let key = match code {
+        KeyCode::Char(c) if modifiers.is_empty() => c.to_string(),
+        KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        code => format!("{code:?}"),
+    };
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        format!("Ctrl+{key}")
+    } else if modifiers.contains(KeyModifiers::ALT) {
+        format!("Alt+{key}")
+    } else {
+        key
+    }
+}
+
+/// The lines of the help overlay: the bindings of every table, grouped by context.
+pub fn help_lines(theme: &Theme) -> Vec<Line<'static>> {
+    let rows = |bindings: Vec<(KeyCode, KeyModifiers, &'static str)>| {
+        bindings
+            .into_iter()
+            .map(|(code, modifiers, description)| (key_label(code, modifiers), description))
+            .collect::<Vec<_>>()
+    };
+    let of_context = |context: KeyContext| {
+        rows(
+            KEYS.iter()
+                .filter(|binding| binding.context == context)
+                .map(|binding| (binding.key, binding.modifiers, binding.description))
+                .collect(),
+        )
+    };
+    let sections = [
+        ("Global", of_context(KeyContext::Global)),
+        ("Lists", of_context(KeyContext::Lists)),
+        ("Chat", of_context(KeyContext::Chat)),
+        (
+            "Message selection",
+            [
+                of_context(KeyContext::MessageSelection),
+                rows(
+                    SELECTION_KEYS
+                        .iter()
+                        .map(|binding| (binding.key, binding.modifiers, binding.description))
+                        .collect(),
+                ),
+            ]
+            .concat(),
+        ),
+        (
+            "Vim normal mode",
+            rows(
+                VIM_NORMAL_KEYS
+                    .iter()
+                    .map(|binding| (binding.key, binding.modifiers, binding.description))
+                    .collect(),
+            ),
+        ),
+    ];
+
+    let key_width = sections
+        .iter()
+        .flat_map(|(_, rows)| rows.iter().map(|(key, _)| key.len()))
+        .max()
+        .unwrap_or(0);
+    let mut lines = Vec::new();
+    for (title, rows) in sections {
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::from(Span::styled(
+            title,
+            Style::default().fg(theme.heading).bold(),
+        )));
+        lines.extend(rows.into_iter().map(|(key, description)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("  {key:<key_width$}  "),
+                    Style::default().fg(theme.accent),
+                ),
+                Span::raw(description),
+            ])
+        }));
+    }
+    lines
+}
+
+/// Draws the help overlay over a dimmed screen, scrolled down by `scroll` lines, and
+/// returns the scroll kept within the lines.
+pub fn draw_key_help(f: &mut Frame, area: Rect, app: &App, scroll: usize) -> usize {
+    let lines = help_lines(&app.theme);
+    let width = (area.width * 3 / 5).max(50).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let rows = usize::from(height.saturating_sub(2));
+    let scroll = scroll.min(lines.len().saturating_sub(rows));
+    let title = if lines.len() > rows {
+        format!(
+            "Keys {}-{} of {} (Up/Down to scroll, Esc to close)",
+            scroll + 1,
+            (scroll + rows).min(lines.len()),
+            lines.len()
+        )
+    } else {
+        "Keys (Esc to close)".to_string()
+    };
+    let block = Block::default()
+        .title(Span::styled(title, Style::default().fg(app.theme.heading)))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    f.buffer_mut()
+        .set_style(area, Style::default().add_modifier(Modifier::DIM));
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .scroll((scroll as u16, 0)),
+        popup_rect,
+    );
+    scroll
+}
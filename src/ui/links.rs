@@ -0,0 +1,429 @@
+use crate::api::Message;
+
+/// Links numbered at most, from the newest message up.
+pub const LINK_LIMIT: usize = 99;
+
+/// The Ctrl+L hints: every link of the loaded messages gets a number, which is typed to
+/// open it.
+#[derive(Debug, Clone)]
+pub struct LinkHints {
+    /// Message id and URL of each link; link `n` is at index `n - 1`.
+    pub links: Vec<(String, String)>,
+    /// Digits typed so far.
+    pub typed: String,
+}
+
+impl LinkHints {
+    /// Numbers the links of `messages`, which are newest first, so the links at the bottom
+    /// of the chat get the shortest numbers.
+    pub fn new(messages: &[Message]) -> Self {
+        let links = messages
+            .iter()
+            .flat_map(|message| {
+                let content = message.content.as_deref().unwrap_or_default();
+                extract_urls(content)
+                    .into_iter()
+                    .map(|url| (message.id.clone(), url))
+            })
+            .take(LINK_LIMIT)
+            .collect();
+
+        Self {
+            links,
+            typed: String::new(),
+        }
+    }
+
+    /// The number and URL of each link of a message.
+    pub fn of_message<'a>(
+        &'a self,
+        message_id: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+        self.links
+            .iter()
+            .enumerate()
+            .filter(move |(_, (id, _))| id == message_id)
+            .map(|(index, (_, url))| (index + 1, url.as_str()))
+    }
+
+    /// The link whose number was typed.
+    pub fn selected(&self) -> Option<&str> {
+        let number = self.typed.parse::<usize>().ok()?;
+        self.links
+            .get(number.checked_sub(1)?)
+            .map(|(_, url)| url.as_str())
+    }
+}
+
+/// Finds the `http://` and `https://` links of a message, in order and without repeats.
+/// Discord's `<https://…>` form and markdown `[text](https://…)` links give the bare URL,
+/// and punctuation ending a sentence right after a link is not part of it.
+pub fn extract_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    let mut searched_to = 0;
+
+    while let Some(found) = text[searched_to..].find("http") {
+        let start = searched_to + found;
+        let rest = &text[start..];
+        searched_to = start + "http".len();
+
+        if !(rest.starts_with("http://") || rest.starts_with("https://")) {
+            continue;
+        }
+        let before = text[..start].chars().next_back();
+        if before.is_some_and(char::is_alphanumeric) {
+            continue;
+        }
+
+        let url = match before {
+            Some('<') => rest.find('>').map(|end| &rest[..end]),
+            Some('(') if text[..start].ends_with("](") => Some(markdown_target(rest)),
+            _ => None,
+        }
+        .unwrap_or_else(|| trim_trailing(bare_url(rest)));
+
+        searched_to = start + url.len();
+        if url.len() > "https://".len() && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+
+    urls
+}
+
+/// The URL at the start of `text`, up to the first whitespace or angle bracket.
+fn bare_url(text: &str) -> &str {
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '<' || c == '>')
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+/// The target of a markdown link starting at `text`, up to the `)` closing the link.
+/// Parentheses inside the URL, as in Wikipedia links, are kept when balanced.
+fn markdown_target(text: &str) -> &str {
+    let mut depth = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return &text[..index],
+            ')' => depth -= 1,
+            c if c.is_whitespace() => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Drops the punctuation ending a sentence after a link, and closing brackets without a
+/// matching opening one inside the link.
+fn trim_trailing(mut url: &str) -> &str {
+    loop {
+        let Some(last) = url.chars().next_back() else {
+            return url;
+        };
+        let unmatched = |open: char, close: char| {
+            last == close && url.matches(open).count() < url.matches(close).count()
+        };
+        if matches!(
+            last,
+            '.' | ',' | ';' | ':' | '!' | '?' | '\'' | '"' | '*' | '_' | '~'
+        ) || unmatched('(', ')')
+            || unmatched('[', ']')
+        {
+            url = &url[..url.len() - last.len_utf8()];
+        } else {
+            return url;
+        }
+    }
+}
+
+/// Hosts whose `/channels/…` links point to a message or channel.
+const DISCORD_HOSTS: &[&str] = &[
+    "discord.com",
+    "www.discord.com",
+    "canary.discord.com",
+    "ptb.discord.com",
+    "discordapp.com",
+    "www.discordapp.com",
+    "canary.discordapp.com",
+    "ptb.discordapp.com",
+];
+
+/// Where a Discord link leads: a channel, and possibly one of its messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permalink {
+    /// `None` for links into a DM, written `@me` in the link.
+    pub guild_id: Option<String>,
+    pub channel_id: String,
+    pub message_id: Option<String>,
+}
+
+/// What `/goto` was given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GotoTarget {
+    Link(Permalink),
+    /// A bare id, of a channel or of a message of the open channel.
+    Id(String),
+}
+
+fn is_snowflake(id: &str) -> bool {
+    (1..=20).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Reads a link such as `https://discord.com/channels/<guild>/<channel>/<message>`, with
+/// `@me` in place of the guild for DMs and the message left out for a channel. Other
+/// hosts and paths give `None`.
+pub fn parse_permalink(url: &str) -> Option<Permalink> {
+    let url = url.trim().trim_start_matches('<').trim_end_matches('>');
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let (host, path) = rest.split_once('/')?;
+    if !DISCORD_HOSTS.contains(&host.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut parts = path
+        .strip_prefix("channels/")?
+        .trim_end_matches('/')
+        .split('/');
+    let guild_id = match parts.next()? {
+        "@me" => None,
+        id if is_snowflake(id) => Some(id.to_string()),
+        _ => return None,
+    };
+    let channel_id = parts.next().filter(|id| is_snowflake(id))?.to_string();
+    let message_id = match parts.next() {
+        Some(id) if is_snowflake(id) => Some(id.to_string()),
+        Some(_) => return None,
+        None => None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some(Permalink {
+        guild_id,
+        channel_id,
+        message_id,
+    })
+}
+
+/// Reads the argument of `/goto`: a link, a bare id, or the `<channel>-<message>` pair
+/// copied with Shift held on "Copy Message ID".
+pub fn parse_goto(text: &str) -> Option<GotoTarget> {
+    let text = text.trim();
+    if is_snowflake(text) {
+        return Some(GotoTarget::Id(text.to_string()));
+    }
+    if let Some((channel_id, message_id)) = text.split_once('-')
+        && is_snowflake(channel_id)
+        && is_snowflake(message_id)
+    {
+        return Some(GotoTarget::Link(Permalink {
+            guild_id: None,
+            channel_id: channel_id.to_string(),
+            message_id: Some(message_id.to_string()),
+        }));
+    }
+    parse_permalink(text).map(GotoTarget::Link)
+}
+
+/// Splits `text` around its Discord channel and message links, found like `extract_urls`
+/// finds bare links. Each piece comes with the link it is, if any; a link piece includes
+/// the angle brackets of Discord's `<https://…>` form.
+pub fn split_permalinks(text: &str) -> Vec<(&str, Option<Permalink>)> {
+    let mut pieces = Vec::new();
+    let mut text_start = 0;
+    let mut searched_to = 0;
+
+    while let Some(found) = text[searched_to..].find("http") {
+        let start = searched_to + found;
+        searched_to = start + "http".len();
+        if text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+        {
+            continue;
+        }
+
+        let url = trim_trailing(bare_url(&text[start..]));
+        let Some(link) = parse_permalink(url) else {
+            continue;
+        };
+        let mut end = start + url.len();
+        let start = if text[..start].ends_with('<') && text[end..].starts_with('>') {
+            end += 1;
+            start - 1
+        } else {
+            start
+        };
+        if text_start < start {
+            pieces.push((&text[text_start..start], None));
+        }
+        pieces.push((&text[start..end], Some(link)));
+        text_start = end;
+        searched_to = end;
+    }
+
+    if text_start < text.len() {
+        pieces.push((&text[text_start..], None));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_links_in_order_without_repeats() {
+        assert_eq!(
+            extract_urls("see https://a.example/x and http://b.example, then https://a.example/x"),
+            ["https://a.example/x", "http://b.example"]
+        );
+    }
+
+    #[test]
+    fn drops_trailing_punctuation_and_unmatched_brackets() {
+        assert_eq!(
+            extract_urls("(go to https://example.com/page). Really? https://example.com/q?a=1!"),
+            ["https://example.com/page", "https://example.com/q?a=1"]
+        );
+        assert_eq!(
+            extract_urls("https://en.wikipedia.org/wiki/Rust_(programming_language)"),
+            ["https://en.wikipedia.org/wiki/Rust_(programming_language)"]
+        );
+    }
+
+    #[test]
+    fn reads_angle_bracket_and_markdown_links() {
+        assert_eq!(
+            extract_urls("<https://example.com/a b> [docs](https://example.com/(x)) done"),
+            ["https://example.com/a b", "https://example.com/(x)"]
+        );
+    }
+
+    #[test]
+    fn skips_what_only_looks_like_a_link() {
+        assert!(extract_urls("xhttps://example.com https:// httpfoo").is_empty());
+    }
+
+    #[test]
+    fn numbers_links_from_the_newest_message() {
+        let messages = [
+            Message::for_tests("2", "https://new.example"),
+            Message::for_tests("1", "https://old.example https://older.example"),
+        ];
+        let mut hints = LinkHints::new(&messages);
+
+        assert_eq!(
+            hints.of_message("1").collect::<Vec<_>>(),
+            [(2, "https://old.example"), (3, "https://older.example")]
+        );
+
+        hints.typed = "3".to_string();
+        assert_eq!(hints.selected(), Some("https://older.example"));
+        hints.typed = "0".to_string();
+        assert_eq!(hints.selected(), None);
+        hints.typed = "4".to_string();
+        assert_eq!(hints.selected(), None);
+    }
+
+    #[test]
+    fn numbers_at_most_the_link_limit() {
+        let content = (0..LINK_LIMIT + 5)
+            .map(|i| format!("https://example.com/{i}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(
+            LinkHints::new(&[Message::for_tests("1", &content)])
+                .links
+                .len(),
+            LINK_LIMIT
+        );
+    }
+
+    fn permalink(guild_id: Option<&str>, channel_id: &str, message_id: Option<&str>) -> Permalink {
+        Permalink {
+            guild_id: guild_id.map(str::to_string),
+            channel_id: channel_id.to_string(),
+            message_id: message_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_message_channel_and_dm_links() {
+        assert_eq!(
+            parse_permalink("https://discord.com/channels/1/2/3"),
+            Some(permalink(Some("1"), "2", Some("3")))
+        );
+        assert_eq!(
+            parse_permalink("<https://canary.discordapp.com/channels/1/2/?x=y>"),
+            Some(permalink(Some("1"), "2", None))
+        );
+        assert_eq!(
+            parse_permalink("https://discord.com/channels/@me/2/3"),
+            Some(permalink(None, "2", Some("3")))
+        );
+    }
+
+    #[test]
+    fn refuses_other_links() {
+        for url in [
+            "https://example.com/channels/1/2/3",
+            "https://discord.com/invite/abc",
+            "https://discord.com/channels/1",
+            "https://discord.com/channels/1/x/3",
+            "https://discord.com/channels/1/2/3/4",
+            "discord.com/channels/1/2/3",
+        ] {
+            assert_eq!(parse_permalink(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn parses_goto_arguments() {
+        assert_eq!(parse_goto(" 123 "), Some(GotoTarget::Id("123".to_string())));
+        assert_eq!(
+            parse_goto("2-3"),
+            Some(GotoTarget::Link(permalink(None, "2", Some("3"))))
+        );
+        assert_eq!(
+            parse_goto("https://discord.com/channels/1/2"),
+            Some(GotoTarget::Link(permalink(Some("1"), "2", None)))
+        );
+        assert_eq!(parse_goto("general"), None);
+        assert_eq!(parse_goto("123456789012345678901"), None);
+    }
+
+    #[test]
+    fn splits_text_around_discord_links() {
+        let text = "see <https://discord.com/channels/1/2/3> and https://example.com, \
+                    or https://discord.com/channels/1/4.";
+        let pieces = split_permalinks(text);
+
+        let links: Vec<(&str, Permalink)> = pieces
+            .iter()
+            .filter_map(|(piece, link)| link.clone().map(|link| (*piece, link)))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                (
+                    "<https://discord.com/channels/1/2/3>",
+                    permalink(Some("1"), "2", Some("3"))
+                ),
+                (
+                    "https://discord.com/channels/1/4",
+                    permalink(Some("1"), "4", None)
+                ),
+            ]
+        );
+        let joined: String = pieces.iter().map(|(piece, _)| *piece).collect();
+        assert_eq!(joined, text);
+    }
+}
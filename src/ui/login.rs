@@ -0,0 +1,108 @@
+use std::io::Stdout;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{
+    Frame, Terminal,
+    layout::{Alignment, Rect},
+    prelude::CrosstermBackend,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+};
+use reqwest::Client;
+
+use crate::{
+    Error,
+    api::{ApiClient, ApiError, BOT_TOKEN_HINT, TokenType, User, api_base_url},
+};
+
+fn draw_prompt(f: &mut Frame, input: &str, message: &str) {
+    let area = f.area();
+    let width = area.width.min(70);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.saturating_sub(6) / 2,
+        width,
+        height: area.height.min(6),
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Rivet Client - Log in",
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double);
+
+    let masked = "•".repeat(input.chars().count());
+    let paragraph = Paragraph::new(vec![
+        Line::from(Span::styled(message, Style::default().fg(Color::Gray))),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Token: ", Style::default().fg(Color::LightBlue)),
+            Span::raw(masked),
+        ]),
+    ])
+    .alignment(Alignment::Left)
+    .wrap(Wrap { trim: false })
+    .block(block);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(paragraph, popup_rect);
+}
+
+/// Asks for a Discord token with a masked input until Discord accepts one, and returns it
+/// with the user it belongs to. Returns `None` when the user gives up with Esc or Ctrl+C.
+pub async fn prompt_token(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+) -> Result<Option<(String, User)>, Error> {
+    let mut input = String::new();
+    let mut message =
+        "No token found. Paste your Discord token and press Enter, or Esc to quit.".to_string();
+
+    loop {
+        terminal.draw(|f| draw_prompt(f, &input, &message))?;
+
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(None);
+                }
+                KeyCode::Enter if !input.trim().is_empty() => {
+                    message = "Checking the token...".to_string();
+                    terminal.draw(|f| draw_prompt(f, &input, &message))?;
+
+                    let token = input.trim().to_string();
+                    let api_client = ApiClient::new(Client::new(), token.clone(), api_base_url());
+
+                    match api_client.get_current_user().await {
+                        Ok(user) => return Ok(Some((token, user))),
+                        Err(ApiError::Unauthorized) => {
+                            input.clear();
+                            message = match api_client.token_type {
+                                TokenType::User => format!(
+                                    "Discord rejected this token. {BOT_TOKEN_HINT} Try another one, or Esc to quit."
+                                ),
+                                TokenType::Bot => {
+                                    "Discord rejected this token. Try another one, or Esc to quit."
+                                        .to_string()
+                                }
+                            };
+                        }
+                        Err(e) => {
+                            message = format!("Could not check the token: {e}");
+                        }
+                    }
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            },
+            Event::Paste(text) => input.push_str(text.trim()),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,300 @@
+/// Formatting applied to a run of message text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MdStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub code: bool,
+    pub spoiler: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdSpan {
+    pub text: String,
+    pub style: MdStyle,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MdLine {
+    Text(Vec<MdSpan>),
+    /// One line of a fenced code block, kept verbatim.
+    Code(String),
+}
+
+const FENCE: &str = "```";
+
+/// Inline markers, longest first so `**` wins over `*`.
+const MARKERS: [&str; 6] = ["***", "**", "__", "~~", "||", "*"];
+
+/// Parses the Discord flavour of markdown in a message into lines of styled spans.
+/// Markers without a matching closer are kept as plain text.
+pub fn parse_markdown(content: &str) -> Vec<MdLine> {
+    let mut lines = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find(FENCE) {
+        let after = &rest[start + FENCE.len()..];
+        let Some(end) = after.find(FENCE) else {
+            break;
+        };
+
+        push_text_lines(&mut lines, &rest[..start]);
+
+        let block = &after[..end];
+        // A first line without spaces names the language, as in ```rust.
+        let block = match block.split_once('\n') {
+            Some((lang, code)) if !lang.contains(' ') => code,
+            _ => block,
+        };
+        let block = block.strip_suffix('\n').unwrap_or(block);
+        lines.extend(block.split('\n').map(|line| MdLine::Code(line.to_string())));
+
+        rest = after[end + FENCE.len()..]
+            .strip_prefix('\n')
+            .unwrap_or(&after[end + FENCE.len()..]);
+    }
+
+    push_text_lines(&mut lines, rest);
+
+    if lines.is_empty() {
+        lines.push(MdLine::Text(Vec::new()));
+    }
+
+    lines
+}
+
+fn push_text_lines(lines: &mut Vec<MdLine>, text: &str) {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    if text.is_empty() {
+        return;
+    }
+
+    for line in text.split('\n') {
+        let mut spans = Vec::new();
+        parse_inline(line, MdStyle::default(), &mut spans);
+        lines.push(MdLine::Text(spans));
+    }
+}
+
+/// Finds where `marker` closes in `text`. A single `*` skips over `**` pairs, which belong
+/// to a nested bold run.
+fn find_closer(text: &str, marker: &str) -> Option<usize> {
+    if marker != "*" {
+        return text.find(marker);
+    }
+
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'*' {
+            if bytes.get(i + 1) == Some(&b'*') {
+                i += 2;
+                continue;
+            }
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+fn apply(style: MdStyle, marker: &str) -> MdStyle {
+    match marker {
+        "***" => MdStyle {
+            bold: true,
+            italic: true,
+            ..style
+        },
+        "**" => MdStyle {
+            bold: true,
+            ..style
+        },
+        "__" => MdStyle {
+            underline: true,
+            ..style
+        },
+        "~~" => MdStyle {
+            strikethrough: true,
+            ..style
+        },
+        "||" => MdStyle {
+            spoiler: true,
+            ..style
+        },
+        _ => MdStyle {
+            italic: true,
+            ..style
+        },
+    }
+}
+
+fn push_span(spans: &mut Vec<MdSpan>, text: &str, style: MdStyle) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if last.style == style => last.text.push_str(text),
+        _ => spans.push(MdSpan {
+            text: text.to_string(),
+            style,
+        }),
+    }
+}
+
+fn parse_inline(text: &str, style: MdStyle, spans: &mut Vec<MdSpan>) {
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+
+        if let Some(code) = rest.strip_prefix('`')
+            && let Some(end) = code.find('`')
+            && end > 0
+        {
+            push_span(spans, &text[literal_start..i], style);
+            push_span(
+                spans,
+                &code[..end],
+                MdStyle {
+                    code: true,
+                    ..style
+                },
+            );
+            i += end + 2;
+            literal_start = i;
+            continue;
+        }
+
+        let matched = MARKERS.iter().find_map(|marker| {
+            let inner = rest.strip_prefix(marker)?;
+            let end = find_closer(inner, marker).filter(|&end| end > 0)?;
+            Some((marker, &inner[..end]))
+        });
+
+        if let Some((marker, inner)) = matched {
+            push_span(spans, &text[literal_start..i], style);
+            parse_inline(inner, apply(style, marker), spans);
+            i += inner.len() + marker.len() * 2;
+            literal_start = i;
+            continue;
+        }
+
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+
+    push_span(spans, &text[literal_start..], style);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, style: MdStyle) -> MdSpan {
+        MdSpan {
+            text: text.to_string(),
+            style,
+        }
+    }
+
+    fn plain(text: &str) -> MdSpan {
+        span(text, MdStyle::default())
+    }
+
+    const BOLD: MdStyle = MdStyle {
+        bold: true,
+        italic: false,
+        underline: false,
+        strikethrough: false,
+        code: false,
+        spoiler: false,
+    };
+
+    #[test]
+    fn styles_inline_markers() {
+        assert_eq!(
+            parse_markdown("a **b** *c* `d` ||e||"),
+            [MdLine::Text(vec![
+                plain("a "),
+                span("b", BOLD),
+                plain(" "),
+                span(
+                    "c",
+                    MdStyle {
+                        italic: true,
+                        ..MdStyle::default()
+                    }
+                ),
+                plain(" "),
+                span(
+                    "d",
+                    MdStyle {
+                        code: true,
+                        ..MdStyle::default()
+                    }
+                ),
+                plain(" "),
+                span(
+                    "e",
+                    MdStyle {
+                        spoiler: true,
+                        ..MdStyle::default()
+                    }
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn nests_italics_inside_bold() {
+        assert_eq!(
+            parse_markdown("**a *b* c**"),
+            [MdLine::Text(vec![
+                span("a ", BOLD),
+                span(
+                    "b",
+                    MdStyle {
+                        italic: true,
+                        ..BOLD
+                    }
+                ),
+                span(" c", BOLD),
+            ])]
+        );
+    }
+
+    #[test]
+    fn keeps_unclosed_markers_as_text() {
+        for content in ["**a", "a * b", "``", "||"] {
+            assert_eq!(
+                parse_markdown(content),
+                [MdLine::Text(vec![plain(content)])],
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_code_blocks_verbatim_without_the_language() {
+        assert_eq!(
+            parse_markdown("before\n```rust\nlet **x** = 1;\n  y\n```\nafter"),
+            [
+                MdLine::Text(vec![plain("before")]),
+                MdLine::Code("let **x** = 1;".to_string()),
+                MdLine::Code("  y".to_string()),
+                MdLine::Text(vec![plain("after")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn an_unclosed_fence_is_text() {
+        assert_eq!(parse_markdown("```a"), [MdLine::Text(vec![plain("```a")])]);
+    }
+
+    #[test]
+    fn empty_content_is_one_empty_line() {
+        assert_eq!(parse_markdown(""), [MdLine::Text(Vec::new())]);
+    }
+}
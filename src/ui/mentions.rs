@@ -0,0 +1,246 @@
+use crate::ui::links::{Permalink, split_permalinks};
+
+/// A piece of message content, split around `<@id>`, `<@!id>`, `<@&id>` and `<#id>`
+/// mentions, `<:name:id>` custom emojis and Discord channel and message links.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    /// A Discord link, with its URL as written.
+    Link(&'a str, Permalink),
+    User(&'a str),
+    Role(&'a str),
+    Channel(&'a str),
+    /// A custom emoji, written `<a:name:id>` when animated.
+    Emoji {
+        name: &'a str,
+        id: &'a str,
+        animated: bool,
+    },
+}
+
+fn is_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Parses the `name:id>` rest of a custom emoji.
+fn parse_emoji(body: &str, animated: bool) -> Option<(Segment<'_>, usize)> {
+    let end = body.find('>')?;
+    let (name, id) = body[..end].split_once(':')?;
+
+    let valid_name =
+        !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+    if !valid_name || !is_id(id) {
+        return None;
+    }
+
+    Some((Segment::Emoji { name, id, animated }, end + 1))
+}
+
+/// Parses the mention or emoji starting at the `<` that opens `input`, returning it
+/// along with its length in bytes.
+fn parse_mention<'a>(input: &'a str) -> Option<(Segment<'a>, usize)> {
+    let rest = input.strip_prefix('<')?;
+
+    if let Some(body) = rest.strip_prefix("a:") {
+        return parse_emoji(body, true).map(|(segment, len)| (segment, 3 + len));
+    }
+    if let Some(body) = rest.strip_prefix(':') {
+        return parse_emoji(body, false).map(|(segment, len)| (segment, 2 + len));
+    }
+
+    let (prefix_len, kind): (usize, fn(&'a str) -> Segment<'a>) = if rest.starts_with("@!") {
+        (2, Segment::User)
+    } else if rest.starts_with("@&") {
+        (2, Segment::Role)
+    } else if rest.starts_with('@') {
+        (1, Segment::User)
+    } else if rest.starts_with('#') {
+        (1, Segment::Channel)
+    } else {
+        return None;
+    };
+
+    let body = &rest[prefix_len..];
+    let end = body.find('>')?;
+    let id = &body[..end];
+
+    if !is_id(id) {
+        return None;
+    }
+
+    Some((kind(id), 1 + prefix_len + end + 1))
+}
+
+/// A mention that pings more than the user it names.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MassMention<'a> {
+    Everyone,
+    Here,
+    Role(&'a str),
+}
+
+/// The `@everyone`, `@here` and role mentions in `content`, each once, in order.
+pub fn mass_mentions(content: &str) -> Vec<MassMention<'_>> {
+    let mut mentions = Vec::new();
+    for segment in parse_mentions(content) {
+        let found = match segment {
+            Segment::Text(text) => [
+                ("@everyone", MassMention::Everyone),
+                ("@here", MassMention::Here),
+            ]
+            .into_iter()
+            .filter(|(word, _)| text.contains(word))
+            .map(|(_, mention)| mention)
+            .collect(),
+            Segment::Role(id) => vec![MassMention::Role(id)],
+            _ => Vec::new(),
+        };
+        for mention in found {
+            if !mentions.contains(&mention) {
+                mentions.push(mention);
+            }
+        }
+    }
+    mentions
+}
+
+/// Adds `text` to `segments`, split around its Discord links.
+fn push_text<'a>(segments: &mut Vec<Segment<'a>>, text: &'a str) {
+    segments.extend(
+        split_permalinks(text)
+            .into_iter()
+            .map(|(piece, link)| match link {
+                Some(link) => Segment::Link(piece, link),
+                None => Segment::Text(piece),
+            }),
+    );
+}
+
+/// Splits `content` into plain text, mentions, custom emojis and Discord links. Anything
+/// that only looks like one, such as `<@abc>`, `<:name>` or an unterminated `<@123`, is
+/// kept as text.
+pub fn parse_mentions(content: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while let Some(offset) = content[pos..].find('<') {
+        let start = pos + offset;
+
+        match parse_mention(&content[start..]) {
+            Some((segment, len)) => {
+                if text_start < start {
+                    push_text(&mut segments, &content[text_start..start]);
+                }
+                segments.push(segment);
+                pos = start + len;
+                text_start = pos;
+            }
+            None => pos = start + 1,
+        }
+    }
+
+    if text_start < content.len() {
+        push_text(&mut segments, &content[text_start..]);
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_user_role_and_channel_mentions() {
+        assert_eq!(
+            parse_mentions("hi <@1> and <@!2>, see <#3> <@&4>"),
+            [
+                Segment::Text("hi "),
+                Segment::User("1"),
+                Segment::Text(" and "),
+                Segment::User("2"),
+                Segment::Text(", see "),
+                Segment::Channel("3"),
+                Segment::Text(" "),
+                Segment::Role("4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_malformed_mentions_as_text() {
+        for content in ["<@abc>", "<@>", "<@123", "a < b", "<#12 3>"] {
+            assert_eq!(
+                parse_mentions(content),
+                [Segment::Text(content)],
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_a_mention_right_after_a_stray_bracket() {
+        assert_eq!(
+            parse_mentions("<<@1>"),
+            [Segment::Text("<"), Segment::User("1")]
+        );
+    }
+
+    #[test]
+    fn parses_empty_content() {
+        assert!(parse_mentions("").is_empty());
+    }
+
+    #[test]
+    fn splits_custom_emojis() {
+        assert_eq!(
+            parse_mentions("hi <:wave:12><a:dance_2:34>!"),
+            [
+                Segment::Text("hi "),
+                Segment::Emoji {
+                    name: "wave",
+                    id: "12",
+                    animated: false,
+                },
+                Segment::Emoji {
+                    name: "dance_2",
+                    id: "34",
+                    animated: true,
+                },
+                Segment::Text("!"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_malformed_emojis_as_text() {
+        for content in [
+            "<:wave>",
+            "<:wave:>",
+            "<:wa ve:12>",
+            "<:wave:1a>",
+            "<b:wave:12>",
+        ] {
+            assert_eq!(
+                parse_mentions(content),
+                [Segment::Text(content)],
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_each_mass_mention_once_in_order() {
+        assert_eq!(
+            mass_mentions("@here <@&5> hey @everyone, @here again <@&5> <@&6> <@7>"),
+            [
+                MassMention::Here,
+                MassMention::Role("5"),
+                MassMention::Everyone,
+                MassMention::Role("6"),
+            ]
+        );
+        assert!(mass_mentions("hi <@7> and everyone").is_empty());
+    }
+}
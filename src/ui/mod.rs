@@ -1,6 +1,36 @@
+pub mod aliases;
+pub mod commands;
 pub mod draw;
+pub mod emojis;
+pub mod errors;
 pub mod events;
+pub mod filter;
+pub mod forum;
+pub mod history;
+pub mod ignore;
+pub mod input;
+pub mod invite;
+pub mod keymap;
+pub mod links;
+pub mod login;
+pub mod markdown;
+pub mod mentions;
+pub mod notifications;
+pub mod pins;
+pub mod presence;
+pub mod preview;
+pub mod profile;
+pub mod rows;
+pub mod sanitize;
+pub mod search;
+pub mod shortcodes;
+pub mod split;
+pub mod switcher;
+pub mod system;
+pub mod theme;
 pub mod vim;
+pub mod webhooks;
+pub mod wrap;
 
 pub use draw::draw_ui;
-pub use events::{handle_input_events, handle_keys_events};
+pub use events::handle_input_events;
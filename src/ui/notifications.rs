@@ -0,0 +1,79 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::Span,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::app::App;
+
+/// Mentions kept for the Ctrl+N overlay.
+pub const NOTIFICATION_LIMIT: usize = 10;
+
+/// A message mentioning us in a channel other than the open one.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub channel_id: String,
+    pub channel_name: String,
+    /// `None` for DMs.
+    pub guild_id: Option<String>,
+    pub guild_name: Option<String>,
+    pub author: String,
+}
+
+impl Notification {
+    pub fn label(&self) -> String {
+        match &self.guild_name {
+            Some(guild_name) => format!("🔔 @you in #{} ({guild_name})", self.channel_name),
+            None => format!("🔔 @you from {} (DM)", self.author),
+        }
+    }
+}
+
+pub fn draw_notifications(f: &mut Frame, area: Rect, app: &App, selection_index: usize) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (app.notifications.len() as u16 + 2).max(3).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Mentions (Enter to jump, Esc to close)",
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let items: Vec<ListItem> = if app.notifications.is_empty() {
+        vec![ListItem::new("No mentions yet").style(Style::default().fg(Color::DarkGray))]
+    } else {
+        app.notifications
+            .iter()
+            .map(|notification| match notification.guild_name {
+                Some(_) => ListItem::new(format!(
+                    "{} by {}",
+                    notification.label(),
+                    notification.author
+                )),
+                None => ListItem::new(notification.label()),
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let selected = (!app.notifications.is_empty()).then_some(selection_index);
+    let mut state = ListState::default().with_selected(selected);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_stateful_widget(list, popup_rect, &mut state);
+}
@@ -0,0 +1,75 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+};
+
+use crate::{api::Message, app::App, config::Density, ui::draw::message_lines};
+
+/// The Ctrl+P overlay listing the pinned messages of a channel.
+#[derive(Debug, Clone)]
+pub struct PinViewer {
+    pub channel_id: String,
+    /// Newest pin first; `None` until they are loaded.
+    pub messages: Option<Vec<Message>>,
+    pub selection_index: usize,
+}
+
+pub fn draw_pins(f: &mut Frame, area: Rect, app: &App, pins: &PinViewer) {
+    let width = (area.width * 4 / 5).max(40).min(area.width);
+    let height = (area.height * 4 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Pinned messages (Enter to jump, Esc to close)",
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let empty_line = |text: &'static str| {
+        vec![Line::from(Span::styled(
+            text,
+            Style::default().fg(Color::DarkGray),
+        ))]
+    };
+
+    // Every pin keeps its header, as they are rarely consecutive in the channel. The list
+    // scrolls by starting at the selected pin.
+    let lines: Vec<Line> = match &pins.messages {
+        None => empty_line("Loading pins..."),
+        Some(messages) if messages.is_empty() => empty_line("No pinned messages in this channel"),
+        Some(messages) => messages
+            .iter()
+            .enumerate()
+            .skip(pins.selection_index)
+            .flat_map(|(index, message)| {
+                let line_style = if index == pins.selection_index {
+                    app.theme.selection
+                } else {
+                    Style::default()
+                };
+                let mut lines = message_lines(app, message, true, Density::Compact, line_style);
+                lines.push(Line::default());
+                lines
+            })
+            .collect(),
+    };
+
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        popup_rect,
+    );
+}
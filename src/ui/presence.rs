@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use ratatui::{
+    style::{Color, Style},
+    text::Span,
+};
+
+use crate::{
+    api::{
+        gateway::Status,
+        user::{OwnPresence, OwnStatus},
+    },
+    app::App,
+    ui::sanitize::sanitize_line,
+};
+
+/// A status not updated for this long is treated as unknown again.
+pub const PRESENCE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Status of a user, unless it is unknown or outdated.
+pub fn presence(app: &App, user_id: &str) -> Option<Status> {
+    app.presences
+        .get(user_id)
+        .filter(|(_, received)| received.elapsed() < PRESENCE_TTL)
+        .map(|(status, _)| *status)
+}
+
+/// Colored dot shown before a name, or blank space when the status is unknown.
+pub fn presence_span(app: &App, user_id: &str) -> Span<'static> {
+    let (symbol, color) = match presence(app, user_id) {
+        Some(Status::Online) => ("● ", Color::LightGreen),
+        Some(Status::Idle) => ("● ", Color::Yellow),
+        Some(Status::DoNotDisturb) => ("⊘ ", Color::LightRed),
+        Some(Status::Offline) => ("○ ", Color::DarkGray),
+        None => ("  ", Color::Reset),
+    };
+    Span::styled(symbol, Style::default().fg(color))
+}
+
+/// How our own presence is written, its status then the custom status.
+pub fn presence_label(presence: &OwnPresence) -> String {
+    let custom = [presence.emoji.as_deref(), presence.text.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if custom.is_empty() {
+        presence.status.as_str().to_string()
+    } else {
+        format!("{} {custom}", presence.status.as_str())
+    }
+}
+
+/// Our own presence, at the right edge of the status bar once set with `/status`.
+pub fn own_presence_segment(app: &App) -> Option<Span<'static>> {
+    let presence = app.presence.as_ref()?;
+    let color = match presence.status {
+        OwnStatus::Online => Color::LightGreen,
+        OwnStatus::Idle => Color::Yellow,
+        OwnStatus::Dnd => Color::LightRed,
+        OwnStatus::Invisible => Color::DarkGray,
+    };
+    Some(Span::styled(
+        sanitize_line(&presence_label(presence)).into_owned(),
+        Style::default().fg(color),
+    ))
+}
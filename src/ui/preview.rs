@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use image::{DynamicImage, RgbaImage, imageops::FilterType};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+
+#[derive(Debug, Clone)]
+pub struct ImagePreview {
+    pub filename: String,
+    image: Arc<DynamicImage>,
+    /// Downscaled copy for the last rendered area, so it isn't resized on every frame.
+    thumbnail: Option<(u16, u16, RgbaImage)>,
+}
+
+impl ImagePreview {
+    pub fn new(filename: String, image: DynamicImage) -> Self {
+        Self {
+            filename,
+            image: Arc::new(image),
+            thumbnail: None,
+        }
+    }
+
+    fn thumbnail(&mut self, width: u16, height: u16) -> &RgbaImage {
+        let stale = !matches!(&self.thumbnail, Some((w, h, _)) if *w == width && *h == height);
+        if stale {
+            // Each terminal cell holds two vertical pixels.
+            let resized = self
+                .image
+                .resize(width as u32, height as u32 * 2, FilterType::Triangle)
+                .to_rgba8();
+            self.thumbnail = Some((width, height, resized));
+        }
+
+        &self
+            .thumbnail
+            .as_ref()
+            .expect("thumbnail was just computed")
+            .2
+    }
+}
+
+/// Renders the image as `▀` cells, the foreground colouring the upper pixel and the
+/// background the lower one.
+pub fn draw_preview(f: &mut Frame, area: Rect, preview: &mut ImagePreview, border: Color) {
+    let block = Block::default()
+        .title(Span::styled(
+            format!("Preview: {} (Esc to close)", preview.filename),
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(border));
+
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    if inner.width == 0 || inner.height == 0 {
+        return;
+    }
+
+    let thumbnail = preview.thumbnail(inner.width, inner.height);
+    let (width, height) = thumbnail.dimensions();
+
+    let lines: Vec<Line> = (0..height.div_ceil(2))
+        .map(|row| {
+            let spans: Vec<Span> = (0..width)
+                .map(|x| {
+                    let top = thumbnail.get_pixel(x, row * 2);
+                    let style = Style::default().fg(Color::Rgb(top[0], top[1], top[2]));
+                    let style = if row * 2 + 1 < height {
+                        let bottom = thumbnail.get_pixel(x, row * 2 + 1);
+                        style.bg(Color::Rgb(bottom[0], bottom[1], bottom[2]))
+                    } else {
+                        style
+                    };
+                    Span::styled("▀", style)
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+
+    let x_offset = (inner.width as u32).saturating_sub(width) / 2;
+    let image_area = Rect {
+        x: inner.x + x_offset as u16,
+        y: inner.y,
+        width: width as u16,
+        height: inner.height,
+    };
+
+    f.render_widget(Paragraph::new(lines), image_area);
+}
@@ -0,0 +1,230 @@
+use chrono::{DateTime, Local, Utc};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    api::{User, guild::GuildMember, snowflake_to_datetime},
+    app::App,
+    ui::sanitize::sanitize_line,
+};
+
+/// What is known of a guild member beyond their user, once fetched.
+#[derive(Debug, Clone)]
+pub enum MemberDetails {
+    Loading,
+    /// The member, and the names of their roles.
+    Loaded(GuildMember, Vec<String>),
+    Failed(String),
+}
+
+/// The popup opened with `i` on a message, about its author.
+#[derive(Debug, Clone)]
+pub struct ProfileViewer {
+    pub user: User,
+    /// `None` in DMs, where there is no member to show.
+    pub member: Option<MemberDetails>,
+}
+
+fn format_date(date: DateTime<Utc>) -> String {
+    date.with_timezone(&Local).format("%Y-%m-%d").to_string()
+}
+
+fn profile_lines(app: &App, profile: &ProfileViewer) -> Vec<Line<'static>> {
+    let user = &profile.user;
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{name:<10}"), Style::default().fg(Color::Gray)),
+            Span::raw(value),
+        ])
+    };
+
+    let mut lines = vec![field("Username", user.username.clone())];
+    if let Some(alias) = app.alias(&user.id) {
+        lines.push(field("Alias", alias.to_string()));
+    }
+    if let Some(global_name) = &user.global_name {
+        lines.push(field("Name", global_name.clone()));
+    }
+    if user.bot {
+        lines.push(field("Account", "Bot".to_string()));
+    }
+    lines.push(field("Id", user.id.clone()));
+    if let Some(created) = snowflake_to_datetime(&user.id) {
+        lines.push(field("Created", format_date(created)));
+    }
+    if let Some(avatar) = user.avatar_url() {
+        lines.push(field("Avatar", avatar));
+    }
+
+    match &profile.member {
+        None => {}
+        Some(MemberDetails::Loading) => lines.push(Line::from(Span::styled(
+            "Loading member details...",
+            Style::default().fg(Color::DarkGray),
+        ))),
+        Some(MemberDetails::Failed(e)) => lines.push(Line::from(Span::styled(
+            format!("Member details unavailable: {e}"),
+            Style::default().fg(Color::DarkGray),
+        ))),
+        Some(MemberDetails::Loaded(member, roles)) => {
+            if let Some(nick) = &member.nick {
+                lines.push(field("Nickname", nick.clone()));
+            }
+            if let Some(joined_at) = member.joined_at {
+                lines.push(field("Joined", format_date(joined_at)));
+            }
+            if !roles.is_empty() {
+                lines.push(field("Roles", roles.join(", ")));
+            }
+        }
+    }
+
+    lines
+}
+
+pub fn draw_profile(f: &mut Frame, area: Rect, app: &App, profile: &ProfileViewer) {
+    let lines = profile_lines(app, profile);
+    let title = user_title(&profile.user);
+
+    // Sized to the content, within the screen.
+    let content_width = lines
+        .iter()
+        .map(Line::width)
+        .chain([title.width()])
+        .max()
+        .unwrap_or(0) as u16;
+    let width = (content_width + 4).max(30).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(title, Style::default().fg(Color::Yellow)))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(Paragraph::new(lines).block(block), popup_rect);
+}
+
+fn user_title(user: &User) -> String {
+    let name = user.global_name.as_deref().unwrap_or(&user.username);
+    format!("{} (a to set an alias, Esc to close)", sanitize_line(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn user() -> User {
+        User {
+            id: "175928847299117063".to_string(),
+            username: "alice".to_string(),
+            global_name: Some("Alice".to_string()),
+            avatar: None,
+            bot: false,
+        }
+    }
+
+    #[test]
+    fn shows_the_user_and_their_creation_date() {
+        let app = App::for_tests();
+        let profile = ProfileViewer {
+            user: user(),
+            member: None,
+        };
+
+        let lines = text(&profile_lines(&app, &profile));
+        let created = format_date(snowflake_to_datetime(&profile.user.id).unwrap());
+        assert_eq!(
+            lines,
+            [
+                "Username  alice".to_string(),
+                "Name      Alice".to_string(),
+                "Id        175928847299117063".to_string(),
+                format!("Created   {created}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn shows_the_member_details_once_loaded() {
+        let app = App::for_tests();
+        let member: GuildMember = serde_json::from_value(json!({
+            "user": { "id": "175928847299117063", "username": "alice" },
+            "nick": "Al",
+            "roles": ["1", "2"],
+            "joined_at": "2020-05-01T10:00:00Z",
+        }))
+        .unwrap();
+        let roles = vec!["Mods".to_string(), "Artists".to_string()];
+        let profile = ProfileViewer {
+            user: user(),
+            member: Some(MemberDetails::Loaded(member, roles)),
+        };
+
+        let lines = text(&profile_lines(&app, &profile));
+        assert!(lines.contains(&"Nickname  Al".to_string()));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("Joined    2020-05-0"))
+        );
+        assert!(lines.contains(&"Roles     Mods, Artists".to_string()));
+    }
+
+    #[test]
+    fn says_when_member_details_are_unavailable() {
+        let app = App::for_tests();
+        let profile = ProfileViewer {
+            user: user(),
+            member: Some(MemberDetails::Failed("Unknown Member".to_string())),
+        };
+
+        let lines = text(&profile_lines(&app, &profile));
+        assert_eq!(
+            lines.last().unwrap(),
+            "Member details unavailable: Unknown Member"
+        );
+    }
+
+    #[test]
+    fn shows_the_alias_after_the_username() {
+        let mut app = App::for_tests();
+        app.aliases
+            .insert("175928847299117063".to_string(), "Ally".to_string());
+        let profile = ProfileViewer {
+            user: user(),
+            member: None,
+        };
+
+        let lines = text(&profile_lines(&app, &profile));
+        assert_eq!(lines[..2], ["Username  alice", "Alias     Ally"]);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use ratatui::text::Line;
+
+use crate::{
+    config::{Density, TimeFormat},
+    ui::ignore::Collapse,
+};
+
+/// Laid out messages kept at most. Past it the cache starts over, which only costs laying
+/// out the messages on screen again.
+pub const ROW_CACHE_LIMIT: usize = 1000;
+
+/// What the rows of a message are laid out from, besides what `App::rows_generation`
+/// covers: the message as of its last edit, where it sits among its neighbors, and how
+/// the chat is set up.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RowKey {
+    pub message_id: String,
+    pub edited_timestamp: Option<String>,
+    pub width: usize,
+    pub time_format: TimeFormat,
+    /// Minute the times were laid out in, with the relative format only, so that "2m ago"
+    /// moves on.
+    pub relative_minute: Option<i64>,
+    pub density: Density,
+    /// Label of the day separator above the message.
+    pub separator: Option<String>,
+    pub show_header: bool,
+    pub collapse: Collapse,
+}
+
+/// The wrapped rows of messages as the chat draws them, kept between frames so that
+/// typing or moving the selection doesn't lay out every message again. A message edited
+/// since, or now placed differently, misses the cache; changes to the rest of the app
+/// the rows show bump `App::rows_generation`, and the cache starts over.
+#[derive(Debug, Clone, Default)]
+pub struct RowCache {
+    /// Rows of the day separator above the message, then the rows of the message.
+    entries: HashMap<RowKey, (usize, Vec<Line<'static>>)>,
+    /// Size of the terminal the entries were laid out in.
+    area: (u16, u16),
+    /// `App::rows_generation` the entries were laid out at.
+    generation: u64,
+}
+
+impl RowCache {
+    /// Forgets every entry when the terminal was resized, when something the rows show
+    /// changed, and when there are too many.
+    pub fn prepare(&mut self, area: (u16, u16), generation: u64) {
+        if self.area != area
+            || self.generation != generation
+            || self.entries.len() > ROW_CACHE_LIMIT
+        {
+            self.entries.clear();
+            self.area = area;
+            self.generation = generation;
+        }
+    }
+
+    pub fn get_or_insert_with(
+        &mut self,
+        key: RowKey,
+        layout: impl FnOnce() -> (usize, Vec<Line<'static>>),
+    ) -> &(usize, Vec<Line<'static>>) {
+        self.entries.entry(key).or_insert_with(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(text: &str) -> (usize, Vec<Line<'static>>) {
+        (0, vec![Line::from(text.to_string())])
+    }
+
+    fn key(message_id: u64) -> RowKey {
+        RowKey {
+            message_id: message_id.to_string(),
+            edited_timestamp: None,
+            width: 80,
+            time_format: TimeFormat::Short,
+            relative_minute: None,
+            density: Density::Compact,
+            separator: None,
+            show_header: true,
+            collapse: Collapse::Shown,
+        }
+    }
+
+    #[test]
+    fn lays_out_each_key_once() {
+        let mut cache = RowCache::default();
+        cache.prepare((80, 24), 0);
+        let mut layouts = 0;
+        for _ in 0..3 {
+            cache.get_or_insert_with(key(1), || {
+                layouts += 1;
+                rows("a")
+            });
+        }
+        assert_eq!(layouts, 1);
+        assert_eq!(
+            cache.get_or_insert_with(key(2), || rows("b")).1[0],
+            Line::from("b")
+        );
+    }
+
+    #[test]
+    fn starts_over_when_the_terminal_is_resized() {
+        let mut cache = RowCache::default();
+        cache.prepare((80, 24), 0);
+        cache.get_or_insert_with(key(1), || rows("a"));
+
+        cache.prepare((80, 24), 0);
+        assert_eq!(cache.entries.len(), 1);
+        cache.prepare((100, 24), 0);
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn starts_over_when_what_the_rows_show_changes() {
+        let mut cache = RowCache::default();
+        cache.prepare((80, 24), 0);
+        cache.get_or_insert_with(key(1), || rows("a"));
+
+        cache.prepare((80, 24), 1);
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn keeps_apart_keys_that_differ_in_anything() {
+        let mut cache = RowCache::default();
+        cache.prepare((80, 24), 0);
+        let edited = RowKey {
+            edited_timestamp: Some("2024-01-01T12:05:00Z".to_string()),
+            ..key(1)
+        };
+        let narrower = RowKey {
+            width: 60,
+            ..key(1)
+        };
+        cache.get_or_insert_with(key(1), || rows("a"));
+        cache.get_or_insert_with(edited, || rows("b"));
+        cache.get_or_insert_with(narrower, || rows("c"));
+
+        assert_eq!(cache.entries.len(), 3);
+        assert_eq!(
+            cache.get_or_insert_with(key(1), || rows("d")).1[0],
+            Line::from("a")
+        );
+    }
+
+    #[test]
+    fn starts_over_past_the_limit() {
+        let mut cache = RowCache::default();
+        cache.prepare((80, 24), 0);
+        for id in 0..=ROW_CACHE_LIMIT as u64 {
+            cache.get_or_insert_with(key(id), || rows("a"));
+        }
+
+        cache.prepare((80, 24), 0);
+        assert!(cache.entries.is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+
+/// Columns a tab is expanded to.
+pub const TAB_WIDTH: usize = 4;
+
+/// The visible stand-in for a control character: its Control Pictures symbol for C0
+/// characters and DEL, such as `␛` for an escape, and `\x9b`-style text for C1 ones.
+fn escape_control(c: char, out: &mut String) {
+    match c {
+        '\t' => out.extend(std::iter::repeat_n(' ', TAB_WIDTH)),
+        '\u{0}'..='\u{1f}' => out.push(char::from_u32(0x2400 + c as u32).unwrap_or('?')),
+        '\u{7f}' => out.push('␡'),
+        _ => out.push_str(&format!("\\x{:02x}", c as u32)),
+    }
+}
+
+/// Makes text from Discord safe to draw: line endings become `\n`, tabs are expanded and
+/// every other control character is shown instead of reaching the terminal, where an
+/// escape sequence could move the cursor or change colors.
+pub fn sanitize(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| c.is_control() && c != '\n') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => out.push('\n'),
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                out.push('\n');
+            }
+            c if c.is_control() => escape_control(c, &mut out),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Like `sanitize`, for text drawn on a single line, such as names: line breaks are shown
+/// as `␊` too.
+pub fn sanitize_line(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    for c in sanitize(text).chars() {
+        match c {
+            '\n' => out.push('␊'),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrows_clean_text() {
+        assert!(matches!(sanitize("hello\nworld"), Cow::Borrowed(_)));
+        assert!(matches!(sanitize_line("日本語 👍"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn shows_escape_sequences_instead_of_running_them() {
+        assert_eq!(sanitize("\x1b[31mred\x1b[0m"), "␛[31mred␛[0m");
+        assert_eq!(sanitize("a\0b\x07\x7f"), "a␀b␇␡");
+        assert_eq!(sanitize("csi\u{9b}2J"), "csi\\x9b2J");
+    }
+
+    #[test]
+    fn normalizes_line_endings_and_tabs() {
+        assert_eq!(sanitize("a\r\nb\rc"), "a\nb\nc");
+        assert_eq!(sanitize("a\tb"), "a    b");
+    }
+
+    #[test]
+    fn keeps_single_lines_on_one_line() {
+        assert_eq!(sanitize_line("two\nlines\r\n"), "two␊lines␊");
+        assert_eq!(sanitize_line("\x1b]0;title\x07"), "␛]0;title␇");
+    }
+
+    #[test]
+    fn leaves_no_control_character() {
+        let text: String = (0..=0x9f_u32).filter_map(char::from_u32).collect();
+        assert!(!sanitize(&text).chars().any(|c| c.is_control() && c != '\n'));
+        assert!(!sanitize_line(&text).chars().any(char::is_control));
+    }
+}
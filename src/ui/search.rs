@@ -0,0 +1,176 @@
+use std::ops::Range;
+
+use chrono::Local;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::{
+    api::{ApiError, Message},
+    app::App,
+    ui::{draw::UNKNOWN_TIME, sanitize::sanitize_line},
+};
+
+/// Pages of history searched, from the newest message, before giving up.
+pub const SEARCH_PAGES: usize = 10;
+pub const SEARCH_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone)]
+pub enum SearchPhase {
+    /// The query is being typed in the input bar.
+    Prompt,
+    /// History is being paged through; holds how many messages were checked so far.
+    Searching(usize),
+    /// Matching messages, newest first, and the selected one.
+    Results(Vec<Message>, usize),
+}
+
+/// A search through the history of the open channel, started with Ctrl+F or `/search`.
+#[derive(Debug, Clone)]
+pub struct MessageSearch {
+    pub channel_id: String,
+    pub query: String,
+    pub phase: SearchPhase,
+}
+
+impl MessageSearch {
+    pub fn is_prompt(&self) -> bool {
+        matches!(self.phase, SearchPhase::Prompt)
+    }
+}
+
+/// What the paging task found, and why it stopped early if it did.
+#[derive(Debug)]
+pub struct SearchOutcome {
+    pub query: String,
+    pub matches: Vec<Message>,
+    pub checked: usize,
+    pub error: Option<ApiError>,
+}
+
+/// Byte ranges of `text` matching `query`. Case is ignored unless lowercasing changes the
+/// length of a character of `text`, which would shift the ranges.
+pub fn match_ranges(text: &str, query: &str) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let same_length = text
+        .chars()
+        .all(|c| c.to_lowercase().map(char::len_utf8).sum::<usize>() == c.len_utf8());
+    let (haystack, needle) = if same_length {
+        (text.to_lowercase(), query.to_lowercase())
+    } else {
+        (text.to_string(), query.to_string())
+    };
+
+    haystack
+        .match_indices(&needle)
+        .map(|(start, found)| start..start + found.len())
+        .collect()
+}
+
+pub fn matches_query(message: &Message, query: &str) -> bool {
+    message
+        .content
+        .as_deref()
+        .is_some_and(|content| !match_ranges(content, query).is_empty())
+}
+
+/// One result row: `[date time] author: content`, with the matches highlighted.
+fn result_line(app: &App, message: &Message, query: &str) -> Line<'static> {
+    let content = message
+        .content
+        .as_deref()
+        .unwrap_or_default()
+        .replace('\n', " ");
+    let mut spans = vec![
+        Span::styled(
+            format!(
+                "[{}]",
+                message.timestamp.map_or(UNKNOWN_TIME.to_string(), |time| {
+                    time.with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+            ),
+            Style::default().fg(app.theme.timestamp),
+        ),
+        Span::styled(
+            format!(" {}: ", sanitize_line(&message.author.username)),
+            Style::default().fg(app.theme.author),
+        ),
+    ];
+
+    let mut end = 0;
+    for range in match_ranges(&content, query) {
+        spans.push(Span::raw(content[end..range.start].to_string()));
+        spans.push(Span::styled(
+            content[range.clone()].to_string(),
+            Style::default().fg(Color::Black).bg(Color::Yellow).bold(),
+        ));
+        end = range.end;
+    }
+    spans.push(Span::raw(content[end..].to_string()));
+
+    Line::from(spans)
+}
+
+pub fn draw_search(f: &mut Frame, area: Rect, app: &App, search: &MessageSearch) {
+    let (items, selected): (Vec<ListItem>, Option<usize>) = match &search.phase {
+        SearchPhase::Prompt => return,
+        SearchPhase::Searching(checked) => (
+            vec![
+                ListItem::new(format!("Searching... {checked} messages checked"))
+                    .style(Style::default().fg(Color::DarkGray)),
+            ],
+            None,
+        ),
+        SearchPhase::Results(matches, _) if matches.is_empty() => (
+            vec![ListItem::new("No matches").style(Style::default().fg(Color::DarkGray))],
+            None,
+        ),
+        SearchPhase::Results(matches, selection_index) => (
+            matches
+                .iter()
+                .map(|message| ListItem::new(result_line(app, message, &search.query)))
+                .collect(),
+            Some(*selection_index),
+        ),
+    };
+
+    let width = (area.width * 4 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            format!(
+                "Search \"{}\" (n/N to move, Enter to jump, Esc to close)",
+                search.query
+            ),
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default().with_selected(selected);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_stateful_widget(list, popup_rect, &mut state);
+}
@@ -0,0 +1,106 @@
+/// Replaces every known `:name:` shortcode in `input` with its Unicode emoji.
+///
+/// Unknown shortcodes are left untouched, as are URLs and custom emoji markup
+/// (`<:name:id>`), whose colons are not shortcode delimiters.
+pub fn expand_shortcodes(input: &str, map: &[(String, String)]) -> String {
+    input
+        .split_inclusive(char::is_whitespace)
+        .map(|token| {
+            if token.contains("://") {
+                token.to_string()
+            } else {
+                expand_token(token, map)
+            }
+        })
+        .collect()
+}
+
+fn is_shortcode_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '+')
+}
+
+fn lookup<'a>(name: &str, map: &'a [(String, String)]) -> Option<&'a str> {
+    if !is_shortcode_name(name) {
+        return None;
+    }
+    map.iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, emoji)| emoji.as_str())
+}
+
+fn expand_token(token: &str, map: &[(String, String)]) -> String {
+    let mut out = String::with_capacity(token.len());
+    let mut rest = token;
+
+    while let Some(start) = rest.find(':') {
+        let before = &rest[..start];
+        let after = &rest[start + 1..];
+
+        let Some(end) = after.find(':') else {
+            break;
+        };
+
+        let is_custom_emoji = before.ends_with('<') || before.ends_with("<a");
+
+        match lookup(&after[..end], map) {
+            Some(emoji) if !is_custom_emoji => {
+                out.push_str(before);
+                out.push_str(emoji);
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> Vec<(String, String)> {
+        vec![
+            ("smile".to_string(), "😄".to_string()),
+            ("+1".to_string(), "👍".to_string()),
+        ]
+    }
+
+    #[test]
+    fn expands_known_shortcodes() {
+        assert_eq!(expand_shortcodes(":smile: hi :+1:", &map()), "😄 hi 👍");
+        assert_eq!(expand_shortcodes("a:smile::smile:b", &map()), "a😄😄b");
+    }
+
+    #[test]
+    fn leaves_unknown_shortcodes_alone() {
+        assert_eq!(expand_shortcodes(":nope: :smile", &map()), ":nope: :smile");
+        assert_eq!(expand_shortcodes("at 12:30:00", &map()), "at 12:30:00");
+    }
+
+    #[test]
+    fn keeps_an_unknown_name_from_eating_the_next_shortcode() {
+        assert_eq!(expand_shortcodes("x:y:smile:", &map()), "x:y😄");
+    }
+
+    #[test]
+    fn skips_urls_and_custom_emoji() {
+        let input = "https://example.com/:smile: <:smile:123> <a:smile:456>";
+        assert_eq!(expand_shortcodes(input, &map()), input);
+    }
+
+    #[test]
+    fn preserves_whitespace() {
+        assert_eq!(
+            expand_shortcodes(" :smile:\n\t:smile: ", &map()),
+            " 😄\n\t😄 "
+        );
+    }
+}
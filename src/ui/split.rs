@@ -0,0 +1,89 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+
+use crate::{
+    api::Message,
+    app::App,
+    ui::{draw::message_rows, sanitize::sanitize_line},
+};
+
+/// Messages kept in the split pane; older ones are dropped as new ones arrive.
+pub const SPLIT_MESSAGE_LIMIT: usize = 100;
+
+/// A channel pinned with Ctrl+S to the right of the chat, read-only and kept up to date
+/// while other channels are opened on the left.
+#[derive(Debug, Clone)]
+pub struct SplitPane {
+    pub channel_id: String,
+    pub name: String,
+    /// Newest first, like the chat.
+    pub messages: Vec<Message>,
+}
+
+impl SplitPane {
+    /// Merges messages fetched for the pane, a full page or only those after its newest.
+    pub fn merge(&mut self, messages: Vec<Message>, full: bool) {
+        let existing = std::mem::take(&mut self.messages);
+        self.messages = if full {
+            Message::merge_latest(existing, messages)
+        } else {
+            Message::merge_newer(existing, messages)
+        };
+        self.messages.truncate(SPLIT_MESSAGE_LIMIT);
+    }
+
+    /// Adds a message that came through the gateway.
+    pub fn push(&mut self, message: Message) {
+        if !self.messages.iter().any(|m| m.id == message.id) {
+            self.messages.insert(0, message);
+            self.messages.truncate(SPLIT_MESSAGE_LIMIT);
+        }
+    }
+}
+
+/// Draws the pane, always showing its newest messages.
+pub fn draw_split_pane(f: &mut Frame, area: Rect, app: &App, split: &SplitPane) {
+    let block = Block::default()
+        .title(Span::styled(
+            format!("{} (Ctrl+S to close)", sanitize_line(&split.name)),
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let width = area.width.saturating_sub(4) as usize;
+    let rows = area.height.saturating_sub(2) as usize;
+    let messages: Vec<&Message> = split.messages.iter().collect();
+
+    // Only as many messages as fill the pane, from the newest up.
+    let mut lines: Vec<Line> = Vec::new();
+    for index in 0..messages.len() {
+        if lines.len() >= rows || width == 0 {
+            break;
+        }
+        let mut message = message_rows(app, &messages, index, width, Style::default());
+        message.append(&mut lines);
+        lines = message;
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No messages yet",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    let scroll = lines.len().saturating_sub(rows);
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .scroll((scroll as u16, 0)),
+        area,
+    );
+}
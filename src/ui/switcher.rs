@@ -0,0 +1,145 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    app::App,
+    ui::{filter::fuzzy_match, sanitize::sanitize_line},
+};
+
+/// State of the Ctrl+K overlay. The view underneath keeps its own state while it is open.
+#[derive(Debug, Clone, Default)]
+pub struct QuickSwitcher {
+    pub query: String,
+    pub selection_index: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwitcherEntry {
+    pub label: String,
+    pub channel_id: String,
+    pub channel_name: String,
+    /// `None` for DMs.
+    pub guild_id: Option<String>,
+}
+
+/// Every readable text channel of the cached guilds, followed by the DMs, filtered by
+/// the switcher query.
+pub fn switcher_entries(app: &App, query: &str) -> Vec<SwitcherEntry> {
+    let channels = app.guilds.iter().flat_map(|guild| {
+        let context = app.context_cache.get(&guild.id);
+        app.channel_cache
+            .get(&guild.id)
+            .into_iter()
+            .flatten()
+            .flat_map(|c| c.children.iter().flatten())
+            .filter(move |c| c.is_text() && context.is_none_or(|context| c.is_readable(context)))
+            .map(|c| SwitcherEntry {
+                label: format!(
+                    "{} / #{}",
+                    sanitize_line(&guild.name),
+                    sanitize_line(&c.name)
+                ),
+                channel_id: c.id.clone(),
+                channel_name: c.name.clone(),
+                guild_id: Some(guild.id.clone()),
+            })
+    });
+
+    let dms = app.dms.iter().map(|dm| {
+        let name = dm.get_name();
+        SwitcherEntry {
+            label: format!("DM / {}", sanitize_line(&name)),
+            channel_id: dm.id.clone(),
+            channel_name: name,
+            guild_id: None,
+        }
+    });
+
+    channels
+        .chain(dms)
+        .filter(|entry| fuzzy_match(query, &entry.label))
+        .collect()
+}
+
+pub fn draw_switcher(f: &mut Frame, area: Rect, app: &App, switcher: &QuickSwitcher) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let pending = app
+        .channel_requests
+        .iter()
+        .filter(|guild_id| !app.channel_cache.contains_key(*guild_id))
+        .count();
+
+    let title = if pending > 0 {
+        format!("Jump to (loading {pending} servers...)")
+    } else {
+        "Jump to".to_string()
+    };
+
+    let block = Block::default()
+        .title(Span::styled(title, Style::default().fg(Color::Yellow)))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let inner = block.inner(popup_rect);
+    f.render_widget(Clear, popup_rect);
+    f.render_widget(block, popup_rect);
+
+    if inner.height < 2 {
+        return;
+    }
+
+    let input_area = Rect { height: 1, ..inner };
+    let list_area = Rect {
+        y: inner.y + 1,
+        height: inner.height - 1,
+        ..inner
+    };
+
+    f.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::LightBlue)),
+            Span::raw(switcher.query.as_str()),
+        ])),
+        input_area,
+    );
+
+    let items: Vec<ListItem> = switcher_entries(app, &switcher.query)
+        .into_iter()
+        .map(|entry| {
+            let color = if entry.guild_id.is_some() {
+                Color::LightCyan
+            } else {
+                Color::LightMagenta
+            };
+            ListItem::new(entry.label).style(Style::default().fg(color))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default().with_selected(Some(switcher.selection_index));
+    f.render_stateful_widget(list, list_area, &mut state);
+
+    let cursor_x = input_area.x + 2 + UnicodeWidthStr::width(switcher.query.as_str()) as u16;
+    f.set_cursor_position((
+        cursor_x.min(input_area.right().saturating_sub(1)),
+        input_area.y,
+    ));
+}
@@ -0,0 +1,185 @@
+use crate::api::Message;
+
+/// The line shown for a system message, such as a join or a pin, instead of its author
+/// and content. `author` is the name the author is shown with. Plain messages, replies,
+/// command responses and types without a text of their own give `None`, and are drawn
+/// like any other message.
+pub fn system_text(message: &Message, author: &str) -> Option<String> {
+    let content = message.content.as_deref().unwrap_or_default();
+    let target = message
+        .mentions
+        .first()
+        .map_or("someone", |user| user.username.as_str());
+
+    let text = match message.message_type {
+        // Recipient added and removed, in group DMs.
+        1 => format!("→ {author} added {target} to the group"),
+        2 if message
+            .mentions
+            .first()
+            .is_none_or(|user| user.id == message.author.id) =>
+        {
+            format!("← {author} left the group")
+        }
+        2 => format!("← {author} removed {target} from the group"),
+        3 => format!("📞 {author} started a call"),
+        4 => format!("✎ {author} changed the channel name: {content}"),
+        5 => format!("✎ {author} changed the channel icon"),
+        6 => format!("📌 {author} pinned a message"),
+        7 => format!("→ {author} joined the server"),
+        // The content holds the number of boosts when there are several.
+        8 => match content.parse::<u32>() {
+            Ok(count) if count > 1 => format!("🚀 {author} boosted the server {count} times"),
+            _ => format!("🚀 {author} boosted the server"),
+        },
+        9..=11 => format!(
+            "🚀 {author} boosted the server (Tier {})",
+            message.message_type - 8
+        ),
+        12 => format!("→ {author} added {content} to this channel"),
+        14 => "⚠ This server was removed from Server Discovery".to_string(),
+        15 => "✓ This server is back in Server Discovery".to_string(),
+        16 => "⚠ This server failed the Discovery activity requirements for 1 week".to_string(),
+        17 => "⚠ This server failed the Discovery activity requirements for 3 weeks".to_string(),
+        18 => format!("🧵 {author} started a thread: {content}"),
+        21 => "🧵 This thread started from the message above".to_string(),
+        22 => "✉ Invite your friends to this server".to_string(),
+        24 => format!("🛡 AutoMod acted on a message from {author}"),
+        25 => format!("⭐ {author} joined a role subscription"),
+        27 => format!("🎙 {author} started the stage: {content}"),
+        28 => format!("🎙 {author} ended the stage: {content}"),
+        29 => format!("🎙 {author} is now a speaker"),
+        31 => format!("🎙 {author} changed the stage topic: {content}"),
+        32 => format!("⭐ {author} subscribed to an app"),
+        36 => format!("🛡 {author} turned security actions on"),
+        37 => format!("🛡 {author} turned security actions off"),
+        38 => format!("🛡 {author} reported a raid"),
+        39 => format!("🛡 {author} reported a false alarm"),
+        44 => format!("🛒 {author} made a purchase"),
+        46 => format!("📊 A poll by {author} has closed"),
+        // 0 is a plain message, 19 a reply and 20 and 23 command responses.
+        _ => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn text(message_type: u8, content: &str) -> Option<String> {
+        let mut message = Message::for_tests("1", content);
+        message.message_type = message_type;
+        system_text(&message, "alice")
+    }
+
+    #[test]
+    fn gives_each_documented_type_its_text() {
+        let cases: &[(u8, &str, Option<&str>)] = &[
+            (0, "hi", None),
+            (1, "", Some("→ alice added someone to the group")),
+            (2, "", Some("← alice left the group")),
+            (3, "", Some("📞 alice started a call")),
+            (
+                4,
+                "lounge",
+                Some("✎ alice changed the channel name: lounge"),
+            ),
+            (5, "", Some("✎ alice changed the channel icon")),
+            (6, "", Some("📌 alice pinned a message")),
+            (7, "", Some("→ alice joined the server")),
+            (8, "", Some("🚀 alice boosted the server")),
+            (8, "1", Some("🚀 alice boosted the server")),
+            (8, "3", Some("🚀 alice boosted the server 3 times")),
+            (9, "", Some("🚀 alice boosted the server (Tier 1)")),
+            (10, "", Some("🚀 alice boosted the server (Tier 2)")),
+            (11, "", Some("🚀 alice boosted the server (Tier 3)")),
+            (12, "news", Some("→ alice added news to this channel")),
+            (
+                14,
+                "",
+                Some("⚠ This server was removed from Server Discovery"),
+            ),
+            (15, "", Some("✓ This server is back in Server Discovery")),
+            (
+                16,
+                "",
+                Some("⚠ This server failed the Discovery activity requirements for 1 week"),
+            ),
+            (
+                17,
+                "",
+                Some("⚠ This server failed the Discovery activity requirements for 3 weeks"),
+            ),
+            (18, "plans", Some("🧵 alice started a thread: plans")),
+            (19, "a reply", None),
+            (20, "pong", None),
+            (
+                21,
+                "",
+                Some("🧵 This thread started from the message above"),
+            ),
+            (22, "", Some("✉ Invite your friends to this server")),
+            (23, "pong", None),
+            (24, "", Some("🛡 AutoMod acted on a message from alice")),
+            (25, "", Some("⭐ alice joined a role subscription")),
+            (27, "Q&A", Some("🎙 alice started the stage: Q&A")),
+            (28, "Q&A", Some("🎙 alice ended the stage: Q&A")),
+            (29, "", Some("🎙 alice is now a speaker")),
+            (31, "Q&A", Some("🎙 alice changed the stage topic: Q&A")),
+            (32, "", Some("⭐ alice subscribed to an app")),
+            (36, "", Some("🛡 alice turned security actions on")),
+            (37, "", Some("🛡 alice turned security actions off")),
+            (38, "", Some("🛡 alice reported a raid")),
+            (39, "", Some("🛡 alice reported a false alarm")),
+            (44, "", Some("🛒 alice made a purchase")),
+            (46, "", Some("📊 A poll by alice has closed")),
+            // Types Discord may add later are drawn as plain messages.
+            (200, "hi", None),
+        ];
+        for &(message_type, content, expected) in cases {
+            assert_eq!(
+                text(message_type, content).as_deref(),
+                expected,
+                "type {message_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn names_who_was_added_or_removed() {
+        let added = |message_type| {
+            let mut message = Message::for_tests("1", "").mentioning("3", "bob");
+            message.message_type = message_type;
+            system_text(&message, "alice")
+        };
+        assert_eq!(added(1).as_deref(), Some("→ alice added bob to the group"));
+        assert_eq!(
+            added(2).as_deref(),
+            Some("← alice removed bob from the group")
+        );
+
+        let mut left = Message::for_tests("1", "").mentioning("2", "alice");
+        left.message_type = 2;
+        assert_eq!(
+            system_text(&left, "alice").as_deref(),
+            Some("← alice left the group")
+        );
+    }
+
+    #[test]
+    fn reads_messages_without_a_type_as_plain() {
+        let message: Message = serde_json::from_value(json!({
+            "id": "1",
+            "channel_id": "c1",
+            "author": { "id": "2", "username": "alice" },
+            "content": "hi",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "edited_timestamp": null,
+        }))
+        .unwrap();
+        assert_eq!(message.message_type, 0);
+    }
+}
@@ -0,0 +1,262 @@
+use std::env;
+
+use ratatui::style::{Color, Style, Stylize};
+
+use crate::config::ThemeConfig;
+
+/// Colors used by `draw_ui`, resolved from the `theme` section of the config.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub selection: Style,
+    pub border: Color,
+    pub author: Color,
+    pub timestamp: Color,
+    pub mention_background: Color,
+    pub status: Color,
+    pub pending: Color,
+    /// Section headings of overlays, such as the key help.
+    pub heading: Color,
+    /// What stands out inside an overlay, such as the keys of the key help.
+    pub accent: Color,
+    /// Colors of authors without a role color, picked per user by `author_color`.
+    pub author_palette: Vec<Color>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            selection: Style::default().reversed(),
+            border: Color::Reset,
+            author: Color::Yellow,
+            timestamp: Color::LightBlue,
+            mention_background: Color::Rgb(70, 55, 10),
+            status: Color::Yellow,
+            pending: Color::DarkGray,
+            heading: Color::Yellow,
+            accent: Color::LightCyan,
+            author_palette: vec![
+                Color::Rgb(0xf2, 0x8b, 0x82),
+                Color::Rgb(0xfb, 0xbc, 0x04),
+                Color::Rgb(0xcc, 0xff, 0x90),
+                Color::Rgb(0x81, 0xc9, 0x95),
+                Color::Rgb(0x78, 0xd9, 0xec),
+                Color::Rgb(0x8a, 0xb4, 0xf8),
+                Color::Rgb(0xc5, 0x8a, 0xf9),
+                Color::Rgb(0xff, 0x8b, 0xcb),
+                Color::Rgb(0xe6, 0xc9, 0xa8),
+                Color::Rgb(0xff, 0xb7, 0x4d),
+            ],
+        }
+    }
+}
+
+impl Theme {
+    pub fn solarized() -> Self {
+        Self {
+            selection: Style::default()
+                .fg(Color::Rgb(0xfd, 0xf6, 0xe3))
+                .bg(Color::Rgb(0x58, 0x6e, 0x75)),
+            border: Color::Rgb(0x65, 0x7b, 0x83),
+            author: Color::Rgb(0xb5, 0x89, 0x00),
+            timestamp: Color::Rgb(0x26, 0x8b, 0xd2),
+            mention_background: Color::Rgb(0x07, 0x36, 0x42),
+            status: Color::Rgb(0x2a, 0xa1, 0x98),
+            pending: Color::Rgb(0x93, 0xa1, 0xa1),
+            heading: Color::Rgb(0xb5, 0x89, 0x00),
+            accent: Color::Rgb(0x2a, 0xa1, 0x98),
+            // The accents, all readable on the dark base.
+            author_palette: vec![
+                Color::Rgb(0xb5, 0x89, 0x00),
+                Color::Rgb(0xcb, 0x4b, 0x16),
+                Color::Rgb(0xdc, 0x32, 0x2f),
+                Color::Rgb(0xd3, 0x36, 0x82),
+                Color::Rgb(0x6c, 0x71, 0xc4),
+                Color::Rgb(0x26, 0x8b, 0xd2),
+                Color::Rgb(0x2a, 0xa1, 0x98),
+                Color::Rgb(0x85, 0x99, 0x00),
+            ],
+        }
+    }
+
+    /// Builds the theme from its preset and overrides, or describes the first invalid entry.
+    pub fn from_config(config: &ThemeConfig) -> Result<Self, String> {
+        let mut theme = match config.preset.as_str() {
+            "default" => Self::default(),
+            "solarized" => Self::solarized(),
+            preset => return Err(format!("unknown theme preset \"{preset}\"")),
+        };
+
+        if let Some(color) = parse_color("selection", &config.selection)? {
+            theme.selection = Style::default().bg(color);
+        }
+        for (name, value, slot) in [
+            ("border", &config.border, &mut theme.border),
+            ("author", &config.author, &mut theme.author),
+            ("timestamp", &config.timestamp, &mut theme.timestamp),
+            (
+                "mention_background",
+                &config.mention_background,
+                &mut theme.mention_background,
+            ),
+            ("status", &config.status, &mut theme.status),
+            ("pending", &config.pending, &mut theme.pending),
+            ("heading", &config.heading, &mut theme.heading),
+            ("accent", &config.accent, &mut theme.accent),
+        ] {
+            if let Some(color) = parse_color(name, value)? {
+                *slot = color;
+            }
+        }
+        if let Some(palette) = &config.author_palette {
+            theme.author_palette = palette
+                .iter()
+                .map(|value| parse_color("author_palette", &Some(value.clone())))
+                .filter_map(Result::transpose)
+                .collect::<Result<_, _>>()?;
+        }
+
+        if !config.truecolor.unwrap_or_else(supports_truecolor) {
+            for color in &mut theme.author_palette {
+                *color = to_ansi256(*color);
+            }
+        }
+
+        Ok(theme)
+    }
+}
+
+/// Whether the terminal says it shows 24-bit colors, as most set `COLORTERM` to tell.
+fn supports_truecolor() -> bool {
+    env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit")
+}
+
+/// The closest color of the 6×6×6 cube of 256-color terminals to an RGB one. Other colors
+/// are already shown by any terminal.
+fn to_ansi256(color: Color) -> Color {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+
+    let level = |value: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|&i| LEVELS[i].abs_diff(value))
+            .unwrap_or(0) as u8
+    };
+    Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+}
+
+/// The color of the author with `user_id`, the same in every session: picked from `palette`
+/// by a hash of the id that, unlike the standard library's, never changes.
+pub fn author_color(user_id: &str, palette: &[Color]) -> Option<Color> {
+    // 64-bit FNV-1a.
+    let hash = user_id
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    let index = (hash % palette.len().max(1) as u64) as usize;
+    palette.get(index).copied()
+}
+
+/// Parses `#rrggbb` or an ANSI color name such as `red` or `lightblue`.
+fn parse_color(name: &str, value: &Option<String>) -> Result<Option<Color>, String> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let color = match value.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 => u32::from_str_radix(hex, 16)
+            .ok()
+            .map(|c| Color::Rgb((c >> 16) as u8, (c >> 8) as u8, c as u8)),
+        Some(_) => None,
+        None => match value.to_lowercase().replace(['_', '-', ' '], "").as_str() {
+            "black" => Some(Color::Black),
+            "red" => Some(Color::Red),
+            "green" => Some(Color::Green),
+            "yellow" => Some(Color::Yellow),
+            "blue" => Some(Color::Blue),
+            "magenta" => Some(Color::Magenta),
+            "cyan" => Some(Color::Cyan),
+            "gray" | "grey" => Some(Color::Gray),
+            "darkgray" | "darkgrey" => Some(Color::DarkGray),
+            "lightred" => Some(Color::LightRed),
+            "lightgreen" => Some(Color::LightGreen),
+            "lightyellow" => Some(Color::LightYellow),
+            "lightblue" => Some(Color::LightBlue),
+            "lightmagenta" => Some(Color::LightMagenta),
+            "lightcyan" => Some(Color::LightCyan),
+            "white" => Some(Color::White),
+            _ => None,
+        },
+    };
+
+    color
+        .map(Some)
+        .ok_or_else(|| format!("invalid color \"{value}\" for theme.{name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(palette: &[&str], truecolor: bool) -> ThemeConfig {
+        ThemeConfig {
+            author_palette: Some(palette.iter().map(|c| c.to_string()).collect()),
+            truecolor: Some(truecolor),
+            ..ThemeConfig::default()
+        }
+    }
+
+    #[test]
+    fn picks_the_same_author_color_every_time() {
+        let palette = Theme::default().author_palette;
+        let color = author_color("80351110224678912", &palette);
+        assert!(color.is_some());
+        assert_eq!(author_color("80351110224678912", &palette), color);
+
+        // FNV-1a of "a" is 0xaf63dc4c8601ec8c, which is 0 modulo 2.
+        assert_eq!(
+            author_color("a", &[Color::Red, Color::Blue]),
+            Some(Color::Red)
+        );
+    }
+
+    #[test]
+    fn spreads_authors_over_the_palette() {
+        let palette = Theme::default().author_palette;
+        let used: std::collections::HashSet<_> = (0..200u64)
+            .map(|id| author_color(&(81384788765712384 + id).to_string(), &palette))
+            .collect();
+        assert_eq!(used.len(), palette.len());
+    }
+
+    #[test]
+    fn has_no_author_color_without_a_palette() {
+        assert_eq!(author_color("1", &[]), None);
+    }
+
+    #[test]
+    fn brings_colors_down_to_the_256_color_cube() {
+        assert_eq!(to_ansi256(Color::Rgb(0, 0, 0)), Color::Indexed(16));
+        assert_eq!(to_ansi256(Color::Rgb(255, 255, 255)), Color::Indexed(231));
+        assert_eq!(to_ansi256(Color::Rgb(255, 0, 0)), Color::Indexed(196));
+        assert_eq!(to_ansi256(Color::Rgb(100, 140, 170)), Color::Indexed(67));
+        assert_eq!(to_ansi256(Color::Yellow), Color::Yellow);
+    }
+
+    #[test]
+    fn reads_the_palette_from_the_config() {
+        let theme = Theme::from_config(&config(&["#ff0000", "light-blue"], true)).unwrap();
+        assert_eq!(
+            theme.author_palette,
+            [Color::Rgb(255, 0, 0), Color::LightBlue]
+        );
+
+        let theme = Theme::from_config(&config(&["#ff0000"], false)).unwrap();
+        assert_eq!(theme.author_palette, [Color::Indexed(196)]);
+
+        let error = Theme::from_config(&config(&["#ff00"], true)).unwrap_err();
+        assert_eq!(error, "invalid color \"#ff00\" for theme.author_palette");
+    }
+}
@@ -1,8 +1,7 @@
 use std::time::Instant;
-use tokio::sync::{MutexGuard, mpsc::Sender};
 use unicode_width::UnicodeWidthStr;
 
-use crate::{App, AppAction, AppState, InputMode};
+use crate::app::{App, AppAction, AppState, Commands, InputMode};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VimOperator {
@@ -39,7 +38,7 @@ impl Default for VimState {
     }
 }
 
-pub fn clamp_cursor(state: &mut MutexGuard<'_, App>) {
+pub fn clamp_cursor(state: &mut App) {
     let len = state.input.len();
     if len == 0 {
         state.cursor_position = 0;
@@ -54,7 +53,7 @@ pub fn clamp_cursor(state: &mut MutexGuard<'_, App>) {
     }
 }
 
-fn get_motion_range(state: &MutexGuard<'_, App>, motion: VimMotion) -> (usize, usize) {
+fn get_motion_range(state: &App, motion: VimMotion) -> (usize, usize) {
     let start = state.cursor_position;
     let len = state.input.len();
     let input = &state.input;
@@ -194,7 +193,7 @@ fn get_motion_range(state: &MutexGuard<'_, App>, motion: VimMotion) -> (usize, u
     (start, end)
 }
 
-fn execute_operator(state: &mut MutexGuard<'_, App>, operator: VimOperator, range: (usize, usize)) {
+fn execute_operator(state: &mut App, operator: VimOperator, range: (usize, usize)) {
     let (start, end) = range;
     let (low, high) = if start < end {
         (start, end)
@@ -219,11 +218,7 @@ fn execute_operator(state: &mut MutexGuard<'_, App>, operator: VimOperator, rang
     }
 }
 
-pub async fn handle_vim_keys(
-    mut state: MutexGuard<'_, App>,
-    c: char,
-    tx_action: Sender<AppAction>,
-) {
+pub fn handle_vim_keys(state: &mut App, c: char, commands: &mut Commands) {
     // Check for timeout
     if let Some(vim_state) = &mut state.vim_state
         && vim_state.operator.is_some()
@@ -326,11 +321,11 @@ pub async fn handle_vim_keys(
                             target_offset += c.len_utf8();
                         }
                         state.cursor_position = next_line_start + target_offset;
-                        clamp_cursor(&mut state);
+                        clamp_cursor(state);
                     }
                 }
             } else {
-                tx_action.send(AppAction::SelectNext).await.ok();
+                commands.dispatch(AppAction::SelectNext);
             }
         }
         'k' => {
@@ -365,10 +360,10 @@ pub async fn handle_vim_keys(
                         target_offset += c.len_utf8();
                     }
                     state.cursor_position = prev_line_start + target_offset;
-                    clamp_cursor(&mut state);
+                    clamp_cursor(state);
                 }
             } else {
-                tx_action.send(AppAction::SelectPrevious).await.ok();
+                commands.dispatch(AppAction::SelectPrevious);
             }
         }
         'h' => {
@@ -386,26 +381,26 @@ pub async fn handle_vim_keys(
         }
         'w' => {
             if let Some(op) = current_operator {
-                let range = get_motion_range(&state, VimMotion::WordForward);
-                execute_operator(&mut state, op, range);
+                let range = get_motion_range(state, VimMotion::WordForward);
+                execute_operator(state, op, range);
                 if let Some(vim_state) = &mut state.vim_state {
                     vim_state.operator = None;
                 }
             } else {
-                let (_, end) = get_motion_range(&state, VimMotion::WordForward);
+                let (_, end) = get_motion_range(state, VimMotion::WordForward);
                 state.cursor_position = end;
-                clamp_cursor(&mut state);
+                clamp_cursor(state);
             }
         }
         'b' => {
             if let Some(op) = current_operator {
-                let range = get_motion_range(&state, VimMotion::WordBackward);
-                execute_operator(&mut state, op, range);
+                let range = get_motion_range(state, VimMotion::WordBackward);
+                execute_operator(state, op, range);
                 if let Some(vim_state) = &mut state.vim_state {
                     vim_state.operator = None;
                 }
             } else {
-                let (_, end) = get_motion_range(&state, VimMotion::WordBackward);
+                let (_, end) = get_motion_range(state, VimMotion::WordBackward);
                 state.cursor_position = end;
             }
         }
@@ -436,7 +431,7 @@ pub async fn handle_vim_keys(
                     state.cursor_position = 0;
                 }
 
-                clamp_cursor(&mut state);
+                clamp_cursor(state);
 
                 if let Some(vim_state) = &mut state.vim_state {
                     vim_state.operator = None;
@@ -454,7 +449,7 @@ pub async fn handle_vim_keys(
             {
                 let char_end = pos + ch.len_utf8();
                 state.input.drain(pos..char_end);
-                clamp_cursor(&mut state);
+                clamp_cursor(state);
             }
         }
         ':' => {
@@ -0,0 +1,89 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::{api::webhook::Webhook, app::App, ui::sanitize::sanitize_line};
+
+/// The `/webhook` overlay listing the webhooks of a channel, to send through one.
+#[derive(Debug, Clone)]
+pub struct WebhookPicker {
+    pub channel_id: String,
+    /// `None` until they are loaded.
+    pub webhooks: Option<Vec<Webhook>>,
+    pub selection_index: usize,
+    /// Overrides typed after `/webhook`, kept for the webhook picked.
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl WebhookPicker {
+    pub fn selected(&self) -> Option<&Webhook> {
+        self.webhooks.as_ref()?.get(self.selection_index)
+    }
+}
+
+fn webhook_row(webhook: &Webhook) -> ListItem<'static> {
+    let name = sanitize_line(webhook.display_name()).into_owned();
+    if webhook.can_execute() {
+        ListItem::new(Line::from(vec![
+            Span::styled(name, Style::default().fg(Color::LightYellow)),
+            Span::styled(
+                format!("  {}", webhook.id),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]))
+    } else {
+        ListItem::new(Line::from(vec![
+            Span::raw(name),
+            Span::styled(
+                "  cannot post, not an incoming webhook",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]))
+    }
+}
+
+pub fn draw_webhook_picker(f: &mut Frame, area: Rect, app: &App, picker: &WebhookPicker) {
+    let width = (area.width * 3 / 5).max(40).min(area.width);
+    let height = (area.height * 3 / 5).max(8).min(area.height);
+    let popup_rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .title(Span::styled(
+            "Webhooks (Enter to send through one, Esc to close)",
+            Style::default().fg(Color::Yellow),
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Double)
+        .border_style(Style::default().fg(app.theme.border));
+
+    let empty_item =
+        |text: &'static str| vec![ListItem::new(text).style(Style::default().fg(Color::DarkGray))];
+    let (items, selected) = match &picker.webhooks {
+        None => (empty_item("Loading webhooks..."), None),
+        Some(webhooks) if webhooks.is_empty() => (empty_item("No webhooks in this channel"), None),
+        Some(webhooks) => (
+            webhooks.iter().map(webhook_row).collect(),
+            Some(picker.selection_index),
+        ),
+    };
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(app.theme.selection)
+        .highlight_symbol(">> ");
+
+    let mut state = ListState::default().with_selected(selected);
+
+    f.render_widget(Clear, popup_rect);
+    f.render_stateful_widget(list, popup_rect, &mut state);
+}
@@ -0,0 +1,219 @@
+//! Word wrapping of styled lines. The chat lays its messages out with this both to measure
+//! and to draw them, so the two can never disagree.
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Columns a tab takes: its real width is up to the terminal.
+const TAB_WIDTH: usize = 4;
+
+/// A grapheme of a line with its style and width in columns.
+#[derive(Clone, Copy)]
+struct Cell<'a> {
+    grapheme: &'a str,
+    style: Style,
+    width: usize,
+}
+
+/// Breaks `line` into rows at most `width` columns wide, between words where it can and
+/// inside the words wider than a row. Line breaks inside the line start a new row.
+pub fn wrap_line(line: &Line<'_>, width: usize) -> Vec<Line<'static>> {
+    let row_line = |cells: &[Cell]| {
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        for cell in cells {
+            match spans.last_mut() {
+                Some(span) if span.style == cell.style => {
+                    span.content.to_mut().push_str(cell.grapheme);
+                }
+                _ => spans.push(Span::styled(cell.grapheme.to_string(), cell.style)),
+            }
+        }
+        let mut row = Line::from(spans).style(line.style);
+        row.alignment = line.alignment;
+        row
+    };
+
+    let mut cells = Vec::new();
+    for span in &line.spans {
+        let style = span.style;
+        for grapheme in span.content.graphemes(true) {
+            match grapheme {
+                "\t" => cells.extend((0..TAB_WIDTH).map(|_| Cell {
+                    grapheme: " ",
+                    style,
+                    width: 1,
+                })),
+                "\n" | "\r\n" => cells.push(Cell {
+                    grapheme: "\n",
+                    style,
+                    width: 0,
+                }),
+                grapheme => cells.push(Cell {
+                    grapheme,
+                    style,
+                    width: grapheme.width(),
+                }),
+            }
+        }
+    }
+
+    if width == 0 {
+        return vec![row_line(&cells)];
+    }
+
+    let mut rows = Vec::new();
+    let mut row: Vec<Cell> = Vec::new();
+    let mut row_width = 0;
+    let mut rest = cells.as_slice();
+
+    while let Some(first) = rest.first() {
+        if first.grapheme == "\n" {
+            rows.push(row_line(&row));
+            row.clear();
+            row_width = 0;
+            rest = &rest[1..];
+            continue;
+        }
+
+        // The next word, or the next run of spaces.
+        let is_space = first.grapheme == " ";
+        let len = rest
+            .iter()
+            .position(|cell| cell.grapheme == "\n" || (cell.grapheme == " ") != is_space)
+            .unwrap_or(rest.len());
+        let (token, after) = rest.split_at(len);
+        rest = after;
+        let token_width: usize = token.iter().map(|cell| cell.width).sum();
+
+        if row_width + token_width <= width {
+            row_width += token_width;
+            row.extend_from_slice(token);
+            continue;
+        }
+
+        if is_space {
+            // Spaces where a row wraps are dropped, as they would only pad it.
+            rows.push(row_line(&row));
+            row.clear();
+            row_width = 0;
+            continue;
+        }
+
+        if !row.is_empty() {
+            rows.push(row_line(&row));
+            row.clear();
+            row_width = 0;
+        }
+        // A word wider than a row is broken wherever the row is full.
+        for cell in token {
+            if row_width + cell.width > width && !row.is_empty() {
+                rows.push(row_line(&row));
+                row.clear();
+                row_width = 0;
+            }
+            row_width += cell.width;
+            row.push(*cell);
+        }
+    }
+
+    if !row.is_empty() || rows.is_empty() {
+        rows.push(row_line(&row));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Color;
+
+    use super::*;
+
+    fn rows(text: &str, width: usize) -> Vec<String> {
+        wrap_line(&Line::from(Span::raw(text)), width)
+            .iter()
+            .map(|row| row.spans.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        // A space that fits stays at the end of its row; one that doesn't is dropped.
+        assert_eq!(rows("the quick brown fox", 10), ["the quick ", "brown fox"]);
+        assert_eq!(rows("the quick  brown", 9), ["the quick", "brown"]);
+        assert_eq!(rows("fits", 10), ["fits"]);
+        assert_eq!(rows("", 10), [""]);
+    }
+
+    #[test]
+    fn breaks_words_wider_than_a_row() {
+        assert_eq!(
+            rows("see https://example.com/long", 10),
+            ["see ", "https://ex", "ample.com/", "long"]
+        );
+    }
+
+    #[test]
+    fn counts_wide_characters_as_two_columns() {
+        assert_eq!(rows("日本語のテキスト", 6), ["日本語", "のテキ", "スト"]);
+        // A wide character never straddles two rows.
+        assert_eq!(rows("a日本", 4), ["a日", "本"]);
+        assert_eq!(rows("👍🏽👍🏽👍🏽", 4), ["👍🏽👍🏽", "👍🏽"]);
+    }
+
+    #[test]
+    fn starts_a_row_at_each_line_break() {
+        assert_eq!(rows("a\nb\r\n\nc", 10), ["a", "b", "", "c"]);
+    }
+
+    #[test]
+    fn expands_tabs() {
+        assert_eq!(rows("a\tb", 10), ["a    b"]);
+    }
+
+    #[test]
+    fn keeps_the_styles_of_each_span() {
+        let line = Line::from(vec![
+            Span::raw("plain "),
+            Span::styled("red words", Style::default().fg(Color::Red)),
+        ]);
+        let wrapped = wrap_line(&line, 9);
+
+        assert_eq!(wrapped.len(), 2);
+        assert_eq!(wrapped[0].spans[0].content, "plain ");
+        assert_eq!(wrapped[0].spans[1].content, "red");
+        assert_eq!(wrapped[0].spans[1].style.fg, Some(Color::Red));
+        assert_eq!(wrapped[1].spans[0].content, "words");
+        assert_eq!(wrapped[1].spans[0].style.fg, Some(Color::Red));
+    }
+
+    #[test]
+    fn never_exceeds_the_width_nor_loses_text() {
+        let pieces = [
+            "a",
+            "word",
+            " ",
+            "  ",
+            "日本",
+            "👍🏽",
+            "https://example.com/x",
+        ];
+        for seed in 0..500usize {
+            let text: String = (0..12)
+                .map(|i| pieces[(seed * 7 + i * (seed % 5 + 1)) % pieces.len()])
+                .collect();
+            for width in 2..12 {
+                let wrapped = rows(&text, width);
+                for row in &wrapped {
+                    assert!(row.width() <= width, "{text:?} at {width}: {row:?}");
+                }
+                let kept: String = wrapped.concat().split_whitespace().collect();
+                let expected: String = text.split_whitespace().collect();
+                assert_eq!(kept, expected, "{text:?} at {width}");
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! A local stand-in for Discord's REST API, serving the routes Rivet uses from what the
+//! test puts in it. Posted messages are kept, so the next poll sees them.
+
+// Each test binary uses its own part of the fake.
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use reqwest::Client;
+use rivetui::api::ApiClient;
+use serde_json::{Value, json};
+use wiremock::{
+    Mock, MockServer, Request, ResponseTemplate,
+    matchers::{method, path, path_regex},
+};
+
+pub const TOKEN: &str = "fake-token";
+/// The user the token belongs to.
+pub const USER_ID: &str = "1";
+
+#[derive(Default)]
+struct State {
+    /// In id order, as Discord lists them.
+    guilds: Vec<Value>,
+    channels: Vec<Value>,
+    /// Messages of each channel, oldest first.
+    messages: HashMap<String, Vec<Value>>,
+    next_id: u64,
+    /// 429s to answer before serving requests again, and the wait they ask for.
+    rate_limits: Vec<f64>,
+}
+
+pub struct FakeDiscord {
+    pub server: MockServer,
+    state: Arc<Mutex<State>>,
+}
+
+/// Query parameter `name` of `request`, parsed as an id.
+fn query_id(request: &Request, name: &str) -> Option<u64> {
+    request
+        .url
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+fn id_of(value: &Value) -> u64 {
+    value["id"]
+        .as_str()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or(0)
+}
+
+impl FakeDiscord {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let state = Arc::new(Mutex::new(State {
+            next_id: 1000,
+            ..State::default()
+        }));
+
+        // Scripted 429s come before anything else.
+        let limits = Arc::clone(&state);
+        Mock::given(move |_: &Request| !limits.lock().unwrap().rate_limits.is_empty())
+            .respond_with({
+                let state = Arc::clone(&state);
+                move |_: &Request| {
+                    let retry_after = state.lock().unwrap().rate_limits.remove(0);
+                    ResponseTemplate::new(429).set_body_json(json!({
+                        "message": "You are being rate limited.",
+                        "retry_after": retry_after,
+                        "global": false,
+                    }))
+                }
+            })
+            .with_priority(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/users/@me/guilds"))
+            .respond_with({
+                let state = Arc::clone(&state);
+                move |request: &Request| {
+                    let after = query_id(request, "after").unwrap_or(0);
+                    let limit = query_id(request, "limit").unwrap_or(200) as usize;
+                    let page: Vec<Value> = state
+                        .lock()
+                        .unwrap()
+                        .guilds
+                        .iter()
+                        .filter(|guild| id_of(guild) > after)
+                        .take(limit)
+                        .cloned()
+                        .collect();
+                    ResponseTemplate::new(200).set_body_json(page)
+                }
+            })
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/guilds/\d+/channels$"))
+            .respond_with({
+                let state = Arc::clone(&state);
+                move |request: &Request| {
+                    let guild_id = request.url.path_segments().unwrap().nth(1).unwrap();
+                    let channels: Vec<Value> = state
+                        .lock()
+                        .unwrap()
+                        .channels
+                        .iter()
+                        .filter(|channel| channel["guild_id"] == guild_id)
+                        .cloned()
+                        .collect();
+                    ResponseTemplate::new(200).set_body_json(channels)
+                }
+            })
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/channels/\d+$"))
+            .respond_with({
+                let state = Arc::clone(&state);
+                move |request: &Request| {
+                    let channel_id = request.url.path_segments().unwrap().nth(1).unwrap();
+                    let state = state.lock().unwrap();
+                    match state.channels.iter().find(|c| c["id"] == channel_id) {
+                        Some(channel) => ResponseTemplate::new(200).set_body_json(channel),
+                        None => ResponseTemplate::new(404).set_body_json(json!({
+                            "code": 10003,
+                            "message": "Unknown Channel",
+                        })),
+                    }
+                }
+            })
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/channels/\d+/messages$"))
+            .respond_with({
+                let state = Arc::clone(&state);
+                move |request: &Request| {
+                    let channel_id = request.url.path_segments().unwrap().nth(1).unwrap();
+                    let before = query_id(request, "before").unwrap_or(u64::MAX);
+                    let after = query_id(request, "after").unwrap_or(0);
+                    let limit = query_id(request, "limit").unwrap_or(50) as usize;
+                    let state = state.lock().unwrap();
+                    let messages = state
+                        .messages
+                        .get(channel_id)
+                        .map_or(&[][..], Vec::as_slice);
+                    let in_range = messages
+                        .iter()
+                        .filter(|m| (after + 1..before).contains(&id_of(m)));
+                    // Newest first: those right after `after` when paging forward, else the
+                    // newest of the range.
+                    let page: Vec<&Value> = if query_id(request, "after").is_some() {
+                        let mut page: Vec<&Value> = in_range.take(limit).collect();
+                        page.reverse();
+                        page
+                    } else {
+                        in_range.rev().take(limit).collect()
+                    };
+                    ResponseTemplate::new(200).set_body_json(page)
+                }
+            })
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path_regex(r"^/channels/\d+/messages$"))
+            .respond_with({
+                let state = Arc::clone(&state);
+                move |request: &Request| {
+                    let channel_id = request.url.path_segments().unwrap().nth(1).unwrap();
+                    let body: Value = request.body_json().unwrap();
+                    let mut state = state.lock().unwrap();
+                    let message =
+                        state.message(channel_id, USER_ID, body["content"].as_str().unwrap());
+                    ResponseTemplate::new(200).set_body_json(message)
+                }
+            })
+            .mount(&server)
+            .await;
+
+        Self { server, state }
+    }
+
+    /// A client signed in with the fake's token.
+    pub fn client(&self) -> ApiClient {
+        ApiClient::new(Client::new(), TOKEN.to_string(), self.server.uri())
+    }
+
+    pub fn add_guild(&self, id: u64, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .guilds
+            .push(json!({ "id": id.to_string(), "name": name }));
+        state.guilds.sort_by_key(id_of);
+    }
+
+    pub fn add_channel(&self, id: u64, guild_id: u64, name: &str) {
+        self.state.lock().unwrap().channels.push(json!({
+            "id": id.to_string(),
+            "guild_id": guild_id.to_string(),
+            "name": name,
+            "type": 0,
+            "position": id,
+        }));
+    }
+
+    /// Posts a message as `author_id`, and returns it.
+    pub fn post(&self, channel_id: u64, author_id: &str, content: &str) -> Value {
+        self.state
+            .lock()
+            .unwrap()
+            .message(&channel_id.to_string(), author_id, content)
+    }
+
+    /// Answers the next requests with a 429 each, asking to wait the given seconds.
+    pub fn rate_limit(&self, retry_after: &[f64]) {
+        self.state.lock().unwrap().rate_limits.extend(retry_after);
+    }
+
+    /// Requests received so far for `path`.
+    pub async fn requests_to(&self, path: &str) -> Vec<Request> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|request| request.url.path() == path)
+            .collect()
+    }
+}
+
+impl State {
+    fn message(&mut self, channel_id: &str, author_id: &str, content: &str) -> Value {
+        self.next_id += 1;
+        let message = json!({
+            "id": self.next_id.to_string(),
+            "channel_id": channel_id,
+            "author": { "id": author_id, "username": format!("user{author_id}") },
+            "content": content,
+            "timestamp": "2024-01-01T00:00:00Z",
+            "edited_timestamp": null,
+        });
+        self.messages
+            .entry(channel_id.to_string())
+            .or_default()
+            .push(message.clone());
+        message
+    }
+}
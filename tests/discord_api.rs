@@ -0,0 +1,161 @@
+//! The API layer against the fake Discord of `common`.
+
+mod common;
+
+use common::{FakeDiscord, TOKEN, USER_ID};
+use rivetui::{
+    api::{ApiError, GUILD_PAGE_SIZE, message::AllowedMentions},
+    app::AppAction,
+    poll::{FULL_POLL_EVERY, poll_channel},
+};
+
+#[tokio::test]
+async fn lists_every_guild() {
+    let discord = FakeDiscord::start().await;
+    for id in 1..=250 {
+        discord.add_guild(100 + id, &format!("Guild {id}"));
+    }
+
+    let guilds = discord
+        .client()
+        .get_all_current_user_guilds()
+        .await
+        .unwrap();
+    assert_eq!(guilds.len(), 250);
+    assert_eq!(guilds[0].name, "Guild 1");
+    assert_eq!(guilds[249].name, "Guild 250");
+
+    let requests = discord.requests_to("/users/@me/guilds").await;
+    assert_eq!(requests.len(), 250 / GUILD_PAGE_SIZE + 1);
+    assert_eq!(requests[0].headers["authorization"], TOKEN);
+}
+
+#[tokio::test]
+async fn pages_through_message_history() {
+    let discord = FakeDiscord::start().await;
+    discord.add_channel(10, 1, "general");
+    let ids: Vec<String> = (0..120)
+        .map(|i| {
+            discord.post(10, "2", &format!("message {i}"))["id"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    let api_client = discord.client();
+
+    let latest = api_client
+        .get_channel_messages("10", None, None, None, Some(50))
+        .await
+        .unwrap();
+    assert_eq!(latest.len(), 50);
+    assert_eq!(latest[0].id, ids[119]);
+    assert_eq!(latest[49].id, ids[70]);
+
+    let older = api_client
+        .get_channel_messages("10", None, Some(latest[49].id.clone()), None, Some(50))
+        .await
+        .unwrap();
+    assert_eq!(older[0].id, ids[69]);
+    assert_eq!(older[49].id, ids[20]);
+
+    let newer = api_client
+        .get_channel_messages("10", None, None, Some(ids[109].clone()), Some(50))
+        .await
+        .unwrap();
+    let newer_ids: Vec<&str> = newer.iter().map(|m| m.id.as_str()).collect();
+    let expected: Vec<&str> = ids[110..].iter().rev().map(String::as_str).collect();
+    assert_eq!(newer_ids, expected);
+}
+
+#[tokio::test]
+async fn a_posted_message_shows_up_in_the_next_poll() {
+    let discord = FakeDiscord::start().await;
+    discord.add_channel(10, 1, "general");
+    discord.post(10, "2", "hello");
+    let api_client = discord.client();
+
+    let mut polls = FULL_POLL_EVERY;
+    let Ok(Some(AppAction::ApiUpdateMessages(loaded))) =
+        poll_channel(&api_client, "10".into(), None, false, &mut polls).await
+    else {
+        panic!("the channel was not loaded whole");
+    };
+    assert_eq!(loaded.len(), 1);
+
+    let sent = api_client
+        .create_message(
+            "10",
+            Some("hi back".into()),
+            false,
+            None,
+            &AllowedMentions::all(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(sent.author.id, USER_ID);
+
+    let newest = Some(loaded[0].id.clone());
+    let Ok(Some(AppAction::ApiUpdateNewerMessages(channel_id, newer))) =
+        poll_channel(&api_client, "10".into(), newest.clone(), false, &mut polls).await
+    else {
+        panic!("the poll missed the message");
+    };
+    assert_eq!(channel_id, "10");
+    assert_eq!(newer.len(), 1);
+    assert_eq!(newer[0].id, sent.id);
+    assert_eq!(newer[0].content.as_deref(), Some("hi back"));
+
+    // Nothing new since.
+    let newest = Some(sent.id.clone());
+    let action = poll_channel(&api_client, "10".into(), newest, false, &mut polls)
+        .await
+        .unwrap();
+    assert!(action.is_none());
+}
+
+#[tokio::test]
+async fn retries_through_rate_limits() {
+    let discord = FakeDiscord::start().await;
+    discord.add_channel(10, 1, "general");
+    discord.rate_limit(&[0.05, 0.05]);
+
+    let channel = discord.client().get_channel("10").await.unwrap();
+    assert_eq!(channel.name, "general");
+    assert_eq!(discord.requests_to("/channels/10").await.len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_on_a_request_rate_limited_every_time() {
+    let discord = FakeDiscord::start().await;
+    discord.add_channel(10, 1, "general");
+    discord.rate_limit(&[0.01, 0.01, 0.01]);
+    let api_client = discord.client();
+
+    let error = api_client.get_channel("10").await.unwrap_err();
+    assert!(matches!(error, ApiError::RateLimited { .. }));
+
+    // The route opens again once the wait is over.
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    assert!(api_client.get_channel("10").await.is_ok());
+}
+
+#[tokio::test]
+async fn reports_an_unknown_channel() {
+    let discord = FakeDiscord::start().await;
+
+    let error = discord.client().get_channel("404").await.unwrap_err();
+    assert!(matches!(error, ApiError::NotFound(message) if message == "Unknown channel"));
+}
+
+#[tokio::test]
+async fn lists_the_channels_of_a_guild() {
+    let discord = FakeDiscord::start().await;
+    discord.add_channel(10, 1, "general");
+    discord.add_channel(11, 1, "random");
+    discord.add_channel(20, 2, "elsewhere");
+
+    let channels = discord.client().get_guild_channels("1").await.unwrap();
+    let names: Vec<&str> = channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["general", "random"]);
+}
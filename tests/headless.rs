@@ -0,0 +1,135 @@
+//! The reducer without a terminal: scripted actions, with the API's answers taken from
+//! the fake Discord of `common`, drive the app from the guild list to a chat and back.
+
+mod common;
+
+use std::collections::VecDeque;
+
+use common::{FakeDiscord, USER_ID};
+use rivetui::{
+    api::{User, channel::PermissionContext},
+    app::{App, AppAction, AppState, Command, Commands, KeywordAction, Window, apply_action},
+};
+use serde_json::json;
+use tokio::sync::mpsc;
+
+/// Applies actions to an app as the main loop does, with the actions they dispatch, but
+/// without running the tasks they spawn: those would reach the disk.
+struct Headless {
+    app: App,
+    spawned: usize,
+}
+
+impl Headless {
+    fn new(discord: &FakeDiscord) -> Self {
+        let user = User {
+            id: USER_ID.to_string(),
+            username: "tester".to_string(),
+            global_name: None,
+            avatar: None,
+            bot: false,
+        };
+        let mut app = App::new(discord.client(), user);
+        app.vim_mode = false;
+        Self { app, spawned: 0 }
+    }
+
+    fn apply(&mut self, action: AppAction) -> Option<KeywordAction> {
+        let (tx_action, _rx_action) = mpsc::channel(16);
+        let mut commands = Commands::new(tx_action);
+        let mut queue = VecDeque::from([action]);
+        let mut outcome = None;
+        while let Some(action) = queue.pop_front() {
+            outcome = apply_action(&mut self.app, action, &mut commands).or(outcome);
+            for command in commands.take() {
+                match command {
+                    Command::Dispatch(action) => queue.push_back(*action),
+                    Command::Spawn(_) | Command::Track(_) => self.spawned += 1,
+                }
+            }
+        }
+        outcome
+    }
+}
+
+/// Everyone can view every channel.
+fn context() -> PermissionContext {
+    serde_json::from_value(json!({
+        "user_id": USER_ID,
+        "user_role_ids": ["1"],
+        "all_guild_roles": [{ "id": "1", "name": "@everyone", "permissions": "1024" }],
+        "everyone_role_id": "1",
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn goes_from_the_guild_list_to_a_chat_and_back() {
+    let discord = FakeDiscord::start().await;
+    discord.add_guild(1, "Rust");
+    discord.add_channel(10, 1, "general");
+    discord.add_channel(11, 1, "help");
+    discord.post(11, "2", "how do I borrow?");
+    let api_client = discord.client();
+    let mut headless = Headless::new(&discord);
+
+    // Home, then the servers.
+    headless.apply(AppAction::InputSubmit);
+    assert!(matches!(headless.app.state, AppState::SelectingGuild));
+    let guilds = api_client.get_all_current_user_guilds().await.unwrap();
+    headless.apply(AppAction::ApiUpdateGuilds(guilds, true));
+    assert_eq!(headless.app.guilds.len(), 1);
+
+    // Entering the guild asks for its channels, which arrive as the task would send them.
+    headless.apply(AppAction::InputSubmit);
+    assert!(headless.spawned > 0);
+    assert_eq!(headless.app.status_message, "Loading channels for Rust...");
+    headless.apply(AppAction::TransitionToLoading(Window::Channel("1".into())));
+    headless.apply(AppAction::ApiUpdateContext(Some(context())));
+    let channels = api_client.get_guild_channels("1").await.unwrap();
+    headless.apply(AppAction::ApiUpdateChannel(channels));
+    headless.apply(AppAction::EndLoading);
+    assert!(matches!(&headless.app.state, AppState::SelectingChannel(id) if id == "1"));
+
+    // Past the heading of the channels without a category, to the second channel.
+    let rows: Vec<&str> = headless
+        .app
+        .channel_rows("1")
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(rows, ["uncategorized", "general", "help"]);
+    headless.apply(AppAction::SelectNext);
+    headless.apply(AppAction::SelectNext);
+    headless.apply(AppAction::InputSubmit);
+    assert!(matches!(&headless.app.state, AppState::Loading(Window::Chat(id)) if id == "11"));
+    let messages = api_client
+        .get_channel_messages("11", None, None, None, Some(100))
+        .await
+        .unwrap();
+    headless.apply(AppAction::ApiUpdateMessages(messages));
+    headless.apply(AppAction::EndLoading);
+    assert!(matches!(&headless.app.state, AppState::Chatting(id) if id == "11"));
+    assert_eq!(headless.app.messages.len(), 1);
+    assert_eq!(
+        headless.app.messages[0].content.as_deref(),
+        Some("how do I borrow?")
+    );
+
+    // Typing a message and going back keeps it as a draft.
+    for c in "later".chars() {
+        headless.apply(AppAction::InputChar(c));
+    }
+    headless.apply(AppAction::InputEscape);
+    assert!(matches!(&headless.app.state, AppState::SelectingChannel(id) if id == "1"));
+    assert_eq!(headless.app.drafts["11"].text, "later");
+
+    headless.apply(AppAction::InputEscape);
+    assert!(matches!(headless.app.state, AppState::SelectingGuild));
+    headless.apply(AppAction::InputEscape);
+    assert!(matches!(headless.app.state, AppState::Home));
+    assert!(matches!(
+        headless.apply(AppAction::InputEscape),
+        Some(KeywordAction::Break)
+    ));
+}